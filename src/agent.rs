@@ -0,0 +1,396 @@
+//! Agent instruction files (AGENT.md, CLAUDE.md, ...) rendered from one
+//! template.
+//!
+//! The generated text sits between [`BEGIN_MARKER`] and [`END_MARKER`];
+//! refreshing a file only replaces that section and keeps everything
+//! written around it. When the markers are gone, the update falls back to
+//! a three-way merge against the last generated section, kept under
+//! [`BASE_DIR`].
+
+use crate::fs_ops;
+use crate::template::{self, Variables};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Agent instructions file, relative to the repository root.
+pub const AGENT_MD: &str = "AGENT.md";
+
+/// Directory holding the last generated section of each target, used as
+/// the merge base. Relative to the repository root.
+pub const BASE_DIR: &str = ".echo";
+
+/// Start of the section managed by echo.
+pub const BEGIN_MARKER: &str = "<!-- echo:begin -->";
+
+/// End of the section managed by echo.
+pub const END_MARKER: &str = "<!-- echo:end -->";
+
+/// Built-in AGENT.md template.
+pub const DEFAULT_TEMPLATE: &str = include_str!("../templates/AGENT.md");
+
+/// Template of a flowmates checkout, relative to it, used instead of
+/// [`DEFAULT_TEMPLATE`] when present.
+pub const FLOWMATES_TEMPLATE: &str = "templates/AGENT.md";
+
+/// The agent instructions template: the one of `flowmates_dir` when it
+/// has one, the built-in one otherwise.
+pub fn load_template(flowmates_dir: Option<&Path>) -> Result<String> {
+    let Some(path) = flowmates_dir
+        .map(|dir| dir.join(FLOWMATES_TEMPLATE))
+        .filter(|path| path.is_file())
+    else {
+        return Ok(DEFAULT_TEMPLATE.to_string());
+    };
+    fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// A file that coding agents read their instructions from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// `AGENT.md`, written by `echo init`.
+    Agent,
+    /// `AGENTS.md`, read by Codex and other agents.
+    AgentsMd,
+    /// `CLAUDE.md`.
+    Claude,
+    /// `.github/copilot-instructions.md`.
+    Copilot,
+    /// `.windsurfrules`.
+    Windsurf,
+}
+
+impl Target {
+    /// All targets, AGENT.md first.
+    pub const ALL: [Target; 5] = [
+        Target::Agent,
+        Target::AgentsMd,
+        Target::Claude,
+        Target::Copilot,
+        Target::Windsurf,
+    ];
+
+    /// File path relative to the repository root.
+    pub fn path(self) -> &'static str {
+        match self {
+            Target::Agent => AGENT_MD,
+            Target::AgentsMd => "AGENTS.md",
+            Target::Claude => "CLAUDE.md",
+            Target::Copilot => ".github/copilot-instructions.md",
+            Target::Windsurf => ".windsurfrules",
+        }
+    }
+
+    /// Merge base path relative to the repository root.
+    pub fn base_path(self) -> PathBuf {
+        Path::new(BASE_DIR).join(format!("{}.base", self.path()))
+    }
+}
+
+/// Outcome of writing an agent file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Update {
+    /// The file did not exist or was overwritten.
+    Created,
+    /// The managed section was replaced or appended.
+    Updated,
+    /// The managed section was already current.
+    Unchanged,
+    /// No markers were found; the new section was merged in cleanly.
+    Merged,
+    /// No markers were found and the merge left conflict markers.
+    Conflict,
+}
+
+/// Render the agent instructions `template` (see [`load_template`]) for
+/// the given variables.
+pub fn render_agent_md(template: &str, vars: &Variables) -> Result<String> {
+    template::render(template, vars).context("failed to render the agent template")
+}
+
+/// Render the managed section, markers included.
+pub fn generated_section(template: &str, vars: &Variables) -> Result<String> {
+    let body = render_agent_md(template, vars)?;
+    Ok(format!(
+        "{}\n{}{}{}\n",
+        BEGIN_MARKER,
+        body,
+        if body.ends_with('\n') { "" } else { "\n" },
+        END_MARKER
+    ))
+}
+
+/// Write AGENT.md: with `force` the whole file is replaced, otherwise only
+/// the managed section is refreshed (see [`update`]).
+pub fn create_agent_md(
+    root: &Path,
+    template: &str,
+    vars: &Variables,
+    force: bool,
+) -> Result<Update> {
+    if !force {
+        return update(root, Target::Agent, template, vars);
+    }
+    let generated = generated_section(template, vars)?;
+    write_target(root, Target::Agent, &generated, &generated)?;
+    Ok(Update::Created)
+}
+
+/// A computed agent file update that has not been written yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub target: Target,
+    /// Current file contents, if the file exists.
+    pub current: Option<String>,
+    /// Contents to write.
+    pub content: String,
+    pub update: Update,
+    generated: String,
+}
+
+impl Plan {
+    /// Unified diff from the current file to the planned contents.
+    pub fn diff(&self) -> String {
+        let current = self.current.as_deref().unwrap_or("");
+        diffy::create_patch(current, &self.content).to_string()
+    }
+
+    /// Write the planned contents and record the generated section as the
+    /// next merge base. Does nothing when the file is already up to date.
+    pub fn write(&self, root: &Path) -> Result<()> {
+        if self.update == Update::Unchanged {
+            return Ok(());
+        }
+        write_target(root, self.target, &self.content, &self.generated)
+    }
+}
+
+/// Compute how refreshing the managed section would change `target`.
+pub fn plan_update(root: &Path, target: Target, template: &str, vars: &Variables) -> Result<Plan> {
+    let generated = generated_section(template, vars)?;
+    let current = read_optional(&root.join(target.path()))?;
+    let base = read_optional(&root.join(target.base_path()))?;
+    let (content, update) = apply_update(current.as_deref(), base.as_deref(), &generated);
+    Ok(Plan {
+        target,
+        current,
+        content,
+        update,
+        generated,
+    })
+}
+
+/// Refresh the managed section of `target`, preserving hand-written
+/// content. A conflicting merge is still written so it can be resolved.
+pub fn update(root: &Path, target: Target, template: &str, vars: &Variables) -> Result<Update> {
+    let plan = plan_update(root, target, template, vars)?;
+    plan.write(root)?;
+    Ok(plan.update)
+}
+
+/// Compute the new file contents from the current file, the last generated
+/// section (`base`) and the freshly generated one.
+pub fn apply_update(
+    current: Option<&str>,
+    base: Option<&str>,
+    generated: &str,
+) -> (String, Update) {
+    let Some(current) = current else {
+        return (generated.to_string(), Update::Created);
+    };
+    if let Some((start, end)) = managed_span(current) {
+        if &current[start..end] == generated.trim_end_matches('\n') {
+            return (current.to_string(), Update::Unchanged);
+        }
+        let mut content = String::with_capacity(current.len() + generated.len());
+        content.push_str(&current[..start]);
+        content.push_str(generated.trim_end_matches('\n'));
+        content.push_str(&current[end..]);
+        return (content, Update::Updated);
+    }
+    match base {
+        Some(base) => match diffy::merge(base, current, generated) {
+            Ok(merged) => (merged, Update::Merged),
+            Err(conflicted) => (conflicted, Update::Conflict),
+        },
+        None => {
+            let mut content = current.to_string();
+            if !content.is_empty() && !content.ends_with("\n\n") {
+                content.push_str(if content.ends_with('\n') {
+                    "\n"
+                } else {
+                    "\n\n"
+                });
+            }
+            content.push_str(generated);
+            (content, Update::Updated)
+        }
+    }
+}
+
+/// Byte range from the begin marker to the end of the end marker.
+fn managed_span(content: &str) -> Option<(usize, usize)> {
+    let start = content.find(BEGIN_MARKER)?;
+    let end = start + content[start..].find(END_MARKER)? + END_MARKER.len();
+    Some((start, end))
+}
+
+fn write_target(root: &Path, target: Target, content: &str, generated: &str) -> Result<()> {
+    let path = root.join(target.path());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs_ops::write_atomic(&path, content.as_bytes(), false)
+        .with_context(|| format!("failed to write {}", target.path()))?;
+    let base = root.join(target.base_path());
+    if let Some(parent) = base.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs_ops::write_atomic(&base, generated.as_bytes(), false)
+        .with_context(|| format!("failed to write {}", target.base_path().display()))
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    fs::read_to_string(path)
+        .map(Some)
+        .with_context(|| format!("failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(remote: &str) -> Variables {
+        let mut vars = Variables::new();
+        vars.insert("PROJECT_NAME".into(), "echo".into());
+        vars.insert("GIT_REMOTE".into(), remote.into());
+        vars
+    }
+
+    fn section(body: &str) -> String {
+        format!("{}\n{}\n{}\n", BEGIN_MARKER, body, END_MARKER)
+    }
+
+    #[test]
+    fn renders_project_name_and_optional_remote() {
+        let local = render_agent_md(DEFAULT_TEMPLATE, &vars("")).unwrap();
+        assert!(local.starts_with("# Agent Guide — echo\n"));
+        assert!(local.contains("`issues/echo/todo/`"));
+        assert!(!local.contains("Upstream"));
+        let remote = render_agent_md(DEFAULT_TEMPLATE, &vars("git@host:echo.git")).unwrap();
+        assert!(remote.contains("\nUpstream: git@host:echo.git\n"));
+        assert!(generated_section(DEFAULT_TEMPLATE, &vars(""))
+            .unwrap()
+            .starts_with("<!-- echo:begin -->\n# Agent Guide"));
+    }
+
+    #[test]
+    fn replaces_only_the_managed_section() {
+        let current = format!("Intro\n\n{}\nOutro\n", section("old"));
+        let (content, update) = apply_update(Some(&current), None, &section("new"));
+        assert_eq!(update, Update::Updated);
+        assert_eq!(content, format!("Intro\n\n{}\nOutro\n", section("new")));
+
+        let (_, update) = apply_update(Some(&content), None, &section("new"));
+        assert_eq!(update, Update::Unchanged);
+    }
+
+    #[test]
+    fn appends_section_to_unmanaged_file() {
+        let (content, update) = apply_update(Some("Mine\n"), None, &section("gen"));
+        assert_eq!(update, Update::Updated);
+        assert_eq!(content, format!("Mine\n\n{}", section("gen")));
+    }
+
+    #[test]
+    fn merges_when_markers_were_removed() {
+        let base = "a\nb\nc\n";
+        let (content, update) = apply_update(Some("a\nb\nc\nmine\n"), Some(base), "a\nB\nc\n");
+        assert_eq!(update, Update::Merged);
+        assert_eq!(content, "a\nB\nc\nmine\n");
+
+        let (content, update) = apply_update(Some("a\nx\nc\n"), Some(base), "a\ny\nc\n");
+        assert_eq!(update, Update::Conflict);
+        assert!(content.contains("<<<<<<<"));
+    }
+
+    #[test]
+    fn loads_the_template_of_the_flowmates_checkout() {
+        let flowmates = tempfile::tempdir().unwrap();
+        assert_eq!(
+            load_template(Some(flowmates.path())).unwrap(),
+            DEFAULT_TEMPLATE
+        );
+        assert_eq!(load_template(None).unwrap(), DEFAULT_TEMPLATE);
+        fs::create_dir(flowmates.path().join("templates")).unwrap();
+        fs::write(
+            flowmates.path().join(FLOWMATES_TEMPLATE),
+            "# Team guide for {{PROJECT_NAME}}\n",
+        )
+        .unwrap();
+        let template = load_template(Some(flowmates.path())).unwrap();
+        assert_eq!(
+            render_agent_md(&template, &vars("")).unwrap(),
+            "# Team guide for echo\n"
+        );
+    }
+
+    #[test]
+    fn plan_shows_diff_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(AGENT_MD), "mine\n").unwrap();
+        let plan = plan_update(dir.path(), Target::Agent, DEFAULT_TEMPLATE, &vars("")).unwrap();
+        assert_eq!(plan.update, Update::Updated);
+        assert!(plan.diff().contains("+<!-- echo:begin -->\n"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join(AGENT_MD)).unwrap(),
+            "mine\n"
+        );
+        plan.write(dir.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(AGENT_MD)).unwrap(),
+            plan.content
+        );
+    }
+
+    #[test]
+    fn writes_targets_in_nested_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            update(dir.path(), Target::Copilot, DEFAULT_TEMPLATE, &vars("")).unwrap(),
+            Update::Created
+        );
+        assert!(dir.path().join(".github/copilot-instructions.md").is_file());
+        assert!(dir
+            .path()
+            .join(".echo/.github/copilot-instructions.md.base")
+            .is_file());
+        assert_eq!(Target::Agent.base_path(), Path::new(".echo/AGENT.md.base"));
+    }
+
+    #[test]
+    fn keeps_hand_written_content_unless_forced() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(AGENT_MD), "mine\n").unwrap();
+        assert_eq!(
+            create_agent_md(dir.path(), DEFAULT_TEMPLATE, &vars(""), false).unwrap(),
+            Update::Updated
+        );
+        let content = fs::read_to_string(dir.path().join(AGENT_MD)).unwrap();
+        assert!(content.starts_with("mine\n\n<!-- echo:begin -->\n"));
+        assert_eq!(
+            update(dir.path(), Target::Agent, DEFAULT_TEMPLATE, &vars("")).unwrap(),
+            Update::Unchanged
+        );
+        assert!(dir.path().join(Target::Agent.base_path()).is_file());
+
+        create_agent_md(dir.path(), DEFAULT_TEMPLATE, &vars(""), true).unwrap();
+        let content = fs::read_to_string(dir.path().join(AGENT_MD)).unwrap();
+        assert!(content.starts_with(BEGIN_MARKER));
+    }
+}
@@ -0,0 +1,496 @@
+//! Log of what mutating commands changed, kept when `.echo.toml` sets
+//! `audit-log = true`, so a repository shared by several agents can tell
+//! who changed its rules and when.
+//!
+//! The files echo manages are hashed before and after each mutating run;
+//! every file created, changed or deleted in between is appended, with
+//! both hashes, as one JSON line of [`LOG_FILE`]. Comparing snapshots
+//! catches changes whatever wrote them. Watched are `.cursor/`, the issue
+//! tree, `.echo.toml`, `.gitignore`, the agent instruction files, echo's
+//! records in `.echo/` and the git hooks.
+//!
+//! The contents the files a run changed had before it are kept under
+//! [`OBJECTS_DIR`], by hash, so [`revert`] can put them back for `echo
+//! undo`; unchanged files are not copied. Each run then [`prune`]s the
+//! contents no logged run needs.
+
+use crate::agent::Target;
+use crate::config::CONFIG_FILE;
+use crate::fs_ops::{self, FsOps};
+use crate::gitutil;
+use crate::issues::ISSUES_DIR;
+use crate::lock;
+use crate::run_lock::RUN_LOCK_FILE;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Log file, relative to the repository root.
+pub const LOG_FILE: &str = ".cursor/echo-audit.log";
+
+/// Contents of the watched files before each run, named by hash.
+pub const OBJECTS_DIR: &str = ".echo/objects";
+
+/// Command recorded for `echo undo`.
+pub const UNDO_COMMAND: &str = "undo";
+
+/// What a directory is recorded as instead of a hash.
+pub const DIRECTORY: &str = "directory";
+
+/// Prefix of what is hashed and kept for a symbolic link, before its
+/// target.
+const SYMLINK_PREFIX: &str = "symlink:";
+
+/// Hashes of the watched files, and [`DIRECTORY`] for their directories,
+/// by path relative to the repository root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    hashes: BTreeMap<PathBuf, String>,
+    /// Contents of the files by hash, held by [`Snapshot::take_keeping`].
+    contents: BTreeMap<String, Vec<u8>>,
+}
+
+impl Snapshot {
+    /// Hash the watched files of the repository at `root`.
+    pub fn take(root: &Path) -> Result<Snapshot> {
+        Snapshot::take_with(root, false)
+    }
+
+    /// Like [`Snapshot::take`], holding the contents of the files in
+    /// memory so [`Snapshot::keep`] can store those a run changed.
+    pub fn take_keeping(root: &Path) -> Result<Snapshot> {
+        Snapshot::take_with(root, true)
+    }
+
+    fn take_with(root: &Path, keep: bool) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::default();
+        let mut watched: Vec<PathBuf> = [".cursor", ".echo", ISSUES_DIR, CONFIG_FILE, ".gitignore"]
+            .into_iter()
+            .chain(Target::ALL.map(Target::path))
+            .map(PathBuf::from)
+            .collect();
+        if let Ok(hooks) = gitutil::git_path(root, "hooks") {
+            watched.push(hooks.strip_prefix(root).unwrap_or(&hooks).to_path_buf());
+        }
+        for rel in watched {
+            hash_tree(root, &rel, keep, &mut snapshot)?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Store under [`OBJECTS_DIR`] of `root` the contents `files`, changed
+    /// since this snapshot was taken, had in it.
+    pub fn keep(&self, root: &Path, files: &[Touched]) -> Result<()> {
+        for hash in files.iter().filter_map(|touched| touched.before.as_ref()) {
+            let Some(content) = self.contents.get(hash) else {
+                continue;
+            };
+            let object = root.join(OBJECTS_DIR).join(hash);
+            if !object.is_file() {
+                fs::create_dir_all(root.join(OBJECTS_DIR))?;
+                fs::write(&object, content)
+                    .with_context(|| format!("failed to write {}", object.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Files that differ from `before`, by path.
+    pub fn changes_since(&self, before: &Snapshot) -> Vec<Touched> {
+        let mut paths: Vec<&PathBuf> = before.hashes.keys().chain(self.hashes.keys()).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let (old, new) = (before.hashes.get(path), self.hashes.get(path));
+                (old != new).then(|| Touched {
+                    path: path.clone(),
+                    before: old.cloned(),
+                    after: new.cloned(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Add the hashes of the files and directories at or under `root/rel` to
+/// `snapshot`, holding the contents of the files with `keep`. A symbolic
+/// link is hashed as its target path, so broken links count.
+fn hash_tree(root: &Path, rel: &Path, keep: bool, snapshot: &mut Snapshot) -> Result<()> {
+    if [OBJECTS_DIR, RUN_LOCK_FILE, LOG_FILE]
+        .iter()
+        .any(|skipped| rel == Path::new(skipped))
+    {
+        return Ok(());
+    }
+    let path = root.join(rel);
+    let Ok(meta) = fs::symlink_metadata(&path) else {
+        return Ok(());
+    };
+    if meta.is_dir() {
+        snapshot
+            .hashes
+            .insert(rel.to_path_buf(), DIRECTORY.to_string());
+        for entry in
+            fs::read_dir(&path).with_context(|| format!("failed to read {}", path.display()))?
+        {
+            hash_tree(root, &rel.join(entry?.file_name()), keep, snapshot)?;
+        }
+        return Ok(());
+    }
+    let Some(content) = contents(&path)? else {
+        return Ok(());
+    };
+    let hash = lock::hash_bytes(&content);
+    if keep {
+        snapshot.contents.insert(hash.clone(), content);
+    }
+    snapshot.hashes.insert(rel.to_path_buf(), hash);
+    Ok(())
+}
+
+/// What `path` is recorded as: [`DIRECTORY`] or the hash of its
+/// [`contents`]; `None` when absent.
+fn current(path: &Path) -> Result<Option<String>> {
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()) {
+        return Ok(Some(DIRECTORY.to_string()));
+    }
+    Ok(contents(path)?.map(|content| lock::hash_bytes(&content)))
+}
+
+/// What is hashed and kept of `path`: the contents of a file, the
+/// prefixed target of a symbolic link; `None` when absent.
+fn contents(path: &Path) -> Result<Option<Vec<u8>>> {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return Ok(None);
+    };
+    if meta.is_symlink() {
+        let target = fs::read_link(path)?;
+        return Ok(Some(
+            format!("{}{}", SYMLINK_PREFIX, target.display()).into_bytes(),
+        ));
+    }
+    fs::read(path)
+        .map(Some)
+        .with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// A file or directory a run created (no `before`), deleted (no `after`)
+/// or changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Touched {
+    pub path: PathBuf,
+    /// SHA-256 before the run, or [`DIRECTORY`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// SHA-256 after the run, or [`DIRECTORY`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+impl Touched {
+    /// `A`, `D` or `M`, as `git status --short` marks it.
+    pub fn mark(&self) -> char {
+        match (&self.before, &self.after) {
+            (None, _) => 'A',
+            (_, None) => 'D',
+            _ => 'M',
+        }
+    }
+}
+
+/// One mutating run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the epoch at the start.
+    pub at: u64,
+    /// Command and subcommands, e.g. `rules disable`.
+    pub command: String,
+    /// Who ran it, as `echo issue move --assignee me` resolves it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by: Option<String>,
+    /// Whether it succeeded.
+    pub ok: bool,
+    pub files: Vec<Touched>,
+}
+
+/// Append `entry` to the log of the repository at `root`.
+pub fn append(root: &Path, entry: &Entry) -> Result<()> {
+    let path = root.join(LOG_FILE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The logged runs of the repository at `root`, oldest first. Lines that
+/// do not parse are skipped.
+pub fn load(root: &Path) -> Result<Vec<Entry>> {
+    let path = root.join(LOG_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Remove the contents under [`OBJECTS_DIR`] that no logged run of the
+/// repository at `root` had before it. Returns how many were removed.
+pub fn prune(root: &Path) -> Result<usize> {
+    let dir = root.join(OBJECTS_DIR);
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let referenced: HashSet<String> = load(root)?
+        .into_iter()
+        .flat_map(|entry| entry.files)
+        .filter_map(|touched| touched.before)
+        .collect();
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let entry = entry?;
+        if referenced.contains(&*entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let path = entry.path();
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// The runs `echo undo` would revert, most recent first: the logged runs
+/// other than undos, less those the successful undos already reverted.
+pub fn revertible(entries: &[Entry]) -> Vec<&Entry> {
+    let mut undone = 0;
+    let mut revertible = Vec::new();
+    for entry in entries.iter().rev() {
+        if entry.command == UNDO_COMMAND {
+            undone += usize::from(entry.ok);
+        } else if undone > 0 {
+            undone -= 1;
+        } else {
+            revertible.push(entry);
+        }
+    }
+    revertible
+}
+
+/// Put the files `entry` touched in the repository at `root` back as they
+/// were before it: kept contents and deleted directories restored,
+/// created files and directories removed. Directories that are no longer
+/// empty stay. Fails, changing nothing, when a file changed since unless
+/// `force`, or when contents were not kept.
+pub fn revert(root: &Path, entry: &Entry, force: bool) -> Result<()> {
+    let mut changed = Vec::new();
+    for touched in &entry.files {
+        if current(&root.join(&touched.path))? != touched.after {
+            changed.push(touched.path.display().to_string());
+        }
+        if let Some(hash) = touched.before.as_ref().filter(|hash| *hash != DIRECTORY) {
+            if !root.join(OBJECTS_DIR).join(hash).is_file() {
+                bail!(
+                    "the contents of {} before `{}` were not kept in {}",
+                    touched.path.display(),
+                    entry.command,
+                    OBJECTS_DIR
+                );
+            }
+        }
+    }
+    if !changed.is_empty() && !force {
+        bail!(
+            "changed since `{}`: {}; pass --force to revert anyway",
+            entry.command,
+            changed.join(", ")
+        );
+    }
+    let hooks = gitutil::git_path(root, "hooks").ok();
+    // Parents sort first: restore top down, then remove bottom up.
+    for touched in &entry.files {
+        let Some(hash) = &touched.before else {
+            continue;
+        };
+        let path = root.join(&touched.path);
+        if fs::symlink_metadata(&path).is_ok_and(|meta| !meta.is_dir()) {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        if hash == DIRECTORY {
+            fs::create_dir_all(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let content = fs::read(root.join(OBJECTS_DIR).join(hash))?;
+        match content.strip_prefix(SYMLINK_PREFIX.as_bytes()) {
+            Some(target) => fs_ops::RealFs
+                .symlink(Path::new(&*String::from_utf8_lossy(target)), &path)
+                .with_context(|| format!("failed to link {}", path.display()))?,
+            None => fs::write(&path, &content)
+                .with_context(|| format!("failed to write {}", path.display()))?,
+        }
+        // Hook scripts are kept without their mode.
+        if hooks.as_ref().is_some_and(|hooks| path.starts_with(hooks)) {
+            fs_ops::RealFs.set_permissions(&path, 0o755)?;
+        }
+    }
+    for touched in entry.files.iter().rev() {
+        let path = root.join(&touched.path);
+        match (&touched.before, touched.after.as_deref()) {
+            (Some(_), _) | (None, None) => {}
+            (None, Some(DIRECTORY)) => {
+                let _ = fs::remove_dir(&path);
+            }
+            (None, Some(_)) => {
+                if fs::symlink_metadata(&path).is_ok() {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("failed to remove {}", path.display()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_the_files_changed_between_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".cursor/rules")).unwrap();
+        fs::write(root.join(".cursor/rules/style.mdc"), "four spaces\n").unwrap();
+        fs::write(root.join(".cursor/rules/old.mdc"), "old\n").unwrap();
+        fs::write(root.join("src.rs"), "fn main() {}\n").unwrap();
+        let before = Snapshot::take(root).unwrap();
+
+        fs::write(root.join(".cursor/rules/style.mdc"), "tabs\n").unwrap();
+        fs::remove_file(root.join(".cursor/rules/old.mdc")).unwrap();
+        fs::write(root.join(CONFIG_FILE), "vendor = true\n").unwrap();
+        fs::write(root.join("src.rs"), "fn main() { run() }\n").unwrap();
+        let files = Snapshot::take(root).unwrap().changes_since(&before);
+        let marks: Vec<(char, &Path)> = files
+            .iter()
+            .map(|touched| (touched.mark(), touched.path.as_path()))
+            .collect();
+        assert_eq!(
+            marks,
+            vec![
+                ('D', Path::new(".cursor/rules/old.mdc")),
+                ('M', Path::new(".cursor/rules/style.mdc")),
+                ('A', Path::new(".echo.toml")),
+            ]
+        );
+        assert_eq!(files[1].after, Some(lock::hash("tabs\n")));
+
+        let entry = Entry {
+            at: 1_700_000_000,
+            command: "update".to_string(),
+            by: Some("alice".to_string()),
+            ok: true,
+            files,
+        };
+        append(root, &entry).unwrap();
+        append(root, &entry).unwrap();
+        assert_eq!(load(root).unwrap(), vec![entry.clone(), entry]);
+        // The log does not log itself.
+        assert_eq!(
+            Snapshot::take(root).unwrap().changes_since(&before).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn reverts_a_run_unless_its_files_changed_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".cursor/rules")).unwrap();
+        fs::write(root.join(".cursor/rules/style.mdc"), "four spaces\n").unwrap();
+        fs::write(root.join(".cursor/rules/old.mdc"), "old\n").unwrap();
+        fs::write(root.join(".cursor/rules/kept.mdc"), "kept\n").unwrap();
+        fs::create_dir_all(root.join(".echo")).unwrap();
+        let before = Snapshot::take_keeping(root).unwrap();
+        fs::write(root.join(".cursor/rules/style.mdc"), "tabs\n").unwrap();
+        fs::remove_file(root.join(".cursor/rules/old.mdc")).unwrap();
+        fs::create_dir_all(root.join(ISSUES_DIR).join("app/todo")).unwrap();
+        fs::write(root.join(ISSUES_DIR).join("app/todo/.gitkeep"), "").unwrap();
+        let entry = Entry {
+            at: 1_700_000_000,
+            command: "update".to_string(),
+            by: None,
+            ok: true,
+            files: Snapshot::take(root).unwrap().changes_since(&before),
+        };
+        // Only the contents of the files the run changed are kept.
+        before.keep(root, &entry.files).unwrap();
+        assert_eq!(fs::read_dir(root.join(OBJECTS_DIR)).unwrap().count(), 2);
+        assert!(!root.join(OBJECTS_DIR).join(lock::hash("kept\n")).exists());
+
+        fs::write(root.join(".cursor/rules/style.mdc"), "two spaces\n").unwrap();
+        let err = revert(root, &entry, false).unwrap_err().to_string();
+        assert!(err.contains("changed since `update`: .cursor/rules/style.mdc"));
+        assert_eq!(
+            fs::read_to_string(root.join(".cursor/rules/style.mdc")).unwrap(),
+            "two spaces\n"
+        );
+
+        // Only the contents the logged run had before survive pruning.
+        fs::write(
+            root.join(OBJECTS_DIR).join(lock::hash("stale\n")),
+            "stale\n",
+        )
+        .unwrap();
+        append(root, &entry).unwrap();
+        assert_eq!(prune(root).unwrap(), 1);
+        assert_eq!(fs::read_dir(root.join(OBJECTS_DIR)).unwrap().count(), 2);
+
+        revert(root, &entry, true).unwrap();
+        assert!(Snapshot::take(root)
+            .unwrap()
+            .changes_since(&before)
+            .is_empty());
+        assert!(!root.join(ISSUES_DIR).exists());
+
+        let run = |command: &str, ok: bool| Entry {
+            command: command.to_string(),
+            ok,
+            ..entry.clone()
+        };
+        let entries = [
+            run("init", true),
+            run("update", true),
+            run(UNDO_COMMAND, true),
+            run(UNDO_COMMAND, false),
+            run("rules disable", true),
+        ];
+        let commands: Vec<&str> = revertible(&entries)
+            .iter()
+            .map(|entry| entry.command.as_str())
+            .collect();
+        assert_eq!(commands, vec!["rules disable", "init"]);
+        assert!(revertible(&entries[..3])
+            .iter()
+            .all(|entry| entry.command == "init"));
+    }
+}
@@ -0,0 +1,152 @@
+//! `echo check`: drift of everything echo manages, for CI pipelines.
+//!
+//! Installed rules, templates and scripts must match their configured
+//! source, git hooks must be current and untouched, issue files must pass
+//! validation and the managed section of AGENT.md must be up to date. Each
+//! problem is reported as a [`Violation`]; scripts the checkout does not
+//! install are only [`warnings`].
+
+use crate::agent::{self, Update};
+use crate::config::Config;
+use crate::gitutil;
+use crate::hooks::{self, verify, HookState};
+use crate::issues::{
+    self, templates,
+    validate::{self, Violation},
+};
+use crate::lock::Outcome;
+use crate::presets;
+use crate::rules;
+use crate::scripts;
+use crate::template::Variables;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Find every drift in `root`; `vars` render AGENT.md.
+pub fn check(root: &Path, config: &Config, vars: &Variables) -> Result<Vec<Violation>> {
+    let mut violations = Vec::new();
+    let mut push = |path: PathBuf, message: String| violations.push(Violation { path, message });
+
+    let mut config = config.clone();
+    let flowmates_dir = config.rules.flowmates_dir();
+    let manifest = presets::load(flowmates_dir.as_deref())?;
+    let names = presets::resolve(root, &config.presets, &manifest);
+    let selection = presets::select(&manifest, &names)?;
+    config.rules.preset = selection.rules;
+    let installed = rules::plan(root, &config.rules)?
+        .into_iter()
+        .flat_map(|(_, installed)| installed)
+        .chain(templates::plan(
+            root,
+            flowmates_dir.as_deref(),
+            selection.templates.as_deref(),
+        )?)
+        .chain(scripts::plan(root, flowmates_dir.as_deref())?);
+    for (path, outcome) in installed {
+        match outcome {
+            Outcome::Installed => push(path, "missing; run `echo update`".into()),
+            Outcome::Updated => push(path, "outdated; run `echo update`".into()),
+            Outcome::Modified => push(path, "differs from its source".into()),
+            _ => {}
+        }
+    }
+
+    if gitutil::is_repo(root) {
+        let available = hooks::available(flowmates_dir.as_deref())?;
+        for hook in hooks::status(root, &available, None)? {
+            if hook.state == HookState::Outdated {
+                push(
+                    PathBuf::from(&hook.name),
+                    "hook installed from another version of its script; run `echo hooks install`"
+                        .into(),
+                );
+            }
+        }
+        for hook in verify::verify(root, None)? {
+            if let verify::Integrity::Modified(reason) = hook.integrity {
+                push(
+                    PathBuf::from(&hook.name),
+                    format!("hook modified: {}", reason),
+                );
+            }
+        }
+    }
+
+    for violation in validate::validate(&issues::scan(root)?) {
+        push(violation.path, violation.message);
+    }
+
+    let agent_md = PathBuf::from(agent::AGENT_MD);
+    let template = agent::load_template(flowmates_dir.as_deref())?;
+    if !root.join(&agent_md).is_file() {
+        push(agent_md, "missing; run `echo init`".into());
+    } else if agent::plan_update(root, agent::Target::Agent, &template, vars)?.update
+        != Update::Unchanged
+    {
+        push(
+            agent_md,
+            "managed section is out of date; run `echo update`".into(),
+        );
+    }
+    Ok(violations)
+}
+
+/// What `echo check` reports without failing: files of the repository's
+/// `scripts/` the flowmates checkout does not install.
+pub fn warnings(root: &Path, config: &Config) -> Result<Vec<String>> {
+    let flowmates_dir = config.rules.flowmates_dir();
+    Ok(scripts::extra(root, flowmates_dir.as_deref())?
+        .into_iter()
+        .map(|path| {
+            format!(
+                "{} is not a script of the flowmates checkout",
+                path.display()
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::ops;
+    use crate::rules::rule_path;
+    use crate::template;
+    use std::fs;
+
+    #[test]
+    fn reports_drift_until_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let config = Config::default();
+        let vars = template::variables(root, "app", 0, &config.variables);
+        ops::create_issue_workflow_structure(root, "app").unwrap();
+        let paths = |violations: Vec<Violation>| -> Vec<PathBuf> {
+            violations.into_iter().map(|v| v.path).collect()
+        };
+        let found = paths(check(root, &config, &vars).unwrap());
+        assert!(found.contains(&rule_path("echo-workflow")));
+        assert!(found.contains(&PathBuf::from(agent::AGENT_MD)));
+
+        agent::update(root, agent::Target::Agent, agent::DEFAULT_TEMPLATE, &vars).unwrap();
+        rules::sync(root, &config.rules, false).unwrap();
+        templates::install(root, None, None, false).unwrap();
+        assert!(check(root, &config, &vars).unwrap().is_empty());
+
+        fs::write(root.join(rule_path("echo-workflow")), "mine\n").unwrap();
+        fs::write(
+            root.join("issues/app/todo/login.md"),
+            "# Login\n\n**Status:** Done\n",
+        )
+        .unwrap();
+        let mut found = paths(check(root, &config, &vars).unwrap());
+        found.dedup();
+        assert_eq!(
+            found,
+            vec![
+                rule_path("echo-workflow"),
+                PathBuf::from("issues/app/todo/login.md")
+            ]
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Verification of a flowmates checkout against a detached [`SUMS_FILE`],
+//! so rules distributed as an archive can be checked before anything is
+//! installed from them.
+//!
+//! The file is in the format of `sha256sum`: one `<hex digest>  <path>`
+//! line per file, paths relative to the checkout. Every rule, template,
+//! script, manifest and `pack.toml` of the checkout must be listed with its
+//! digest.
+//!
+//! When trusted keys are configured, the sums file must also carry a
+//! detached minisign signature, [`SIGNATURE_FILE`], by one of them.
+
+use crate::issues::templates::FLOWMATES_TEMPLATES_DIR;
+use crate::pack::PACK_FILE;
+use crate::presets::{MANIFEST_FILE, PRESETS_DIR};
+use crate::rules::{bundles, RulesConfig, FLOWMATES_RULES_DIR};
+use crate::scan_cache::ScanCache;
+use crate::scripts::SCRIPTS_DIR;
+use crate::source_scan::SourceFilter;
+use anyhow::{anyhow, bail, Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sums file name, at the root of the checkout.
+pub const SUMS_FILE: &str = "SHA256SUMS";
+
+/// Minisign signature of [`SUMS_FILE`], next to it.
+pub const SIGNATURE_FILE: &str = "SHA256SUMS.minisig";
+
+/// Verify the flowmates checkout `config` installs from, if there is one:
+/// its signature when `trusted-keys` are set, then its files, hashed
+/// through `cache` when given, which must be of the checkout. Returns the
+/// number of files verified, as [`verify`] does.
+pub fn verify_source(config: &RulesConfig, cache: Option<&ScanCache>) -> Result<Option<usize>> {
+    let Some(dir) = config.flowmates_dir().filter(|dir| dir.is_dir()) else {
+        return Ok(None);
+    };
+    let signed = !config.trusted_keys.is_empty();
+    if signed {
+        verify_signature(&dir, &config.trusted_keys)?;
+    }
+    let required = config.require_checksums || signed;
+    match cache {
+        Some(cache) => verify_with(&dir, required, cache),
+        None => verify(&dir, required),
+    }
+}
+
+/// Check that the [`SUMS_FILE`] of `flowmates_dir` is signed by one of
+/// `trusted_keys`, minisign public keys in base64.
+pub fn verify_signature(flowmates_dir: &Path, trusted_keys: &[String]) -> Result<()> {
+    let path = flowmates_dir.join(SIGNATURE_FILE);
+    if !path.is_file() {
+        bail!(
+            "{} has no {} and trusted keys are configured",
+            flowmates_dir.display(),
+            SIGNATURE_FILE
+        );
+    }
+    let signature =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let signature = Signature::decode(&signature)
+        .map_err(|err| anyhow!("invalid {}: {}", path.display(), err))?;
+    let sums = flowmates_dir.join(SUMS_FILE);
+    let content = fs::read(&sums).with_context(|| format!("failed to read {}", sums.display()))?;
+    for key in trusted_keys {
+        let key = PublicKey::from_base64(key.trim())
+            .map_err(|err| anyhow!("invalid trusted key '{}': {}", key, err))?;
+        if key.verify(&content, &signature, false).is_ok() {
+            return Ok(());
+        }
+    }
+    bail!(
+        "{} of {} is not signed by a trusted key, nothing installed",
+        SUMS_FILE,
+        flowmates_dir.display()
+    );
+}
+
+/// Check the files of `flowmates_dir` against its [`SUMS_FILE`]. Returns
+/// the number of files verified, or `None` without a sums file, which is
+/// an error with `required`.
+pub fn verify(flowmates_dir: &Path, required: bool) -> Result<Option<usize>> {
+    verify_with(flowmates_dir, required, &ScanCache::disabled(flowmates_dir))
+}
+
+/// Like [`verify`], hashing through `cache`, which must be of
+/// `flowmates_dir`.
+pub fn verify_with(
+    flowmates_dir: &Path,
+    required: bool,
+    cache: &ScanCache,
+) -> Result<Option<usize>> {
+    let path = flowmates_dir.join(SUMS_FILE);
+    if !path.is_file() {
+        if required {
+            bail!(
+                "{} has no {} and [rules] require-checksums is set",
+                flowmates_dir.display(),
+                SUMS_FILE
+            );
+        }
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let sums = parse(&content)?;
+    let mut problems = Vec::new();
+    for (file, expected) in &sums {
+        match cache.hash(file) {
+            Ok(hash) if hash == *expected => {}
+            Ok(_) => problems.push(format!("{}: checksum mismatch", file.display())),
+            Err(_) => problems.push(format!("{}: listed but missing", file.display())),
+        }
+    }
+    for file in covered_files(flowmates_dir)? {
+        if !sums.contains_key(&file) {
+            problems.push(format!("{}: not listed in {}", file.display(), SUMS_FILE));
+        }
+    }
+    if !problems.is_empty() {
+        bail!(
+            "{} failed verification, nothing installed:\n  {}",
+            flowmates_dir.display(),
+            problems.join("\n  ")
+        );
+    }
+    Ok(Some(sums.len()))
+}
+
+/// Digests by path of a sums file. A `*` before the path, as written by
+/// `sha256sum --binary`, is ignored.
+fn parse(content: &str) -> Result<BTreeMap<PathBuf, String>> {
+    let mut sums = BTreeMap::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((digest, file)) = line.split_once(char::is_whitespace) else {
+            bail!(
+                "{} line {}: expected `<sha256>  <path>`",
+                SUMS_FILE,
+                number + 1
+            );
+        };
+        let file = file.trim_start();
+        let file = file.strip_prefix('*').unwrap_or(file);
+        if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("{} line {}: invalid SHA-256 digest", SUMS_FILE, number + 1);
+        }
+        let file = file.strip_prefix("./").unwrap_or(file);
+        sums.insert(PathBuf::from(file), digest.to_ascii_lowercase());
+    }
+    Ok(sums)
+}
+
+/// Files of the checkout echo installs from, relative to it.
+fn covered_files(flowmates_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for manifest in [MANIFEST_FILE, bundles::MANIFEST_FILE, PACK_FILE] {
+        if flowmates_dir.join(manifest).is_file() {
+            files.push(PathBuf::from(manifest));
+        }
+    }
+    for dir in [
+        FLOWMATES_RULES_DIR,
+        FLOWMATES_TEMPLATES_DIR,
+        PRESETS_DIR,
+        SCRIPTS_DIR,
+    ] {
+        collect(flowmates_dir, Path::new(dir), &mut files)?;
+    }
+    Ok(files)
+}
+
+/// Add the source files under `base/rel` to `files`, as paths relative
+/// to `base`, a flowmates checkout (see [`crate::source_scan`]).
+pub(crate) fn collect(base: &Path, rel: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    collect_filtered(base, rel, &SourceFilter::load(base)?, files)
+}
+
+fn collect_filtered(
+    base: &Path,
+    rel: &Path,
+    filter: &SourceFilter,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let dir = base.join(rel);
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let rel = rel.join(entry.file_name());
+        let path = entry.path();
+        if filter.skips(&rel) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_filtered(base, &rel, filter, files)?;
+        } else if path.is_file() {
+            files.push(rel);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock;
+
+    #[test]
+    fn verifies_every_installable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(verify(dir.path(), false).unwrap(), None);
+        assert!(verify(dir.path(), true).is_err());
+
+        fs::create_dir_all(dir.path().join("rules/rust")).unwrap();
+        fs::write(dir.path().join("rules/rust/style.mdc"), "style\n").unwrap();
+        fs::write(dir.path().join("README.md"), "not installed\n").unwrap();
+        let sums = format!("{}  ./rules/rust/style.mdc\n", lock::hash("style\n"));
+        fs::write(dir.path().join(SUMS_FILE), &sums).unwrap();
+        assert_eq!(verify(dir.path(), true).unwrap(), Some(1));
+
+        fs::write(dir.path().join("rules/rust/style.mdc"), "tampered\n").unwrap();
+        fs::write(dir.path().join("rules/extra.mdc"), "extra\n").unwrap();
+        let err = verify(dir.path(), false).unwrap_err().to_string();
+        assert!(err.contains("rules/rust/style.mdc: checksum mismatch"));
+        assert!(err.contains("rules/extra.mdc: not listed in SHA256SUMS"));
+
+        fs::write(dir.path().join(SUMS_FILE), "abc rules/extra.mdc\n").unwrap();
+        assert!(verify(dir.path(), false).is_err());
+    }
+
+    #[test]
+    fn installs_nothing_from_an_unverified_checkout() {
+        let flowmates = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        fs::create_dir(flowmates.path().join("rules")).unwrap();
+        fs::write(flowmates.path().join("rules/style.mdc"), "style\n").unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            require_checksums: true,
+            ..RulesConfig::default()
+        };
+        let err = crate::rules::sync(repo.path(), &config, false).unwrap_err();
+        assert!(err.to_string().contains("require-checksums"));
+        assert!(fs::read_dir(repo.path()).unwrap().next().is_none());
+    }
+
+    // From the test vectors of minisign-verify: "test", signed.
+    const KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+    #[test]
+    fn requires_a_trusted_signature_when_keys_are_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(dir.path().to_path_buf()),
+            trusted_keys: vec![KEY.to_string()],
+            ..RulesConfig::default()
+        };
+        let err = verify_source(&config, None).unwrap_err().to_string();
+        assert!(err.contains("has no SHA256SUMS.minisig"), "{}", err);
+
+        fs::write(dir.path().join(SUMS_FILE), "test").unwrap();
+        fs::write(dir.path().join(SIGNATURE_FILE), SIGNATURE).unwrap();
+        verify_signature(dir.path(), &config.trusted_keys).unwrap();
+        assert!(verify_signature(dir.path(), &[]).is_err());
+        assert!(verify_signature(dir.path(), &["not a key".to_string()]).is_err());
+
+        fs::write(dir.path().join(SUMS_FILE), "tampered").unwrap();
+        let err = verify_source(&config, None).unwrap_err().to_string();
+        assert!(err.contains("not signed by a trusted key"), "{}", err);
+    }
+}
@@ -0,0 +1,152 @@
+//! Repository configuration read from `.echo.toml`.
+
+use crate::context::ContextConfig;
+use crate::fs_ops;
+use crate::gitignore::ExcludeStrategy;
+use crate::hooks::runner::HooksConfig;
+use crate::issues::lint::LintConfig;
+use crate::notify::NotificationsConfig;
+use crate::plugins::PluginsConfig;
+use crate::rules::RulesConfig;
+use crate::source_pin::SourceConfig;
+use crate::template::Variables;
+use crate::workspace::WorkspaceConfig;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Configuration file name, relative to the repository root.
+pub const CONFIG_FILE: &str = ".echo.toml";
+
+/// Contents of `.echo.toml`. Every section is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Presets applied by `echo init` and `echo update`; auto-detected when
+    /// empty.
+    pub presets: Vec<String>,
+    /// Commit `.cursor/` instead of listing it in `.gitignore`.
+    pub vendor: bool,
+    /// File the `.cursor/` ignore entry goes to when not vendoring.
+    #[serde(rename = "exclude-strategy")]
+    pub exclude_strategy: ExcludeStrategy,
+    /// Log the files each mutating command changes to
+    /// [`crate::audit_log::LOG_FILE`].
+    #[serde(rename = "audit-log")]
+    pub audit_log: bool,
+    pub hooks: HooksConfig,
+    /// Sections and budget of `echo context build`.
+    pub context: ContextConfig,
+    pub lint: LintConfig,
+    /// Webhook told about issues changing state.
+    pub notifications: NotificationsConfig,
+    pub rules: RulesConfig,
+    /// Flowmates repository pinned for every contributor.
+    pub source: SourceConfig,
+    /// Versions of the flowmates rule pack accepted, e.g. `^2.1` (see
+    /// [`crate::pack`]).
+    #[serde(rename = "rules-pack", alias = "rules_pack")]
+    pub rules_pack: Option<String>,
+    /// User-defined template variables.
+    pub variables: Variables,
+    /// Packages set up by `echo init --workspace`.
+    pub workspace: WorkspaceConfig,
+    /// Plugins, by name.
+    pub plugins: PluginsConfig,
+    /// Shell commands run after a successful `echo init`.
+    #[serde(rename = "post-init")]
+    pub post_init: Vec<String>,
+    /// Shell commands run after a successful `echo update`.
+    #[serde(rename = "post-update")]
+    pub post_update: Vec<String>,
+}
+
+impl Config {
+    /// Load `.echo.toml` from `root`, falling back to defaults when absent.
+    pub fn load(root: &Path) -> Result<Config> {
+        let path = root.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(Config::default());
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {}", CONFIG_FILE))?;
+        Config::parse(&content)
+    }
+
+    /// Parse the TOML contents of a configuration file.
+    pub fn parse(content: &str) -> Result<Config> {
+        toml::from_str(content).with_context(|| format!("invalid {}", CONFIG_FILE))
+    }
+}
+
+/// Edit `.echo.toml` in place, keeping comments and formatting, and
+/// validate the result before writing. The file is created if missing.
+pub fn edit(root: &Path, f: impl FnOnce(&mut toml_edit::DocumentMut) -> Result<()>) -> Result<()> {
+    let path = root.join(CONFIG_FILE);
+    let content = if path.is_file() {
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", CONFIG_FILE))?
+    } else {
+        String::new()
+    };
+    let mut doc: toml_edit::DocumentMut = content
+        .parse()
+        .with_context(|| format!("invalid {}", CONFIG_FILE))?;
+    f(&mut doc)?;
+    let updated = doc.to_string();
+    Config::parse(&updated)?;
+    fs_ops::write_atomic(&path, updated.as_bytes(), true)
+        .with_context(|| format!("failed to write {}", CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_uses_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Config::load(dir.path()).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn parses_lint_section() {
+        let config =
+            Config::parse("[lint]\nplaceholders = false\nmax-title-length = 40\n").unwrap();
+        assert!(!config.lint.placeholders);
+        assert!(config.lint.title);
+        assert_eq!(config.lint.max_title_length, 40);
+    }
+
+    #[test]
+    fn parses_variables() {
+        let config = Config::parse("[variables]\nTEAM = \"core\"\n").unwrap();
+        assert_eq!(config.variables["TEAM"], "core");
+    }
+
+    #[test]
+    fn parses_exclude_strategy() {
+        let config = Config::parse("exclude-strategy = \"info-exclude\"\n").unwrap();
+        assert_eq!(config.exclude_strategy, ExcludeStrategy::InfoExclude);
+        assert!(Config::parse("exclude-strategy = \"svn\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_post_run_commands() {
+        let config = Config::parse("post-update = [\"cargo fmt\"]\n").unwrap();
+        assert_eq!(config.post_update, vec!["cargo fmt"]);
+        assert!(config.post_init.is_empty());
+    }
+
+    #[test]
+    fn parses_plugins() {
+        let config = Config::parse("[plugins.secrets]\ninit = true\n").unwrap();
+        assert!(config.plugins["secrets"].init);
+        assert_eq!(config.plugins["secrets"].path, None);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(Config::parse("[lint]\nplaceholder = false\n").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! `echo context build`: a single markdown file bootstrapping an agent
+//! with the repository's instructions, rules and open issues, sized to a
+//! token budget.
+//!
+//! Tokens are estimated rather than counted with a model's tokenizer (see
+//! [`estimate_tokens`]). Sections are filled in order of their weight,
+//! `[context.weights]` in `.echo.toml`; the first piece that does not fit
+//! is cut at a line boundary, and the pieces after it are left out.
+
+use crate::agent::AGENT_MD;
+use crate::issues::{self, State};
+use crate::provenance;
+use crate::rules::{self, mdc, RulesConfig};
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File written by default, at the repository root.
+pub const CONTEXT_FILE: &str = "context.md";
+
+/// Budget used without `--max-tokens` or `[context] max-tokens`.
+pub const DEFAULT_MAX_TOKENS: usize = 8000;
+
+/// Smallest remainder of the budget worth filling with a cut piece.
+const MIN_CUT_TOKENS: usize = 50;
+
+/// `[context]` section of `.echo.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ContextConfig {
+    /// Token budget of `echo context build`.
+    pub max_tokens: Option<usize>,
+    /// Sections included when `--include` is not given.
+    pub include: Vec<Section>,
+    /// Priority of each section; higher goes first. Defaults to
+    /// [`Section::default_weight`].
+    pub weights: BTreeMap<Section, u32>,
+}
+
+/// Content a context can include.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Section {
+    /// AGENT.md.
+    Agent,
+    /// The enabled installed rules.
+    Rules,
+    /// Issues in todo/ and in_progress/.
+    OpenIssues,
+}
+
+impl Section {
+    pub const ALL: [Section; 3] = [Section::Agent, Section::Rules, Section::OpenIssues];
+
+    /// Weight without `[context.weights]`: instructions first, then rules,
+    /// then issues.
+    pub fn default_weight(self) -> u32 {
+        match self {
+            Section::Agent => 30,
+            Section::Rules => 20,
+            Section::OpenIssues => 10,
+        }
+    }
+}
+
+/// A piece of a section: AGENT.md, one rule or one issue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piece {
+    section: Section,
+    title: String,
+    body: String,
+}
+
+/// What [`build`] put in the context.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Built {
+    /// The context, as markdown.
+    pub content: String,
+    /// Estimated tokens of [`Built::content`].
+    pub tokens: usize,
+    /// Titles of the pieces included whole.
+    pub included: Vec<String>,
+    /// Titles of the pieces cut to fit.
+    pub truncated: Vec<String>,
+    /// Titles of the pieces left out.
+    pub omitted: Vec<String>,
+}
+
+/// Estimated token count of `text`, after the ratios of BPE tokenizers on
+/// English and code: about four characters of a word per token, and a
+/// token for each punctuation character.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    for word in text.split_whitespace() {
+        let mut run: usize = 0;
+        for c in word.chars() {
+            if c.is_alphanumeric() {
+                run += 1;
+            } else {
+                tokens += run.div_ceil(4) + 1;
+                run = 0;
+            }
+        }
+        tokens += run.div_ceil(4);
+    }
+    tokens
+}
+
+/// Build the context of the repository at `root` from `sections`, within
+/// `max_tokens`.
+pub fn build(
+    root: &Path,
+    rules_config: &RulesConfig,
+    config: &ContextConfig,
+    sections: &[Section],
+    max_tokens: usize,
+) -> Result<Built> {
+    let mut pieces = Vec::new();
+    for &section in sections {
+        pieces.extend(collect(root, rules_config, section)?);
+    }
+    let weight = |section: Section| {
+        config
+            .weights
+            .get(&section)
+            .copied()
+            .unwrap_or(section.default_weight())
+    };
+    // Stable: pieces of equal weight keep the order of `sections`.
+    pieces.sort_by_key(|piece| std::cmp::Reverse(weight(piece.section)));
+
+    let mut built = Built {
+        content: "# Context\n".to_string(),
+        ..Built::default()
+    };
+    let mut used = estimate_tokens(&built.content);
+    let mut current = None;
+    for piece in pieces {
+        let mut text = String::new();
+        if current != Some(piece.section) {
+            text.push_str(&format!("\n## {}\n", heading(piece.section)));
+        }
+        text.push_str(&format!("\n### {}\n\n{}\n", piece.title, piece.body.trim()));
+        let tokens = estimate_tokens(&text);
+        if built.truncated.is_empty() && used + tokens <= max_tokens {
+            used += tokens;
+            built.content.push_str(&text);
+            built.included.push(piece.title);
+            current = Some(piece.section);
+        } else if built.truncated.is_empty() && max_tokens.saturating_sub(used) >= MIN_CUT_TOKENS {
+            let cut = cut(&text, max_tokens - used);
+            used += estimate_tokens(&cut);
+            built.content.push_str(&cut);
+            built.truncated.push(piece.title);
+            current = Some(piece.section);
+        } else {
+            built.omitted.push(piece.title);
+        }
+    }
+    built.tokens = used;
+    Ok(built)
+}
+
+/// Write `built` to `path`.
+pub fn write(path: &Path, built: &Built) -> Result<()> {
+    crate::fs_ops::write_atomic(path, built.content.as_bytes(), false)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Where the context goes: `output`, or [`CONTEXT_FILE`] under `root`.
+pub fn output_path(root: &Path, output: Option<&Path>) -> PathBuf {
+    output.map_or_else(|| root.join(CONTEXT_FILE), Path::to_path_buf)
+}
+
+fn heading(section: Section) -> &'static str {
+    match section {
+        Section::Agent => "Agent instructions",
+        Section::Rules => "Rules",
+        Section::OpenIssues => "Open issues",
+    }
+}
+
+/// The longest run of whole lines of `text` within `budget` tokens,
+/// marked as cut. Code fences left open are closed.
+fn cut(text: &str, budget: usize) -> String {
+    const MARK: &str = "\n_(truncated)_\n";
+    // Room for the mark and a closing fence.
+    let budget = budget.saturating_sub(estimate_tokens(MARK) + estimate_tokens("```\n"));
+    let mut kept = String::new();
+    let mut tokens = 0;
+    let mut in_fence = false;
+    for line in text.split_inclusive('\n') {
+        let line_tokens = estimate_tokens(line);
+        if tokens + line_tokens > budget {
+            break;
+        }
+        tokens += line_tokens;
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        kept.push_str(line);
+    }
+    if in_fence {
+        kept.push_str("```\n");
+    }
+    kept.push_str(MARK);
+    kept
+}
+
+fn collect(root: &Path, rules_config: &RulesConfig, section: Section) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    match section {
+        Section::Agent => {
+            let path = root.join(AGENT_MD);
+            if path.is_file() {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", AGENT_MD))?;
+                pieces.push(Piece {
+                    section,
+                    title: AGENT_MD.to_string(),
+                    body: content,
+                });
+            }
+        }
+        Section::Rules => {
+            for rule in rules::list(root, rules_config)? {
+                if rule.disabled {
+                    continue;
+                }
+                let content = fs::read_to_string(root.join(&rule.path))
+                    .with_context(|| format!("failed to read {}", rule.path.display()))?;
+                let content = provenance::strip(&content);
+                let body = mdc::parse(&content).map_or(&*content, |mdc| mdc.body);
+                let body = match &rule.description {
+                    Some(description) => format!("_{}_\n\n{}", description, body.trim()),
+                    None => body.to_string(),
+                };
+                pieces.push(Piece {
+                    section,
+                    title: rule.name,
+                    body,
+                });
+            }
+        }
+        Section::OpenIssues => {
+            for issue in issues::scan(root)? {
+                if !matches!(issue.state, State::Todo | State::InProgress) {
+                    continue;
+                }
+                let content = fs::read_to_string(root.join(&issue.path))
+                    .with_context(|| format!("failed to read {}", issue.path.display()))?;
+                // The issue's own `# ` title becomes the piece title.
+                let body = content
+                    .split_once('\n')
+                    .filter(|(first, _)| first.starts_with("# "))
+                    .map_or(content.as_str(), |(_, rest)| rest)
+                    .to_string();
+                pieces.push(Piece {
+                    section,
+                    title: format!("{} ({}, {})", issue.title, issue.id(), issue.state.label()),
+                    body,
+                });
+            }
+        }
+    }
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_tokens_like_a_bpe_tokenizer() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("the cat sat"), 3);
+        assert_eq!(estimate_tokens("internationalization"), 5);
+        assert_eq!(estimate_tokens("fn main() {}"), 6);
+    }
+
+    #[test]
+    fn fills_the_budget_by_weight_and_cuts_the_first_piece_over_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(AGENT_MD), "# Agent\n\nRead the rules.\n").unwrap();
+        let rules_dir = root.join(rules::RULES_DIR);
+        fs::create_dir_all(&rules_dir).unwrap();
+        let long: String = (0..200)
+            .map(|i| format!("Line {} of the rule.\n", i))
+            .collect();
+        fs::write(
+            rules_dir.join("a-long.mdc"),
+            format!("---\ndescription: Long\n---\n{}", long),
+        )
+        .unwrap();
+        fs::write(rules_dir.join("b-short.mdc"), "Short rule.\n").unwrap();
+        let rules_config = RulesConfig::default();
+        let config = ContextConfig::default();
+
+        let all = build(root, &rules_config, &config, &Section::ALL, 100_000).unwrap();
+        assert_eq!(all.included, vec!["AGENT.md", "a-long", "b-short"]);
+        assert!(all
+            .content
+            .contains("## Agent instructions\n\n### AGENT.md"));
+        assert!(all.content.contains("_Long_\n\nLine 0 of the rule."));
+        assert_eq!(all.tokens, estimate_tokens(&all.content));
+
+        let small = build(root, &rules_config, &config, &Section::ALL, 200).unwrap();
+        assert_eq!(small.included, vec!["AGENT.md"]);
+        assert_eq!(small.truncated, vec!["a-long"]);
+        assert_eq!(small.omitted, vec!["b-short"]);
+        assert!(small.content.ends_with("_(truncated)_\n"));
+        assert!(small.tokens <= 200);
+
+        let config = ContextConfig {
+            weights: BTreeMap::from([(Section::Agent, 1)]),
+            ..ContextConfig::default()
+        };
+        let rules_first = build(root, &rules_config, &config, &Section::ALL, 100_000).unwrap();
+        assert_eq!(rules_first.included[2], "AGENT.md");
+        let agent_only = build(root, &rules_config, &config, &[Section::Agent], 100).unwrap();
+        assert_eq!(agent_only.included, vec!["AGENT.md"]);
+    }
+}
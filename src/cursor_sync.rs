@@ -0,0 +1,270 @@
+//! `echo sync-cursor`: keep the global Cursor rules of `~/.cursor/rules/`
+//! in step with the rules of the flowmates checkout.
+//!
+//! What was synced is recorded in [`STATE_FILE`] of the flowmates
+//! configuration directory: the hash of each rule written, so local edits
+//! are kept and rules deleted upstream can be pruned, and the time of the
+//! last sync, so `echo init` can warn when it is stale.
+
+use crate::fs_ops;
+use crate::lock::{self, Outcome};
+use crate::rules::{self, rule_path, Source, RULES_DIR};
+use crate::uninstall::prune_empty_parents;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// State file name, in the flowmates configuration directory.
+pub const STATE_FILE: &str = "cursor-sync.toml";
+
+/// Age after which the last sync is reported as stale: a week.
+pub const STALE_AFTER: u64 = 7 * 24 * 60 * 60;
+
+/// Contents of [`STATE_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct State {
+    /// When the rules were last synced, in seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_synced: Option<u64>,
+    /// SHA-256 of each rule written, by rule name.
+    pub rules: BTreeMap<String, String>,
+}
+
+impl State {
+    /// Load the state of `config_dir`; empty when absent.
+    pub fn load(config_dir: &Path) -> Result<State> {
+        let path = config_dir.join(STATE_FILE);
+        if !path.is_file() {
+            return Ok(State::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    /// Write the state to `config_dir`, creating the directory.
+    pub fn save(&self, config_dir: &Path) -> Result<()> {
+        fs::create_dir_all(config_dir)
+            .with_context(|| format!("failed to create {}", config_dir.display()))?;
+        let path = config_dir.join(STATE_FILE);
+        fs_ops::write_atomic(&path, toml::to_string(self)?.as_bytes(), true)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// A warning when the last sync is older than [`STALE_AFTER`] at
+    /// `now`. Nothing when the rules were never synced.
+    pub fn staleness(&self, now: u64) -> Option<String> {
+        let age = now.saturating_sub(self.last_synced?);
+        (age > STALE_AFTER).then(|| {
+            format!(
+                "~/.cursor/rules was last synced {} days ago; run `echo sync-cursor`",
+                age / (24 * 60 * 60)
+            )
+        })
+    }
+}
+
+/// How a global rule differs from the flowmates checkout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    /// In the checkout, not installed.
+    Missing,
+    /// Installed as synced, but the checkout has changed since.
+    Outdated,
+    /// Edited since it was synced.
+    Modified,
+    /// Synced before, since deleted from the checkout.
+    Deleted,
+}
+
+impl Drift {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Drift::Missing => "missing",
+            Drift::Outdated => "outdated",
+            Drift::Modified => "modified locally",
+            Drift::Deleted => "deleted upstream",
+        }
+    }
+}
+
+/// What [`sync`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Synced {
+    /// Rules written or left alone, relative to the home directory.
+    pub installed: Vec<(PathBuf, Outcome)>,
+    /// Rules deleted upstream and removed.
+    pub removed: Vec<PathBuf>,
+    /// Rules deleted upstream but kept, because they were edited since.
+    pub kept: Vec<PathBuf>,
+}
+
+/// Rules of the flowmates checkout, by name.
+fn sources(flowmates_dir: &Path) -> Result<BTreeMap<String, String>> {
+    Ok(rules::sources(Some(flowmates_dir))?
+        .into_values()
+        .filter(|rule| rule.source == Source::Flowmates)
+        .map(|rule| (rule.name, rule.content))
+        .collect())
+}
+
+/// Compare the rules under `home/.cursor/rules/` with those of
+/// `flowmates_dir`. Rules in step are left out.
+pub fn check(home: &Path, flowmates_dir: &Path, state: &State) -> Result<Vec<(PathBuf, Drift)>> {
+    let sources = sources(flowmates_dir)?;
+    let mut drift = Vec::new();
+    for (name, content) in &sources {
+        let path = rule_path(name);
+        let outcome = Outcome::of(
+            home,
+            &path,
+            content,
+            false,
+            state.rules.get(name).map(String::as_str),
+        );
+        match outcome {
+            Outcome::Installed => drift.push((path, Drift::Missing)),
+            Outcome::Updated => drift.push((path, Drift::Outdated)),
+            Outcome::Modified => drift.push((path, Drift::Modified)),
+            _ => {}
+        }
+    }
+    for name in state
+        .rules
+        .keys()
+        .filter(|name| !sources.contains_key(*name))
+    {
+        let path = rule_path(name);
+        if home.join(&path).is_file() {
+            drift.push((path, Drift::Deleted));
+        }
+    }
+    Ok(drift)
+}
+
+/// Install the rules of `flowmates_dir` under `home/.cursor/rules/`,
+/// keeping those edited since the last sync, and with `prune` remove the
+/// synced rules deleted upstream. Updates `state`, `now` included.
+pub fn sync(
+    home: &Path,
+    flowmates_dir: &Path,
+    state: &mut State,
+    prune: bool,
+    now: u64,
+) -> Result<Synced> {
+    let sources = sources(flowmates_dir)?;
+    let mut synced = Synced::default();
+    for (name, content) in &sources {
+        let path = rule_path(name);
+        let locked = state.rules.get(name).map(String::as_str);
+        let outcome = Outcome::of(home, &path, content, false, locked);
+        if outcome.is_written() {
+            let full = home.join(&path);
+            fs_ops::write_file(&fs_ops::RealFs, &full, content.as_bytes())
+                .with_context(|| format!("failed to write {}", full.display()))?;
+        }
+        if outcome != Outcome::Modified {
+            state.rules.insert(name.clone(), lock::hash(content));
+        }
+        synced.installed.push((path, outcome));
+    }
+    if prune {
+        let deleted: Vec<String> = state
+            .rules
+            .keys()
+            .filter(|name| !sources.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in deleted {
+            let path = rule_path(&name);
+            let full = home.join(&path);
+            let hash = &state.rules[&name];
+            match fs::read_to_string(&full) {
+                Ok(content) if lock::hash(&content) != *hash => {
+                    synced.kept.push(path);
+                    continue;
+                }
+                Ok(_) => {
+                    fs::remove_file(&full)
+                        .with_context(|| format!("failed to remove {}", full.display()))?;
+                    prune_empty_parents(&home.join(RULES_DIR), &full);
+                    synced.removed.push(path);
+                }
+                Err(_) => {}
+            }
+            state.rules.remove(&name);
+        }
+    }
+    state.last_synced = Some(now);
+    Ok(synced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::FLOWMATES_RULES_DIR;
+
+    fn source(flowmates: &Path, name: &str, content: &str) {
+        let path = flowmates
+            .join(FLOWMATES_RULES_DIR)
+            .join(format!("{}.mdc", name));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn syncs_checks_and_prunes_global_rules() {
+        let home = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        source(flowmates.path(), "style", "style\n");
+        source(flowmates.path(), "old/review", "review\n");
+        let mut state = State::default();
+        assert_eq!(
+            check(home.path(), flowmates.path(), &state).unwrap(),
+            vec![
+                (rule_path("old/review"), Drift::Missing),
+                (rule_path("style"), Drift::Missing),
+            ]
+        );
+        sync(home.path(), flowmates.path(), &mut state, false, 10).unwrap();
+        assert!(check(home.path(), flowmates.path(), &state)
+            .unwrap()
+            .is_empty());
+        assert_eq!(state.last_synced, Some(10));
+
+        fs::remove_file(flowmates.path().join("rules/old/review.mdc")).unwrap();
+        source(flowmates.path(), "style", "tabs\n");
+        assert_eq!(
+            check(home.path(), flowmates.path(), &state).unwrap(),
+            vec![
+                (rule_path("style"), Drift::Outdated),
+                (rule_path("old/review"), Drift::Deleted),
+            ]
+        );
+        let synced = sync(home.path(), flowmates.path(), &mut state, true, 20).unwrap();
+        assert_eq!(synced.removed, vec![rule_path("old/review")]);
+        assert!(!home.path().join(".cursor/rules/old").exists());
+        assert_eq!(
+            fs::read_to_string(home.path().join(rule_path("style"))).unwrap(),
+            "tabs\n"
+        );
+        assert!(!state.rules.contains_key("old/review"));
+    }
+
+    #[test]
+    fn warns_when_the_last_sync_is_stale() {
+        let state = State {
+            last_synced: Some(0),
+            ..State::default()
+        };
+        assert_eq!(state.staleness(STALE_AFTER), None);
+        assert!(state
+            .staleness(STALE_AFTER + 3 * 24 * 60 * 60)
+            .unwrap()
+            .contains("10 days ago"));
+        assert_eq!(State::default().staleness(u64::MAX), None);
+    }
+}
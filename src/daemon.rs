@@ -0,0 +1,238 @@
+//! `echo daemon`: keep every registered repository up to date with its
+//! rule and template sources.
+//!
+//! Each round syncs the repositories of the [`crate::registry`]: missing
+//! rules and templates are installed and rules not edited since echo wrote
+//! them follow their source (see [`rules::sync`]). The outcome of each
+//! round is kept in [`STATE_FILE`] for `echo daemon status`. A repository
+//! is synced under its run lock, waiting for the run holding it.
+
+use crate::environment::Environment;
+use crate::issues::templates;
+use crate::lock::Outcome;
+use crate::presets;
+use crate::registry::Registry;
+use crate::rules;
+use crate::run_lock::RunLock;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// State file name, in the flowmates configuration directory.
+pub const STATE_FILE: &str = "daemon.toml";
+
+/// Default delay between two rounds, in seconds.
+pub const DEFAULT_INTERVAL: u64 = 300;
+
+/// Contents of [`STATE_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct State {
+    /// Process running the rounds.
+    pub pid: Option<u32>,
+    /// Seconds between rounds.
+    pub interval: u64,
+    /// End of the last round, in seconds since the epoch.
+    pub last_round: Option<u64>,
+    /// Outcome per repository path.
+    #[serde(rename = "repo")]
+    pub repos: BTreeMap<String, RepoState>,
+}
+
+/// Outcome of the last sync of one repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RepoState {
+    /// Last successful sync, in seconds since the epoch.
+    pub last_sync: Option<u64>,
+    /// Files written by that sync.
+    pub written: usize,
+    /// Why the last attempt failed, if it did.
+    pub error: Option<String>,
+}
+
+impl State {
+    /// Load the state of `config_dir`; empty when absent.
+    pub fn load(config_dir: &Path) -> Result<State> {
+        let path = config_dir.join(STATE_FILE);
+        if !path.is_file() {
+            return Ok(State::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    fn save(&self, config_dir: &Path) -> Result<()> {
+        fs::create_dir_all(config_dir)?;
+        let path = config_dir.join(STATE_FILE);
+        let content = format!(
+            "# Written by `echo daemon`; do not edit.\n\n{}",
+            toml::to_string(self)?
+        );
+        fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Whether a daemon finished a round recently enough to be alive at
+    /// `now`: within two intervals.
+    pub fn is_running(&self, now: u64) -> bool {
+        self.last_round
+            .is_some_and(|last| now.saturating_sub(last) <= 2 * self.interval.max(1))
+    }
+}
+
+/// Bring the repository at `root` up to date, with its configuration as
+/// `env` completes it; returns the paths written.
+pub fn sync_repo(env: &Environment, root: &Path) -> Result<Vec<PathBuf>> {
+    let mut config = env.load_config(root)?;
+    let flowmates_dir = config.rules.flowmates_dir();
+    let manifest = presets::load(flowmates_dir.as_deref())?;
+    let names = presets::resolve(root, &config.presets, &manifest);
+    let selection = presets::select(&manifest, &names)?;
+    config.rules.preset = selection.rules;
+    let mut installed: Vec<(PathBuf, Outcome)> = rules::sync(root, &config.rules, false)?
+        .into_iter()
+        .flat_map(|(_, installed)| installed)
+        .collect();
+    installed.extend(templates::install(
+        root,
+        flowmates_dir.as_deref(),
+        selection.templates.as_deref(),
+        false,
+    )?);
+    Ok(installed
+        .into_iter()
+        .filter(|(_, outcome)| outcome.is_written())
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// Sync every registered repository once and record the outcome. `report`
+/// receives a line per repository that changed or failed.
+pub fn round(
+    env: &Environment,
+    config_dir: &Path,
+    interval: u64,
+    mut report: impl FnMut(&str),
+) -> Result<State> {
+    let registry = Registry::load(config_dir)?;
+    let mut state = State::load(config_dir)?;
+    state.pid = Some(std::process::id());
+    state.interval = interval;
+    state
+        .repos
+        .retain(|path, _| registry.projects.iter().any(|p| p == Path::new(path)));
+    for root in &registry.projects {
+        let repo = state.repos.entry(root.display().to_string()).or_default();
+        let outcome = if root.is_dir() {
+            RunLock::acquire(root, true, |_| {}).and_then(|_lock| sync_repo(env, root))
+        } else {
+            Err(anyhow::anyhow!("{} does not exist", root.display()))
+        };
+        match outcome {
+            Ok(written) => {
+                repo.last_sync = Some(env.now());
+                repo.written = written.len();
+                repo.error = None;
+                if !written.is_empty() {
+                    report(&format!(
+                        "{}: wrote {} file(s)",
+                        root.display(),
+                        written.len()
+                    ));
+                }
+            }
+            Err(err) => {
+                report(&format!("{}: {:#}", root.display(), err));
+                repo.error = Some(format!("{:#}", err));
+            }
+        }
+    }
+    state.last_round = Some(env.now());
+    state.save(config_dir)?;
+    Ok(state)
+}
+
+/// Run a [`round`] every `interval` seconds until interrupted.
+pub fn run(
+    env: &Environment,
+    config_dir: &Path,
+    interval: u64,
+    mut report: impl FnMut(&str),
+) -> Result<()> {
+    loop {
+        round(env, config_dir, interval, &mut report)?;
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::PROJECTS_FILE;
+    use crate::rules::rule_path;
+
+    #[test]
+    fn syncs_registered_repositories_and_records_the_outcome() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let gone = repo.path().join("gone");
+        fs::create_dir(flowmates.path().join("rules")).unwrap();
+        fs::write(flowmates.path().join("rules/style.mdc"), "style\n").unwrap();
+        // The registry's source, as `env.load_config` finds it.
+        fs::write(
+            config_dir.path().join(PROJECTS_FILE),
+            format!(
+                "source = {:?}\nprojects = [{:?}, {:?}]\n",
+                flowmates.path().display().to_string(),
+                repo.path().display().to_string(),
+                gone.display().to_string()
+            ),
+        )
+        .unwrap();
+        let env = Environment {
+            now: Some(1_700_000_000),
+            ..Environment::new(repo.path())
+        }
+        .with_var(
+            "FLOWMATES_CONFIG_DIR",
+            config_dir.path().display().to_string(),
+        );
+
+        let mut lines = Vec::new();
+        let state = round(&env, config_dir.path(), 60, |line| {
+            lines.push(line.to_string())
+        })
+        .unwrap();
+        assert!(repo.path().join(rule_path("echo-workflow")).is_file());
+        assert!(repo.path().join(rule_path("style")).is_file());
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with("does not exist"));
+        let synced = &state.repos[&repo.path().display().to_string()];
+        assert_eq!(synced.last_sync, Some(1_700_000_000));
+        assert!(synced.written > 0);
+        assert!(state.repos[&gone.display().to_string()].error.is_some());
+
+        let loaded = State::load(config_dir.path()).unwrap();
+        assert_eq!(loaded, state);
+        assert!(loaded.is_running(loaded.last_round.unwrap() + 120));
+        assert!(!loaded.is_running(loaded.last_round.unwrap() + 121));
+
+        // A round waits for the run holding the lock of the repository.
+        let lock = RunLock::acquire(repo.path(), false, |_| {}).unwrap();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            drop(lock);
+        });
+        let started = std::time::Instant::now();
+        let state = round(&env, config_dir.path(), 60, |_| {}).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        release.join().unwrap();
+        assert_eq!(state.repos[&repo.path().display().to_string()].written, 0);
+    }
+}
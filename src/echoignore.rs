@@ -0,0 +1,97 @@
+//! `.echoignore`: files echo must never overwrite or delete.
+//!
+//! One glob pattern per line, relative to the repository root, e.g.
+//! `issues/shared/templates/custom-*.md`; blank lines and `#` comments are
+//! skipped. Installs leave matching files that already exist alone, and
+//! `echo uninstall` keeps them.
+
+use crate::lock::Outcome;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::Path;
+
+/// Ignore file, relative to the repository root.
+pub const ECHOIGNORE: &str = ".echoignore";
+
+/// Patterns of an `.echoignore`.
+#[derive(Debug, Clone)]
+pub struct EchoIgnore {
+    patterns: GlobSet,
+}
+
+impl EchoIgnore {
+    /// Load the `.echoignore` of `root`; empty when absent.
+    pub fn load(root: &Path) -> Result<EchoIgnore> {
+        let path = root.join(ECHOIGNORE);
+        if !path.is_file() {
+            return EchoIgnore::parse("");
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {}", ECHOIGNORE))?;
+        EchoIgnore::parse(&content)
+    }
+
+    fn parse(content: &str) -> Result<EchoIgnore> {
+        let mut builder = GlobSetBuilder::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let pattern = line.trim_start_matches('/');
+            builder.add(
+                GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("invalid pattern '{}' in {}", line, ECHOIGNORE))?,
+            );
+        }
+        Ok(EchoIgnore {
+            patterns: builder.build()?,
+        })
+    }
+
+    /// Whether `path`, relative to the repository root, must be left alone.
+    pub fn is_protected(&self, path: &Path) -> bool {
+        self.patterns.is_match(path)
+    }
+
+    /// `outcome` of installing `path`, turned into [`Outcome::Protected`]
+    /// when it would replace a protected file.
+    pub fn guard(&self, path: &Path, outcome: Outcome) -> Outcome {
+        match outcome {
+            Outcome::Installed | Outcome::UpToDate => outcome,
+            _ if self.is_protected(path) => Outcome::Protected,
+            _ => outcome,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_paths_from_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = EchoIgnore::load(dir.path()).unwrap();
+        assert!(!empty.is_protected(Path::new(".cursor/rules/style.mdc")));
+
+        fs::write(
+            dir.path().join(ECHOIGNORE),
+            "# keep ours\n\nissues/shared/templates/custom-*.md\n/.cursor/rules/**\n",
+        )
+        .unwrap();
+        let ignore = EchoIgnore::load(dir.path()).unwrap();
+        assert!(ignore.is_protected(Path::new("issues/shared/templates/custom-bug.md")));
+        assert!(!ignore.is_protected(Path::new("issues/shared/templates/bug_template.md")));
+        assert!(!ignore.is_protected(Path::new("issues/shared/templates/x/custom-a.md")));
+        assert!(ignore.is_protected(Path::new(".cursor/rules/rust/style.mdc")));
+        let custom = Path::new("issues/shared/templates/custom-bug.md");
+        assert_eq!(ignore.guard(custom, Outcome::Updated), Outcome::Protected);
+        assert_eq!(ignore.guard(custom, Outcome::Installed), Outcome::Installed);
+
+        fs::write(dir.path().join(ECHOIGNORE), "[\n").unwrap();
+        assert!(EchoIgnore::load(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,391 @@
+//! The runtime environment commands depend on: working directory,
+//! environment variables and clock.
+//!
+//! `main` captures it once with [`Environment::from_process`]; tests build
+//! one with [`Environment::new`] so nothing reads the real home directory
+//! or `$FLOWMATES_DIR`.
+
+use crate::config::{Config, CONFIG_FILE};
+use crate::gitutil;
+use crate::legacy_config::{LegacyConfig, LEGACY_FILE};
+use crate::profiles::{self, Profile};
+use crate::registry::Registry;
+use crate::source_pin;
+use crate::template;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Working directory, environment variables and, when frozen, the time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    pub cwd: PathBuf,
+    pub vars: BTreeMap<String, String>,
+    /// Seconds since the epoch to use instead of the system clock.
+    pub now: Option<u64>,
+}
+
+impl Environment {
+    /// An environment in `cwd` without variables, on the system clock.
+    pub fn new(cwd: impl Into<PathBuf>) -> Environment {
+        Environment {
+            cwd: cwd.into(),
+            ..Environment::default()
+        }
+    }
+
+    /// The environment of the running process. Variables that are not
+    /// valid UTF-8 are left out.
+    pub fn from_process() -> Result<Environment> {
+        Ok(Environment {
+            cwd: std::env::current_dir().context("failed to resolve current directory")?,
+            vars: std::env::vars_os()
+                .filter_map(|(name, value)| {
+                    Some((name.into_string().ok()?, value.into_string().ok()?))
+                })
+                .collect(),
+            now: None,
+        })
+    }
+
+    /// Set the variable `name`.
+    pub fn with_var(mut self, name: &str, value: impl Into<String>) -> Environment {
+        self.vars.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Value of the variable `name`; unset when empty.
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.vars
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    pub fn home(&self) -> Option<PathBuf> {
+        self.var("HOME").map(PathBuf::from)
+    }
+
+    /// The flowmates checkout named by `$FLOWMATES_DIR`.
+    pub fn flowmates_dir(&self) -> Option<PathBuf> {
+        self.var("FLOWMATES_DIR").map(PathBuf::from)
+    }
+
+    /// The flowmates configuration directory: `$FLOWMATES_CONFIG_DIR`, else
+    /// `flowmates/` under `$XDG_CONFIG_HOME` or `~/.config`.
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = self.var("FLOWMATES_CONFIG_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        let base = self
+            .var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| self.home().map(|home| home.join(".config")))?;
+        Some(base.join("flowmates"))
+    }
+
+    /// echo's cache directory: `echo/` under `$XDG_CACHE_HOME` or
+    /// `~/.cache`.
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        let base = self
+            .var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| self.home().map(|home| home.join(".cache")))?;
+        Some(base.join("echo"))
+    }
+
+    /// echo's data directory: `echo/` under `$XDG_DATA_HOME` or
+    /// `~/.local/share`.
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        let base = self
+            .var("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| self.home().map(|home| home.join(".local/share")))?;
+        Some(base.join("echo"))
+    }
+
+    /// Seconds since the epoch.
+    pub fn now(&self) -> u64 {
+        self.now.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Root of the repository to work on: the work tree containing `dir`,
+    /// relative to the working directory, or the working directory itself.
+    pub fn repository_root(&self, dir: Option<&Path>) -> PathBuf {
+        let dir = match dir {
+            Some(dir) => self.cwd.join(dir),
+            None => self.cwd.clone(),
+        };
+        // Run from anywhere in a work tree as from its root.
+        gitutil::toplevel(&dir).unwrap_or(dir)
+    }
+
+    /// `~/.flowmates/config.json`, read as a fallback (see
+    /// [`crate::legacy_config`]).
+    pub fn legacy_config(&self) -> Result<Option<LegacyConfig>> {
+        match self.home() {
+            Some(home) => LegacyConfig::load(&home),
+            None => Ok(None),
+        }
+    }
+
+    /// The profile named by [`profiles::PROFILE_VAR`], if any, from
+    /// `profiles.toml`, else from `~/.flowmates/config.json`.
+    pub fn profile(&self) -> Result<Option<Profile>> {
+        let Some(name) = self.var(profiles::PROFILE_VAR) else {
+            return Ok(None);
+        };
+        let config_dir = self
+            .config_dir()
+            .context("cannot locate the flowmates configuration for profiles")?;
+        if !config_dir.join(profiles::PROFILES_FILE).is_file() {
+            if let (Some(legacy), Some(home)) = (self.legacy_config()?, self.home()) {
+                let path = home.join(LEGACY_FILE);
+                return profiles::pick(legacy.profiles, name, &path).map(Some);
+            }
+        }
+        profiles::find(&config_dir, name).map(Some)
+    }
+
+    /// Load the `.echo.toml` of `root`, completed by the selected profile
+    /// (see [`profiles`]). The flowmates checkout is that of its `[source]`
+    /// (see [`source_pin`]), else comes from `$FLOWMATES_DIR`, else from
+    /// the source of the registry (see [`Registry::source`]), whose
+    /// trusted keys are added to those of `[rules]`. The source and keys
+    /// the registry leaves unset come from `~/.flowmates/config.json`.
+    pub fn load_config(&self, root: &Path) -> Result<Config> {
+        let mut config = Config::load(root)?;
+        match (&config.source.url, &config.source.rev) {
+            (Some(_), _) if config.rules.flowmates_dir.is_some() => bail!(
+                "{} sets both [source] url and [rules] flowmates-dir; keep one",
+                CONFIG_FILE
+            ),
+            (Some(url), _) => {
+                let data_dir = self
+                    .data_dir()
+                    .context("cannot locate echo's data directory for [source]")?;
+                config.rules.flowmates_dir = Some(source_pin::checkout_dir(&data_dir, url));
+            }
+            (None, Some(_)) => bail!("[source] ref of {} needs a url", CONFIG_FILE),
+            (None, None) => {}
+        }
+        if let Some(profile) = self.profile()? {
+            profile.apply(&mut config);
+        }
+        config.rules.now = Some(self.now());
+        let mut registry = match self.config_dir() {
+            Some(config_dir) => Registry::load(&config_dir)?,
+            None => Registry::default(),
+        };
+        if let Some(legacy) = self.legacy_config()? {
+            registry.source = registry.source.or(legacy.source);
+            if registry.trusted_keys.is_empty() {
+                registry.trusted_keys = legacy.trusted_keys;
+            }
+        }
+        config.rules.trusted_keys.extend(registry.trusted_keys);
+        if config.rules.flowmates_dir.is_none() {
+            config.rules.flowmates_dir = self.flowmates_dir().or(registry.source);
+        }
+        Ok(config)
+    }
+}
+
+/// `secs` since the epoch as a UTC date, `YYYY-MM-DD`.
+pub fn date(secs: u64) -> String {
+    let (year, month, day) = template::civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Seconds since the epoch at the start of a UTC date, `YYYY-MM-DD`;
+/// the inverse of [`date`].
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days from civil, after Howard Hinnant's algorithm.
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u64::try_from(days).ok().map(|days| days * 86_400)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn resolves_directories_from_its_variables() {
+        let env = Environment::new("/work").with_var("HOME", "/home/me");
+        assert_eq!(
+            env.config_dir(),
+            Some(PathBuf::from("/home/me/.config/flowmates"))
+        );
+        let env = env.with_var("XDG_CONFIG_HOME", "/xdg");
+        assert_eq!(env.config_dir(), Some(PathBuf::from("/xdg/flowmates")));
+        let env = env.with_var("FLOWMATES_CONFIG_DIR", "/flowmates");
+        assert_eq!(env.config_dir(), Some(PathBuf::from("/flowmates")));
+        assert_eq!(Environment::new("/work").config_dir(), None);
+        assert_eq!(
+            Environment::new("/work")
+                .with_var("HOME", "/home/me")
+                .cache_dir(),
+            Some(PathBuf::from("/home/me/.cache/echo"))
+        );
+        assert_eq!(
+            Environment::new("/work")
+                .with_var("HOME", "/home/me")
+                .data_dir(),
+            Some(PathBuf::from("/home/me/.local/share/echo"))
+        );
+    }
+
+    #[test]
+    fn loads_the_flowmates_dir_from_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Environment::new(dir.path()).with_var("FLOWMATES_DIR", "/checkout");
+        let config = env.load_config(dir.path()).unwrap();
+        assert_eq!(config.rules.flowmates_dir, Some(PathBuf::from("/checkout")));
+        assert_eq!(env.repository_root(None), dir.path());
+        let frozen = Environment {
+            now: Some(42),
+            ..env
+        };
+        assert_eq!(frozen.now(), 42);
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(1_709_251_200), "2024-03-01");
+        assert_eq!(parse_date("2024-03-01"), Some(1_709_251_200));
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("soon"), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_registered_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = dir.path().join("flowmates");
+        let env = Environment::new(dir.path())
+            .with_var("FLOWMATES_CONFIG_DIR", config_dir.to_string_lossy());
+        assert_eq!(
+            env.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            None
+        );
+        let mut registry = Registry::default();
+        assert!(registry.set_source(dir.path()).unwrap());
+        assert!(!registry.set_source(dir.path()).unwrap());
+        registry.save(&config_dir).unwrap();
+        assert_eq!(
+            env.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            Some(fs::canonicalize(dir.path()).unwrap())
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_legacy_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = dir.path().join(".config/flowmates");
+        let env = Environment::new(dir.path()).with_var("HOME", dir.path().to_string_lossy());
+        fs::create_dir_all(dir.path().join(".flowmates")).unwrap();
+        fs::write(
+            dir.path().join(LEGACY_FILE),
+            r#"{"source": "/legacy", "trusted-keys": ["RWQ"],
+                "profiles": {"oss": {"source": "/oss"}}}"#,
+        )
+        .unwrap();
+        let config = env.load_config(dir.path()).unwrap();
+        assert_eq!(config.rules.flowmates_dir, Some(PathBuf::from("/legacy")));
+        assert_eq!(config.rules.trusted_keys, vec!["RWQ"]);
+        let oss = env.clone().with_var(profiles::PROFILE_VAR, "oss");
+        assert_eq!(
+            oss.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            Some(PathBuf::from("/oss"))
+        );
+
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join(profiles::PROFILES_FILE),
+            "[work]
+",
+        )
+        .unwrap();
+        assert!(oss.load_config(dir.path()).is_err());
+        let mut registry = Registry::default();
+        assert!(registry.set_source(dir.path()).unwrap());
+        registry.save(&config_dir).unwrap();
+        assert_eq!(
+            env.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            Some(fs::canonicalize(dir.path()).unwrap())
+        );
+    }
+
+    #[test]
+    fn completes_the_config_with_the_selected_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Environment::new(dir.path())
+            .with_var("FLOWMATES_CONFIG_DIR", dir.path().to_string_lossy())
+            .with_var("FLOWMATES_DIR", "/checkout");
+        fs::write(
+            dir.path().join(profiles::PROFILES_FILE),
+            "[oss]\nsource = \"/oss\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            env.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            Some(PathBuf::from("/checkout"))
+        );
+        let oss = env.clone().with_var(profiles::PROFILE_VAR, "oss");
+        assert_eq!(
+            oss.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            Some(PathBuf::from("/oss"))
+        );
+        let work = env.with_var(profiles::PROFILE_VAR, "work");
+        assert!(work.load_config(dir.path()).is_err());
+    }
+
+    #[test]
+    fn a_pinned_source_wins_over_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Environment::new(dir.path())
+            .with_var("HOME", "/home/me")
+            .with_var("FLOWMATES_DIR", "/checkout");
+        let url = "https://example.com/flowmates.git";
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            format!("[source]\nurl = \"{}\"\nref = \"v2\"\n", url),
+        )
+        .unwrap();
+        assert_eq!(
+            env.load_config(dir.path()).unwrap().rules.flowmates_dir,
+            Some(source_pin::checkout_dir(
+                Path::new("/home/me/.local/share/echo"),
+                url
+            ))
+        );
+        fs::write(dir.path().join(CONFIG_FILE), "[source]\nref = \"v2\"\n").unwrap();
+        assert!(env.load_config(dir.path()).is_err());
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            format!(
+                "[source]\nurl = \"{}\"\n[rules]\nflowmates-dir = \"/x\"\n",
+                url
+            ),
+        )
+        .unwrap();
+        assert!(env.load_config(dir.path()).is_err());
+    }
+}
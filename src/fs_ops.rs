@@ -0,0 +1,463 @@
+//! Filesystem writes behind a trait, so a run can write for real, only
+//! record what it would write ([`DryRun`]), or write and keep a log of
+//! every change ([`Audit`]).
+//!
+//! Reads go straight to the filesystem; only changes go through
+//! [`FsOps`]. [`RealFs`] writes files with [`write_atomic`], so a crash
+//! leaves either the old contents or the new ones.
+
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The filesystem changes echo makes. Implementations are shared between
+/// the threads of [`crate::parallel::map`].
+pub trait FsOps: Sync {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// Like [`FsOps::write`], also flushing the file to disk, for files
+    /// such as the lockfile that must survive a power loss.
+    fn write_synced(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.write(path, contents)
+    }
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Create a symbolic link at `path` to `target`.
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()>;
+    /// Set the Unix permission bits of `path`, e.g. `0o755`.
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()>;
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+}
+
+/// Write `contents` to `path` through `fs`, creating its directory.
+pub fn write_file(fs: &dyn FsOps, path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs.create_dir_all(parent)?;
+    }
+    fs.write(path, contents)
+}
+
+/// Write `contents` to `path` through a temporary file in the same
+/// directory renamed into place, keeping the permissions of the file it
+/// replaces. A symbolic link is written through, not replaced. With
+/// `sync`, the file and its directory are flushed to disk before
+/// returning.
+pub fn write_atomic(path: &Path, contents: &[u8], sync: bool) -> io::Result<()> {
+    let resolved;
+    let path = if path.is_symlink() {
+        resolved = fs::canonicalize(path)?;
+        resolved.as_path()
+    } else {
+        path
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".echo-tmp-{}", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+    let written = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(contents)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        if sync {
+            file.sync_all()?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written?;
+    #[cfg(unix)]
+    if sync {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::File::open(parent)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+/// Give `path`, just written from `source`, the permissions and
+/// modification time of `source`. Permissions stay within those `path`
+/// was written with, so the umask still applies, executable bits being
+/// kept where reading is allowed. Returns the modification time, in
+/// seconds since the epoch.
+pub fn preserve_metadata(fs: &dyn FsOps, source: &Path, path: &Path) -> io::Result<u64> {
+    let metadata = fs::metadata(source)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        let written = fs::metadata(path).map_or(mode, |m| m.permissions().mode());
+        let allowed = written | (written & 0o444) >> 2;
+        fs.set_permissions(path, mode & allowed)?;
+    }
+    let modified = metadata.modified()?;
+    fs.set_modified(path, modified)?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default())
+}
+
+/// Copy the directory `from` to `to`, following symbolic links and
+/// leaving out files and directories named `skip` at any depth. Files are
+/// cloned copy-on-write where the filesystem allows it (reflinks on btrfs
+/// and XFS, `clonefile` on APFS) and copied otherwise.
+pub fn clone_dir(from: &Path, to: &Path, skip: &str) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == skip {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            clone_dir(&path, &to.join(&name), skip)?;
+        } else if path.is_file() {
+            reflink_copy::reflink_or_copy(&path, to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// The real filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl FsOps for RealFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        write_atomic(path, contents, false)
+    }
+
+    fn write_synced(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        write_atomic(path, contents, true)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, path)
+    }
+
+    #[cfg(not(unix))]
+    fn symlink(&self, _target: &Path, _path: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(unix)]
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn set_permissions(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()> {
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(time)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+}
+
+/// Kind of a [`Change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    CreateDir,
+    Write,
+    Copy,
+    Symlink,
+    SetPermissions,
+    SetModified,
+    RemoveFile,
+}
+
+/// A change made, or that would be made, to `path`; the destination for
+/// copies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub op: Op,
+    pub path: PathBuf,
+}
+
+/// Changes recorded in order, shared by [`DryRun`] and [`Audit`].
+#[derive(Debug, Default)]
+struct Log(Mutex<Vec<Change>>);
+
+impl Log {
+    fn push(&self, op: Op, path: &Path) {
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(Change {
+                op,
+                path: path.to_path_buf(),
+            });
+    }
+
+    fn into_changes(self) -> Vec<Change> {
+        self.0.into_inner().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Records the changes it is asked for and makes none. Directories that
+/// already exist are not recorded.
+#[derive(Debug, Default)]
+pub struct DryRun {
+    log: Log,
+}
+
+impl DryRun {
+    pub fn new() -> DryRun {
+        DryRun::default()
+    }
+
+    /// The changes that would have been made, in order.
+    pub fn into_changes(self) -> Vec<Change> {
+        self.log.into_changes()
+    }
+}
+
+impl FsOps for DryRun {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        if !path.is_dir() {
+            self.log.push(Op::CreateDir, path);
+        }
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, _contents: &[u8]) -> io::Result<()> {
+        self.log.push(Op::Write, path);
+        Ok(())
+    }
+
+    fn copy(&self, _from: &Path, to: &Path) -> io::Result<()> {
+        self.log.push(Op::Copy, to);
+        Ok(())
+    }
+
+    fn symlink(&self, _target: &Path, path: &Path) -> io::Result<()> {
+        self.log.push(Op::Symlink, path);
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &Path, _mode: u32) -> io::Result<()> {
+        self.log.push(Op::SetPermissions, path);
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, _time: SystemTime) -> io::Result<()> {
+        self.log.push(Op::SetModified, path);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.log.push(Op::RemoveFile, path);
+        Ok(())
+    }
+}
+
+/// Makes changes through `inner` and records those that succeeded.
+/// Directories that already existed are not recorded.
+#[derive(Debug, Default)]
+pub struct Audit<F> {
+    inner: F,
+    log: Log,
+}
+
+impl<F: FsOps> Audit<F> {
+    pub fn new(inner: F) -> Audit<F> {
+        Audit {
+            inner,
+            log: Log::default(),
+        }
+    }
+
+    /// The changes made, in order.
+    pub fn into_changes(self) -> Vec<Change> {
+        self.log.into_changes()
+    }
+}
+
+impl<F: FsOps> FsOps for Audit<F> {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let existed = path.is_dir();
+        self.inner.create_dir_all(path)?;
+        if !existed {
+            self.log.push(Op::CreateDir, path);
+        }
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.inner.write(path, contents)?;
+        self.log.push(Op::Write, path);
+        Ok(())
+    }
+
+    fn write_synced(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.inner.write_synced(path, contents)?;
+        self.log.push(Op::Write, path);
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inner.copy(from, to)?;
+        self.log.push(Op::Copy, to);
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        self.inner.symlink(target, path)?;
+        self.log.push(Op::Symlink, path);
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.inner.set_permissions(path, mode)?;
+        self.log.push(Op::SetPermissions, path);
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, time: SystemTime) -> io::Result<()> {
+        self.inner.set_modified(path, time)?;
+        self.log.push(Op::SetModified, path);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.inner.remove_file(path)?;
+        self.log.push(Op::RemoveFile, path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_trees_without_the_skipped_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        fs::create_dir_all(from.join("rules/vendor/.git")).unwrap();
+        fs::create_dir_all(from.join(".git")).unwrap();
+        fs::write(from.join(".git/HEAD"), "ref\n").unwrap();
+        fs::write(from.join("rules/vendor/.git/HEAD"), "ref\n").unwrap();
+        fs::write(from.join("rules/style.mdc"), "style\n").unwrap();
+
+        let to = dir.path().join("to");
+        clone_dir(&from, &to, ".git").unwrap();
+        assert_eq!(
+            fs::read_to_string(to.join("rules/style.mdc")).unwrap(),
+            "style\n"
+        );
+        assert!(!to.join(".git").exists());
+        assert!(to.join("rules/vendor").is_dir());
+        assert!(!to.join("rules/vendor/.git").exists());
+    }
+
+    #[test]
+    fn dry_run_records_and_audit_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a/b.txt");
+
+        let dry_run = DryRun::new();
+        write_file(&dry_run, &path, b"hi").unwrap();
+        dry_run.remove_file(&path).unwrap();
+        assert!(!path.exists());
+        let ops: Vec<Op> = dry_run.into_changes().iter().map(|c| c.op).collect();
+        assert_eq!(ops, vec![Op::CreateDir, Op::Write, Op::RemoveFile]);
+
+        let audit = Audit::new(RealFs);
+        write_file(&audit, &path, b"hi").unwrap();
+        write_file(&audit, &path, b"again").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "again");
+        assert!(audit.remove_file(&dir.path().join("missing")).is_err());
+        assert_eq!(
+            audit.into_changes(),
+            vec![
+                Change {
+                    op: Op::CreateDir,
+                    path: dir.path().join("a"),
+                },
+                Change {
+                    op: Op::Write,
+                    path: path.clone(),
+                },
+                Change {
+                    op: Op::Write,
+                    path,
+                },
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_writes_replace_the_file_and_keep_its_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.sh");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        write_atomic(&path, b"new", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(write_atomic(&dir.path().join("missing/file"), b"x", false).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn preserves_mode_within_the_written_permissions_and_mtime() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.sh");
+        let path = dir.path().join("copy.sh");
+        fs::write(&source, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o775)).unwrap();
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert_eq!(
+            preserve_metadata(&RealFs, &source, &path).unwrap(),
+            1_000_000
+        );
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+        assert_eq!(metadata.modified().unwrap(), time);
+    }
+}
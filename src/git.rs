@@ -0,0 +1,129 @@
+//! Thin wrappers around the `git` command line, for commands changing a
+//! repository; its layout is read through [`crate::gitutil`].
+
+use crate::gitutil;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Run `git` in `root` and return its trimmed stdout.
+pub fn run(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Clone the repository at `url` into `dest`, which must not exist.
+pub fn clone(url: &str, dest: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .arg("clone")
+        .arg("--quiet")
+        .arg("--")
+        .arg(url)
+        .arg(dest)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git clone {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Whether git tracks any file at or below `rel_path`.
+pub fn is_tracked(root: &Path, rel_path: &Path) -> bool {
+    let path = rel_path.to_string_lossy();
+    run(root, &["ls-files", "--", &path]).is_ok_and(|out| !out.is_empty())
+}
+
+/// Move `from` to `to` (both relative to `root`), through `git mv` when
+/// the source is tracked so history follows the rename.
+pub fn move_path(root: &Path, from: &Path, to: &Path) -> Result<()> {
+    if gitutil::is_repo(root) && is_tracked(root, from) {
+        run(
+            root,
+            &["mv", &from.to_string_lossy(), &to.to_string_lossy()],
+        )?;
+        // Untracked files are left behind by `git mv`.
+        if root.join(from).exists() {
+            move_remaining(&root.join(from), &root.join(to))?;
+        }
+        return Ok(());
+    }
+    std::fs::rename(root.join(from), root.join(to))
+        .with_context(|| format!("failed to move {} to {}", from.display(), to.display()))
+}
+
+fn move_remaining(from: &Path, to: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            std::fs::create_dir_all(&dest)?;
+            move_remaining(&entry.path(), &dest)?;
+        } else {
+            std::fs::rename(entry.path(), &dest)?;
+        }
+    }
+    std::fs::remove_dir(from)?;
+    Ok(())
+}
+
+/// Web page for opening a pull request from `branch`, for GitHub and
+/// GitLab remotes given as SSH or HTTPS URLs.
+pub fn pull_request_url(remote: &str, branch: &str) -> Option<String> {
+    let remote = remote.trim().trim_end_matches('/');
+    let remote = remote.strip_suffix(".git").unwrap_or(remote);
+    let (host, path) = if let Some(rest) = remote.strip_prefix("git@") {
+        rest.split_once(':')?
+    } else {
+        let rest = ["ssh://git@", "https://", "http://"]
+            .iter()
+            .find_map(|scheme| remote.strip_prefix(scheme))?;
+        let (host, path) = rest.split_once('/')?;
+        (host.rsplit('@').next()?, path)
+    };
+    let web = format!("https://{}/{}", host, path);
+    if host.contains("github") {
+        Some(format!("{}/compare/{}?expand=1", web, branch))
+    } else if host.contains("gitlab") {
+        Some(format!(
+            "{}/-/merge_requests/new?merge_request%5Bsource_branch%5D={}",
+            web, branch
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_pull_request_urls() {
+        assert_eq!(
+            pull_request_url("git@github.com:Lsh0x/flowmates.git", "rules/rust").as_deref(),
+            Some("https://github.com/Lsh0x/flowmates/compare/rules/rust?expand=1")
+        );
+        assert_eq!(
+            pull_request_url("https://token@gitlab.com/team/flowmates", "x").as_deref(),
+            Some("https://gitlab.com/team/flowmates/-/merge_requests/new?merge_request%5Bsource_branch%5D=x")
+        );
+        assert_eq!(pull_request_url("/srv/git/flowmates.git", "x"), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Output for GitHub Actions: workflow command annotations and the job's
+//! step summary.
+//!
+//! Both only apply when running in Actions (`GITHUB_ACTIONS=true`), so
+//! pipelines get annotated files and a summary without wrapper scripts.
+
+use crate::environment::Environment;
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// Severity of an annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Notice,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn command(self) -> &'static str {
+        match self {
+            Level::Notice => "notice",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Whether echo runs in a GitHub Actions job.
+pub fn is_actions(env: &Environment) -> bool {
+    env.var("GITHUB_ACTIONS")
+        .is_some_and(|value| value == "true")
+}
+
+/// Workflow command annotating `file`, relative to the repository root,
+/// with `message`; a plain one without a file.
+pub fn annotation(level: Level, file: Option<&Path>, message: &str) -> String {
+    match file {
+        Some(file) => format!(
+            "::{} file={}::{}",
+            level.command(),
+            escape_property(&file.display().to_string()),
+            escape_data(message)
+        ),
+        None => format!("::{}::{}", level.command(), escape_data(message)),
+    }
+}
+
+/// Markdown table with a `title` heading, one row per `(file, status)`.
+pub fn summary_table(title: &str, rows: &[(String, String)]) -> String {
+    let mut markdown = format!("### {}\n\n", title);
+    if rows.is_empty() {
+        markdown.push_str("Nothing to report.\n\n");
+        return markdown;
+    }
+    markdown.push_str("| File | Status |\n| --- | --- |\n");
+    for (file, status) in rows {
+        markdown.push_str(&format!(
+            "| `{}` | {} |\n",
+            file.replace('|', "\\|"),
+            status.replace('|', "\\|")
+        ));
+    }
+    markdown.push('\n');
+    markdown
+}
+
+/// Append `markdown` to the file named by `GITHUB_STEP_SUMMARY`, when set.
+pub fn append_summary(env: &Environment, markdown: &str) -> Result<()> {
+    let Some(path) = env.var("GITHUB_STEP_SUMMARY") else {
+        return Ok(());
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", Path::new(path).display()))?;
+    file.write_all(markdown.as_bytes())
+        .with_context(|| format!("failed to write {}", Path::new(path).display()))
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_workflow_commands() {
+        assert_eq!(
+            annotation(
+                Level::Warning,
+                Some(Path::new("issues/a,b.md")),
+                "kept: 100%\nedited"
+            ),
+            "::warning file=issues/a%2Cb.md::kept: 100%25%0Aedited"
+        );
+        assert_eq!(annotation(Level::Error, None, "drift"), "::error::drift");
+        assert_eq!(
+            summary_table("echo check", &[("a|b.md".into(), "missing".into())]),
+            "### echo check\n\n| File | Status |\n| --- | --- |\n| `a\\|b.md` | missing |\n\n"
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! The ignore-file block keeping `.cursor/` out of the repository.
+//!
+//! Rules are installed per checkout from the flowmates source, so by
+//! default `.cursor/` is ignored. Vendor mode (`vendor = true` in
+//! `.echo.toml`) commits it instead and drops the entry. With `audit-log`
+//! set, the block also lists `.echo/objects/`, where the audit log keeps
+//! file contents. The `exclude-strategy` setting picks the file holding the
+//! block: the shared `.gitignore`, the local `.git/info/exclude`, or
+//! neither.
+//!
+//! Entries written by echo sit between [`BEGIN_MARKER`] and [`END_MARKER`];
+//! later runs only rewrite that block and `echo uninstall` removes it.
+
+use crate::fs_ops;
+use crate::gitutil;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Ignore file, relative to the repository root.
+pub const GITIGNORE: &str = ".gitignore";
+
+/// Local ignore file, relative to the git directory.
+pub const INFO_EXCLUDE: &str = "info/exclude";
+
+/// Entry written by echo for the installed rules.
+pub const CURSOR_ENTRY: &str = ".cursor/";
+
+/// Entry written by echo for the contents kept by the audit log.
+pub const OBJECTS_ENTRY: &str = ".echo/objects/";
+
+/// Start of the block managed by echo.
+pub const BEGIN_MARKER: &str = "# echo:begin";
+
+/// End of the block managed by echo.
+pub const END_MARKER: &str = "# echo:end";
+
+/// Where the managed block is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExcludeStrategy {
+    /// The committed `.gitignore`.
+    #[default]
+    Gitignore,
+    /// `.git/info/exclude`, which stays local to the clone.
+    InfoExclude,
+    /// Nowhere; the user handles it.
+    None,
+}
+
+impl ExcludeStrategy {
+    /// Value of the strategy in `.echo.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExcludeStrategy::Gitignore => "gitignore",
+            ExcludeStrategy::InfoExclude => "info-exclude",
+            ExcludeStrategy::None => "none",
+        }
+    }
+}
+
+/// An ignore file whose managed block changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// File path as shown to the user.
+    pub file: String,
+    /// Whether the block was written, or removed.
+    pub added: bool,
+}
+
+/// Whether `.gitignore` or `.git/info/exclude` has an entry ignoring the
+/// whole `.cursor` directory, inside the managed block or not. Entries are
+/// compared whole, so `.cursortmp/` does not count.
+pub fn is_cursor_ignored(root: &Path) -> Result<bool> {
+    for path in ignore_files(root) {
+        if read(&path)?.lines().any(|line| ignores(line, CURSOR_ENTRY)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Write the managed block listing [`CURSOR_ENTRY`] to the file chosen by
+/// `strategy` and drop it from the other one.
+pub fn ensure_cursor_ignored(root: &Path, strategy: ExcludeStrategy) -> Result<Vec<Change>> {
+    ensure_ignored(root, strategy, &[CURSOR_ENTRY])
+}
+
+/// Write the managed block listing `entries` to the file chosen by
+/// `strategy` and drop it from the other one. Entries the file already
+/// ignores outside of the block are left out, and so is the block when
+/// none remain.
+pub fn ensure_ignored(
+    root: &Path,
+    strategy: ExcludeStrategy,
+    entries: &[&str],
+) -> Result<Vec<Change>> {
+    let target = match strategy {
+        ExcludeStrategy::Gitignore => Some(root.join(GITIGNORE)),
+        ExcludeStrategy::InfoExclude => match info_exclude(root) {
+            Some(path) => Some(path),
+            None => bail!("exclude-strategy = \"info-exclude\" needs a git repository"),
+        },
+        ExcludeStrategy::None => None,
+    };
+    let mut changes = Vec::new();
+    for path in ignore_files(root) {
+        let content = read(&path)?;
+        let outside = outside_block(&content);
+        let entries: Vec<&str> = if Some(&path) == target.as_ref() {
+            entries
+                .iter()
+                .copied()
+                .filter(|entry| !outside.lines().any(|line| ignores(line, entry)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if set_block(&path, &content, &entries)? {
+            changes.push(Change {
+                file: display(root, &path),
+                added: !entries.is_empty(),
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// Remove the managed block from both ignore files.
+pub fn remove_managed_block(root: &Path) -> Result<Vec<Change>> {
+    ensure_ignored(root, ExcludeStrategy::None, &[])
+}
+
+/// Whether `line` ignores the whole directory `entry`, e.g. `/.cursor` or
+/// `.cursor/**` for `.cursor/`.
+fn ignores(line: &str, entry: &str) -> bool {
+    let line = line.trim();
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let line = line
+        .strip_suffix("/**")
+        .or_else(|| line.strip_suffix('/'))
+        .unwrap_or(line);
+    line == entry.trim_end_matches('/')
+}
+
+/// `.gitignore`, then `.git/info/exclude` when `root` is a repository.
+fn ignore_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![root.join(GITIGNORE)];
+    files.extend(info_exclude(root));
+    files
+}
+
+/// Path of `info/exclude` in the git directory of `root`, which differs
+/// from `.git/info/exclude` in worktrees.
+fn info_exclude(root: &Path) -> Option<PathBuf> {
+    if !gitutil::is_repo(root) {
+        return None;
+    }
+    gitutil::git_path(root, INFO_EXCLUDE).ok()
+}
+
+fn display(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Content outside the managed block. A bare [`CURSOR_ENTRY`] line, as
+/// written by earlier versions of echo, counts as part of the block.
+fn outside_block(content: &str) -> String {
+    let mut outside = String::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        match line.trim() {
+            BEGIN_MARKER => {
+                // Drop the blank line written before the block.
+                if outside.ends_with("\n\n") {
+                    outside.pop();
+                }
+                in_block = true;
+            }
+            END_MARKER if in_block => in_block = false,
+            _ if in_block => {}
+            CURSOR_ENTRY => {}
+            _ => {
+                outside.push_str(line);
+                outside.push('\n');
+            }
+        }
+    }
+    outside
+}
+
+fn set_block(path: &Path, content: &str, entries: &[&str]) -> Result<bool> {
+    let mut updated = outside_block(content);
+    if !entries.is_empty() {
+        if !updated.is_empty() && !updated.ends_with("\n\n") {
+            updated.push('\n');
+        }
+        updated.push_str(BEGIN_MARKER);
+        updated.push('\n');
+        for entry in entries {
+            updated.push_str(entry);
+            updated.push('\n');
+        }
+        updated.push_str(END_MARKER);
+        updated.push('\n');
+    } else {
+        while updated.ends_with("\n\n") {
+            updated.pop();
+        }
+    }
+    if updated == content || (content.is_empty() && updated.is_empty()) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs_ops::write_atomic(path, updated.as_bytes(), false)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+fn read(path: &Path) -> Result<String> {
+    if !path.is_file() {
+        return Ok(String::new());
+    }
+    fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git;
+
+    fn gitignore(dir: &Path) -> String {
+        fs::read_to_string(dir.join(GITIGNORE)).unwrap()
+    }
+
+    fn ensure(dir: &Path) -> bool {
+        !ensure_cursor_ignored(dir, ExcludeStrategy::Gitignore)
+            .unwrap()
+            .is_empty()
+    }
+
+    fn remove(dir: &Path) -> bool {
+        !remove_managed_block(dir).unwrap().is_empty()
+    }
+
+    #[test]
+    fn manages_a_marked_block() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_cursor_ignored(dir.path()).unwrap());
+        fs::write(dir.path().join(GITIGNORE), "target/").unwrap();
+        assert!(ensure(dir.path()));
+        assert!(!ensure(dir.path()));
+        assert_eq!(
+            gitignore(dir.path()),
+            "target/\n\n# echo:begin\n.cursor/\n# echo:end\n"
+        );
+        assert!(is_cursor_ignored(dir.path()).unwrap());
+
+        fs::write(
+            dir.path().join(GITIGNORE),
+            gitignore(dir.path()) + "*.log\n",
+        )
+        .unwrap();
+        assert!(remove(dir.path()));
+        assert!(!remove(dir.path()));
+        assert_eq!(gitignore(dir.path()), "target/\n*.log\n");
+    }
+
+    #[test]
+    fn adopts_legacy_lines_and_respects_user_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(GITIGNORE), "target/\n.cursor/\n").unwrap();
+        assert!(ensure(dir.path()));
+        assert_eq!(
+            gitignore(dir.path()),
+            "target/\n\n# echo:begin\n.cursor/\n# echo:end\n"
+        );
+
+        fs::write(dir.path().join(GITIGNORE), "/.cursor\n").unwrap();
+        assert!(!ensure(dir.path()));
+        assert!(!remove(dir.path()));
+        assert!(is_cursor_ignored(dir.path()).unwrap());
+
+        fs::write(dir.path().join(GITIGNORE), ".cursortmp/\n").unwrap();
+        assert!(!is_cursor_ignored(dir.path()).unwrap());
+        assert!(ensure(dir.path()));
+        assert!(gitignore(dir.path()).starts_with(".cursortmp/\n\n# echo:begin\n"));
+    }
+
+    #[test]
+    fn lists_the_audit_objects_in_the_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries = [CURSOR_ENTRY, OBJECTS_ENTRY];
+        fs::write(dir.path().join(GITIGNORE), "/.cursor\n").unwrap();
+        ensure_ignored(dir.path(), ExcludeStrategy::Gitignore, &entries).unwrap();
+        assert_eq!(
+            gitignore(dir.path()),
+            "/.cursor\n\n# echo:begin\n.echo/objects/\n# echo:end\n"
+        );
+
+        fs::write(dir.path().join(GITIGNORE), "").unwrap();
+        ensure_ignored(dir.path(), ExcludeStrategy::Gitignore, &entries).unwrap();
+        assert_eq!(
+            gitignore(dir.path()),
+            "# echo:begin\n.cursor/\n.echo/objects/\n# echo:end\n"
+        );
+        assert_eq!(
+            ensure_ignored(dir.path(), ExcludeStrategy::Gitignore, &[OBJECTS_ENTRY])
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(!is_cursor_ignored(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn moves_the_entry_between_ignore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ensure_cursor_ignored(dir.path(), ExcludeStrategy::InfoExclude).is_err());
+        git::run(dir.path(), &["init", "-q"]).unwrap();
+        assert!(ensure(dir.path()));
+
+        let changes = ensure_cursor_ignored(dir.path(), ExcludeStrategy::InfoExclude).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    file: GITIGNORE.into(),
+                    added: false
+                },
+                Change {
+                    file: ".git/info/exclude".into(),
+                    added: true
+                },
+            ]
+        );
+        assert_eq!(gitignore(dir.path()), "");
+        assert!(is_cursor_ignored(dir.path()).unwrap());
+
+        assert_eq!(
+            ensure_cursor_ignored(dir.path(), ExcludeStrategy::None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(!is_cursor_ignored(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn writes_the_shared_exclude_file_from_a_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main");
+        fs::create_dir_all(&main).unwrap();
+        git::run(&main, &["init", "-q"]).unwrap();
+        git::run(
+            &main,
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+        let worktree = dir.path().join("feature");
+        git::run(
+            &main,
+            &["worktree", "add", "-q", &worktree.to_string_lossy()],
+        )
+        .unwrap();
+
+        ensure_cursor_ignored(&worktree, ExcludeStrategy::InfoExclude).unwrap();
+        assert!(fs::read_to_string(main.join(".git").join(INFO_EXCLUDE))
+            .unwrap()
+            .contains(CURSOR_ENTRY));
+        assert!(is_cursor_ignored(&main).unwrap());
+        ensure_cursor_ignored(&worktree, ExcludeStrategy::Gitignore).unwrap();
+        assert!(worktree.join(GITIGNORE).is_file());
+        assert!(!main.join(GITIGNORE).exists());
+    }
+}
@@ -0,0 +1,237 @@
+//! Repository layout through libgit2: the work tree root, the git and
+//! common directories, the hooks directory, remotes, refs and
+//! configuration.
+//!
+//! Linked worktrees and submodules keep their git directory away from the
+//! work tree, and share some of it with the main repository; the paths
+//! resolved here account for both, as `git rev-parse --git-path` does.
+//! Commands that change the repository still go through [`crate::git`].
+
+use anyhow::{bail, Context, Result};
+use git2::Repository;
+use std::path::{Path, PathBuf};
+
+/// Entries of the git directory shared by every worktree of a repository,
+/// as listed by git.
+const SHARED: [&str; 14] = [
+    "branches",
+    "common",
+    "config",
+    "description",
+    "hooks",
+    "info",
+    "logs",
+    "lost-found",
+    "objects",
+    "packed-refs",
+    "refs",
+    "remotes",
+    "rr-cache",
+    "shallow",
+];
+
+/// Paths under [`SHARED`] entries that are each worktree's own.
+const PER_WORKTREE: [&str; 5] = [
+    "info/sparse-checkout",
+    "logs/HEAD",
+    "refs/bisect",
+    "refs/rewritten",
+    "refs/worktree",
+];
+
+/// The repository containing `dir`.
+pub fn open(dir: &Path) -> Result<Repository> {
+    Repository::discover(dir)
+        .with_context(|| format!("{} is not in a git repository", dir.display()))
+}
+
+/// Whether `dir` is inside a git work tree.
+pub fn is_repo(dir: &Path) -> bool {
+    Repository::discover(dir).is_ok_and(|repo| !repo.is_bare())
+}
+
+/// Commit checked out at `root`, if any.
+pub fn head(root: &Path) -> Option<String> {
+    let repo = Repository::discover(root).ok()?;
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Root of the work tree containing `dir`, which is that of the submodule
+/// or linked worktree when `dir` is in one.
+pub fn toplevel(dir: &Path) -> Result<PathBuf> {
+    let repo = open(dir)?;
+    match repo.workdir() {
+        Some(workdir) => Ok(normalize(workdir)),
+        None => bail!("{} is in a bare repository", dir.display()),
+    }
+}
+
+/// Directory shared by every worktree of the repository at `root`: its
+/// `.git` directory, also for linked worktrees.
+pub fn common_dir(root: &Path) -> Result<PathBuf> {
+    Ok(normalize(open(root)?.commondir()))
+}
+
+/// Hooks directory of the repository at `root`: `core.hooksPath`, relative
+/// to the work tree, when set, and `hooks` of the common directory
+/// otherwise.
+pub fn hooks_dir(root: &Path) -> Result<PathBuf> {
+    let repo = open(root)?;
+    if let Ok(path) = repo.config()?.get_path("core.hooksPath") {
+        let base = repo.workdir().unwrap_or_else(|| repo.path());
+        return Ok(normalize(&base.join(path)));
+    }
+    Ok(normalize(&repo.commondir().join("hooks")))
+}
+
+/// Where `path` of the git directory lives for the work tree at `root`,
+/// e.g. `hooks` or `info/exclude`. Shared paths resolve to the common
+/// directory, others to the git directory of the worktree;
+/// `core.hooksPath` is honoured for `hooks`.
+pub fn git_path(root: &Path, path: &str) -> Result<PathBuf> {
+    let path = path.trim_matches('/');
+    if path == "hooks" {
+        return hooks_dir(root);
+    }
+    if let Some(hook) = path.strip_prefix("hooks/") {
+        return Ok(hooks_dir(root)?.join(hook));
+    }
+    let repo = open(root)?;
+    let first = path.split('/').next().unwrap_or_default();
+    let own = PER_WORKTREE
+        .iter()
+        .any(|own| path == *own || path.starts_with(&format!("{}/", own)));
+    let base = if SHARED.contains(&first) && !own {
+        repo.commondir()
+    } else {
+        repo.path()
+    };
+    Ok(normalize(&base.join(path)))
+}
+
+/// URL of the remote `name` of the repository at `root`, if there is one.
+pub fn remote_url(root: &Path, name: &str) -> Option<String> {
+    let repo = Repository::discover(root).ok()?;
+    let remote = repo.find_remote(name).ok()?;
+    remote.url().map(str::to_string)
+}
+
+/// Branch checked out at `root`, even one without commits yet, or `HEAD`
+/// when detached, as `git rev-parse --abbrev-ref HEAD` prints it.
+pub fn branch(root: &Path) -> Option<String> {
+    let repo = Repository::discover(root).ok()?;
+    if repo.head_detached().ok()? {
+        return Some("HEAD".to_string());
+    }
+    let head = repo.find_reference("HEAD").ok()?;
+    let target = head.symbolic_target()?;
+    Some(
+        target
+            .strip_prefix("refs/heads/")
+            .unwrap_or(target)
+            .to_string(),
+    )
+}
+
+/// Commit `spec` names in the repository at `root`, e.g. `origin/main`
+/// or a tag.
+pub fn resolve(root: &Path, spec: &str) -> Option<String> {
+    let repo = Repository::discover(root).ok()?;
+    let commit = repo.revparse_single(spec).ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Value of the configuration `name`, e.g. `user.name`, for the
+/// repository at `root`: its own configuration, then the global and
+/// system ones, which alone apply outside of a repository.
+pub fn config(root: &Path, name: &str) -> Option<String> {
+    let config = match Repository::discover(root) {
+        Ok(repo) => repo.config().ok()?,
+        Err(_) => git2::Config::open_default().ok()?,
+    };
+    config.get_string(name).ok()
+}
+
+/// `path` without the trailing separator libgit2 leaves on directories.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::run;
+
+    #[test]
+    fn resolves_paths_from_worktrees_and_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main");
+        std::fs::create_dir_all(main.join("src")).unwrap();
+        run(&main, &["init", "-q", "-b", "trunk"]).unwrap();
+        assert_eq!(branch(&main).as_deref(), Some("trunk"));
+        run(
+            &main,
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+        let main = std::fs::canonicalize(&main).unwrap();
+        assert_eq!(toplevel(&main.join("src")).unwrap(), main);
+        assert_eq!(remote_url(&main, "origin"), None);
+        assert_eq!(
+            head(&main),
+            Some(run(&main, &["rev-parse", "HEAD"]).unwrap())
+        );
+
+        let linked = dir.path().join("linked");
+        run(&main, &["worktree", "add", "-q", &linked.to_string_lossy()]).unwrap();
+        let linked = std::fs::canonicalize(&linked).unwrap();
+        assert!(is_repo(&linked));
+        assert_eq!(toplevel(&linked).unwrap(), linked);
+        assert_eq!(common_dir(&linked).unwrap(), main.join(".git"));
+        assert_eq!(git_path(&linked, "hooks").unwrap(), main.join(".git/hooks"));
+        assert_eq!(
+            git_path(&linked, "info/exclude").unwrap(),
+            main.join(".git/info/exclude")
+        );
+        assert_eq!(
+            git_path(&linked, "HEAD").unwrap(),
+            main.join(".git/worktrees/linked/HEAD")
+        );
+
+        run(&main, &["config", "core.hooksPath", ".githooks"]).unwrap();
+        assert_eq!(git_path(&main, "hooks").unwrap(), main.join(".githooks"));
+        run(
+            &main,
+            &["remote", "add", "origin", "git@github.com:team/app.git"],
+        )
+        .unwrap();
+        assert_eq!(
+            remote_url(&linked, "origin").as_deref(),
+            Some("git@github.com:team/app.git")
+        );
+        run(&main, &["config", "user.name", "Dev"]).unwrap();
+        assert_eq!(config(&linked, "user.name").as_deref(), Some("Dev"));
+        assert_eq!(
+            branch(&main),
+            Some(run(&main, &["branch", "--show-current"]).unwrap())
+        );
+        let commit = head(&main).unwrap();
+        assert_eq!(resolve(&main, "HEAD").as_deref(), Some(commit.as_str()));
+        assert_eq!(resolve(&main, "no-such-ref"), None);
+        run(&main, &["checkout", "-q", "--detach"]).unwrap();
+        assert_eq!(branch(&main).as_deref(), Some("HEAD"));
+        assert!(!is_repo(dir.path()));
+        assert!(toplevel(dir.path()).is_err());
+    }
+}
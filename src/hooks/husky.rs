@@ -0,0 +1,177 @@
+//! Husky-format shims, used when husky manages the hooks.
+//!
+//! Husky regenerates its own hooks directory and runs the committed
+//! `.husky/<name>` scripts, so echo keeps its script in
+//! `.husky/echo/<name>` and calls it from a block of `.husky/<name>`
+//! between [`BEGIN_MARKER`] and [`END_MARKER`].
+
+use super::{read, remove_if_present, write_executable, Action, Hook, HookState};
+use crate::gitutil;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Husky scripts directory, relative to the repository root.
+pub const HUSKY_DIR: &str = ".husky";
+
+/// Directory of echo's scripts, relative to [`HUSKY_DIR`].
+pub const SCRIPTS_DIR: &str = "echo";
+
+/// Start of the block calling echo's script.
+pub const BEGIN_MARKER: &str = "# echo:begin";
+
+/// End of the block calling echo's script.
+pub const END_MARKER: &str = "# echo:end";
+
+/// Whether husky manages the hooks of `root`: `.husky/` exists or
+/// `core.hooksPath` points into it.
+pub fn detect(root: &Path) -> bool {
+    root.join(HUSKY_DIR).is_dir()
+        || gitutil::config(root, "core.hooksPath").is_some_and(|path| path.contains(HUSKY_DIR))
+}
+
+/// Block added to `.husky/<name>`. Husky runs that file with `$0` set to
+/// its path, so the script is found next to it.
+pub fn shim(name: &str) -> String {
+    format!(
+        "{}\n\"$(dirname \"$0\")/{}/{}\" \"$@\"\n{}\n",
+        BEGIN_MARKER, SCRIPTS_DIR, name, END_MARKER
+    )
+}
+
+/// Where echo's script for `name` goes.
+pub(crate) fn script_path(root: &Path, name: &str) -> PathBuf {
+    root.join(HUSKY_DIR).join(SCRIPTS_DIR).join(name)
+}
+
+/// State of the hook `name`; `hook` is the manifest entry, if any.
+pub fn state(root: &Path, name: &str, hook: Option<&Hook>) -> Result<HookState> {
+    let path = root.join(HUSKY_DIR).join(name);
+    let content = if path.is_file() {
+        read(&path)?
+    } else {
+        String::new()
+    };
+    if !content.contains(&shim(name)) {
+        return Ok(HookState::Missing);
+    }
+    let script = script_path(root, name);
+    match hook {
+        Some(hook) if hook.is_placed_at(&script)? => Ok(HookState::Installed),
+        _ => Ok(HookState::Outdated),
+    }
+}
+
+/// Write the block calling echo's script, which the caller places at
+/// [`script_path`].
+pub fn install(root: &Path, hook: &Hook, force: bool) -> Result<Action> {
+    let action = match state(root, &hook.name, Some(hook))? {
+        HookState::Installed if !force => return Ok(Action::Unchanged),
+        HookState::Missing => Action::Installed,
+        _ => Action::Updated,
+    };
+    let path = root.join(HUSKY_DIR).join(&hook.name);
+    let mut content = if path.is_file() {
+        remove_block(&read(&path)?)
+    } else {
+        String::new()
+    };
+    if !content.is_empty() && !content.ends_with("\n\n") {
+        content.push('\n');
+    }
+    content.push_str(&shim(&hook.name));
+    write_executable(&path, &content)?;
+    Ok(action)
+}
+
+/// Remove echo's script and block for `name`; returns whether there was
+/// one. `.husky/<name>` is deleted when nothing else is left in it.
+pub fn uninstall(root: &Path, name: &str) -> Result<bool> {
+    if state(root, name, None)? == HookState::Missing {
+        return Ok(false);
+    }
+    let path = root.join(HUSKY_DIR).join(name);
+    let content = remove_block(&read(&path)?);
+    if content.trim().is_empty() {
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    } else {
+        fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    remove_if_present(&script_path(root, name))?;
+    // Only succeeds once the last script is gone.
+    let _ = fs::remove_dir(root.join(HUSKY_DIR).join(SCRIPTS_DIR));
+    Ok(true)
+}
+
+/// `content` without the echo block and the blank line before it.
+fn remove_block(content: &str) -> String {
+    let mut out = String::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        match line {
+            BEGIN_MARKER => {
+                if out.ends_with("\n\n") {
+                    out.pop();
+                }
+                in_block = true;
+            }
+            END_MARKER if in_block => in_block = false,
+            _ if in_block => {}
+            _ => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git;
+    use crate::hooks::{place_script, LinkMode};
+
+    #[test]
+    fn adds_and_removes_a_shim_block() {
+        let dir = tempfile::tempdir().unwrap();
+        git::run(dir.path(), &["init", "-q"]).unwrap();
+        assert!(!detect(dir.path()));
+        git::run(dir.path(), &["config", "core.hooksPath", ".husky/_"]).unwrap();
+        assert!(detect(dir.path()));
+
+        let husky = dir.path().join(HUSKY_DIR);
+        fs::create_dir_all(&husky).unwrap();
+        fs::write(husky.join("pre-commit"), "npm test\n").unwrap();
+        let hook = Hook {
+            name: "pre-commit".into(),
+            description: String::new(),
+            source: "test".into(),
+            path: None,
+            content: "#!/bin/sh\nexit 0\n".into(),
+        };
+        assert_eq!(
+            install(dir.path(), &hook, false).unwrap(),
+            Action::Installed
+        );
+        let script = script_path(dir.path(), "pre-commit");
+        place_script(&script, &hook, LinkMode::Copy).unwrap();
+        assert_eq!(
+            install(dir.path(), &hook, false).unwrap(),
+            Action::Unchanged
+        );
+        assert_eq!(
+            fs::read_to_string(husky.join("pre-commit")).unwrap(),
+            format!("npm test\n\n{}", shim("pre-commit"))
+        );
+        assert!(husky.join("echo/pre-commit").is_file());
+
+        assert!(uninstall(dir.path(), "pre-commit").unwrap());
+        assert!(!uninstall(dir.path(), "pre-commit").unwrap());
+        assert_eq!(
+            fs::read_to_string(husky.join("pre-commit")).unwrap(),
+            "npm test\n"
+        );
+        assert!(!husky.join("echo").exists());
+    }
+}
@@ -0,0 +1,910 @@
+//! Git hooks installed from a manifest, for `echo hooks`.
+//!
+//! The available hooks are listed in a `hooks.toml` manifest: the one of
+//! the configured flowmates checkout when present, the embedded one
+//! otherwise.
+//!
+//! Each hook is installed as a dispatcher running the scripts of
+//! `<hooks>/<name>.d/` in name order: echo's as [`SCRIPT_NAME`], and a hook
+//! already in place as [`PREVIOUS_NAME`], so echo coexists with other
+//! tooling. Files echo wrote carry [`MARKER`] on their second line. The
+//! hooks directory is the one git uses, so `core.hooksPath` is honoured;
+//! when husky manages the hooks, echo writes [`husky`] shims instead.
+
+pub mod husky;
+pub mod runner;
+pub mod verify;
+
+use crate::echoignore::EchoIgnore;
+use crate::gitutil;
+use crate::lock::{self, Lock, LockedFile};
+use crate::provenance::{Comment, Provenance};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Manifest file name at the root of a flowmates checkout.
+pub const MANIFEST_FILE: &str = "hooks.toml";
+
+/// Manifest shipped with echo.
+pub const DEFAULT_MANIFEST: &str = include_str!("../../templates/hooks.toml");
+
+/// Hooks echo can manage.
+pub const SUPPORTED: [&str; 4] = ["pre-commit", "commit-msg", "pre-push", "post-checkout"];
+
+/// Line marking a hook script as installed by echo.
+pub const MARKER: &str = "# Installed by echo hooks; `echo hooks uninstall` removes it.";
+
+/// Name of echo's script in `<name>.d/`.
+pub const SCRIPT_NAME: &str = "50-echo";
+
+/// Name given in `<name>.d/` to a hook found in place at install time;
+/// it sorts before [`SCRIPT_NAME`].
+pub const PREVIOUS_NAME: &str = "00-previous";
+
+/// Scripts referenced by the embedded manifest.
+const EMBEDDED_SCRIPTS: [(&str, &str); 2] = [
+    (
+        "scripts/commit-msg-hook",
+        include_str!("../../scripts/commit-msg-hook"),
+    ),
+    (
+        "scripts/pre-commit-hook",
+        include_str!("../../scripts/pre-commit-hook"),
+    ),
+];
+
+/// A manifest entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Entry {
+    script: String,
+    description: String,
+}
+
+/// A hook available for installation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    /// Git hook name, e.g. `pre-commit`.
+    pub name: String,
+    pub description: String,
+    /// Script path, `embedded/<script>` for the embedded manifest.
+    pub source: String,
+    /// Script file on disk, which can be linked to; `None` when embedded.
+    pub path: Option<PathBuf>,
+    /// Script contents.
+    pub content: String,
+}
+
+impl Hook {
+    /// Contents written to the hooks directory: the script with
+    /// [`MARKER`] and its provenance header after its shebang.
+    pub fn installed_content(&self) -> String {
+        let header = Provenance {
+            source: self.source.clone(),
+            version: None,
+            hash: lock::hash(&self.content),
+            installed: None,
+        }
+        .line(Comment::Hash);
+        match self.content.split_once('\n') {
+            Some((shebang, rest)) if shebang.starts_with("#!") => {
+                format!("{}\n{}\n{}\n{}", shebang, MARKER, header, rest)
+            }
+            _ => format!("{}\n{}\n{}", MARKER, header, self.content),
+        }
+    }
+
+    /// Whether `path` holds this script, copied with [`MARKER`] or linked
+    /// to the source.
+    fn is_placed_at(&self, path: &Path) -> Result<bool> {
+        if !path.is_file() {
+            return Ok(false);
+        }
+        let content = read(path)?;
+        Ok(content == self.installed_content() || content == self.content)
+    }
+}
+
+/// How echo's script is put in the hooks directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkMode {
+    /// A copy with [`MARKER`] added; the only option for embedded scripts.
+    #[default]
+    Copy,
+    /// A symbolic link to the manifest script, so edits to it apply at once.
+    Symlink,
+    /// A hard link to the manifest script.
+    Hardlink,
+}
+
+impl LinkMode {
+    /// Name recorded in the lockfile.
+    pub fn name(self) -> &'static str {
+        match self {
+            LinkMode::Copy => "copy",
+            LinkMode::Symlink => "symlink",
+            LinkMode::Hardlink => "hardlink",
+        }
+    }
+}
+
+/// Target of hook scripts in the lockfile.
+pub const LOCK_TARGET: &str = "hooks";
+
+/// State of a hook in the hooks directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookState {
+    /// Dispatcher and script installed by echo and matching the manifest.
+    Installed,
+    /// Installed by echo from another version of the script.
+    Outdated,
+    /// A hook echo did not write.
+    Foreign,
+    Missing,
+}
+
+impl HookState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookState::Installed => "installed",
+            HookState::Outdated => "outdated",
+            HookState::Foreign => "foreign",
+            HookState::Missing => "missing",
+        }
+    }
+}
+
+/// Outcome of installing one hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Installed,
+    Updated,
+    Unchanged,
+    /// Installed after moving the existing hook, written by the named tool
+    /// when recognized, to `<name>.d/`.
+    Chained(Option<&'static str>),
+    /// Left alone because of `--skip`.
+    Skipped,
+    /// Left alone because the hook or its script is listed in
+    /// `.echoignore`.
+    Protected,
+    /// Up to date, but the hook or script had lost its executable bit,
+    /// which git and the dispatcher need to run it.
+    MadeExecutable,
+}
+
+/// A supported hook and where it stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookStatus {
+    pub name: String,
+    /// Whether the manifest provides it.
+    pub available: bool,
+    pub state: HookState,
+}
+
+/// Fail unless `name` is a hook echo manages.
+pub fn check_name(name: &str) -> Result<()> {
+    if !SUPPORTED.contains(&name) {
+        bail!(
+            "unsupported hook '{}'; expected one of {}",
+            name,
+            SUPPORTED.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Hooks of the flowmates manifest if there is one, else the embedded
+/// ones, sorted by name.
+pub fn available(flowmates_dir: Option<&Path>) -> Result<Vec<Hook>> {
+    if let Some(dir) = flowmates_dir {
+        let path = dir.join(MANIFEST_FILE);
+        if path.is_file() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let manifest =
+                parse(&content).with_context(|| format!("invalid {}", path.display()))?;
+            return manifest
+                .into_iter()
+                .map(|(name, entry)| {
+                    let script = dir.join(&entry.script);
+                    let content = fs::read_to_string(&script)
+                        .with_context(|| format!("failed to read {}", script.display()))?;
+                    Ok(Hook {
+                        name,
+                        description: entry.description,
+                        source: script.display().to_string(),
+                        path: Some(script),
+                        content,
+                    })
+                })
+                .collect();
+        }
+    }
+    parse(DEFAULT_MANIFEST)?
+        .into_iter()
+        .map(|(name, entry)| {
+            let Some((_, content)) = EMBEDDED_SCRIPTS.iter().find(|(s, _)| *s == entry.script)
+            else {
+                bail!("embedded hook script {} is missing", entry.script);
+            };
+            Ok(Hook {
+                name,
+                description: entry.description,
+                source: format!("embedded/{}", entry.script),
+                path: None,
+                content: content.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse(content: &str) -> Result<BTreeMap<String, Entry>> {
+    let manifest: BTreeMap<String, Entry> = toml::from_str(content)?;
+    for name in manifest.keys() {
+        check_name(name)?;
+    }
+    Ok(manifest)
+}
+
+/// Hooks directory of the repository at `root`, honouring
+/// `core.hooksPath`.
+pub fn hooks_dir(root: &Path) -> Result<PathBuf> {
+    if !gitutil::is_repo(root) {
+        bail!("{} is not a git repository", root.display());
+    }
+    gitutil::git_path(root, "hooks")
+}
+
+/// Dispatcher installed as `<hooks>/<name>`: runs every executable of
+/// `<name>.d/` in name order and stops at the first failure. `pre-push`
+/// reads the pushed refs on stdin, so they are buffered for each script.
+pub fn dispatcher(name: &str) -> String {
+    let (read, run) = if name == "pre-push" {
+        (
+            "input=$(cat)\n",
+            "printf '%s\\n' \"$input\" | \"$hook\" \"$@\" || exit $?",
+        )
+    } else {
+        ("", "\"$hook\" \"$@\" || exit $?")
+    };
+    format!(
+        "#!/bin/sh\n{}\n# Runs each executable in {}.d/ in name order.\n{}for hook in \"$0.d\"/*; do\n    [ -x \"$hook\" ] || continue\n    {}\ndone\n",
+        MARKER, name, read, run
+    )
+}
+
+/// State of the hook `name`; `hook` is the manifest entry, if any.
+pub fn state(root: &Path, name: &str, hook: Option<&Hook>) -> Result<HookState> {
+    let dir = hooks_dir(root)?;
+    if husky::detect(root) {
+        return husky::state(root, name, hook);
+    }
+    let path = dir.join(name);
+    if !path.is_file() {
+        return Ok(HookState::Missing);
+    }
+    let content = read(&path)?;
+    if !is_managed(&content) {
+        return Ok(HookState::Foreign);
+    }
+    let script = dir.join(format!("{}.d", name)).join(SCRIPT_NAME);
+    match hook {
+        Some(hook) if content == dispatcher(name) && hook.is_placed_at(&script)? => {
+            Ok(HookState::Installed)
+        }
+        _ => Ok(HookState::Outdated),
+    }
+}
+
+/// Every supported hook, or only `only`, with its state.
+pub fn status(root: &Path, hooks: &[Hook], only: Option<&str>) -> Result<Vec<HookStatus>> {
+    let mut report = Vec::new();
+    for name in SUPPORTED {
+        if only.is_some_and(|only| only != name) {
+            continue;
+        }
+        let hook = hooks.iter().find(|h| h.name == name);
+        report.push(HookStatus {
+            name: name.to_string(),
+            available: hook.is_some(),
+            state: state(root, name, hook)?,
+        });
+    }
+    Ok(report)
+}
+
+/// Install the available hooks, or only `only`. A hook echo did not write
+/// is moved to `<name>.d/` and keeps running before echo's script; names in
+/// `skip` are left alone. `force` rewrites hooks that are up to date.
+/// Scripts are placed as `link` asks when possible, else copied; the mode
+/// used is returned for each script written and recorded in the lockfile.
+pub fn install(
+    root: &Path,
+    hooks: &[Hook],
+    only: Option<&str>,
+    force: bool,
+    skip: &[String],
+    link: LinkMode,
+) -> Result<Vec<(String, Action, Option<LinkMode>)>> {
+    if let Some(name) = only {
+        check_name(name)?;
+        if !hooks.iter().any(|h| h.name == name) {
+            bail!("hook '{}' is not in the hooks manifest", name);
+        }
+    }
+    let dir = hooks_dir(root)?;
+    let husky = husky::detect(root);
+    let ignore = EchoIgnore::load(root)?;
+    let mut lock = Lock::load(root)?;
+    let mut report = Vec::new();
+    for hook in hooks {
+        if only.is_some_and(|only| only != hook.name) {
+            continue;
+        }
+        if skip.contains(&hook.name) {
+            report.push((hook.name.clone(), Action::Skipped, None));
+            continue;
+        }
+        let (entry_point, script) = if husky {
+            (
+                root.join(husky::HUSKY_DIR).join(&hook.name),
+                husky::script_path(root, &hook.name),
+            )
+        } else {
+            (
+                dir.join(&hook.name),
+                dir.join(format!("{}.d", hook.name)).join(SCRIPT_NAME),
+            )
+        };
+        let protected = [&entry_point, &script].into_iter().any(|path| {
+            path.exists() && ignore.is_protected(path.strip_prefix(root).unwrap_or(path))
+        });
+        if protected {
+            report.push((hook.name.clone(), Action::Protected, None));
+            continue;
+        }
+        let action = if husky {
+            husky::install(root, hook, force)?
+        } else {
+            install_dispatcher(root, &dir, hook, force)?
+        };
+        if action == Action::Unchanged {
+            let fixed = make_executable(&entry_point)? | make_executable(&script)?;
+            let action = if fixed {
+                Action::MadeExecutable
+            } else {
+                action
+            };
+            report.push((hook.name.clone(), action, None));
+            continue;
+        }
+        let placed = place_script(&script, hook, link)?;
+        lock.record(LockedFile {
+            target: LOCK_TARGET.to_string(),
+            path: script.strip_prefix(root).unwrap_or(&script).to_path_buf(),
+            name: hook.name.clone(),
+            source: hook.source.clone(),
+            hash: lock::hash_installed(&read(&script)?),
+            link: Some(placed.name().to_string()),
+            mtime: None,
+        });
+        report.push((hook.name.clone(), action, Some(placed)));
+    }
+    lock.save(root)?;
+    Ok(report)
+}
+
+/// Put the dispatcher in place for `hook`, chaining a foreign hook, and
+/// say what installing the script will amount to.
+fn install_dispatcher(root: &Path, dir: &Path, hook: &Hook, force: bool) -> Result<Action> {
+    let path = dir.join(&hook.name);
+    let chain_dir = dir.join(format!("{}.d", hook.name));
+    let action = match state(root, &hook.name, Some(hook))? {
+        HookState::Installed if !force => return Ok(Action::Unchanged),
+        HookState::Installed | HookState::Outdated => Action::Updated,
+        HookState::Missing => Action::Installed,
+        HookState::Foreign => {
+            let tool = read(&path)?
+                .contains("pre-commit.com")
+                .then_some("pre-commit framework");
+            fs::create_dir_all(&chain_dir)?;
+            let previous = free_name(&chain_dir, PREVIOUS_NAME);
+            fs::rename(&path, &previous)
+                .with_context(|| format!("failed to move {}", path.display()))?;
+            Action::Chained(tool)
+        }
+    };
+    write_executable(&path, &dispatcher(&hook.name))?;
+    Ok(action)
+}
+
+/// Place the script of `hook` at `path` as `link` asks, copying it when
+/// the script is embedded, not executable or cannot be linked. Returns
+/// the mode used.
+pub(crate) fn place_script(path: &Path, hook: &Hook, link: LinkMode) -> Result<LinkMode> {
+    // Writing through an existing link would change its target.
+    remove_if_present(path)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let (Some(source), LinkMode::Symlink | LinkMode::Hardlink) = (&hook.path, link) {
+        if is_executable(source) && link_file(source, path, link).is_ok() {
+            return Ok(link);
+        }
+    }
+    write_executable(path, &hook.installed_content())?;
+    Ok(LinkMode::Copy)
+}
+
+fn link_file(source: &Path, path: &Path, link: LinkMode) -> std::io::Result<()> {
+    match link {
+        #[cfg(unix)]
+        LinkMode::Symlink => std::os::unix::fs::symlink(fs::canonicalize(source)?, path),
+        LinkMode::Hardlink => fs::hard_link(source, path),
+        _ => Err(std::io::ErrorKind::Unsupported.into()),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Set the executable bits of `path` when missing; returns whether they
+/// were.
+fn make_executable(path: &Path) -> Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if path.is_file() && !is_executable(path) {
+            let mode = fs::metadata(path)?.permissions().mode();
+            fs::set_permissions(path, fs::Permissions::from_mode(mode | 0o111))
+                .with_context(|| format!("failed to make {} executable", path.display()))?;
+            return Ok(true);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(false)
+}
+
+/// Remove `path`, a dangling link included, when there is one.
+fn remove_if_present(path: &Path) -> Result<()> {
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path).with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Remove the hooks echo installed, or only `only`, and return their
+/// names. A chained hook is put back in place when it is the only one
+/// left.
+pub fn uninstall(root: &Path, only: Option<&str>) -> Result<Vec<String>> {
+    if let Some(name) = only {
+        check_name(name)?;
+    }
+    let dir = hooks_dir(root)?;
+    let husky = husky::detect(root);
+    let mut lock = Lock::load(root)?;
+    let mut lock_changed = false;
+    let mut removed = Vec::new();
+    for name in SUPPORTED {
+        if only.is_some_and(|only| only != name) {
+            continue;
+        }
+        let script = if husky {
+            husky::script_path(root, name)
+        } else {
+            dir.join(format!("{}.d", name)).join(SCRIPT_NAME)
+        };
+        lock_changed |= lock.remove(script.strip_prefix(root).unwrap_or(&script));
+        if husky {
+            if husky::uninstall(root, name)? {
+                removed.push(name.to_string());
+            }
+            continue;
+        }
+        if !matches!(
+            state(root, name, None)?,
+            HookState::Installed | HookState::Outdated
+        ) {
+            continue;
+        }
+        let path = dir.join(name);
+        let chain_dir = dir.join(format!("{}.d", name));
+        remove_if_present(&script)?;
+        let rest = chained(&chain_dir)?;
+        match rest.as_slice() {
+            [] => {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?;
+                if chain_dir.is_dir() {
+                    fs::remove_dir(&chain_dir)?;
+                }
+            }
+            [only] if only.file_name().is_some_and(|n| n == PREVIOUS_NAME) => {
+                fs::rename(only, &path)
+                    .with_context(|| format!("failed to restore {}", path.display()))?;
+                fs::remove_dir(&chain_dir)?;
+            }
+            // Other hooks are registered; the dispatcher keeps running them.
+            _ => {}
+        }
+        removed.push(name.to_string());
+    }
+    if lock_changed {
+        lock.save(root)?;
+    }
+    Ok(removed)
+}
+
+/// Entries of a `<name>.d/` directory, sorted.
+fn chained(chain_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !chain_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut entries = fs::read_dir(chain_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// `dir/name`, or `dir/name-2`, ... when taken.
+fn free_name(dir: &Path, name: &str) -> PathBuf {
+    let mut path = dir.join(name);
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}", name, n));
+        n += 1;
+    }
+    path
+}
+
+fn is_managed(content: &str) -> bool {
+    content.lines().take(2).any(|line| line == MARKER)
+}
+
+pub(crate) fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+pub(crate) fn write_executable(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("failed to make {} executable", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git;
+
+    fn repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        git::run(dir.path(), &["init", "-q"]).unwrap();
+        dir
+    }
+
+    /// The embedded `pre-commit` hook alone.
+    fn pre_commit() -> Vec<Hook> {
+        let mut hooks = available(None).unwrap();
+        hooks.retain(|h| h.name == "pre-commit");
+        hooks
+    }
+
+    #[test]
+    fn loads_the_embedded_and_flowmates_manifests() {
+        let embedded = available(None).unwrap();
+        assert_eq!(embedded.len(), 2);
+        assert_eq!(embedded[1].source, "embedded/scripts/pre-commit-hook");
+        assert!(embedded[1]
+            .installed_content()
+            .starts_with(&format!("#!/bin/sh\n{}\n", MARKER)));
+        assert_eq!(
+            crate::provenance::find(&embedded[1].installed_content()).map(|p| p.source),
+            Some("embedded/scripts/pre-commit-hook".to_string())
+        );
+
+        let flowmates = tempfile::tempdir().unwrap();
+        fs::write(
+            flowmates.path().join(MANIFEST_FILE),
+            "[commit-msg]\nscript = \"msg.sh\"\ndescription = \"Check subjects\"\n",
+        )
+        .unwrap();
+        fs::write(flowmates.path().join("msg.sh"), "#!/bin/sh\nexit 0\n").unwrap();
+        let hooks = available(Some(flowmates.path())).unwrap();
+        assert_eq!(hooks[0].name, "commit-msg");
+        assert_eq!(hooks[0].description, "Check subjects");
+
+        fs::write(flowmates.path().join(MANIFEST_FILE), "[pre-merge]\n").unwrap();
+        assert!(available(Some(flowmates.path())).is_err());
+    }
+
+    #[test]
+    fn chains_existing_hooks_and_restores_them() {
+        let dir = repo();
+        let mut hooks = pre_commit();
+        hooks.push(Hook {
+            name: "pre-push".into(),
+            description: String::new(),
+            source: "test".into(),
+            path: None,
+            content: "#!/bin/sh\nexit 0\n".into(),
+        });
+        let hooks_path = hooks_dir(dir.path()).unwrap();
+        fs::create_dir_all(&hooks_path).unwrap();
+        let mine = "#!/bin/sh\n# File generated by pre-commit: https://pre-commit.com\n";
+        fs::write(hooks_path.join("pre-push"), mine).unwrap();
+
+        let report = install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap();
+        assert_eq!(
+            report,
+            vec![
+                (
+                    "pre-commit".to_string(),
+                    Action::Installed,
+                    Some(LinkMode::Copy)
+                ),
+                (
+                    "pre-push".to_string(),
+                    Action::Chained(Some("pre-commit framework")),
+                    Some(LinkMode::Copy)
+                ),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(hooks_path.join("pre-push")).unwrap(),
+            dispatcher("pre-push")
+        );
+        assert_eq!(
+            fs::read_to_string(hooks_path.join("pre-push.d/00-previous")).unwrap(),
+            mine
+        );
+        let skipped = install(
+            dir.path(),
+            &hooks,
+            None,
+            false,
+            &["pre-push".into()],
+            LinkMode::Copy,
+        )
+        .unwrap();
+        assert_eq!(skipped[0].1, Action::Unchanged);
+        assert_eq!(skipped[1].1, Action::Skipped);
+        assert!(install(
+            dir.path(),
+            &hooks,
+            Some("post-checkout"),
+            false,
+            &[],
+            LinkMode::Copy
+        )
+        .is_err());
+
+        hooks[0].content.push_str("# v2\n");
+        let states = status(dir.path(), &hooks, None).unwrap();
+        assert_eq!(states[0].state, HookState::Outdated);
+        assert!(!states[1].available);
+        assert_eq!(states[2].state, HookState::Installed);
+        assert_eq!(
+            install(
+                dir.path(),
+                &hooks,
+                Some("pre-commit"),
+                false,
+                &[],
+                LinkMode::Copy
+            )
+            .unwrap()[0]
+                .1,
+            Action::Updated
+        );
+
+        assert_eq!(
+            uninstall(dir.path(), None).unwrap(),
+            vec!["pre-commit", "pre-push"]
+        );
+        assert!(!hooks_path.join("pre-commit").exists());
+        assert!(!hooks_path.join("pre-commit.d").exists());
+        assert_eq!(
+            fs::read_to_string(hooks_path.join("pre-push")).unwrap(),
+            mine
+        );
+        assert!(!hooks_path.join("pre-push.d").exists());
+    }
+
+    #[test]
+    fn installs_into_the_configured_hooks_path() {
+        let dir = repo();
+        git::run(dir.path(), &["config", "core.hooksPath", "tools/hooks"]).unwrap();
+        assert_eq!(
+            hooks_dir(dir.path()).unwrap(),
+            dir.path().join("tools/hooks")
+        );
+        let hooks = pre_commit();
+        assert_eq!(
+            install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap()[0].1,
+            Action::Installed
+        );
+        assert!(dir
+            .path()
+            .join("tools/hooks/pre-commit.d/50-echo")
+            .is_file());
+        assert_eq!(
+            install(dir.path(), &hooks, None, true, &[], LinkMode::Copy).unwrap()[0].1,
+            Action::Updated
+        );
+
+        git::run(dir.path(), &["config", "core.hooksPath", ".husky/_"]).unwrap();
+        assert_eq!(
+            install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap()[0].1,
+            Action::Installed
+        );
+        assert!(dir.path().join(".husky/echo/pre-commit").is_file());
+        assert_eq!(uninstall(dir.path(), None).unwrap(), vec!["pre-commit"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_manifest_scripts_and_records_the_mode() {
+        let dir = repo();
+        let flowmates = tempfile::tempdir().unwrap();
+        let source = flowmates.path().join("pre-commit.sh");
+        write_executable(&source, "#!/bin/sh\nexit 0\n").unwrap();
+        let mut hook = Hook {
+            name: "pre-commit".into(),
+            description: String::new(),
+            source: source.display().to_string(),
+            path: Some(source.clone()),
+            content: "#!/bin/sh\nexit 0\n".into(),
+        };
+        let report = install(
+            dir.path(),
+            std::slice::from_ref(&hook),
+            None,
+            false,
+            &[],
+            LinkMode::Symlink,
+        )
+        .unwrap();
+        assert_eq!(report[0].2, Some(LinkMode::Symlink));
+        let script = hooks_dir(dir.path()).unwrap().join("pre-commit.d/50-echo");
+        assert!(script.symlink_metadata().unwrap().is_symlink());
+        let lock = Lock::load(dir.path()).unwrap();
+        assert_eq!(lock.files[0].link.as_deref(), Some("symlink"));
+
+        // Edits to the source apply without reinstalling.
+        fs::write(&source, "#!/bin/sh\nexit 1\n").unwrap();
+        hook.content = "#!/bin/sh\nexit 1\n".into();
+        assert_eq!(
+            state(dir.path(), "pre-commit", Some(&hook)).unwrap(),
+            HookState::Installed
+        );
+
+        // A script that cannot run is copied instead.
+        fs::set_permissions(&source, std::os::unix::fs::PermissionsExt::from_mode(0o644)).unwrap();
+        let report = install(
+            dir.path(),
+            std::slice::from_ref(&hook),
+            None,
+            true,
+            &[],
+            LinkMode::Hardlink,
+        )
+        .unwrap();
+        assert_eq!(report[0].2, Some(LinkMode::Copy));
+        assert!(!script.symlink_metadata().unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&source).unwrap(), "#!/bin/sh\nexit 1\n");
+
+        assert_eq!(uninstall(dir.path(), None).unwrap(), vec!["pre-commit"]);
+        assert!(Lock::load(dir.path()).unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn installs_worktree_hooks_into_the_common_directory() {
+        let dir = repo();
+        git::run(
+            dir.path(),
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "init",
+            ],
+        )
+        .unwrap();
+        let linked = tempfile::tempdir().unwrap();
+        let worktree = linked.path().join("feature");
+        git::run(
+            dir.path(),
+            &["worktree", "add", "-q", &worktree.to_string_lossy()],
+        )
+        .unwrap();
+        // `.git` is a file pointing at the main repository.
+        assert!(worktree.join(".git").is_file());
+
+        let hooks = pre_commit();
+        install(&worktree, &hooks, None, false, &[], LinkMode::Copy).unwrap();
+        assert!(dir.path().join(".git/hooks/pre-commit").is_file());
+        assert!(worktree.join(".echo/lock.toml").is_file());
+        assert_eq!(
+            state(dir.path(), "pre-commit", Some(&hooks[0])).unwrap(),
+            HookState::Installed
+        );
+        assert_eq!(uninstall(&worktree, None).unwrap(), vec!["pre-commit"]);
+        assert!(!dir.path().join(".git/hooks/pre-commit").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restores_lost_executable_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = repo();
+        let hooks = pre_commit();
+        install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap();
+        let script = hooks_dir(dir.path())
+            .unwrap()
+            .join("pre-commit.d")
+            .join(SCRIPT_NAME);
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let report = install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap();
+        assert_eq!(report[0].1, Action::MadeExecutable);
+        assert!(is_executable(&script));
+        let report = install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap();
+        assert_eq!(report[0].1, Action::Unchanged);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dispatcher_runs_chained_hooks_in_order() {
+        let dir = repo();
+        let hooks = vec![Hook {
+            name: "pre-commit".into(),
+            description: String::new(),
+            source: "test".into(),
+            path: None,
+            content: "#!/bin/sh\necho echo >> \"$1\"\n".into(),
+        }];
+        let hooks_path = hooks_dir(dir.path()).unwrap();
+        write_executable(
+            &hooks_path.join("pre-commit"),
+            "#!/bin/sh\necho previous >> \"$1\"\n",
+        )
+        .unwrap();
+        install(dir.path(), &hooks, None, false, &[], LinkMode::Copy).unwrap();
+
+        let log = dir.path().join("log");
+        let status = std::process::Command::new(hooks_path.join("pre-commit"))
+            .arg(&log)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&log).unwrap(), "previous\necho\n");
+    }
+}
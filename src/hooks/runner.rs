@@ -0,0 +1,314 @@
+//! Checks run by installed hooks through `echo hooks run <hook>`.
+//!
+//! The installed hook scripts only call echo, so upgrading echo changes
+//! what they check without reinstalling them. Checks are tuned in the
+//! `[hooks]` section of `.echo.toml`.
+
+use super::check_name;
+use crate::agent::AGENT_MD;
+use crate::config::Config;
+use crate::environment::Environment;
+use crate::git;
+use crate::gitutil;
+use crate::issues::{self, lint, ops, validate, State, ISSUES_DIR};
+use crate::rules;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Branches commits may not be made on directly.
+pub const PROTECTED_BRANCHES: [&str; 2] = ["main", "master"];
+
+/// Staged file count above which a catch-all `git add` is suspected.
+pub const MAX_STAGED_FILES: usize = 20;
+
+/// `[hooks]` section of `.echo.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct HooksConfig {
+    pub commit_msg: CommitMsgConfig,
+}
+
+/// `[hooks.commit-msg]`: issue references in commit messages.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CommitMsgConfig {
+    /// Require the message to name an issue of `issues/`.
+    pub require_issue: bool,
+    /// Subject prefixes exempt from the requirement, matched
+    /// case-insensitively.
+    pub allow: Vec<String>,
+}
+
+impl Default for CommitMsgConfig {
+    fn default() -> Self {
+        CommitMsgConfig {
+            require_issue: false,
+            allow: ["merge", "revert", "fixup!", "squash!", "amend!"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// Outcome of a hook run; the hook fails when there are errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Run the checks of `hook` in the repository at `root`, in `env`; `args`
+/// are the arguments git passed to the hook. Hooks without checks pass.
+pub fn run(
+    env: &Environment,
+    root: &Path,
+    config: &Config,
+    hook: &str,
+    args: &[String],
+) -> Result<Report> {
+    check_name(hook)?;
+    let mut report = Report::default();
+    match hook {
+        "pre-commit" => pre_commit(env, root, config, &mut report)?,
+        "commit-msg" => {
+            if let Some(message_file) = args.first() {
+                commit_msg(root, &config.hooks.commit_msg, message_file, &mut report)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(report)
+}
+
+/// Protected branches, issue workflow, lint and ownership of staged issues,
+/// AGENT.md presence, and installed rules drifting from their source.
+fn pre_commit(env: &Environment, root: &Path, config: &Config, report: &mut Report) -> Result<()> {
+    let branch = gitutil::branch(root).unwrap_or_default();
+    if PROTECTED_BRANCHES.contains(&branch.as_str()) {
+        report.errors.push(format!(
+            "cannot commit directly to {}; create a feature branch first",
+            branch
+        ));
+    }
+
+    let staged = staged_files(root)?;
+    if staged.iter().any(|path| path.starts_with(ISSUES_DIR)) {
+        for violation in validate::validate(&issues::scan(root)?) {
+            report.errors.push(violation.to_string());
+        }
+        for path in &staged {
+            // Templates and other non-issue files under issues/ are skipped.
+            let Ok(issue) = issues::read_issue_at(root, path) else {
+                continue;
+            };
+            let content = fs::read_to_string(root.join(path))
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            for violation in lint::lint(&issue, &content, &config.lint) {
+                report.errors.push(violation.to_string());
+            }
+            if issue.state == State::InProgress && !in_head(root, path) {
+                let me = ops::current_assignee(root, env.var(ops::ASSIGNEE_VAR));
+                if let Err(err) = ops::check_owner(&issue, me.as_deref(), false) {
+                    report.errors.push(err.to_string());
+                }
+            }
+        }
+    }
+
+    if !root.join(AGENT_MD).is_file() {
+        report.errors.push(format!(
+            "{} is missing; run `echo init` to create it",
+            AGENT_MD
+        ));
+    }
+
+    for (name, _) in rules::diff(root, &config.rules, None)? {
+        report.warnings.push(format!(
+            "rule {} differs from its source; see `echo rules diff {}`",
+            name, name
+        ));
+    }
+    if staged.len() > MAX_STAGED_FILES {
+        report.warnings.push(format!(
+            "{} files staged; prefer `git add <file>` over `git add -A`",
+            staged.len()
+        ));
+    }
+    Ok(())
+}
+
+/// When required, the message in `message_file` must name an issue id,
+/// alone or as part of a path such as `echo/feature-login`.
+fn commit_msg(
+    root: &Path,
+    config: &CommitMsgConfig,
+    message_file: &str,
+    report: &mut Report,
+) -> Result<()> {
+    if !config.require_issue {
+        return Ok(());
+    }
+    let content = fs::read_to_string(root.join(message_file))
+        .with_context(|| format!("failed to read {}", message_file))?;
+    // Git drops comment lines from the final message.
+    let message: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    let Some(subject) = message.iter().find(|line| !line.trim().is_empty()) else {
+        return Ok(());
+    };
+    let subject = subject.trim().to_lowercase();
+    if config
+        .allow
+        .iter()
+        .any(|prefix| subject.starts_with(&prefix.to_lowercase()))
+    {
+        return Ok(());
+    }
+    let issues = issues::scan(root)?;
+    let ids: BTreeSet<String> = issues.iter().map(|i| i.id().to_lowercase()).collect();
+    let referenced = message
+        .join("\n")
+        .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/')))
+        .filter_map(|token| token.rsplit('/').next())
+        .any(|token| ids.contains(&token.to_lowercase()));
+    if !referenced {
+        report.errors.push(format!(
+            "commit message names no issue of {}/; mention an issue id such as `{}`, or start the subject with one of: {}",
+            ISSUES_DIR,
+            issues.first().map_or("feature-login", |i| i.id()),
+            config.allow.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `path` exists in the HEAD commit; issues moved to another state
+/// are new at their path.
+fn in_head(root: &Path, path: &Path) -> bool {
+    git::run(
+        root,
+        &["cat-file", "-e", &format!("HEAD:{}", path.display())],
+    )
+    .is_ok()
+}
+
+/// Added, copied, modified and renamed files in the index.
+fn staged_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let output = git::run(
+        root,
+        &["diff", "--cached", "--name-only", "--diff-filter=ACMR"],
+    )?;
+    Ok(output.lines().map(PathBuf::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_branch_issues_agent_md_and_rule_drift() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let env = Environment::new(root);
+        git::run(root, &["init", "-q", "-b", "main"]).unwrap();
+        let config = Config::default();
+
+        let report = run(&env, root, &config, "pre-commit", &[]).unwrap();
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors[0].starts_with("cannot commit directly to main"));
+        assert!(report.errors[1].starts_with("AGENT.md is missing"));
+        assert!(run(&env, root, &config, "pre-merge", &[]).is_err());
+        assert!(run(&env, root, &config, "post-checkout", &[])
+            .unwrap()
+            .errors
+            .is_empty());
+
+        git::run(root, &["checkout", "-q", "-b", "feat/x"]).unwrap();
+        fs::write(root.join(AGENT_MD), "# Agent\n").unwrap();
+        let todo = root.join("issues/app/todo");
+        fs::create_dir_all(&todo).unwrap();
+        fs::write(
+            todo.join("login.md"),
+            "# Login\n\n**Type:** Feature\n**Status:** Done\n",
+        )
+        .unwrap();
+        git::run(root, &["add", "issues"]).unwrap();
+        let installed = rules::install(root, &config.rules, false).unwrap();
+        fs::write(root.join(&installed[0].0), "edited\n").unwrap();
+
+        let report = run(&env, root, &config, "pre-commit", &[]).unwrap();
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.starts_with("issues/app/todo/login.md")));
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].starts_with("rule "));
+    }
+
+    #[test]
+    fn refuses_taking_over_an_issue_assigned_to_someone_else() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let env = Environment::new(root);
+        git::run(root, &["init", "-q", "-b", "feat/x"]).unwrap();
+        git::run(root, &["config", "user.name", "bob"]).unwrap();
+        fs::write(root.join(AGENT_MD), "# Agent\n").unwrap();
+        let in_progress = root.join("issues/app/in_progress");
+        fs::create_dir_all(&in_progress).unwrap();
+        let issue = |env: &Environment, assignee: &str| {
+            fs::write(
+                in_progress.join("login.md"),
+                format!(
+                    "# Login\n\n**Type:** feature\n**Status:** in_progress\n**Assignee:** {}\n\n## Context\n\n## Acceptance Criteria\n",
+                    assignee
+                ),
+            )
+            .unwrap();
+            git::run(root, &["add", "issues"]).unwrap();
+            run(env, root, &Config::default(), "pre-commit", &[])
+                .unwrap()
+                .errors
+        };
+
+        let errors = issue(&env, "alice");
+        assert_eq!(
+            errors,
+            vec!["issues/app/in_progress/login.md is assigned to alice; pass --steal to take it over"]
+        );
+        assert!(issue(&env, "bob").is_empty());
+        let alice = env.with_var(ops::ASSIGNEE_VAR, "alice");
+        assert!(issue(&alice, "alice").is_empty());
+    }
+
+    #[test]
+    fn requires_issue_references_when_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let env = Environment::new(root);
+        let done = root.join("issues/app/done");
+        fs::create_dir_all(&done).unwrap();
+        fs::write(done.join("feature-login.md"), "# Login\n").unwrap();
+        let check = |config: &Config, message: &str| {
+            fs::write(root.join("MSG"), message).unwrap();
+            run(&env, root, config, "commit-msg", &["MSG".to_string()])
+                .unwrap()
+                .errors
+        };
+
+        let mut config = Config::default();
+        assert!(check(&config, "Add login\n").is_empty());
+        config.hooks.commit_msg.require_issue = true;
+        assert_eq!(check(&config, "Add login\n# feature-login\n").len(), 1);
+        assert!(check(&config, "Add login (feature-login)\n").is_empty());
+        assert!(check(&config, "Add login\n\nRefs app/Feature-Login.\n").is_empty());
+        assert!(check(&config, "Merge branch 'main'\n").is_empty());
+        assert_eq!(check(&config, "chore: bump deps\n").len(), 1);
+        config.hooks.commit_msg.allow = vec!["chore:".into()];
+        assert!(check(&config, "Chore: bump deps\n").is_empty());
+    }
+}
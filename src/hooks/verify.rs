@@ -0,0 +1,196 @@
+//! Integrity of installed hooks, for `echo hooks verify`.
+//!
+//! A hook is checked against what echo recorded when installing it: the
+//! dispatcher or husky shim must still be in place, and echo's script must
+//! still hash to the lockfile entry, or still link to its source when it
+//! was linked. Tools that rewrite `.git/hooks/` otherwise replace echo's
+//! hooks without anyone noticing.
+
+use super::{dispatcher, hooks_dir, husky, is_managed, read, LinkMode, SCRIPT_NAME, SUPPORTED};
+use crate::lock::{self, Lock, LockedFile};
+use crate::provenance::{self, Provenance};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// How an installed hook compares to what echo wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrity {
+    /// Exactly as echo installed it.
+    Managed,
+    /// Written by echo and changed since, for the given reason.
+    Modified(&'static str),
+    /// A hook echo did not write, or that replaced echo's.
+    Foreign,
+    /// Recorded in the lockfile but gone from the hooks directory.
+    Missing,
+}
+
+impl Integrity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Integrity::Managed => "managed by echo",
+            Integrity::Modified(_) => "modified locally",
+            Integrity::Foreign => "foreign hook",
+            Integrity::Missing => "missing",
+        }
+    }
+}
+
+/// Integrity of one hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub name: String,
+    pub integrity: Integrity,
+    /// Whether the lockfile records echo's script for this hook.
+    pub recorded: bool,
+    /// Provenance header of echo's script, when it still has one.
+    pub provenance: Option<Provenance>,
+}
+
+impl Verification {
+    /// Whether the hook no longer is what echo installed: edited, or
+    /// recorded and since replaced or removed.
+    pub fn is_problem(&self) -> bool {
+        match self.integrity {
+            Integrity::Managed => false,
+            Integrity::Modified(_) => true,
+            Integrity::Foreign | Integrity::Missing => self.recorded,
+        }
+    }
+}
+
+/// Check every supported hook present or recorded in the lockfile, or only
+/// `only`.
+pub fn verify(root: &Path, only: Option<&str>) -> Result<Vec<Verification>> {
+    let dir = hooks_dir(root)?;
+    let husky = husky::detect(root);
+    let lock = Lock::load(root)?;
+    let mut report = Vec::new();
+    for name in SUPPORTED {
+        if only.is_some_and(|only| only != name) {
+            continue;
+        }
+        let (entry_point, script) = if husky {
+            (
+                root.join(husky::HUSKY_DIR).join(name),
+                husky::script_path(root, name),
+            )
+        } else {
+            (
+                dir.join(name),
+                dir.join(format!("{}.d", name)).join(SCRIPT_NAME),
+            )
+        };
+        let locked = lock.get(script.strip_prefix(root).unwrap_or(&script));
+        if !entry_point.is_file() {
+            if locked.is_some() {
+                report.push(Verification {
+                    name: name.to_string(),
+                    integrity: Integrity::Missing,
+                    recorded: true,
+                    provenance: None,
+                });
+            }
+            continue;
+        }
+        let content = read(&entry_point)?;
+        let integrity = if husky {
+            if !content.contains(&husky::shim(name)) {
+                Integrity::Foreign
+            } else {
+                check_script(&script, locked)?
+            }
+        } else if !is_managed(&content) {
+            Integrity::Foreign
+        } else if content != dispatcher(name) {
+            Integrity::Modified("dispatcher edited")
+        } else {
+            check_script(&script, locked)?
+        };
+        report.push(Verification {
+            name: name.to_string(),
+            integrity,
+            recorded: locked.is_some(),
+            provenance: fs::read_to_string(&script)
+                .ok()
+                .and_then(|script| provenance::find(&script)),
+        });
+    }
+    Ok(report)
+}
+
+/// Compare echo's script at `path` with its lockfile entry.
+fn check_script(path: &Path, locked: Option<&LockedFile>) -> Result<Integrity> {
+    if !path.is_file() {
+        return Ok(Integrity::Modified("script removed"));
+    }
+    let Some(locked) = locked else {
+        return Ok(Integrity::Modified("script not in the lockfile"));
+    };
+    let source = Path::new(&locked.source);
+    let intact = match locked.link.as_deref() {
+        // Edits to the source are expected to apply through the link.
+        Some(mode) if mode == LinkMode::Symlink.name() => {
+            path.symlink_metadata()?.is_symlink()
+                && fs::canonicalize(path).ok() == fs::canonicalize(source).ok()
+        }
+        Some(mode) if mode == LinkMode::Hardlink.name() => {
+            source.is_file() && read(path)? == read(source)?
+        }
+        _ => lock::hash_installed(&read(path)?) == locked.hash,
+    };
+    Ok(if intact {
+        Integrity::Managed
+    } else {
+        Integrity::Modified("script edited")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git;
+    use crate::hooks::{available, install};
+
+    #[test]
+    fn tells_managed_modified_and_foreign_hooks_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        git::run(root, &["init", "-q"]).unwrap();
+        assert!(verify(root, None).unwrap().is_empty());
+
+        let hooks = available(None).unwrap();
+        install(root, &hooks, None, false, &[], LinkMode::Copy).unwrap();
+        let report = verify(root, None).unwrap();
+        assert_eq!(report.len(), 2);
+        assert!(report.iter().all(|v| v.integrity == Integrity::Managed));
+        assert_eq!(
+            report[0].provenance.as_ref().map(|p| p.source.as_str()),
+            Some("embedded/scripts/pre-commit-hook")
+        );
+
+        let hooks_dir = hooks_dir(root).unwrap();
+        let script = hooks_dir.join("pre-commit.d").join(SCRIPT_NAME);
+        fs::write(&script, "#!/bin/sh\nexit 0\n").unwrap();
+        fs::write(hooks_dir.join("commit-msg"), "#!/bin/sh\nnpx lint\n").unwrap();
+        fs::write(hooks_dir.join("pre-push"), "#!/bin/sh\nmake test\n").unwrap();
+        let report = verify(root, None).unwrap();
+        let integrity: Vec<_> = report
+            .iter()
+            .map(|v| (v.name.as_str(), v.integrity, v.is_problem()))
+            .collect();
+        assert_eq!(
+            integrity,
+            vec![
+                ("pre-commit", Integrity::Modified("script edited"), true),
+                ("commit-msg", Integrity::Foreign, true),
+                ("pre-push", Integrity::Foreign, false),
+            ]
+        );
+
+        fs::remove_file(hooks_dir.join("commit-msg")).unwrap();
+        let report = verify(root, Some("commit-msg")).unwrap();
+        assert_eq!(report[0].integrity, Integrity::Missing);
+    }
+}
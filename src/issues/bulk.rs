@@ -0,0 +1,215 @@
+//! `echo issue bulk-move` and `echo issue bulk-edit`: one command for
+//! every issue matching a filter, with a dry run listing them first.
+
+use super::{ops, Issue, State};
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+/// Which issues a bulk operation applies to; unset criteria match every
+/// issue.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub state: Option<State>,
+    /// An entry of the `**Labels:**` field.
+    pub label: Option<String>,
+    /// The `**Assignee:**` field.
+    pub assignee: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, issue: &Issue) -> bool {
+        self.state.is_none_or(|state| issue.state == state)
+            && self
+                .label
+                .as_deref()
+                .is_none_or(|label| issue.labels().contains(&label))
+            && self
+                .assignee
+                .as_deref()
+                .is_none_or(|assignee| issue.field(ops::ASSIGNEE_FIELD) == Some(assignee))
+    }
+
+    /// The issues of `issues` matching.
+    pub fn select(&self, issues: Vec<Issue>) -> Vec<Issue> {
+        issues
+            .into_iter()
+            .filter(|issue| self.matches(issue))
+            .collect()
+    }
+}
+
+/// Move `issues` to `to` on behalf of `claim`, or only report what would
+/// move with `dry_run`. Every move is checked before any is made (see
+/// [`ops::Transition::check`]). Returns each transition with the new path.
+pub fn move_all(
+    root: &Path,
+    issues: &[Issue],
+    to: State,
+    claim: ops::Claim,
+    dry_run: bool,
+) -> Result<Vec<(ops::Transition, PathBuf)>> {
+    let transitions = issues
+        .iter()
+        .filter(|issue| issue.state != to)
+        .map(|issue| ops::Transition::check(root, issue.clone(), to, claim))
+        .collect::<Result<Vec<_>>>()?;
+    let mut moved = Vec::new();
+    for transition in transitions {
+        let dest = if dry_run {
+            let issue = &transition.issue;
+            issue
+                .path
+                .parent()
+                .and_then(Path::parent)
+                .unwrap_or(Path::new(""))
+                .join(to.dir_name())
+                .join(issue.path.file_name().unwrap_or_default())
+        } else {
+            transition.apply(root)?
+        };
+        moved.push((transition, dest));
+    }
+    Ok(moved)
+}
+
+/// Parse a `name=value` assignment of `--set`.
+pub fn parse_assignment(assignment: &str) -> Result<(String, String)> {
+    let Some((name, value)) = assignment.split_once('=') else {
+        bail!("invalid --set '{}': expected name=value", assignment);
+    };
+    let name = name.trim();
+    if name.is_empty() || name.contains(['*', ':', '\n']) {
+        bail!("invalid field name '{}' in --set", name);
+    }
+    if name.eq_ignore_ascii_case("status") {
+        bail!("the status follows the state directory; use `echo issue bulk-move`");
+    }
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// Set `fields` on every issue of `issues`, or only report which would
+/// change with `dry_run`. A field keeps the spelling it has in the issue;
+/// new ones are capitalized, `assignee` becoming `**Assignee:**`.
+pub fn edit_all(
+    root: &Path,
+    issues: &[Issue],
+    fields: &[(String, String)],
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut edited = Vec::new();
+    for issue in issues {
+        let changes: Vec<(String, &str)> = fields
+            .iter()
+            .filter(|(name, value)| issue.field(name) != Some(value.as_str()))
+            .map(|(name, value)| (field_name(issue, name), value.as_str()))
+            .collect();
+        if changes.is_empty() {
+            continue;
+        }
+        if !dry_run {
+            let changes: Vec<(&str, &str)> = changes
+                .iter()
+                .map(|(name, value)| (name.as_str(), *value))
+                .collect();
+            ops::set_fields(root, &issue.path, &changes)?;
+        }
+        edited.push(issue.path.clone());
+    }
+    Ok(edited)
+}
+
+/// `name` as spelled in the header of `issue`, or capitalized.
+fn field_name(issue: &Issue, name: &str) -> String {
+    if let Some((existing, _)) = issue
+        .fields
+        .iter()
+        .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+    {
+        return existing.clone();
+    }
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::{scan, tests::write_issue};
+    use std::fs;
+
+    #[test]
+    fn moves_and_edits_the_matching_issues() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let header = "# X\n\n**Type:** feature\n**Status:** proposal\n";
+        write_issue(
+            root,
+            "app",
+            "proposal",
+            "a.md",
+            &format!("{}**Labels:** approved\n", header),
+        );
+        write_issue(root, "app", "proposal", "b.md", header);
+        write_issue(
+            root,
+            "app",
+            "todo",
+            "c.md",
+            "# C\n**Labels:** approved\n**assignee:** bob\n",
+        );
+
+        let approved = Filter {
+            state: Some(State::Proposal),
+            label: Some("approved".into()),
+            ..Filter::default()
+        };
+        let selected = approved.select(scan(root).unwrap());
+        let paths = |moved: Vec<(ops::Transition, PathBuf)>| -> Vec<(PathBuf, PathBuf)> {
+            moved
+                .into_iter()
+                .map(|(transition, dest)| (transition.issue.path, dest))
+                .collect()
+        };
+        let claim = ops::Claim::default();
+        let planned = paths(move_all(root, &selected, State::Todo, claim, true).unwrap());
+        assert_eq!(
+            planned,
+            vec![(
+                PathBuf::from("issues/app/proposal/a.md"),
+                PathBuf::from("issues/app/todo/a.md")
+            )]
+        );
+        assert!(root.join("issues/app/proposal/a.md").is_file());
+        assert_eq!(
+            paths(move_all(root, &selected, State::Todo, claim, false).unwrap()),
+            planned
+        );
+        assert!(root.join("issues/app/todo/a.md").is_file());
+
+        let todo = Filter {
+            state: Some(State::Todo),
+            ..Filter::default()
+        };
+        let fields = vec![parse_assignment("assignee = alice").unwrap()];
+        let selected = todo.select(scan(root).unwrap());
+        assert_eq!(edit_all(root, &selected, &fields, true).unwrap().len(), 2);
+        assert!(!fs::read_to_string(root.join("issues/app/todo/a.md"))
+            .unwrap()
+            .contains("alice"));
+        edit_all(root, &selected, &fields, false).unwrap();
+        let a = fs::read_to_string(root.join("issues/app/todo/a.md")).unwrap();
+        assert!(a.contains("**Labels:** approved\n**Assignee:** alice\n"));
+        let c = fs::read_to_string(root.join("issues/app/todo/c.md")).unwrap();
+        assert!(c.contains("**assignee:** alice\n"));
+        let selected = todo.select(scan(root).unwrap());
+        assert!(edit_all(root, &selected, &fields, false)
+            .unwrap()
+            .is_empty());
+
+        assert!(parse_assignment("assignee").is_err());
+        assert!(parse_assignment("Status=done").is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! Dependencies between issues, declared in a `**Depends on:**` header
+//! field, and their graph for `echo issue graph`.
+//!
+//! A dependency names an issue id, e.g. `**Depends on:** [feature-login,
+//! bug-crash]`, of the same project; `<project>/<id>` names an issue of
+//! another project.
+
+use super::render::escape_mermaid;
+use super::{Issue, State};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Header field listing the issues an issue depends on.
+pub const DEPENDS_FIELD: &str = "Depends on";
+
+/// Output format for `echo issue graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+/// Ids listed in the `**Depends on:**` field of `issue`, with or without
+/// surrounding brackets.
+pub fn dependencies(issue: &Issue) -> Vec<&str> {
+    let Some(value) = issue.field(DEPENDS_FIELD) else {
+        return Vec::new();
+    };
+    let value = value.trim();
+    let value = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(value);
+    value
+        .split(',')
+        .map(|id| {
+            id.trim()
+                .trim_matches(['"', '\'', '`'])
+                .trim_end_matches(".md")
+        })
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+/// The issue of `issues` that dependency `id` of `from` names, if any.
+pub fn resolve<'a>(issues: &'a [Issue], from: &Issue, id: &str) -> Option<&'a Issue> {
+    let (project, id) = id.split_once('/').unwrap_or((&from.project, id));
+    issues
+        .iter()
+        .find(|issue| issue.project == project && issue.id() == id)
+}
+
+/// An edge of the graph: `from` depends on `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge<'a> {
+    pub from: &'a Issue,
+    /// `None` when the dependency names no known issue.
+    pub to: Option<&'a Issue>,
+    /// The dependency as written.
+    pub id: &'a str,
+}
+
+/// Every dependency of `issues`, in issue order.
+pub fn edges(issues: &[Issue]) -> Vec<Edge<'_>> {
+    issues
+        .iter()
+        .flat_map(|from| {
+            dependencies(from).into_iter().map(move |id| Edge {
+                from,
+                to: resolve(issues, from, id),
+                id,
+            })
+        })
+        .collect()
+}
+
+/// Dependencies of `issue` that are not done: open issues, and ids naming
+/// no issue of `issues`.
+pub fn unmet<'a>(issues: &'a [Issue], issue: &'a Issue) -> Vec<Edge<'a>> {
+    dependencies(issue)
+        .into_iter()
+        .map(|id| Edge {
+            from: issue,
+            to: resolve(issues, issue, id),
+            id,
+        })
+        .filter(|edge| edge.to.is_none_or(|to| to.state != State::Done))
+        .collect()
+}
+
+/// Ids of the issues on a dependency cycle, the first one repeated at the
+/// end, when there is one.
+pub fn cycle(issues: &[Issue]) -> Option<Vec<String>> {
+    let edges = edges(issues);
+    let mut next: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for edge in &edges {
+        if let Some(to) = edge.to {
+            next.entry(key(edge.from)).or_default().push(key(to));
+        }
+    }
+    // Depth-first search; `path` holds the issues being visited.
+    fn visit(
+        node: &str,
+        next: &BTreeMap<String, Vec<String>>,
+        done: &mut Vec<String>,
+        path: &mut Vec<String>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|n| n == node) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(node.to_string());
+            return Some(cycle);
+        }
+        if done.iter().any(|n| n == node) {
+            return None;
+        }
+        path.push(node.to_string());
+        for to in next.get(node).into_iter().flatten() {
+            if let Some(cycle) = visit(to, next, done, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.push(node.to_string());
+        None
+    }
+    let mut done = Vec::new();
+    next.keys()
+        .find_map(|node| visit(node, &next, &mut done, &mut Vec::new()))
+}
+
+/// Render the dependency graph of `issues` in the requested format.
+pub fn render(issues: &[Issue], format: Format) -> String {
+    match format {
+        Format::Dot => render_dot(issues),
+        Format::Mermaid => render_mermaid(issues),
+    }
+}
+
+/// Render a graphviz digraph, an arrow pointing from each issue to the
+/// issues it depends on.
+pub fn render_dot(issues: &[Issue]) -> String {
+    let mut out = String::from("digraph issues {\n    rankdir=LR;\n    node [shape=box];\n");
+    for issue in issues {
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{}\\n{}\", style=filled, fillcolor=\"{}\"];",
+            key(issue),
+            escape_dot(&issue.title),
+            issue.state.label(),
+            color(issue.state)
+        );
+    }
+    for edge in edges(issues) {
+        let to = match edge.to {
+            Some(to) => key(to),
+            None => {
+                let _ = writeln!(
+                    out,
+                    "    \"{}\" [label=\"{} (unknown)\", style=dashed];",
+                    escape_dot(edge.id),
+                    escape_dot(edge.id)
+                );
+                escape_dot(edge.id)
+            }
+        };
+        let _ = writeln!(out, "    \"{}\" -> \"{}\";", key(edge.from), to);
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Render a mermaid flowchart, one class per state.
+pub fn render_mermaid(issues: &[Issue]) -> String {
+    let mut out = String::from("flowchart LR\n");
+    for issue in issues {
+        let _ = writeln!(
+            out,
+            "    {}[\"{}\"]:::{}",
+            node(&key(issue)),
+            escape_mermaid(&issue.title),
+            issue.state.dir_name()
+        );
+    }
+    for edge in edges(issues) {
+        let to = match edge.to {
+            Some(to) => node(&key(to)),
+            None => {
+                let unknown = node(edge.id);
+                let _ = writeln!(
+                    out,
+                    "    {}[\"{} (unknown)\"]",
+                    unknown,
+                    escape_mermaid(edge.id)
+                );
+                unknown
+            }
+        };
+        let _ = writeln!(out, "    {} --> {}", node(&key(edge.from)), to);
+    }
+    for state in State::ALL {
+        let _ = writeln!(
+            out,
+            "    classDef {} fill:{}",
+            state.dir_name(),
+            color(state)
+        );
+    }
+    out
+}
+
+/// `<project>/<id>`, unique across projects.
+fn key(issue: &Issue) -> String {
+    format!("{}/{}", issue.project, issue.id())
+}
+
+/// A mermaid node id for `key`.
+fn node(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn color(state: State) -> &'static str {
+    match state {
+        State::Proposal => "#eeeeee",
+        State::Todo => "#fff3c4",
+        State::InProgress => "#c4e1ff",
+        State::Done => "#c8f0c8",
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn issue(project: &str, state: State, id: &str, depends: Option<&str>) -> Issue {
+        Issue {
+            path: PathBuf::from(format!("issues/{}/{}/{}.md", project, state.dir_name(), id)),
+            project: project.into(),
+            state,
+            title: id.to_uppercase(),
+            fields: depends
+                .map(|d| vec![(DEPENDS_FIELD.to_string(), d.to_string())])
+                .unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn resolves_dependencies_and_finds_the_unmet_ones() {
+        let issues = [
+            issue("app", State::Todo, "c", Some("[a, b.md, api/d, nope]")),
+            issue("app", State::Done, "a", None),
+            issue("app", State::InProgress, "b", None),
+            issue("api", State::Todo, "d", Some("a")),
+        ];
+        assert_eq!(dependencies(&issues[0]), vec!["a", "b", "api/d", "nope"]);
+        let open: Vec<_> = unmet(&issues, &issues[0])
+            .into_iter()
+            .map(|edge| (edge.id, edge.to.map(|to| to.state)))
+            .collect();
+        assert_eq!(
+            open,
+            vec![
+                ("b", Some(State::InProgress)),
+                ("api/d", Some(State::Todo)),
+                ("nope", None)
+            ]
+        );
+        // `a` of api does not exist: ids resolve within their project.
+        assert_eq!(unmet(&issues, &issues[3]).len(), 1);
+        assert_eq!(cycle(&issues), None);
+
+        let dot = render_dot(&issues);
+        assert!(dot.contains("\"app/c\" -> \"app/b\";"));
+        assert!(dot.contains("\"app/c\" -> \"nope\";"));
+        let mermaid = render_mermaid(&issues);
+        assert!(mermaid.contains("    app_c[\"C\"]:::todo\n"));
+        assert!(mermaid.contains("    app_c --> api_d\n"));
+    }
+
+    #[test]
+    fn finds_cycles() {
+        let issues = [
+            issue("app", State::Todo, "a", Some("b")),
+            issue("app", State::Todo, "b", Some("c")),
+            issue("app", State::Todo, "c", Some("a")),
+        ];
+        assert_eq!(
+            cycle(&issues).unwrap(),
+            vec!["app/a", "app/b", "app/c", "app/a"]
+        );
+    }
+}
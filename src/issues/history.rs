@@ -0,0 +1,168 @@
+//! `echo issue history`: the lifecycle of an issue, rebuilt from the git
+//! commits that created, moved and edited its file.
+//!
+//! Moves between state directories are renames, which `git log --follow`
+//! tracks; the state of each commit is the directory the file was in.
+
+use super::{Issue, State};
+use crate::environment;
+use crate::git;
+use crate::gitutil;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::path::Path;
+
+/// What a commit did to an issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Created(State),
+    Moved { from: State, to: State },
+    Edited(State),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Created(state) => write!(f, "created in {}", state),
+            Change::Moved { from, to } => write!(f, "moved {} -> {}", from, to),
+            Change::Edited(state) => write!(f, "edited in {}", state),
+        }
+    }
+}
+
+/// A commit touching an issue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub commit: String,
+    pub author: String,
+    /// Seconds since the epoch.
+    pub at: u64,
+    pub subject: String,
+    pub change: Change,
+}
+
+impl Event {
+    /// One line of the timeline, e.g. `2024-03-01  alice  moved todo ->
+    /// in_progress  (1a2b3c4 Start login)`.
+    pub fn line(&self) -> String {
+        format!(
+            "{}  {}  {}  ({} {})",
+            environment::date(self.at),
+            self.author,
+            self.change,
+            &self.commit[..self.commit.len().min(7)],
+            self.subject
+        )
+    }
+}
+
+/// Events of `issue` in the repository at `root`, oldest first.
+pub fn history(root: &Path, issue: &Issue) -> Result<Vec<Event>> {
+    if !gitutil::is_repo(root) {
+        bail!("issue history needs a git repository");
+    }
+    let path = issue.path.to_string_lossy();
+    let log = git::run(
+        root,
+        &[
+            "log",
+            "--follow",
+            "--name-status",
+            "--format=%x00%H%x09%an%x09%at%x09%s",
+            "--",
+            &path,
+        ],
+    )?;
+    let mut events: Vec<Event> = log.split('\0').filter_map(parse_commit).collect();
+    events.reverse();
+    Ok(events)
+}
+
+/// An [`Event`] from a commit of the log: its header, then `--name-status`
+/// lines.
+fn parse_commit(entry: &str) -> Option<Event> {
+    let mut lines = entry.lines().filter(|line| !line.trim().is_empty());
+    let mut header = lines.next()?.splitn(4, '\t');
+    let commit = header.next()?.to_string();
+    let author = header.next()?.to_string();
+    let at = header.next()?.parse().ok()?;
+    let subject = header.next().unwrap_or_default().to_string();
+    let status = lines.next()?;
+    let mut fields = status.split('\t');
+    let code = fields.next()?;
+    let paths: Vec<&str> = fields.collect();
+    let change = match (code.chars().next()?, paths.as_slice()) {
+        ('R' | 'C', [from, to]) => match (state_of(from)?, state_of(to)?) {
+            (from, to) if from == to => Change::Edited(to),
+            (from, to) => Change::Moved { from, to },
+        },
+        ('A', [path]) => Change::Created(state_of(path)?),
+        (_, [.., path]) => Change::Edited(state_of(path)?),
+        _ => return None,
+    };
+    Some(Event {
+        commit,
+        author,
+        at,
+        subject,
+        change,
+    })
+}
+
+/// State of the directory holding the issue at `path`.
+fn state_of(path: &str) -> Option<State> {
+    let dir = Path::new(path).parent()?.file_name()?.to_str()?;
+    State::from_name(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::{ops, read_issue_at, tests::write_issue};
+    use std::fs;
+
+    #[test]
+    fn follows_an_issue_across_states() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        git::run(root, &["init", "-q", "-b", "main"]).unwrap();
+        git::run(root, &["config", "user.email", "dev@example.com"]).unwrap();
+        git::run(root, &["config", "user.name", "Dev"]).unwrap();
+        let commit = |message: &str| {
+            git::run(root, &["add", "-A"]).unwrap();
+            git::run(root, &["commit", "-q", "-m", message]).unwrap();
+        };
+        let content = "# Login\n\n**Type:** feature\n**Status:** todo\n\nSome context.\n";
+        write_issue(root, "app", "todo", "login.md", content);
+        commit("Add login");
+        let path = ops::move_issue(root, "app", "login", State::InProgress).unwrap();
+        commit("Start login");
+        let mut content = fs::read_to_string(root.join(&path)).unwrap();
+        content.push_str("\nNotes.\n");
+        fs::write(root.join(&path), content).unwrap();
+        commit("Take notes");
+
+        let issue = read_issue_at(root, &path).unwrap();
+        let changes: Vec<_> = history(root, &issue)
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.change, event.subject))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (Change::Created(State::Todo), "Add login".to_string()),
+                (
+                    Change::Moved {
+                        from: State::Todo,
+                        to: State::InProgress
+                    },
+                    "Start login".to_string()
+                ),
+                (Change::Edited(State::InProgress), "Take notes".to_string()),
+            ]
+        );
+        let line = history(root, &issue).unwrap()[1].line();
+        assert!(line.contains("  Dev  moved todo -> in_progress  ("));
+    }
+}
@@ -0,0 +1,249 @@
+//! Structural checks on issue files for `echo issue lint`.
+//!
+//! Each check can be switched off in the `[lint]` section of `.echo.toml`.
+
+use super::validate::Violation;
+use super::{Issue, State};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// `[lint]` section of `.echo.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LintConfig {
+    /// Require the sections listed in `required-sections`.
+    pub sections: bool,
+    /// Require a non-empty `# ` title on the first line, without a
+    /// trailing period.
+    pub title: bool,
+    /// Limit the title to `max-title-length` characters.
+    pub title_length: bool,
+    /// Reject `{{...}}` placeholders left over from the templates.
+    pub placeholders: bool,
+    /// Allow placeholders in proposals and issues still in `todo/`, which
+    /// `echo issue new` creates with the template hints left to fill in.
+    pub draft_placeholders: bool,
+    /// Require `**Type:**` and a known `**Status:**` in the header.
+    pub front_matter: bool,
+    /// Sections required of issues whose type has no entry in
+    /// `sections-by-type`.
+    pub required_sections: Vec<String>,
+    /// Sections required of issues by `**Type:**`, e.g. `bug`.
+    pub sections_by_type: BTreeMap<String, Vec<String>>,
+    pub max_title_length: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            sections: true,
+            title: true,
+            title_length: true,
+            placeholders: true,
+            draft_placeholders: true,
+            front_matter: true,
+            required_sections: vec!["Context".into(), "Acceptance Criteria".into()],
+            // The sections of the bundled templates lacking the defaults.
+            sections_by_type: [
+                ("adr", ["Context", "Decision"]),
+                ("bug", ["Reproduction", "Acceptance Criteria"]),
+                ("proposal", ["Context", "Proposal"]),
+                ("roadmap", ["Problem / Context", "Outcomes"]),
+            ]
+            .into_iter()
+            .map(|(kind, sections)| (kind.into(), sections.map(String::from).to_vec()))
+            .collect(),
+            max_title_length: 80,
+        }
+    }
+}
+
+impl LintConfig {
+    /// Sections required of `issue`.
+    pub fn sections_of(&self, issue: &Issue) -> &[String] {
+        issue
+            .field("Type")
+            .and_then(|kind| self.sections_by_type.get(kind))
+            .unwrap_or(&self.required_sections)
+    }
+}
+
+/// Lint one issue given its raw file content.
+pub fn lint(issue: &Issue, content: &str, config: &LintConfig) -> Vec<Violation> {
+    let mut messages = Vec::new();
+    if config.title {
+        check_title(content, &mut messages);
+    }
+    if config.title_length && issue.title.chars().count() > config.max_title_length {
+        messages.push(format!(
+            "title is {} characters long (max {})",
+            issue.title.chars().count(),
+            config.max_title_length
+        ));
+    }
+    if config.sections {
+        let headings: Vec<_> = content.lines().filter_map(section_name).collect();
+        for section in config.sections_of(issue) {
+            if !headings.iter().any(|h| h.eq_ignore_ascii_case(section)) {
+                messages.push(format!("missing section '{}'", section));
+            }
+        }
+    }
+    let draft = matches!(issue.state, State::Proposal | State::Todo);
+    if config.placeholders && !(draft && config.draft_placeholders) {
+        for (n, line) in content.lines().enumerate() {
+            if let Some(placeholder) = find_placeholder(line) {
+                messages.push(format!(
+                    "line {}: template placeholder {} left",
+                    n + 1,
+                    placeholder
+                ));
+            }
+        }
+    }
+    if config.front_matter {
+        if issue.field("Type").is_none_or(str::is_empty) {
+            messages.push("missing **Type:** field".into());
+        }
+        match issue.field("Status") {
+            None | Some("") => messages.push("missing **Status:** field".into()),
+            Some(status) if State::from_name(status).is_none() => {
+                messages.push(format!("unknown status '{}'", status))
+            }
+            Some(_) => {}
+        }
+    }
+    messages
+        .into_iter()
+        .map(|message| Violation {
+            path: issue.path.clone(),
+            message,
+        })
+        .collect()
+}
+
+pub(super) fn check_title(content: &str, messages: &mut Vec<String>) {
+    let first = content.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    match first.trim().strip_prefix("# ").map(str::trim) {
+        None | Some("") => messages.push("first line must be a '# ' title".into()),
+        Some(title) if title.ends_with('.') => {
+            messages.push("title must not end with a period".into())
+        }
+        Some(_) => {}
+    }
+}
+
+/// Name of a `## ` section heading, without leading emoji or symbols.
+pub(super) fn section_name(line: &str) -> Option<&str> {
+    let heading = line.trim().strip_prefix("## ")?;
+    Some(
+        heading
+            .trim_start_matches(|c: char| !c.is_alphanumeric())
+            .trim(),
+    )
+}
+
+fn find_placeholder(line: &str) -> Option<&str> {
+    let start = line.find("{{")?;
+    let end = line[start..].find("}}")? + start + 2;
+    Some(&line[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::parse_header;
+    use std::path::PathBuf;
+
+    fn lint_str(content: &str, config: &LintConfig) -> Vec<String> {
+        let (title, fields) = parse_header(content, "x");
+        let issue = Issue {
+            path: PathBuf::from("issues/echo/todo/x.md"),
+            project: "echo".into(),
+            state: State::InProgress,
+            title,
+            fields,
+        };
+        lint(&issue, content, config)
+            .into_iter()
+            .map(|v| v.message)
+            .collect()
+    }
+
+    const GOOD: &str = "# Add lint\n\n**Type:** feature  \n**Status:** todo  \n\n## 🧠 Context\nWhy.\n\n## 🧩 Acceptance Criteria\n- [ ] Works\n";
+
+    #[test]
+    fn well_formed_issue_passes() {
+        assert!(lint_str(GOOD, &LintConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn reports_template_leftovers() {
+        let content = "# {{title}}\n\n**Type:** feature  \n**Status:** todo  \n\n## Context\n{{short background}}\n";
+        assert_eq!(
+            lint_str(content, &LintConfig::default()),
+            vec![
+                "missing section 'Acceptance Criteria'",
+                "line 1: template placeholder {{title}} left",
+                "line 7: template placeholder {{short background}} left",
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_sections_by_type_and_allows_hints_in_drafts() {
+        let bug = "# Crash\n\n**Type:** bug  \n**Status:** todo  \n\n## 🧪 Reproduction\n{{step}}\n\n## ✅ Acceptance Criteria\n- [ ] Fixed\n";
+        assert_eq!(
+            lint_str(bug, &LintConfig::default()),
+            vec!["line 7: template placeholder {{step}} left"]
+        );
+        let (title, fields) = parse_header(bug, "x");
+        let draft = Issue {
+            path: PathBuf::from("issues/echo/todo/x.md"),
+            project: "echo".into(),
+            state: State::Todo,
+            title,
+            fields,
+        };
+        let config = LintConfig::default();
+        assert!(lint(&draft, bug, &config).is_empty());
+        let strict = LintConfig {
+            draft_placeholders: false,
+            sections_by_type: BTreeMap::new(),
+            ..config
+        };
+        assert_eq!(lint(&draft, bug, &strict).len(), 2);
+    }
+
+    #[test]
+    fn checks_title_and_front_matter() {
+        let content = "Intro\n# Too long a title.\n**Status:** later\n";
+        let config = LintConfig {
+            sections: false,
+            max_title_length: 10,
+            ..LintConfig::default()
+        };
+        assert_eq!(
+            lint_str(content, &config),
+            vec![
+                "first line must be a '# ' title",
+                "title is 17 characters long (max 10)",
+                "missing **Type:** field",
+                "unknown status 'later'",
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_checks_are_skipped() {
+        let config = LintConfig {
+            sections: false,
+            title: false,
+            title_length: false,
+            placeholders: false,
+            front_matter: false,
+            ..LintConfig::default()
+        };
+        assert!(lint_str("{{title}}", &config).is_empty());
+    }
+}
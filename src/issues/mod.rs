@@ -0,0 +1,397 @@
+//! Issue files stored under `issues/<project>/<state>/`.
+//!
+//! Each issue is a markdown file whose header carries `**Field:** value`
+//! lines (Type, Status, Branch, ...) as produced by the templates in
+//! `issues/shared/templates/`.
+
+pub mod bulk;
+pub mod graph;
+pub mod history;
+pub mod lint;
+pub mod ops;
+pub mod render;
+pub mod stale;
+pub mod template_lint;
+pub mod templates;
+pub mod validate;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory holding all issue projects, relative to the repository root.
+pub const ISSUES_DIR: &str = "issues";
+
+/// Directory under `issues/` shared by every project (templates).
+pub const SHARED_DIR: &str = "shared";
+
+/// Header field listing the labels of an issue, e.g. `backend, api`.
+pub const LABELS_FIELD: &str = "Labels";
+
+/// Workflow state of an issue, matching its directory name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Proposal,
+    Todo,
+    InProgress,
+    Done,
+}
+
+impl State {
+    /// All states in workflow order.
+    pub const ALL: [State; 4] = [State::Proposal, State::Todo, State::InProgress, State::Done];
+
+    /// Directory name used for this state.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            State::Proposal => "proposal",
+            State::Todo => "todo",
+            State::InProgress => "in_progress",
+            State::Done => "done",
+        }
+    }
+
+    /// Human readable label.
+    pub fn label(self) -> &'static str {
+        match self {
+            State::Proposal => "Proposal",
+            State::Todo => "Todo",
+            State::InProgress => "In Progress",
+            State::Done => "Done",
+        }
+    }
+
+    /// Parse a directory name or `**Status:**` value.
+    pub fn from_name(name: &str) -> Option<State> {
+        State::ALL.into_iter().find(|s| s.dir_name() == name)
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.dir_name())
+    }
+}
+
+/// A parsed issue file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Path relative to the repository root.
+    pub path: PathBuf,
+    pub project: String,
+    /// State derived from the directory the file lives in.
+    pub state: State,
+    pub title: String,
+    /// Header fields in file order, e.g. `("Type", "feature")`.
+    pub fields: Vec<(String, String)>,
+}
+
+impl Issue {
+    /// Issue identifier: the file stem.
+    pub fn id(&self) -> &str {
+        self.path.file_stem().and_then(|s| s.to_str()).unwrap_or("")
+    }
+
+    /// Value of a header field, matched case-insensitively.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Labels of the `**Labels:**` field, comma-separated, with or without
+    /// surrounding brackets.
+    pub fn labels(&self) -> Vec<&str> {
+        let value = self.field(LABELS_FIELD).unwrap_or_default().trim();
+        value
+            .strip_prefix('[')
+            .and_then(|v| v.strip_suffix(']'))
+            .unwrap_or(value)
+            .split(',')
+            .map(|label| label.trim().trim_matches(['"', '\'', '`']))
+            .filter(|label| !label.is_empty())
+            .collect()
+    }
+}
+
+/// Parse the title and `**Field:** value` header lines of an issue.
+///
+/// The title is the first `# ` heading, falling back to `fallback_title`.
+pub fn parse_header(content: &str, fallback_title: &str) -> (String, Vec<(String, String)>) {
+    let mut title = None;
+    let mut fields = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if title.is_none() {
+            if let Some(heading) = line.strip_prefix("# ") {
+                title = Some(heading.trim().to_string());
+                continue;
+            }
+        }
+        if let Some((key, value)) = parse_field_line(line) {
+            fields.push((key, value));
+        }
+    }
+    let title = title
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| fallback_title.to_string());
+    (title, fields)
+}
+
+/// Parse a single `**Key:** value` line.
+pub fn parse_field_line(line: &str) -> Option<(String, String)> {
+    let rest = line.trim().strip_prefix("**")?;
+    let (key, value) = rest.split_once(":**")?;
+    if key.is_empty() || key.contains('*') {
+        return None;
+    }
+    Some((key.trim().to_string(), value.trim().to_string()))
+}
+
+/// Read and parse one issue file.
+pub fn read_issue(root: &Path, rel_path: &Path, project: &str, state: State) -> Result<Issue> {
+    let content = fs::read_to_string(root.join(rel_path))
+        .with_context(|| format!("failed to read {}", rel_path.display()))?;
+    let stem = rel_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let (title, fields) = parse_header(&content, stem);
+    Ok(Issue {
+        path: rel_path.to_path_buf(),
+        project: project.to_string(),
+        state,
+        title,
+        fields,
+    })
+}
+
+/// Read an issue from its path relative to `root`, deriving project and
+/// state from the `issues/<project>/<state>/` components.
+pub fn read_issue_at(root: &Path, rel_path: &Path) -> Result<Issue> {
+    let parts: Vec<_> = rel_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    match parts.as_slice() {
+        [dir, project, state, _] if dir == ISSUES_DIR => {
+            let state = State::from_name(state).with_context(|| {
+                format!(
+                    "{}: unknown state directory '{}'",
+                    rel_path.display(),
+                    state
+                )
+            })?;
+            read_issue(root, rel_path, project, state)
+        }
+        _ => bail!(
+            "{} is not an issue file (expected issues/<project>/<state>/<name>.md)",
+            rel_path.display()
+        ),
+    }
+}
+
+/// Discover issue projects: every directory under `issues/` except
+/// `shared/`, sorted by name.
+pub fn projects(root: &Path) -> Result<Vec<String>> {
+    let issues_root = root.join(ISSUES_DIR);
+    let mut projects = Vec::new();
+    if !issues_root.is_dir() {
+        return Ok(projects);
+    }
+    for entry in fs::read_dir(&issues_root)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() && name != SHARED_DIR && !name.starts_with('.') {
+            projects.push(name);
+        }
+    }
+    projects.sort();
+    Ok(projects)
+}
+
+/// Pick the project a command operates on.
+///
+/// An explicit name must exist under `issues/`. Without one, the single
+/// discovered project is used; zero or several projects are an error.
+pub fn resolve_project(root: &Path, requested: Option<&str>) -> Result<String> {
+    let known = projects(root)?;
+    match requested {
+        Some(name) => {
+            if known.iter().any(|p| p == name) {
+                Ok(name.to_string())
+            } else {
+                bail!(
+                    "unknown project '{}' (known: {}); run `echo init --project {}` first",
+                    name,
+                    list_or_none(&known),
+                    name
+                )
+            }
+        }
+        None => match known.as_slice() {
+            [only] => Ok(only.clone()),
+            [] => bail!("no issue projects found under issues/; run `echo init` first"),
+            _ => bail!(
+                "several issue projects found ({}); pass --project <name>",
+                known.join(", ")
+            ),
+        },
+    }
+}
+
+fn list_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+/// Scan every project under `issues/` and return its issues, ordered by
+/// project, state and file name.
+pub fn scan(root: &Path) -> Result<Vec<Issue>> {
+    let mut issues = Vec::new();
+    for project in projects(root)? {
+        issues.extend(scan_project(root, &project)?);
+    }
+    Ok(issues)
+}
+
+/// Scan the state directories of a single project.
+pub fn scan_project(root: &Path, project: &str) -> Result<Vec<Issue>> {
+    let mut issues = Vec::new();
+    for state in State::ALL {
+        let rel_dir = Path::new(ISSUES_DIR).join(project).join(state.dir_name());
+        let dir = root.join(&rel_dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_file() && Path::new(&name).extension() == Some("md".as_ref()) {
+                names.push(name);
+            }
+        }
+        names.sort();
+        for name in names {
+            issues.push(read_issue(root, &rel_dir.join(name), project, state)?);
+        }
+    }
+    Ok(issues)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Write an issue file with the given header into a temporary tree.
+    pub(crate) fn write_issue(root: &Path, project: &str, state: &str, name: &str, content: &str) {
+        let dir = root.join(ISSUES_DIR).join(project).join(state);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(name), content).unwrap();
+    }
+
+    #[test]
+    fn parses_title_and_fields() {
+        let content = "# Initialize Repository\n\n**Type:** feature  \n**Status:** done  \n**Branch:** feat/init-repo  \n";
+        let (title, fields) = parse_header(content, "fallback");
+        assert_eq!(title, "Initialize Repository");
+        assert_eq!(
+            fields,
+            vec![
+                ("Type".to_string(), "feature".to_string()),
+                ("Status".to_string(), "done".to_string()),
+                ("Branch".to_string(), "feat/init-repo".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_labels() {
+        let (title, fields) = parse_header("# X\n**Labels:** [backend, `api`, ]\n", "x");
+        let issue = Issue {
+            path: PathBuf::from("issues/app/todo/x.md"),
+            project: "app".into(),
+            state: State::Todo,
+            title,
+            fields,
+        };
+        assert_eq!(issue.labels(), vec!["backend", "api"]);
+    }
+
+    #[test]
+    fn falls_back_to_file_stem_title() {
+        let (title, _) = parse_header("no heading here", "feature-x");
+        assert_eq!(title, "feature-x");
+    }
+
+    #[test]
+    fn ignores_bold_text_that_is_not_a_field() {
+        assert_eq!(parse_field_line("**Note**: something"), None);
+        assert_eq!(parse_field_line("plain"), None);
+    }
+
+    #[test]
+    fn scans_projects_in_workflow_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_issue(dir.path(), "echo", "done", "b.md", "# B\n");
+        write_issue(dir.path(), "echo", "todo", "a.md", "# A\n");
+        fs::create_dir_all(dir.path().join("issues/shared/templates")).unwrap();
+        fs::write(dir.path().join("issues/shared/templates/x.md"), "# T").unwrap();
+
+        let issues = scan(dir.path()).unwrap();
+        let ids: Vec<_> = issues.iter().map(|i| (i.id(), i.state)).collect();
+        assert_eq!(ids, vec![("a", State::Todo), ("b", State::Done)]);
+    }
+
+    #[test]
+    fn reads_issue_from_path() {
+        let dir = tempfile::tempdir().unwrap();
+        write_issue(dir.path(), "api", "in_progress", "x.md", "# X\n");
+        let issue = read_issue_at(dir.path(), Path::new("issues/api/in_progress/x.md")).unwrap();
+        assert_eq!(
+            (issue.project.as_str(), issue.state),
+            ("api", State::InProgress)
+        );
+        assert!(read_issue_at(dir.path(), Path::new("README.md")).is_err());
+        assert!(read_issue_at(dir.path(), Path::new("issues/api/later/x.md")).is_err());
+    }
+
+    #[test]
+    fn resolves_single_project_implicitly() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("issues/shared/templates")).unwrap();
+        fs::create_dir_all(dir.path().join("issues/api/todo")).unwrap();
+        assert_eq!(resolve_project(dir.path(), None).unwrap(), "api");
+        assert_eq!(resolve_project(dir.path(), Some("api")).unwrap(), "api");
+    }
+
+    #[test]
+    fn ambiguous_or_unknown_project_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("issues/api")).unwrap();
+        fs::create_dir_all(dir.path().join("issues/web")).unwrap();
+        let err = resolve_project(dir.path(), None).unwrap_err().to_string();
+        assert!(err.contains("api, web"), "{}", err);
+        assert!(resolve_project(dir.path(), Some("cli")).is_err());
+    }
+
+    #[test]
+    fn no_project_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(resolve_project(dir.path(), None).is_err());
+    }
+
+    #[test]
+    fn scan_without_issues_dir_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(scan(dir.path()).unwrap().is_empty());
+    }
+}
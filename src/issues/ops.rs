@@ -0,0 +1,622 @@
+//! Commands that change the `issues/` tree: project setup, creating and
+//! moving issues.
+
+use super::{
+    graph, parse_header, projects, scan, scan_project, templates, Issue, State, ISSUES_DIR,
+    SHARED_DIR,
+};
+use crate::git;
+use crate::gitutil;
+use crate::provenance;
+use crate::template::{self, Variables};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Header field naming who owns an issue.
+pub const ASSIGNEE_FIELD: &str = "Assignee";
+
+/// Environment variable naming who `me` is, for agents sharing a git
+/// identity.
+pub const ASSIGNEE_VAR: &str = "ECHO_ASSIGNEE";
+
+/// The assignee `me` stands for: `var`, the value of [`ASSIGNEE_VAR`],
+/// else the git `user.name` of `root`.
+pub fn current_assignee(root: &Path, var: Option<&str>) -> Option<String> {
+    var.filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .or_else(|| gitutil::config(root, "user.name"))
+        .filter(|name| !name.is_empty())
+}
+
+/// `name`, with `me` replaced by `current`.
+pub fn resolve_assignee(name: &str, current: Option<&str>) -> Result<String> {
+    if name != "me" {
+        return Ok(name.to_string());
+    }
+    current.map(str::to_string).with_context(|| {
+        format!(
+            "cannot tell who 'me' is; set {} or git user.name",
+            ASSIGNEE_VAR
+        )
+    })
+}
+
+/// Fail when `issue` is assigned to someone other than `claimant`, unless
+/// `steal` is set.
+pub fn check_owner(issue: &Issue, claimant: Option<&str>, steal: bool) -> Result<()> {
+    let Some(owner) = issue
+        .field(ASSIGNEE_FIELD)
+        .filter(|owner| !owner.is_empty())
+    else {
+        return Ok(());
+    };
+    if steal || claimant == Some(owner) {
+        return Ok(());
+    }
+    bail!(
+        "{} is assigned to {}; pass --steal to take it over",
+        issue.path.display(),
+        owner
+    )
+}
+
+/// Who moves an issue: `by` claims it when starting it, taking it over
+/// from its assignee with `steal`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Claim<'a> {
+    pub by: Option<&'a str>,
+    pub steal: bool,
+}
+
+/// A move of an issue to another state, checked as every command moving
+/// issues checks it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    /// The issue, as it was before moving.
+    pub issue: Issue,
+    pub to: State,
+    /// Dependencies of the issue still open or unknown, when starting it.
+    pub warnings: Vec<String>,
+}
+
+impl Transition {
+    /// Check moving `issue` to `to`. Starting an issue assigned to someone
+    /// other than `claim.by` fails unless stolen (see [`check_owner`]),
+    /// and warns about its unmet dependencies.
+    pub fn check(root: &Path, issue: Issue, to: State, claim: Claim) -> Result<Self> {
+        let mut warnings = Vec::new();
+        if to == State::InProgress {
+            check_owner(&issue, claim.by, claim.steal)?;
+            let issues = scan(root)?;
+            for edge in graph::unmet(&issues, &issue) {
+                warnings.push(match edge.to {
+                    Some(to) => {
+                        format!("{} depends on {}, still {}", issue.id(), edge.id, to.state)
+                    }
+                    None => format!(
+                        "{} depends on {}, which is no known issue",
+                        issue.id(),
+                        edge.id
+                    ),
+                });
+            }
+        }
+        Ok(Self {
+            issue,
+            to,
+            warnings,
+        })
+    }
+
+    /// Move the issue, returning its new path relative to `root`.
+    pub fn apply(&self, root: &Path) -> Result<PathBuf> {
+        move_issue(root, &self.issue.project, self.issue.id(), self.to)
+    }
+}
+
+/// Move issue `id` of `project` to `to` once [`Transition::check`] allows
+/// it, returning the transition and the new path.
+pub fn transition(
+    root: &Path,
+    project: &str,
+    id: &str,
+    to: State,
+    claim: Claim,
+) -> Result<(Transition, PathBuf)> {
+    let transition = Transition::check(root, find_issue(root, project, id)?, to, claim)?;
+    let path = transition.apply(root)?;
+    Ok((transition, path))
+}
+
+/// Default project name: the repository directory name.
+pub fn detect_project_name(root: &Path) -> Result<String> {
+    root.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .with_context(|| format!("cannot derive a project name from {}", root.display()))
+}
+
+/// Check that `name` can be used as a directory under `issues/`.
+pub fn validate_project_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == SHARED_DIR || name.starts_with('.') || name.contains(['/', '\\'])
+    {
+        bail!("invalid project name '{}'", name);
+    }
+    Ok(())
+}
+
+/// Create the state directories of one project, returning the directories
+/// that did not exist yet. Existing directories are left untouched.
+pub fn create_issue_workflow_structure(root: &Path, project: &str) -> Result<Vec<PathBuf>> {
+    validate_project_name(project)?;
+    let mut created = Vec::new();
+    for state in State::ALL {
+        let rel_dir = Path::new(ISSUES_DIR).join(project).join(state.dir_name());
+        let dir = root.join(&rel_dir);
+        if dir.is_dir() {
+            continue;
+        }
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", rel_dir.display()))?;
+        created.push(rel_dir);
+    }
+    Ok(created)
+}
+
+/// Rename a project: move `issues/<old>/` to `issues/<new>/` (with
+/// `git mv` when tracked) and rewrite `issues/<old>/` references and
+/// `**Project:**` fields in every issue file. Returns the rewritten files.
+pub fn rename_project(root: &Path, old: &str, new: &str) -> Result<Vec<PathBuf>> {
+    validate_project_name(new)?;
+    let known = projects(root)?;
+    if !known.iter().any(|p| p == old) {
+        bail!("unknown project '{}'", old);
+    }
+    if known.iter().any(|p| p == new) {
+        bail!("project '{}' already exists", new);
+    }
+    git::move_path(
+        root,
+        &Path::new(ISSUES_DIR).join(old),
+        &Path::new(ISSUES_DIR).join(new),
+    )?;
+    create_issue_workflow_structure(root, new)?;
+
+    let old_ref = format!("{}/{}/", ISSUES_DIR, old);
+    let new_ref = format!("{}/{}/", ISSUES_DIR, new);
+    let mut rewritten = Vec::new();
+    for issue in scan(root)? {
+        let path = root.join(&issue.path);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", issue.path.display()))?;
+        let mut updated = content.replace(&old_ref, &new_ref);
+        if issue.project == new && issue.field("Project") == Some(old) {
+            updated = set_field(&updated, "Project", new);
+        }
+        if updated != content {
+            fs::write(&path, updated)
+                .with_context(|| format!("failed to write {}", issue.path.display()))?;
+            rewritten.push(issue.path);
+        }
+    }
+    Ok(rewritten)
+}
+
+/// Turn a title into a lowercase, dash-separated file name stem.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+/// Create a new issue of `project` from the shared template for `kind` and
+/// return its path relative to `root`. It goes to the state directory its
+/// `**Status:**` names, `todo/` when that is no state.
+///
+/// The file is named `<kind>-<slug>.md`. The template is rendered with
+/// `vars` plus `title`, `slug` and `ISSUE_ID`; author hints such as
+/// `{{short background}}` are kept. It may extend other templates and
+/// include partials (see [`templates::loader`]).
+pub fn new_issue(
+    root: &Path,
+    project: &str,
+    kind: &str,
+    title: &str,
+    vars: &Variables,
+) -> Result<PathBuf> {
+    let slug = slugify(title);
+    if slug.is_empty() {
+        bail!("issue title '{}' has no usable characters", title);
+    }
+    let (template_path, template) = read_template(root, kind)?;
+    let id = format!("{}-{}", kind, slug);
+    let vars = issue_variables(kind, title, vars);
+    let content = template::render_issue_template_with(&template, &vars, templates::loader(root))
+        .with_context(|| format!("failed to render {}", template_path.display()))?;
+
+    let (_, fields) = parse_header(&content, &id);
+    let state = fields
+        .iter()
+        .find(|(name, _)| name == "Status")
+        .and_then(|(_, status)| State::from_name(status))
+        .unwrap_or(State::Todo);
+    let rel_path = Path::new(ISSUES_DIR)
+        .join(project)
+        .join(state.dir_name())
+        .join(format!("{}.md", id));
+    let path = root.join(&rel_path);
+    if path.exists() {
+        bail!("{} already exists", rel_path.display());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content).with_context(|| format!("failed to write {}", rel_path.display()))?;
+    Ok(rel_path)
+}
+
+/// Variables the template for `kind` references that `vars` does not
+/// define, for `issue new` to ask for (see [`new_issue`]).
+pub fn undefined_variables(root: &Path, kind: &str, vars: &Variables) -> Result<Vec<String>> {
+    let (template_path, template) = read_template(root, kind)?;
+    template::undefined_issue_variables(
+        &template,
+        &issue_variables(kind, "", vars),
+        templates::loader(root),
+    )
+    .with_context(|| format!("failed to render {}", template_path.display()))
+}
+
+fn read_template(root: &Path, kind: &str) -> Result<(PathBuf, String)> {
+    let template_path = templates::template_path(kind);
+    let template = fs::read_to_string(root.join(&template_path))
+        .with_context(|| format!("no template for issue type '{}'", kind))?;
+    let template = provenance::strip(&template).into_owned();
+    Ok((template_path, template))
+}
+
+/// `vars` plus those of the `kind` issue titled `title`: `title`, `slug`
+/// and `ISSUE_ID`.
+pub fn issue_variables(kind: &str, title: &str, vars: &Variables) -> Variables {
+    let slug = slugify(title);
+    let mut vars = vars.clone();
+    vars.insert("title".into(), title.to_string());
+    vars.insert("ISSUE_ID".into(), format!("{}-{}", kind, slug));
+    vars.insert("slug".into(), slug);
+    vars
+}
+
+/// Find an issue of `project` by id (file stem) or file name.
+pub fn find_issue(root: &Path, project: &str, id: &str) -> Result<Issue> {
+    let id = id.strip_suffix(".md").unwrap_or(id);
+    let mut matches: Vec<_> = scan_project(root, project)?
+        .into_iter()
+        .filter(|i| i.id() == id)
+        .collect();
+    match matches.len() {
+        0 => bail!("no issue '{}' in project '{}'", id, project),
+        1 => Ok(matches.remove(0)),
+        _ => bail!(
+            "issue '{}' exists in several states: {}",
+            id,
+            matches
+                .iter()
+                .map(|i| i.state.dir_name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Move an issue to another state directory and update its `**Status:**`
+/// line. Returns the new path relative to `root`.
+pub fn move_issue(root: &Path, project: &str, id: &str, to: State) -> Result<PathBuf> {
+    let issue = find_issue(root, project, id)?;
+    if issue.state == to {
+        bail!("{} is already in {}", issue.path.display(), to);
+    }
+    let file_name = issue
+        .path
+        .file_name()
+        .context("issue path has no file name")?;
+    let rel_dest = Path::new(ISSUES_DIR)
+        .join(project)
+        .join(to.dir_name())
+        .join(file_name);
+    let dest = root.join(&rel_dest);
+    if dest.exists() {
+        bail!("{} already exists", rel_dest.display());
+    }
+    let content = fs::read_to_string(root.join(&issue.path))
+        .with_context(|| format!("failed to read {}", issue.path.display()))?;
+    fs::create_dir_all(dest.parent().unwrap_or(root))?;
+    fs::write(&dest, set_status(&content, to))
+        .with_context(|| format!("failed to write {}", rel_dest.display()))?;
+    fs::remove_file(root.join(&issue.path))
+        .with_context(|| format!("failed to remove {}", issue.path.display()))?;
+    Ok(rel_dest)
+}
+
+/// Set header fields of the issue at `rel_path`, adding the missing ones
+/// after the last header field.
+pub fn set_fields(root: &Path, rel_path: &Path, fields: &[(&str, &str)]) -> Result<()> {
+    let path = root.join(rel_path);
+    let mut content = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", rel_path.display()))?;
+    for (name, value) in fields {
+        content = upsert_field(&content, name, value);
+    }
+    fs::write(&path, content).with_context(|| format!("failed to write {}", rel_path.display()))
+}
+
+/// Rewrite the value of the first `**Status:**` line.
+fn set_status(content: &str, state: State) -> String {
+    set_field(content, "Status", state.dir_name())
+}
+
+/// Rewrite the value of the first `**<name>:**` line, keeping the
+/// markdown line break after it.
+fn set_field(content: &str, name: &str, value: &str) -> String {
+    let prefix = format!("**{}:**", name);
+    let mut out = String::with_capacity(content.len());
+    let mut done = false;
+    for line in content.split_inclusive('\n') {
+        if !done && line.trim_start().starts_with(&prefix) {
+            let body = line.trim_end_matches(['\n', '\r']);
+            let trailing_spaces = body.len() - body.trim_end_matches(' ').len();
+            let indent = &line[..line.len() - line.trim_start().len()];
+            out.push_str(indent);
+            out.push_str(&prefix);
+            out.push(' ');
+            out.push_str(value);
+            out.push_str(&" ".repeat(trailing_spaces));
+            out.push_str(&line[body.len()..]);
+            done = true;
+        } else {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+/// Like [`set_field`], adding the field when missing: after the last
+/// header field before the first section, else after the title.
+fn upsert_field(content: &str, name: &str, value: &str) -> String {
+    let prefix = format!("**{}:**", name);
+    if content
+        .lines()
+        .any(|line| line.trim_start().starts_with(&prefix))
+    {
+        return set_field(content, name, value);
+    }
+    let mut after = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        if line.starts_with("## ") {
+            break;
+        }
+        if super::parse_field_line(line).is_some() || (after.is_none() && line.starts_with("# ")) {
+            after = Some((offset, line));
+        }
+    }
+    let Some((at, previous)) = after else {
+        return format!("{} {}  \n{}", prefix, value, content);
+    };
+    // Keep the markdown line break of the line above.
+    let body = previous.trim_end_matches(['\n', '\r']);
+    let trailing = &body[body.trim_end_matches(' ').len()..];
+    let mut out = content[..at].to_string();
+    if !previous.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&format!("{} {}{}\n", prefix, value, trailing));
+    out.push_str(&content[at..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::tests::write_issue;
+
+    #[test]
+    fn init_creates_missing_state_dirs_only() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("issues/api/todo")).unwrap();
+        let created = create_issue_workflow_structure(dir.path(), "api").unwrap();
+        assert_eq!(created.len(), 3);
+        assert!(dir.path().join("issues/api/in_progress").is_dir());
+        assert!(create_issue_workflow_structure(dir.path(), "shared").is_err());
+        assert!(create_issue_workflow_structure(dir.path(), "a/b").is_err());
+    }
+
+    #[test]
+    fn slugify_collapses_separators() {
+        assert_eq!(slugify("Add --project flag!"), "add-project-flag");
+        assert_eq!(slugify("  "), "");
+    }
+
+    #[test]
+    fn new_issue_uses_shared_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates = dir.path().join("issues/shared/templates");
+        fs::create_dir_all(&templates).unwrap();
+        fs::write(
+            templates.join("bug_template.md"),
+            "# {{title}}\n\n**Status:** todo  \n**Branch:** fix/{{scope}}-{{slug}}  \n**Team:** {{ TEAM }}\n",
+        )
+        .unwrap();
+
+        let vars = Variables::from([("TEAM".to_string(), "core".to_string())]);
+        let path = new_issue(dir.path(), "api", "bug", "Crash on start", &vars).unwrap();
+        assert_eq!(path, Path::new("issues/api/todo/bug-crash-on-start.md"));
+        let content = fs::read_to_string(dir.path().join(&path)).unwrap();
+        assert!(content.starts_with("# Crash on start\n"));
+        assert!(content.contains("fix/{{scope}}-crash-on-start"));
+        assert!(content.contains("**Team:** core\n"));
+        assert!(new_issue(dir.path(), "api", "bug", "Crash on start", &vars).is_err());
+        assert!(new_issue(dir.path(), "api", "nope", "X", &vars).is_err());
+        let missing = Variables::new();
+        assert!(new_issue(dir.path(), "api", "bug", "Other", &missing).is_err());
+    }
+
+    #[test]
+    fn new_issue_extends_a_base_template_and_includes_partials() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates = dir.path().join("issues/shared/templates");
+        fs::create_dir_all(templates.join("partials")).unwrap();
+        fs::write(
+            templates.join("base_template.md"),
+            "# {{title}}\n\n{% block fields %}{% endblock %}\n\n{% include \"partials/criteria\" %}",
+        )
+        .unwrap();
+        fs::write(
+            templates.join("partials/criteria_template.md"),
+            "## Acceptance Criteria\n- [ ] {{criterion}}\n",
+        )
+        .unwrap();
+        fs::write(
+            templates.join("spike_template.md"),
+            "extends: base\n{% block fields %}**Type:** spike  \n**Status:** todo{% endblock %}\n",
+        )
+        .unwrap();
+
+        let path = new_issue(dir.path(), "api", "spike", "Try it", &Variables::new()).unwrap();
+        let content = fs::read_to_string(dir.path().join(&path)).unwrap();
+        assert_eq!(
+            content,
+            "# Try it\n\n**Type:** spike  \n**Status:** todo\n\n## Acceptance Criteria\n- [ ] {{criterion}}\n"
+        );
+    }
+
+    #[test]
+    fn upserts_header_fields() {
+        let issue =
+            "# Login\n\n**Type:** feature  \n**Status:** todo  \n\n## Context\n**Note:** body\n";
+        let set = upsert_field(issue, "Status", "in_progress");
+        assert!(set.contains("**Status:** in_progress  \n\n## Context"));
+        let added = upsert_field(&set, "Agent", "alice");
+        assert!(added.contains("**Status:** in_progress  \n**Agent:** alice  \n\n## Context"));
+        assert_eq!(
+            upsert_field("# Login\n\nBody\n", "Agent", "bob"),
+            "# Login\n**Agent:** bob\n\nBody\n"
+        );
+    }
+
+    #[test]
+    fn checks_the_owner_of_an_issue() {
+        let mut issue = Issue {
+            path: PathBuf::from("issues/api/todo/a.md"),
+            project: "api".into(),
+            state: State::Todo,
+            title: "A".into(),
+            fields: Vec::new(),
+        };
+        assert!(check_owner(&issue, None, false).is_ok());
+        issue.fields.push((ASSIGNEE_FIELD.into(), "alice".into()));
+        assert!(check_owner(&issue, Some("alice"), false).is_ok());
+        let err = check_owner(&issue, Some("bob"), false).unwrap_err();
+        assert!(err.to_string().contains("assigned to alice"));
+        assert!(check_owner(&issue, None, false).is_err());
+        assert!(check_owner(&issue, Some("bob"), true).is_ok());
+
+        assert_eq!(resolve_assignee("bob", None).unwrap(), "bob");
+        assert_eq!(resolve_assignee("me", Some("carol")).unwrap(), "carol");
+        assert!(resolve_assignee("me", None).is_err());
+    }
+
+    #[test]
+    fn transitions_check_the_owner_and_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_issue(
+            root,
+            "api",
+            "todo",
+            "b.md",
+            "# B\n\n**Assignee:** alice  \n**Depends on:** [a, gone]  \n",
+        );
+        write_issue(root, "api", "todo", "a.md", "# A\n");
+        let bob = Claim {
+            by: Some("bob"),
+            steal: false,
+        };
+        let err = transition(root, "api", "b", State::InProgress, bob).unwrap_err();
+        assert!(err.to_string().contains("assigned to alice"));
+        assert!(transition(root, "api", "b", State::Done, bob).is_ok());
+        move_issue(root, "api", "b", State::Todo).unwrap();
+
+        let steal = Claim { steal: true, ..bob };
+        let (moved, path) = transition(root, "api", "b", State::InProgress, steal).unwrap();
+        assert_eq!(path, Path::new("issues/api/in_progress/b.md"));
+        assert_eq!(moved.issue.state, State::Todo);
+        assert_eq!(
+            moved.warnings,
+            vec![
+                "b depends on a, still todo",
+                "b depends on gone, which is no known issue"
+            ]
+        );
+    }
+
+    #[test]
+    fn move_updates_directory_and_status() {
+        let dir = tempfile::tempdir().unwrap();
+        write_issue(
+            dir.path(),
+            "api",
+            "todo",
+            "feature-x.md",
+            "# X\n\n**Type:** feature  \n**Status:** todo  \n",
+        );
+        let path = move_issue(dir.path(), "api", "feature-x", State::InProgress).unwrap();
+        assert_eq!(path, Path::new("issues/api/in_progress/feature-x.md"));
+        assert!(!dir.path().join("issues/api/todo/feature-x.md").exists());
+        let content = fs::read_to_string(dir.path().join(&path)).unwrap();
+        assert!(content.contains("**Status:** in_progress  \n"));
+        assert!(move_issue(dir.path(), "api", "feature-x", State::InProgress).is_err());
+        assert!(move_issue(dir.path(), "api", "missing", State::Done).is_err());
+    }
+
+    #[test]
+    fn rename_moves_project_and_rewrites_references() {
+        let dir = tempfile::tempdir().unwrap();
+        write_issue(
+            dir.path(),
+            "old",
+            "todo",
+            "feature-x.md",
+            "# X\n\n**Project:** old  \nSee issues/old/done/feature-y.md\n",
+        );
+        write_issue(dir.path(), "old", "done", "feature-y.md", "# Y\n");
+        write_issue(
+            dir.path(),
+            "web",
+            "todo",
+            "a.md",
+            "Blocked by issues/old/todo/feature-x.md\n",
+        );
+
+        let rewritten = rename_project(dir.path(), "old", "new").unwrap();
+        assert_eq!(rewritten.len(), 2);
+        assert!(!dir.path().join("issues/old").exists());
+        assert!(dir.path().join("issues/new/proposal").is_dir());
+        let x = fs::read_to_string(dir.path().join("issues/new/todo/feature-x.md")).unwrap();
+        assert!(x.contains("**Project:** new  \nSee issues/new/done/feature-y.md"));
+        let a = fs::read_to_string(dir.path().join("issues/web/todo/a.md")).unwrap();
+        assert_eq!(a, "Blocked by issues/new/todo/feature-x.md\n");
+        assert!(rename_project(dir.path(), "new", "web").is_err());
+        assert!(rename_project(dir.path(), "old", "other").is_err());
+    }
+}
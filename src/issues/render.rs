@@ -0,0 +1,118 @@
+//! Board exports of the current workflow state.
+
+use super::{Issue, State};
+use std::fmt::Write;
+
+/// Output format for `echo issue render`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Mermaid,
+}
+
+/// Render issues in the requested format.
+pub fn render(issues: &[Issue], format: Format) -> String {
+    match format {
+        Format::Markdown => render_markdown(issues),
+        Format::Mermaid => render_mermaid(issues),
+    }
+}
+
+/// Render a kanban-style markdown document, one section per state.
+pub fn render_markdown(issues: &[Issue]) -> String {
+    let mut out = String::from("# Issue Board\n");
+    for state in State::ALL {
+        let _ = write!(out, "\n## {}\n\n", state.label());
+        let in_state: Vec<_> = issues.iter().filter(|i| i.state == state).collect();
+        if in_state.is_empty() {
+            out.push_str("_No issues._\n");
+            continue;
+        }
+        for issue in in_state {
+            let path = issue.path.to_string_lossy().replace('\\', "/");
+            let _ = write!(out, "- [{}]({})", escape_markdown(&issue.title), path);
+            if let Some(kind) = issue.field("Type") {
+                let _ = write!(out, " — {}", kind);
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Render a mermaid flowchart with one column (subgraph) per state.
+pub fn render_mermaid(issues: &[Issue]) -> String {
+    let mut out = String::from("flowchart LR\n");
+    for state in State::ALL {
+        let _ = writeln!(
+            out,
+            "    subgraph {}[\"{}\"]",
+            state.dir_name(),
+            state.label()
+        );
+        out.push_str("        direction TB\n");
+        let in_state: Vec<_> = issues.iter().filter(|i| i.state == state).collect();
+        if in_state.is_empty() {
+            let _ = writeln!(out, "        {}_empty[\"(empty)\"]", state.dir_name());
+        }
+        for (n, issue) in in_state.into_iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "        {}_{}[\"{}\"]",
+                state.dir_name(),
+                n,
+                escape_mermaid(&issue.title)
+            );
+        }
+        out.push_str("    end\n");
+    }
+    out
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+pub(super) fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('[', "#91;")
+        .replace(']', "#93;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn issue(title: &str, state: State) -> Issue {
+        Issue {
+            path: PathBuf::from(format!("issues/echo/{}/x.md", state.dir_name())),
+            project: "echo".into(),
+            state,
+            title: title.into(),
+            fields: vec![("Type".into(), "feature".into())],
+        }
+    }
+
+    #[test]
+    fn markdown_lists_every_state() {
+        let out = render_markdown(&[issue("Init", State::Done)]);
+        assert!(out.contains("## Proposal\n\n_No issues._"));
+        assert!(out.contains("## In Progress\n\n_No issues._"));
+        assert!(out.contains("## Done\n\n- [Init](issues/echo/done/x.md) — feature\n"));
+    }
+
+    #[test]
+    fn markdown_escapes_brackets() {
+        let out = render_markdown(&[issue("Fix [bug]", State::Todo)]);
+        assert!(out.contains("- [Fix \\[bug\\]]("));
+    }
+
+    #[test]
+    fn mermaid_escapes_quotes_and_brackets() {
+        let out = render_mermaid(&[issue("Say \"hi\" [now]", State::Todo)]);
+        assert!(out.starts_with("flowchart LR\n"));
+        assert!(out.contains("todo_0[\"Say #quot;hi#quot; #91;now#93;\"]"));
+        assert!(out.contains("done_empty[\"(empty)\"]"));
+    }
+}
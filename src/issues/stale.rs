@@ -0,0 +1,181 @@
+//! Issues left behind, for `echo issue stale`: in-progress issues not
+//! touched for a while, and open issues past their `**Due:**` date.
+//!
+//! An issue is touched when a commit changes it, or when its `**Updated:**`
+//! field says so; for files git does not track, their modification time
+//! counts instead.
+
+use super::{Issue, State};
+use crate::environment;
+use crate::git;
+use crate::gitutil;
+use crate::notify;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Header field with the date an issue is due, `YYYY-MM-DD`.
+pub const DUE_FIELD: &str = "Due";
+
+/// Header field with the date an issue was last worked on, `YYYY-MM-DD`.
+pub const UPDATED_FIELD: &str = "Updated";
+
+/// Days without change after which an issue in progress is stale.
+pub const DEFAULT_DAYS: u64 = 14;
+
+/// Why an issue is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// In progress and untouched for longer than the threshold.
+    Stale,
+    /// Open past its due date.
+    Overdue,
+}
+
+/// An issue reported by [`find`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub path: PathBuf,
+    pub title: String,
+    pub state: String,
+    pub reasons: Vec<Reason>,
+    /// Day the issue was last touched, when known.
+    pub touched: Option<String>,
+    /// Whole days since then.
+    pub idle_days: Option<u64>,
+    pub due: Option<String>,
+}
+
+/// When the issue at `issue.path` was last touched, in seconds since the
+/// epoch: its last commit or modification time, or its `**Updated:**`
+/// field when later.
+pub fn last_touched(root: &Path, issue: &Issue) -> Option<u64> {
+    let path = issue.path.to_string_lossy();
+    let committed = gitutil::is_repo(root)
+        .then(|| git::run(root, &["log", "-1", "--format=%ct", "--", &path]).ok())
+        .flatten()
+        .and_then(|time| time.trim().parse().ok());
+    let modified = committed.or_else(|| {
+        fs::metadata(root.join(&issue.path))
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|age| age.as_secs())
+    });
+    let updated = issue.field(UPDATED_FIELD).and_then(environment::parse_date);
+    modified.max(updated)
+}
+
+/// In-progress issues of `issues` untouched for more than `days` at `now`,
+/// and open issues past their due date, in `issues` order.
+pub fn find(root: &Path, issues: &[Issue], now: u64, days: u64) -> Vec<Report> {
+    let today = environment::date(now);
+    let mut reports = Vec::new();
+    for issue in issues {
+        if !matches!(issue.state, State::Todo | State::InProgress) {
+            continue;
+        }
+        let touched = last_touched(root, issue);
+        let idle_days = touched.map(|touched| now.saturating_sub(touched) / 86_400);
+        let mut reasons = Vec::new();
+        if issue.state == State::InProgress && idle_days.is_none_or(|idle| idle > days) {
+            reasons.push(Reason::Stale);
+        }
+        let due = issue.field(DUE_FIELD).filter(|due| !due.is_empty());
+        // Dates compare as strings; malformed ones are never overdue.
+        if due.is_some_and(|due| environment::parse_date(due).is_some() && due < today.as_str()) {
+            reasons.push(Reason::Overdue);
+        }
+        if reasons.is_empty() {
+            continue;
+        }
+        reports.push(Report {
+            path: issue.path.clone(),
+            title: issue.title.clone(),
+            state: issue.state.dir_name().to_string(),
+            reasons,
+            touched: touched.map(environment::date),
+            idle_days,
+            due: due.map(str::to_string),
+        });
+    }
+    reports
+}
+
+/// One line per report, e.g. `issues/app/in_progress/x.md: stale, idle
+/// 20 days`.
+pub fn describe(report: &Report) -> String {
+    let mut details = Vec::new();
+    if report.reasons.contains(&Reason::Stale) {
+        details.push(match report.idle_days {
+            Some(days) => format!("stale, idle {} days", days),
+            None => "stale".to_string(),
+        });
+    }
+    if let (true, Some(due)) = (report.reasons.contains(&Reason::Overdue), &report.due) {
+        details.push(format!("overdue since {}", due));
+    }
+    format!("{}: {}", report.path.display(), details.join(", "))
+}
+
+/// POST `reports` as JSON to `url`. The `text` member holds the lines
+/// of [`describe`], as chat webhooks expect.
+pub fn post(url: &str, reports: &[Report]) -> Result<()> {
+    let text = reports.iter().map(describe).collect::<Vec<_>>().join("\n");
+    notify::post_json(
+        url,
+        &serde_json::json!({
+            "text": format!("{} stale or overdue issue(s)\n{}", reports.len(), text),
+            "issues": reports,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::{read_issue_at, tests::write_issue};
+
+    #[test]
+    fn reports_stale_and_overdue_issues() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_issue(root, "app", "in_progress", "a.md", "# A\n");
+        write_issue(
+            root,
+            "app",
+            "in_progress",
+            "b.md",
+            "# B\n**Updated:** 2099-01-01\n",
+        );
+        write_issue(root, "app", "todo", "c.md", "# C\n**Due:** 2000-01-01\n");
+        write_issue(root, "app", "todo", "d.md", "# D\n**Due:** someday\n");
+        write_issue(root, "app", "done", "e.md", "# E\n**Due:** 2000-01-01\n");
+        let issues: Vec<_> = [
+            "in_progress/a",
+            "in_progress/b",
+            "todo/c",
+            "todo/d",
+            "done/e",
+        ]
+        .iter()
+        .map(|path| read_issue_at(root, Path::new(&format!("issues/app/{}.md", path))).unwrap())
+        .collect();
+
+        let now = last_touched(root, &issues[0]).unwrap() + 20 * 86_400;
+        let reports = find(root, &issues, now, DEFAULT_DAYS);
+        let found: Vec<_> = reports.iter().map(describe).collect();
+        assert_eq!(
+            found,
+            vec![
+                "issues/app/in_progress/a.md: stale, idle 20 days",
+                "issues/app/todo/c.md: overdue since 2000-01-01",
+            ]
+        );
+        assert_eq!(find(root, &issues, now, 30).len(), 1);
+        assert!(post("nope://example.com", &reports).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Checks on issue templates for `echo template lint`: variables echo does
+//! not define, includes and `extends` naming no template, header fields
+//! that will not parse, and the sections and fields a schema requires.
+//!
+//! The schema is an optional `schema.toml` next to the templates:
+//!
+//! ```toml
+//! # Required of every template
+//! sections = ["Context"]
+//! fields = ["Type", "Status"]
+//!
+//! [template.bug]
+//! sections = ["Reproduction", "Acceptance Criteria"]
+//! ```
+//!
+//! Partials are only checked for what they render; the structure checks
+//! apply to the issue a template produces, base templates included.
+
+use super::lint::{check_title, section_name};
+use super::templates::{PARTIALS_DIR, TEMPLATE_SUFFIX};
+use super::validate::Violation;
+use super::{ops, parse_field_line, parse_header, State};
+use crate::provenance;
+use crate::template::{self, Variables};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Schema file in a templates directory.
+pub const SCHEMA_FILE: &str = "schema.toml";
+
+/// What the issue a template produces must contain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Requirements {
+    /// `## ` section headings, compared without leading emoji.
+    pub sections: Vec<String>,
+    /// `**Name:**` header fields.
+    pub fields: Vec<String>,
+}
+
+/// Contents of [`SCHEMA_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schema {
+    /// Requirements of every template.
+    #[serde(flatten)]
+    pub all: Requirements,
+    /// Further requirements by template name.
+    pub template: BTreeMap<String, Requirements>,
+}
+
+impl Schema {
+    /// Load the schema at `path`; no file requires nothing.
+    pub fn load(path: &Path) -> Result<Schema> {
+        if !path.is_file() {
+            return Ok(Schema::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    fn requirements<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a Requirements> {
+        std::iter::once(&self.all).chain(self.template.get(name))
+    }
+}
+
+/// Lint the templates of `dir`, a path relative to `root` or absolute,
+/// rendered with `vars` plus the variables of an issue.
+pub fn lint(root: &Path, dir: &Path, schema: &Schema, vars: &Variables) -> Result<Vec<Violation>> {
+    let full = root.join(dir);
+    if !full.is_dir() {
+        bail!("no templates directory {}", dir.display());
+    }
+    let mut violations = Vec::new();
+    for name in names(&full)? {
+        let path = dir.join(format!("{}{}", name, TEMPLATE_SUFFIX));
+        let source = fs::read_to_string(root.join(&path))
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let messages = lint_template(&full, &name, &provenance::strip(&source), schema, vars);
+        violations.extend(messages.into_iter().map(|message| Violation {
+            path: path.clone(),
+            message,
+        }));
+    }
+    Ok(violations)
+}
+
+/// Names of the templates in `dir`, partials included, sorted.
+fn names(dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for (sub, prefix) in [
+        (dir.to_path_buf(), ""),
+        (dir.join(PARTIALS_DIR), "partials/"),
+    ] {
+        if !sub.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&sub)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(name) = file_name.strip_suffix(TEMPLATE_SUFFIX) {
+                if entry.file_type()?.is_file() {
+                    names.push(format!("{}{}", prefix, name));
+                }
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn lint_template(
+    dir: &Path,
+    name: &str,
+    source: &str,
+    schema: &Schema,
+    vars: &Variables,
+) -> Vec<String> {
+    let mut vars = ops::issue_variables(name, "Example", vars);
+    let undefined = match template::undefined_issue_variables(
+        source,
+        &vars,
+        super::templates::loader_in(dir),
+    ) {
+        Ok(undefined) => undefined,
+        Err(err) => return vec![format!("does not render: {:#}", err)],
+    };
+    let mut messages: Vec<String> = undefined
+        .iter()
+        .map(|variable| format!("unknown variable {}", variable))
+        .collect();
+    vars.extend(
+        undefined
+            .into_iter()
+            .map(|variable| (variable, String::new())),
+    );
+    let rendered =
+        match template::render_issue_template_with(source, &vars, super::templates::loader_in(dir))
+        {
+            Ok(rendered) => rendered,
+            Err(err) => {
+                messages.push(format!("does not render: {:#}", err));
+                return messages;
+            }
+        };
+    if !name.starts_with("partials/") {
+        check_structure(name, &rendered, schema, &mut messages);
+    }
+    messages
+}
+
+/// Checks on the issue `rendered` from template `name`.
+fn check_structure(name: &str, rendered: &str, schema: &Schema, messages: &mut Vec<String>) {
+    check_title(rendered, messages);
+    // Header fields come before the first section.
+    let mut seen = BTreeSet::new();
+    for line in rendered
+        .lines()
+        .take_while(|line| section_name(line).is_none())
+    {
+        let line = line.trim();
+        if !line.starts_with("**") {
+            continue;
+        }
+        let Some((field, value)) = parse_field_line(line) else {
+            messages.push(format!("malformed header field '{}'", line));
+            continue;
+        };
+        if !seen.insert(field.to_lowercase()) {
+            messages.push(format!("duplicate **{}:** field", field));
+        }
+        // `todo | done` lists choices; placeholders are filled in later.
+        let fixed = !value.is_empty() && !value.contains(['|', '{']);
+        if field.eq_ignore_ascii_case("status") && fixed && State::from_name(&value).is_none() {
+            messages.push(format!("unknown status '{}'", value));
+        }
+    }
+    let headings: Vec<_> = rendered.lines().filter_map(section_name).collect();
+    let (_, fields) = parse_header(rendered, name);
+    for requirements in schema.requirements(name) {
+        for section in &requirements.sections {
+            if !headings.iter().any(|h| h.eq_ignore_ascii_case(section)) {
+                messages.push(format!("missing section '{}'", section));
+            }
+        }
+        for field in &requirements.fields {
+            if !fields.iter().any(|(f, _)| f.eq_ignore_ascii_case(field)) {
+                messages.push(format!("missing **{}:** field", field));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_templates_are_clean() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let dir = Path::new("issues/shared/templates");
+        let violations = lint(root, dir, &Schema::default(), &Variables::new()).unwrap();
+        assert!(violations.is_empty(), "{:?}", violations);
+    }
+
+    #[test]
+    fn reports_template_mistakes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let write = |name: &str, content: &str| {
+            let path = root.join(format!("{}{}", name, TEMPLATE_SUFFIX));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            "bug",
+            "# {{title}}\n**Type:** bug\n**Status:** later\n**Status:** todo\n**Team** {{ TEAM }}\n\n## Context\n{% include \"partials/steps\" %}\n",
+        );
+        write("partials/steps", "{{ STEPS }}\n");
+        write("chore", "extends: nope\n");
+        let schema: Schema = toml::from_str(
+            "sections = [\"Context\"]\n[template.bug]\nsections = [\"Reproduction\"]\nfields = [\"Severity\"]\n",
+        )
+        .unwrap();
+
+        let found: Vec<_> = lint(root, Path::new(""), &schema, &Variables::new())
+            .unwrap()
+            .into_iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        assert_eq!(found.len(), 9, "{:#?}", found);
+        assert_eq!(found[0], "bug_template.md: unknown variable STEPS");
+        assert_eq!(found[1], "bug_template.md: unknown variable TEAM");
+        assert_eq!(found[2], "bug_template.md: unknown status 'later'");
+        assert_eq!(found[3], "bug_template.md: duplicate **Status:** field");
+        assert_eq!(
+            found[4],
+            "bug_template.md: malformed header field '**Team**'"
+        );
+        assert_eq!(found[5], "bug_template.md: missing section 'Reproduction'");
+        assert_eq!(found[6], "bug_template.md: missing **Severity:** field");
+        assert!(found[7].starts_with("chore_template.md: does not render"));
+        assert_eq!(
+            found[8],
+            "partials/steps_template.md: unknown variable STEPS"
+        );
+    }
+}
@@ -0,0 +1,467 @@
+//! Issue templates stored in `issues/shared/templates/<name>_template.md`.
+
+use super::{parse_header, ISSUES_DIR, SHARED_DIR};
+use crate::echoignore::EchoIgnore;
+use crate::fs_ops::{self, FsOps, RealFs};
+use crate::lock::{Conflict, Outcome, Resolution};
+use crate::parallel;
+use crate::provenance;
+use crate::rules::{Source, Stamp};
+use crate::source_scan::SourceFilter;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory under `issues/shared/` holding the issue templates.
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// File name suffix shared by every template.
+pub const TEMPLATE_SUFFIX: &str = "_template.md";
+
+/// Directory under the templates holding partials, the pieces templates
+/// include: `{% include "partials/<name>" %}` reads
+/// `partials/<name>_template.md`.
+pub const PARTIALS_DIR: &str = "partials";
+
+/// Directory of a flowmates checkout holding `<name>_template.md` files,
+/// which take precedence over the embedded templates.
+pub const FLOWMATES_TEMPLATES_DIR: &str = "templates";
+
+/// Templates shipped with echo and installed by `echo init`.
+const EMBEDDED: [(&str, &str); 6] = [
+    (
+        "adr",
+        include_str!("../../issues/shared/templates/adr_template.md"),
+    ),
+    (
+        "bug",
+        include_str!("../../issues/shared/templates/bug_template.md"),
+    ),
+    (
+        "enhancement",
+        include_str!("../../issues/shared/templates/enhancement_template.md"),
+    ),
+    (
+        "feature",
+        include_str!("../../issues/shared/templates/feature_template.md"),
+    ),
+    (
+        "proposal",
+        include_str!("../../issues/shared/templates/proposal_template.md"),
+    ),
+    (
+        "roadmap",
+        include_str!("../../issues/shared/templates/roadmap_template.md"),
+    ),
+];
+
+/// Skeleton for `echo template new`; `{{name}}` is the template name.
+const SKELETON: &str = "# {{title}}\n\
+\n\
+**Type:** {{name}}  \n\
+**Status:** todo  \n\
+**Branch:** {{name}}/{{scope}}-{{slug}}  \n\
+\n\
+---\n\
+\n\
+## 🧠 Context\n\
+{{short background}}\n\
+\n\
+## 🎯 Goal\n\
+{{clear description of what needs to change}}\n\
+\n\
+## 🧩 Acceptance Criteria\n\
+- [ ] {{criterion}}\n";
+
+/// A template found in the shared templates directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub name: String,
+    /// Path relative to the repository root.
+    pub path: PathBuf,
+    pub description: String,
+}
+
+/// Path of the template `name`, relative to the repository root.
+pub fn template_path(name: &str) -> PathBuf {
+    Path::new(ISSUES_DIR)
+        .join(SHARED_DIR)
+        .join(TEMPLATES_DIR)
+        .join(format!("{}{}", name, TEMPLATE_SUFFIX))
+}
+
+/// Loader of the templates of `root` by name, for templates extending or
+/// including others: `base` is `base_template.md`, `partials/criteria`
+/// is `partials/criteria_template.md`.
+pub fn loader(root: &Path) -> impl Fn(&str) -> Result<Option<String>> + Send + Sync + 'static {
+    loader_in(&root.join(ISSUES_DIR).join(SHARED_DIR).join(TEMPLATES_DIR))
+}
+
+/// [`loader`] of the templates in `dir`, such as the `templates/`
+/// directory of a flowmates checkout.
+pub fn loader_in(dir: &Path) -> impl Fn(&str) -> Result<Option<String>> + Send + Sync + 'static {
+    let dir = dir.to_path_buf();
+    move |name| {
+        let valid = name.split('/').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+        if !valid {
+            bail!("invalid template name '{}'", name);
+        }
+        let path = dir.join(format!("{}{}", name, TEMPLATE_SUFFIX));
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Some(provenance::strip(&content).into_owned()))
+    }
+}
+
+/// List the available templates, sorted by name.
+pub fn list(root: &Path) -> Result<Vec<Template>> {
+    let dir = root.join(ISSUES_DIR).join(SHARED_DIR).join(TEMPLATES_DIR);
+    let mut templates = Vec::new();
+    if !dir.is_dir() {
+        return Ok(templates);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(name) = file_name.strip_suffix(TEMPLATE_SUFFIX) else {
+            continue;
+        };
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let path = template_path(name);
+        let content = fs::read_to_string(root.join(&path))
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        templates.push(Template {
+            name: name.to_string(),
+            path,
+            description: describe(&content),
+        });
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+/// Create the template `name` from the built-in skeleton and return its
+/// path relative to the repository root.
+pub fn create(root: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        bail!(
+            "invalid template name '{}' (use lowercase letters, digits, '-' and '_')",
+            name
+        );
+    }
+    let path = template_path(name);
+    let full = root.join(&path);
+    if full.exists() {
+        bail!("{} already exists", path.display());
+    }
+    if let Some(parent) = full.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&full, SKELETON.replace("{{name}}", name))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Install the embedded and flowmates templates, restricted to `only` when
+/// given. Existing templates that differ are kept unless `force` is set.
+/// Returns each template path, relative to the repository root, with its
+/// outcome.
+pub fn install(
+    root: &Path,
+    flowmates_dir: Option<&Path>,
+    only: Option<&[String]>,
+    force: bool,
+) -> Result<Vec<(PathBuf, Outcome)>> {
+    install_with(&RealFs, root, flowmates_dir, only, false, None, |_| {
+        Ok(if force {
+            Resolution::Take
+        } else {
+            Resolution::Keep
+        })
+    })
+}
+
+/// Like [`install`], writing through `fs` and asking `resolve` what to do
+/// with each existing template that differs. Templates keep no merge base.
+/// Callers sync the rules first, which verifies the flowmates checkout
+/// (see [`crate::checksums::verify_source`]).
+/// With `preserve_metadata`, templates written from flowmates get the
+/// permissions and modification time of their source.
+pub fn install_with(
+    fs: &dyn FsOps,
+    root: &Path,
+    flowmates_dir: Option<&Path>,
+    only: Option<&[String]>,
+    preserve_metadata: bool,
+    stamp: Option<&Stamp>,
+    mut resolve: impl FnMut(&Conflict) -> Result<Resolution>,
+) -> Result<Vec<(PathBuf, Outcome)>> {
+    let selected = selected_sources(flowmates_dir, only)?;
+    let ignore = EchoIgnore::load(root)?;
+    // With the provenance header of the source of `name`, when stamping.
+    let stamped = |name: &str, source: &Option<PathBuf>, content: &str| match stamp {
+        Some(stamp) => {
+            let (kind, file) = match source {
+                Some(_) => (
+                    Source::Flowmates,
+                    format!("{}/{}{}", FLOWMATES_TEMPLATES_DIR, name, TEMPLATE_SUFFIX),
+                ),
+                None => (Source::Embedded, format!("{}{}", name, TEMPLATE_SUFFIX)),
+            };
+            stamp.apply(kind, &file, content)
+        }
+        None => content.to_string(),
+    };
+    let installed = parallel::map(&selected, |(name, content, source)| -> Result<_> {
+        let path = template_path(name);
+        let outcome = ignore.guard(&path, Outcome::of(root, &path, content, false, None));
+        if outcome.is_written() {
+            write_template(fs, root, &path, &stamped(name, source, content))?;
+            if let Some(source) = source.as_ref().filter(|_| preserve_metadata) {
+                fs_ops::preserve_metadata(fs, source, &root.join(&path))
+                    .with_context(|| format!("failed to copy metadata to {}", path.display()))?;
+            }
+        }
+        Ok((path, outcome))
+    });
+    let mut report = Vec::new();
+    for ((name, content, source), installed) in selected.iter().zip(installed) {
+        let (path, mut outcome) = installed?;
+        if outcome == Outcome::Modified {
+            let local = fs::read_to_string(root.join(&path))
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let local = provenance::strip(&local);
+            let conflict = Conflict {
+                path: &path,
+                local: &local,
+                source: content,
+                base: None,
+            };
+            let (resolved, resolution) = conflict.resolve(resolve(&conflict)?);
+            if let Some(resolved) = resolved {
+                write_template(fs, root, &path, &stamped(name, source, &resolved))?;
+            }
+            outcome = resolution;
+        }
+        report.push((path, outcome));
+    }
+    Ok(report)
+}
+
+/// What [`install`] would do, without writing anything.
+pub fn plan(
+    root: &Path,
+    flowmates_dir: Option<&Path>,
+    only: Option<&[String]>,
+) -> Result<Vec<(PathBuf, Outcome)>> {
+    let ignore = EchoIgnore::load(root)?;
+    Ok(selected_sources(flowmates_dir, only)?
+        .iter()
+        .map(|(name, content, _)| {
+            let path = template_path(name);
+            let outcome = ignore.guard(&path, Outcome::of(root, &path, content, false, None));
+            (path, outcome)
+        })
+        .collect())
+}
+
+/// Contents of the embedded and flowmates templates by name, with the
+/// file of flowmates ones, restricted to `only` when given.
+fn selected_sources(
+    flowmates_dir: Option<&Path>,
+    only: Option<&[String]>,
+) -> Result<Vec<(String, String, Option<PathBuf>)>> {
+    let mut sources: BTreeMap<String, (String, Option<PathBuf>)> = EMBEDDED
+        .iter()
+        .map(|(name, content)| (name.to_string(), (content.to_string(), None)))
+        .collect();
+    let filter = flowmates_dir.map(SourceFilter::load).transpose()?;
+    let dir = flowmates_dir.map(|dir| dir.join(FLOWMATES_TEMPLATES_DIR));
+    for (dir, prefix) in dir
+        .iter()
+        .flat_map(|dir| [(dir.clone(), ""), (dir.join(PARTIALS_DIR), "partials/")])
+    {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(TEMPLATE_SUFFIX))
+            else {
+                continue;
+            };
+            let skipped = filter
+                .as_ref()
+                .zip(flowmates_dir)
+                .is_some_and(|(filter, root)| {
+                    filter.skips(path.strip_prefix(root).unwrap_or(&path))
+                });
+            if skipped || !path.is_file() {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            sources.insert(format!("{}{}", prefix, name), (content, Some(path.clone())));
+        }
+    }
+    if let Some(only) = only {
+        if let Some(unknown) = only.iter().find(|name| !sources.contains_key(*name)) {
+            bail!("unknown template '{}'", unknown);
+        }
+    }
+    // Partials go along with any template, which may include them.
+    Ok(sources
+        .into_iter()
+        .filter(|(name, _)| {
+            name.starts_with("partials/") || only.is_none_or(|only| only.contains(name))
+        })
+        .map(|(name, (content, path))| (name, content, path))
+        .collect())
+}
+
+fn write_template(fs: &dyn FsOps, root: &Path, path: &Path, content: &str) -> Result<()> {
+    fs_ops::write_file(fs, &root.join(path), content.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Describe a template by its first heading with placeholders removed,
+/// falling back to its `**Type:**` field.
+fn describe(content: &str) -> String {
+    let (title, fields) = parse_header(content, "");
+    let heading = strip_placeholders(&title);
+    let heading = heading.trim_end_matches(|c: char| !c.is_alphanumeric());
+    if !heading.is_empty() {
+        return heading.to_string();
+    }
+    fields
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("Type"))
+        .map(|(_, v)| strip_placeholders(v))
+        .filter(|v| !v.is_empty())
+        .map(|v| format!("{} issue", v))
+        .unwrap_or_default()
+}
+
+fn strip_placeholders(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("}}") {
+            Some(end) => rest = &rest[start + end + 2..],
+            None => rest = "",
+        }
+    }
+    out.push_str(rest);
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_templates_from_heading_or_type() {
+        assert_eq!(describe("# ADR {{number}} — {{title}}\n"), "ADR");
+        assert_eq!(describe("# Roadmap Entry — {{title}}\n"), "Roadmap Entry");
+        assert_eq!(describe("# {{title}}\n\n**Type:** bug  \n"), "bug issue");
+        assert_eq!(describe("# {{title}}\n\n**Type:** {{type}}\n"), "");
+    }
+
+    #[test]
+    fn creates_and_lists_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = create(dir.path(), "spike").unwrap();
+        assert_eq!(path, Path::new("issues/shared/templates/spike_template.md"));
+        let content = fs::read_to_string(dir.path().join(&path)).unwrap();
+        assert!(content.contains("**Type:** spike  \n"));
+        assert!(create(dir.path(), "spike").is_err());
+        assert!(create(dir.path(), "Bad Name").is_err());
+
+        fs::write(dir.path().join("issues/shared/templates/notes.md"), "x").unwrap();
+        let templates = list(dir.path()).unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].name, "spike");
+        assert_eq!(templates[0].description, "spike issue");
+    }
+
+    #[test]
+    fn installs_selected_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        fs::create_dir(flowmates.path().join("templates")).unwrap();
+        fs::write(flowmates.path().join("templates/bug_template.md"), "custom").unwrap();
+        fs::create_dir(flowmates.path().join("templates/partials")).unwrap();
+        fs::write(
+            flowmates
+                .path()
+                .join("templates/partials/criteria_template.md"),
+            "- [ ] {{criterion}}\n",
+        )
+        .unwrap();
+        let only = vec!["bug".to_string(), "feature".to_string()];
+        let installed = install(dir.path(), Some(flowmates.path()), Some(&only), false).unwrap();
+        assert_eq!(
+            installed,
+            vec![
+                (template_path("bug"), Outcome::Installed),
+                (template_path("feature"), Outcome::Installed),
+                (template_path("partials/criteria"), Outcome::Installed)
+            ]
+        );
+        let load = loader(dir.path());
+        assert_eq!(
+            load("partials/criteria").unwrap().as_deref(),
+            Some("- [ ] {{criterion}}\n")
+        );
+        assert_eq!(load("partials/nope").unwrap(), None);
+        assert!(load("../secrets").is_err());
+        let bug = fs::read_to_string(dir.path().join(template_path("bug"))).unwrap();
+        assert_eq!(bug, "custom");
+
+        let installed = install(dir.path(), None, None, false).unwrap();
+        let outcome = |name: &str| {
+            installed
+                .iter()
+                .find(|(path, _)| *path == template_path(name))
+                .map(|(_, outcome)| *outcome)
+        };
+        assert_eq!(outcome("bug"), Some(Outcome::Modified));
+        assert_eq!(outcome("feature"), Some(Outcome::UpToDate));
+        assert_eq!(
+            fs::read_to_string(dir.path().join(template_path("bug"))).unwrap(),
+            "custom"
+        );
+        fs::write(
+            dir.path().join(".echoignore"),
+            "issues/shared/templates/bug_*\n",
+        )
+        .unwrap();
+        let forced = install(dir.path(), None, Some(&["bug".to_string()]), true).unwrap();
+        assert_eq!(forced, vec![(template_path("bug"), Outcome::Protected)]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join(template_path("bug"))).unwrap(),
+            "custom"
+        );
+        assert_eq!(list(dir.path()).unwrap().len(), EMBEDDED.len());
+        assert!(install(dir.path(), None, Some(&["spike".to_string()]), false).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Workflow state validation for `echo issue validate`.
+
+use super::{Issue, State};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Header fields every issue must carry.
+pub const REQUIRED_FIELDS: [&str; 2] = ["Type", "Status"];
+
+/// A broken workflow rule, attached to the offending file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Check that each issue lives in the directory matching its `**Status:**`,
+/// that no issue exists in several states of a project and that required
+/// fields are present.
+pub fn validate(issues: &[Issue]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut seen: BTreeMap<(&str, &str), Vec<&Issue>> = BTreeMap::new();
+    for issue in issues {
+        seen.entry((&issue.project, issue.id()))
+            .or_default()
+            .push(issue);
+        for field in REQUIRED_FIELDS {
+            if issue.field(field).is_none_or(str::is_empty) {
+                violations.push(violation(issue, format!("missing **{}:** field", field)));
+            }
+        }
+        if let Some(status) = issue.field("Status").filter(|s| !s.is_empty()) {
+            match State::from_name(status) {
+                Some(state) if state == issue.state => {}
+                Some(state) => violations.push(violation(
+                    issue,
+                    format!(
+                        "status is '{}' but the file is in {}/",
+                        state,
+                        issue.state.dir_name()
+                    ),
+                )),
+                None => violations.push(violation(issue, format!("unknown status '{}'", status))),
+            }
+        }
+    }
+    for ((_, id), copies) in seen {
+        if copies.len() < 2 {
+            continue;
+        }
+        let states: Vec<_> = copies.iter().map(|i| i.state.dir_name()).collect();
+        for issue in copies {
+            violations.push(violation(
+                issue,
+                format!(
+                    "issue '{}' exists in several states: {}",
+                    id,
+                    states.join(", ")
+                ),
+            ));
+        }
+    }
+    violations
+}
+
+fn violation(issue: &Issue, message: String) -> Violation {
+    Violation {
+        path: issue.path.clone(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(project: &str, state: State, name: &str, fields: &[(&str, &str)]) -> Issue {
+        Issue {
+            path: PathBuf::from(format!(
+                "issues/{}/{}/{}.md",
+                project,
+                state.dir_name(),
+                name
+            )),
+            project: project.into(),
+            state,
+            title: name.into(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn valid_issue_has_no_violations() {
+        let issues = [issue(
+            "echo",
+            State::Todo,
+            "a",
+            &[("Type", "feature"), ("Status", "todo")],
+        )];
+        assert!(validate(&issues).is_empty());
+    }
+
+    #[test]
+    fn reports_status_mismatch_and_missing_fields() {
+        let issues = [
+            issue(
+                "echo",
+                State::Done,
+                "a",
+                &[("Type", "bug"), ("Status", "todo")],
+            ),
+            issue("echo", State::Todo, "b", &[("Status", "later")]),
+        ];
+        let messages: Vec<_> = validate(&issues).into_iter().map(|v| v.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "status is 'todo' but the file is in done/",
+                "missing **Type:** field",
+                "unknown status 'later'",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_per_project() {
+        let fields = [("Type", "feature"), ("Status", "todo")];
+        let done = [("Type", "feature"), ("Status", "done")];
+        let issues = [
+            issue("echo", State::Todo, "a", &fields),
+            issue("echo", State::Done, "a", &done),
+            issue("web", State::Todo, "a", &fields),
+        ];
+        let violations = validate(&issues);
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0]
+            .message
+            .contains("exists in several states: todo, done"));
+    }
+}
@@ -0,0 +1,70 @@
+//! `~/.flowmates/config.json`, where earlier flowmates tools kept the
+//! canonical source, trusted keys and profiles. echo keeps them in
+//! `projects.toml` and `profiles.toml` of the flowmates configuration
+//! directory (see
+//! [`Environment::config_dir`](crate::environment::Environment::config_dir))
+//! and only reads this file as a fallback: for the source and trusted keys
+//! `projects.toml` does not set, and for profiles when there is no
+//! `profiles.toml`.
+
+use crate::profiles::Profile;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file, relative to the home directory.
+pub const LEGACY_FILE: &str = ".flowmates/config.json";
+
+/// Contents of [`LEGACY_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct LegacyConfig {
+    /// The canonical flowmates checkout.
+    pub source: Option<PathBuf>,
+    /// Minisign public keys trusted to sign flowmates checkouts.
+    #[serde(rename = "trusted-keys", alias = "trustedKeys")]
+    pub trusted_keys: Vec<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl LegacyConfig {
+    /// Load the file under `home`; `None` when absent.
+    pub fn load(home: &Path) -> Result<Option<LegacyConfig>> {
+        let path = home.join(LEGACY_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("invalid {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_source_keys_and_profiles() {
+        let home = tempfile::tempdir().unwrap();
+        assert_eq!(LegacyConfig::load(home.path()).unwrap(), None);
+        fs::create_dir_all(home.path().join(".flowmates")).unwrap();
+        fs::write(
+            home.path().join(LEGACY_FILE),
+            r#"{"source": "/flowmates", "trustedKeys": ["RWQ"],
+                "profiles": {"oss": {"source": "/oss", "presets": ["rust"]}}}"#,
+        )
+        .unwrap();
+        let legacy = LegacyConfig::load(home.path()).unwrap().unwrap();
+        assert_eq!(legacy.source, Some(PathBuf::from("/flowmates")));
+        assert_eq!(legacy.trusted_keys, vec!["RWQ"]);
+        assert_eq!(legacy.profiles["oss"].presets, vec!["rust"]);
+
+        fs::write(home.path().join(LEGACY_FILE), "{").unwrap();
+        assert!(LegacyConfig::load(home.path()).is_err());
+    }
+}
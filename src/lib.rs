@@ -1,3 +1,51 @@
+pub mod agent;
+pub mod audit_log;
+pub mod check;
+pub mod checksums;
+pub mod config;
+pub mod context;
+pub mod cursor_sync;
+pub mod daemon;
+pub mod echoignore;
+pub mod environment;
+pub mod fs_ops;
+pub mod git;
+pub mod github;
+pub mod gitignore;
+pub mod gitutil;
+pub mod hooks;
+pub mod issues;
+pub mod legacy_config;
+pub mod lock;
+pub mod mcp;
+pub mod notify;
+pub mod pack;
+pub mod parallel;
+pub mod plugins;
+pub mod post_run;
+pub mod presets;
+pub mod profiles;
+pub mod provenance;
+pub mod prune;
+pub mod registry;
+pub mod report;
+pub mod rules;
+pub mod run_lock;
+pub mod scan_cache;
+pub mod scripts;
+pub mod session;
+pub mod source_cache;
+pub mod source_pin;
+pub mod source_repo;
+pub mod source_scan;
+pub mod stats;
+pub mod status;
+pub mod template;
+pub mod uninstall;
+pub mod watch;
+pub mod workspace;
+pub mod worktree;
+
 pub fn default_fn() -> i32 {
     42
 }
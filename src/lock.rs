@@ -0,0 +1,403 @@
+//! Record of the files echo installed, kept in [`LOCK_FILE`].
+//!
+//! Each entry names the target it was installed for, where it came from
+//! and the hash of the contents written, so later runs can tell files echo
+//! manages from files created by hand.
+
+use crate::agent::BASE_DIR;
+use crate::fs_ops::{self, FsOps, RealFs};
+use crate::provenance;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lockfile path, relative to the repository root.
+pub const LOCK_FILE: &str = ".echo/lock.toml";
+
+/// Contents of the lockfile.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lock {
+    /// Commit of the flowmates checkout the rules were last installed
+    /// from, when it is a git repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Version of the rule pack installed, from its `pack.toml`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
+    #[serde(rename = "file")]
+    pub files: Vec<LockedFile>,
+}
+
+/// A file installed by echo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedFile {
+    /// Target the file belongs to, e.g. `cursor` or `windsurf`.
+    pub target: String,
+    /// Path relative to the repository root.
+    pub path: PathBuf,
+    /// Rule or template name.
+    pub name: String,
+    /// `embedded` or `flowmates`.
+    pub source: String,
+    /// SHA-256 of the contents installed from the source, hex encoded. A
+    /// file still hashing to it was not edited since.
+    pub hash: String,
+    /// How the file was installed (`copy`, `symlink` or `hardlink`), for
+    /// files that may be links to their source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Modification time copied from the source, in seconds since the
+    /// epoch, for files installed with `preserve-metadata`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<u64>,
+}
+
+impl Lock {
+    /// Load the lockfile of `root`; empty when absent.
+    pub fn load(root: &Path) -> Result<Lock> {
+        let path = root.join(LOCK_FILE);
+        if !path.is_file() {
+            return Ok(Lock::default());
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {}", LOCK_FILE))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", LOCK_FILE))
+    }
+
+    /// Write the lockfile, entries sorted by target and path.
+    pub fn save(&mut self, root: &Path) -> Result<()> {
+        self.save_in(&RealFs, root)
+    }
+
+    /// Like [`Lock::save`], writing through `fs`.
+    pub fn save_in(&mut self, fs: &dyn FsOps, root: &Path) -> Result<()> {
+        self.files
+            .sort_by(|a, b| (&a.target, &a.path).cmp(&(&b.target, &b.path)));
+        let content = format!(
+            "# Generated by echo; do not edit.\n\n{}",
+            toml::to_string(self)?
+        );
+        let path = root.join(LOCK_FILE);
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs.write_synced(&path, content.as_bytes())
+            .with_context(|| format!("failed to write {}", LOCK_FILE))
+    }
+
+    /// Add `file`, replacing the entry for the same path.
+    pub fn record(&mut self, file: LockedFile) {
+        self.files.retain(|f| f.path != file.path);
+        self.files.push(file);
+    }
+
+    /// Drop the entry for `path`; returns whether there was one.
+    pub fn remove(&mut self, path: &Path) -> bool {
+        let count = self.files.len();
+        self.files.retain(|f| f.path != path);
+        self.files.len() != count
+    }
+
+    /// Entry for `path`, if echo installed it.
+    pub fn get(&self, path: &Path) -> Option<&LockedFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+}
+
+/// What installing a file did, by comparing the contents to install with
+/// those already there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The file was missing and has been written.
+    Installed,
+    /// The file was outdated and has been overwritten.
+    Updated,
+    /// The file already had the contents to install.
+    UpToDate,
+    /// The file was edited locally and has been kept.
+    Modified,
+    /// The file was edited locally and the source changes were merged in.
+    Merged,
+    /// As [`Outcome::Merged`], but the merge left conflict markers.
+    Conflicted,
+    /// The file differs but is listed in `.echoignore`, so it was left
+    /// alone.
+    Protected,
+}
+
+impl Outcome {
+    /// Decide what installing `content` at `rel_path` does. An installed
+    /// file that differs is overwritten with `force`, or when its hash is
+    /// still `locked`, the one echo recorded when writing it.
+    pub fn of(
+        root: &Path,
+        rel_path: &Path,
+        content: &str,
+        force: bool,
+        locked: Option<&str>,
+    ) -> Outcome {
+        Outcome::of_bytes(root, rel_path, content.as_bytes(), force, locked)
+    }
+
+    /// Like [`Outcome::of`], for contents that may not be text.
+    pub fn of_bytes(
+        root: &Path,
+        rel_path: &Path,
+        content: &[u8],
+        force: bool,
+        locked: Option<&str>,
+    ) -> Outcome {
+        let path = root.join(rel_path);
+        if !path.exists() {
+            return Outcome::Installed;
+        }
+        let installed = fs::read(&path).ok().map(strip_bytes);
+        if installed.as_deref() == Some(content) {
+            Outcome::UpToDate
+        } else if force
+            || installed.is_some_and(|installed| Some(hash_bytes(&installed).as_str()) == locked)
+        {
+            Outcome::Updated
+        } else {
+            Outcome::Modified
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Installed => "installed",
+            Outcome::Updated => "updated",
+            Outcome::UpToDate => "up to date",
+            Outcome::Modified => "modified",
+            Outcome::Merged => "merged",
+            Outcome::Conflicted => "merged with conflicts",
+            Outcome::Protected => "protected",
+        }
+    }
+
+    /// Whether the file is to be written.
+    pub fn is_written(self) -> bool {
+        !matches!(
+            self,
+            Outcome::UpToDate | Outcome::Modified | Outcome::Protected
+        )
+    }
+}
+
+/// An installed file edited locally whose source has changed.
+#[derive(Debug, Clone, Copy)]
+pub struct Conflict<'a> {
+    /// Path relative to the repository root.
+    pub path: &'a Path,
+    /// Contents of the file.
+    pub local: &'a str,
+    /// Contents to install.
+    pub source: &'a str,
+    /// Contents echo last wrote, when kept (see [`base_path`]).
+    pub base: Option<&'a str>,
+}
+
+impl Conflict<'_> {
+    /// Unified diff from the local contents to the source.
+    pub fn diff(&self) -> String {
+        diffy::create_patch(self.local, self.source).to_string()
+    }
+
+    /// Apply `resolution`: returns the contents to write, if any, and the
+    /// resulting outcome. Merging without a base keeps the file.
+    pub fn resolve(&self, resolution: Resolution) -> (Option<String>, Outcome) {
+        match (resolution, self.base) {
+            (Resolution::Take, _) => (Some(self.source.to_string()), Outcome::Updated),
+            (Resolution::Merge, Some(base)) => match diffy::merge(base, self.local, self.source) {
+                Ok(merged) => (Some(merged), Outcome::Merged),
+                Err(conflicted) => (Some(conflicted), Outcome::Conflicted),
+            },
+            _ => (None, Outcome::Modified),
+        }
+    }
+}
+
+/// How to settle a [`Conflict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the local file.
+    Keep,
+    /// Overwrite it with the source.
+    Take,
+    /// Merge the source changes into it, three-way against the base.
+    Merge,
+}
+
+/// Where the contents echo last wrote to `path` are kept, relative to the
+/// repository root, as the base of later merges.
+pub fn base_path(path: &Path) -> PathBuf {
+    Path::new(BASE_DIR).join(format!("{}.base", path.display()))
+}
+
+/// The kept base of `path`, if its hash is still `hash`.
+pub fn read_base(root: &Path, path: &Path, hash: &str) -> Option<String> {
+    fs::read_to_string(root.join(base_path(path)))
+        .ok()
+        .filter(|base| self::hash(base) == hash)
+}
+
+/// Keep `content` as the base of `path`, unless it already is.
+pub fn write_base(fs: &dyn FsOps, root: &Path, path: &Path, content: &str) -> Result<()> {
+    let base = root.join(base_path(path));
+    if fs::read_to_string(&base).is_ok_and(|kept| kept == content) {
+        return Ok(());
+    }
+    fs_ops::write_file(fs, &base, content.as_bytes())
+        .with_context(|| format!("failed to write {}", base_path(path).display()))
+}
+
+/// SHA-256 of `content`, hex encoded.
+pub fn hash(content: &str) -> String {
+    hash_bytes(content.as_bytes())
+}
+
+/// SHA-256 of the installed file `content`, leaving out its provenance
+/// header (see [`crate::provenance`]); what lockfile entries record.
+pub fn hash_installed(content: &str) -> String {
+    hash(&provenance::strip(content))
+}
+
+/// Like [`hash_installed`], for contents that may not be text.
+pub fn hash_installed_bytes(content: &[u8]) -> String {
+    match std::str::from_utf8(content) {
+        Ok(text) => hash_installed(text),
+        Err(_) => hash_bytes(content),
+    }
+}
+
+/// `content` without its provenance header, when it is text.
+fn strip_bytes(content: Vec<u8>) -> Vec<u8> {
+    match String::from_utf8(content) {
+        Ok(text) => provenance::strip(&text).into_owned().into_bytes(),
+        Err(err) => err.into_bytes(),
+    }
+}
+
+/// Like [`hash`], for contents that may not be text.
+pub fn hash_bytes(content: &[u8]) -> String {
+    let digest = Sha256::digest(content);
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_round_trips_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Lock::load(dir.path()).unwrap(), Lock::default());
+        let entry = |target: &str, path: &str, hash: &str| LockedFile {
+            target: target.into(),
+            path: path.into(),
+            name: "style".into(),
+            source: "embedded".into(),
+            hash: hash.into(),
+            link: None,
+            mtime: None,
+        };
+        let mut lock = Lock::default();
+        lock.record(entry("windsurf", ".windsurf/rules/style.md", "a"));
+        lock.record(entry("cursor", ".cursor/rules/style.mdc", "b"));
+        lock.record(entry("cursor", ".cursor/rules/style.mdc", "c"));
+        lock.save(dir.path()).unwrap();
+
+        let loaded = Lock::load(dir.path()).unwrap();
+        assert_eq!(loaded.files.len(), 2);
+        assert_eq!(loaded.files[0].target, "cursor");
+        assert_eq!(
+            loaded
+                .get(Path::new(".cursor/rules/style.mdc"))
+                .map(|f| f.hash.as_str()),
+            Some("c")
+        );
+        assert_eq!(hash("").len(), 64);
+
+        let mut loaded = loaded;
+        assert!(loaded.remove(Path::new(".cursor/rules/style.mdc")));
+        assert!(!loaded.remove(Path::new(".cursor/rules/style.mdc")));
+        assert_eq!(loaded.files.len(), 1);
+    }
+
+    #[test]
+    fn compares_contents_to_decide_outcomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let rel = Path::new("style.mdc");
+        assert_eq!(
+            Outcome::of(root, rel, "v1", false, None),
+            Outcome::Installed
+        );
+        fs::write(root.join(rel), "v1").unwrap();
+        assert_eq!(Outcome::of(root, rel, "v1", true, None), Outcome::UpToDate);
+        let locked = hash("v1");
+        assert_eq!(
+            Outcome::of(root, rel, "v2", false, Some(&locked)),
+            Outcome::Updated
+        );
+        fs::write(root.join(rel), "mine").unwrap();
+        assert_eq!(
+            Outcome::of(root, rel, "v2", false, Some(&locked)),
+            Outcome::Modified
+        );
+        assert_eq!(Outcome::of(root, rel, "v2", true, None), Outcome::Updated);
+    }
+
+    #[test]
+    fn resolves_conflicts_against_the_kept_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let rel = Path::new(".cursor/rules/style.mdc");
+        write_base(&RealFs, dir.path(), rel, "a\nb\nc\n").unwrap();
+        assert!(dir
+            .path()
+            .join(".echo/.cursor/rules/style.mdc.base")
+            .is_file());
+        assert_eq!(read_base(dir.path(), rel, &hash("other")), None);
+        let base = read_base(dir.path(), rel, &hash("a\nb\nc\n"));
+
+        let conflict = Conflict {
+            path: rel,
+            local: "a\nb\nc\nmine\n",
+            source: "A\nb\nc\n",
+            base: base.as_deref(),
+        };
+        assert!(conflict.diff().contains("-mine\n"));
+        assert_eq!(
+            conflict.resolve(Resolution::Keep),
+            (None, Outcome::Modified)
+        );
+        assert_eq!(
+            conflict.resolve(Resolution::Take),
+            (Some("A\nb\nc\n".into()), Outcome::Updated)
+        );
+        assert_eq!(
+            conflict.resolve(Resolution::Merge),
+            (Some("A\nb\nc\nmine\n".into()), Outcome::Merged)
+        );
+        let clashing = Conflict {
+            local: "x\nb\nc\n",
+            ..conflict
+        };
+        assert_eq!(clashing.resolve(Resolution::Merge).1, Outcome::Conflicted);
+        let baseless = Conflict {
+            base: None,
+            ..conflict
+        };
+        assert_eq!(baseless.resolve(Resolution::Merge).1, Outcome::Modified);
+    }
+}
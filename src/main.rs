@@ -0,0 +1,3469 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use echo::agent;
+use echo::audit_log;
+use echo::check;
+use echo::checksums;
+use echo::config::{self, Config};
+use echo::context;
+use echo::cursor_sync;
+use echo::daemon;
+use echo::environment::{self, Environment};
+use echo::fs_ops::{Audit, RealFs};
+use echo::git;
+use echo::github::{self, Level};
+use echo::gitignore::{self, ExcludeStrategy};
+use echo::gitutil;
+use echo::hooks;
+use echo::issues::{
+    self, bulk, graph, history, lint, ops, render, stale, template_lint, templates, validate, State,
+};
+use echo::lock::{Conflict, Lock, Outcome, Resolution};
+use echo::mcp;
+use echo::notify;
+use echo::pack;
+use echo::plugins;
+use echo::post_run;
+use echo::presets;
+use echo::profiles;
+use echo::prune;
+use echo::registry;
+use echo::report::{self, Report};
+use echo::rules::{self, changelog};
+use echo::run_lock::RunLock;
+use echo::scan_cache::ScanCache;
+use echo::scripts;
+use echo::session;
+use echo::source_cache;
+use echo::source_pin;
+use echo::source_repo;
+use echo::source_scan;
+use echo::stats;
+use echo::status;
+use echo::template;
+use echo::uninstall;
+use echo::watch;
+use echo::workspace;
+use echo::worktree;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(
+    name = "echo",
+    version,
+    about = "Workflow tooling for agent-driven repositories"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// When another echo run is modifying the repository, wait for it
+    /// instead of failing
+    #[arg(long, global = true)]
+    wait: bool,
+    /// Work on the repository containing this directory instead of the
+    /// current one
+    #[arg(long, global = true, value_name = "DIR")]
+    root: Option<PathBuf>,
+    /// Hash every file of the flowmates checkout instead of reusing the
+    /// fingerprints cached by earlier runs
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Never prompt: take the safe default or fail with what to pass
+    /// instead. Implied when stdin is not a terminal or `$CI` is true
+    #[arg(long, global = true)]
+    non_interactive: bool,
+    /// Scan hidden, backup and gitignored files of the flowmates checkout
+    /// too
+    #[arg(long, global = true)]
+    scan_ignored: bool,
+    /// Profile of ~/.config/flowmates/profiles.toml whose source, presets
+    /// and variables complete .echo.toml; $ECHO_PROFILE by default
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+}
+
+impl Command {
+    /// Whether the command writes to the repository and must not run
+    /// alongside another one that does.
+    fn is_mutating(&self) -> bool {
+        match self {
+            Command::Init { .. } | Command::Uninstall => true,
+            Command::Undo { list, .. } => !list,
+            Command::Prune { yes, .. } => *yes,
+            Command::Update { all, .. } => !all,
+            Command::Agent { .. } | Command::Project { .. } | Command::Context { .. } => true,
+            Command::Session { command } => !matches!(command, SessionCommand::List),
+            Command::Hooks { command } => matches!(
+                command,
+                HooksCommand::Install { .. } | HooksCommand::Uninstall { .. }
+            ),
+            Command::Rules { command } => matches!(
+                command,
+                RulesCommand::Disable { .. }
+                    | RulesCommand::Enable { .. }
+                    | RulesCommand::Install { .. }
+                    | RulesCommand::New { .. }
+                    | RulesCommand::MigrateCursorrules
+                    | RulesCommand::Export { .. }
+            ),
+            Command::Template { command } => matches!(command, TemplateCommand::New { .. }),
+            Command::Issue { command } => {
+                matches!(
+                    command,
+                    IssueCommand::New { .. }
+                        | IssueCommand::Move { .. }
+                        | IssueCommand::BulkMove { .. }
+                        | IssueCommand::BulkEdit { .. }
+                )
+            }
+            _ => false,
+        }
+    }
+
+    fn verbosity(&self) -> Verbosity {
+        match self {
+            Command::Init { quiet, summary, .. } | Command::Update { quiet, summary, .. } => {
+                match (quiet, summary) {
+                    (true, _) => Verbosity::Quiet,
+                    (_, true) => Verbosity::Summary,
+                    _ => Verbosity::Normal,
+                }
+            }
+            _ => Verbosity::Normal,
+        }
+    }
+}
+
+/// With `--summary`, print the counts of `report`, after `prefix`.
+fn print_summary(prefix: &str, report: &Report) {
+    if verbosity() == Verbosity::Summary {
+        println!("{}{}", prefix, report.summary());
+    }
+}
+
+/// How much `echo init` and `echo update` print.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    /// Each file and step.
+    Normal,
+    /// One line counting what was done.
+    Summary,
+    /// Errors only.
+    Quiet,
+}
+
+/// Verbosity of the running command, set once in `main`.
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or(Verbosity::Normal)
+}
+
+/// Whether echo may prompt, set once in `main`.
+static INTERACTIVE: OnceLock<bool> = OnceLock::new();
+
+fn interactive() -> bool {
+    INTERACTIVE
+        .get()
+        .copied()
+        .unwrap_or_else(|| io::stdin().is_terminal())
+}
+
+/// Whether `--no-cache` is set, set once in `main`.
+static NO_CACHE: OnceLock<bool> = OnceLock::new();
+
+/// The scan cache of the flowmates checkout `dir`, disabled by
+/// `--no-cache` or without a cache directory.
+fn scan_cache(env: &Environment, dir: &Path) -> ScanCache {
+    match env.cache_dir() {
+        Some(cache_dir) if NO_CACHE.get() != Some(&true) => ScanCache::load(&cache_dir, dir),
+        _ => ScanCache::disabled(dir),
+    }
+}
+
+/// Keep the fingerprints of `cache` for the next run; failing to is only
+/// worth a warning.
+fn save_scan_cache(cache: &ScanCache) {
+    if let Err(err) = cache.save() {
+        eprintln!("warning: {:#}", err);
+    }
+}
+
+/// `println!` for per-file progress, silenced by `--quiet` and `--summary`.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if verbosity() == Verbosity::Normal {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the issues/<project>/ workflow directories
+    Init {
+        /// Project to set up; repeatable. Defaults to the repository directory name
+        #[arg(long = "project")]
+        projects: Vec<String>,
+        /// Set up a project per package of the Cargo, pnpm or npm workspace,
+        /// or of the `[workspace]` packages of .echo.toml
+        #[arg(long)]
+        workspace: bool,
+        /// Install the rules and templates of this preset (rust, python, web,
+        /// minimal, ...); repeatable. Detected from manifest files by default
+        #[arg(long = "preset")]
+        presets: Vec<String>,
+        /// Use the detected presets as if passed with --preset, instead of
+        /// asking on a terminal
+        #[arg(long, conflicts_with = "presets")]
+        auto_preset: bool,
+        /// Commit .cursor/ instead of adding it to .gitignore (recorded as
+        /// `vendor = true` in .echo.toml)
+        #[arg(long)]
+        vendor: bool,
+        /// Link the flowmates rules in .cursor/rules/ to their source instead
+        /// of copying them, so upstream edits apply at once (recorded as
+        /// `link = true` under [rules] in .echo.toml)
+        #[arg(long)]
+        link: bool,
+        /// Set up the flowmates checkout itself: check its layout, lint its
+        /// rules and templates, install its hooks and register it as the
+        /// canonical source
+        #[arg(long, conflicts_with_all = ["workspace", "presets", "auto_preset", "link", "offline"])]
+        source_repo: bool,
+        /// Clone the flowmates repository at this URL under
+        /// ~/.local/share/echo/, register it as the source and install from
+        /// it; asked for on a terminal when no checkout is configured
+        #[arg(long, value_name = "URL", conflicts_with_all = ["source_repo", "offline"])]
+        source_url: Option<String>,
+        /// Where to write the .cursor/ ignore entry: the shared .gitignore,
+        /// the local .git/info/exclude, or nowhere (recorded as
+        /// `exclude-strategy` in .echo.toml)
+        #[arg(long, value_enum)]
+        exclude_strategy: Option<ExcludeStrategyArg>,
+        /// Overwrite AGENT.md entirely instead of refreshing its managed section,
+        /// and locally edited rules and templates unless --strategy says otherwise
+        #[arg(long)]
+        force: bool,
+        /// What to do with rules and templates edited locally whose source
+        /// changed. Defaults to theirs with --force, else ask on a terminal
+        /// and ours otherwise
+        #[arg(long, value_enum)]
+        strategy: Option<StrategyArg>,
+        /// Skip the post-run commands of .echo.toml
+        #[arg(long)]
+        no_run_hooks: bool,
+        /// Write a JSON report of the run to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+        /// Fail when the run leaves warnings: files kept or merged with
+        /// conflicts, a missing flowmates checkout
+        #[arg(long)]
+        strict: bool,
+        /// Install from the cached copy of the last flowmates checkout used,
+        /// for when it is unreachable
+        #[arg(long)]
+        offline: bool,
+        /// Print nothing but errors
+        #[arg(long, short, conflicts_with = "summary")]
+        quiet: bool,
+        /// Print a single line counting what was done instead of each file
+        #[arg(long)]
+        summary: bool,
+    },
+    /// Refresh generated files (the managed section of AGENT.md and of any
+    /// other agent file already present)
+    Update {
+        /// Project used for PROJECT_NAME; defaults to the only project or the
+        /// repository directory name
+        #[arg(long)]
+        project: Option<String>,
+        /// Update every repository of `echo projects list` instead
+        #[arg(long, conflicts_with_all = ["project", "report"])]
+        all: bool,
+        /// What to do with rules and templates edited locally whose source
+        /// changed. Defaults to ask on a terminal and ours otherwise
+        #[arg(long, value_enum)]
+        strategy: Option<StrategyArg>,
+        /// Skip the post-run commands of .echo.toml
+        #[arg(long)]
+        no_run_hooks: bool,
+        /// Write a JSON report of the run to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+        /// Fail when the run leaves warnings: files kept or merged with
+        /// conflicts, a missing flowmates checkout
+        #[arg(long)]
+        strict: bool,
+        /// Install from the cached copy of the last flowmates checkout used,
+        /// for when it is unreachable
+        #[arg(long)]
+        offline: bool,
+        /// Install a rule pack of a new major version (see `rules-pack`)
+        #[arg(long)]
+        allow_major: bool,
+        /// Print nothing but errors
+        #[arg(long, short, conflicts_with = "summary")]
+        quiet: bool,
+        /// Print a single line counting what was done instead of each file
+        #[arg(long)]
+        summary: bool,
+    },
+    /// Summarize the echo setup and warn about inconsistencies
+    Status {
+        /// Summarize every repository of `echo projects list` in a table
+        #[arg(long)]
+        all: bool,
+    },
+    /// Register repositories for `update --all`, `status --all` and the
+    /// daemon
+    Projects {
+        #[command(subcommand)]
+        command: ProjectsCommand,
+    },
+    /// Keep every repository registered in the flowmates configuration up
+    /// to date with its sources
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonCommand>,
+        /// Seconds between two rounds
+        #[arg(long, default_value_t = daemon::DEFAULT_INTERVAL)]
+        interval: u64,
+    },
+    /// Read the log of the files mutating commands changed, kept with
+    /// `audit-log = true` in .echo.toml
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+    /// Revert the most recent command of the audit log not undone yet
+    Undo {
+        /// List the logged commands that can be undone, most recent first,
+        /// instead
+        #[arg(long)]
+        list: bool,
+        /// Revert files changed since the command too
+        #[arg(long, conflicts_with = "list")]
+        force: bool,
+    },
+    /// Summarize the usage statistics recorded on this machine, once
+    /// enabled
+    Stats {
+        #[command(subcommand)]
+        command: Option<StatsCommand>,
+    },
+    /// Model Context Protocol server for agents
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommand,
+    },
+    /// Reinstall rules and templates as they change in the flowmates
+    /// checkout, keeping files edited in the repository
+    Watch {
+        /// Also keep ~/.cursor/rules/ in step, as `echo sync-cursor` does
+        #[arg(long)]
+        global: bool,
+    },
+    /// Remove the files echo installed, unless edited since, and its
+    /// .gitignore block
+    Uninstall,
+    /// List installed rules whose source was deleted, and remove them with
+    /// --yes; rules edited since are kept
+    Prune {
+        /// Remove the orphans instead of listing them
+        #[arg(long)]
+        yes: bool,
+        /// Only list the orphans (the default)
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+    },
+    /// Install the rules of the flowmates checkout to ~/.cursor/rules/,
+    /// keeping local edits
+    SyncCursor {
+        /// Report how ~/.cursor/rules/ differs from the checkout, changing
+        /// nothing; fails when it does
+        #[arg(long)]
+        check: bool,
+        /// Also remove synced rules deleted from the checkout
+        #[arg(long, conflicts_with = "check")]
+        prune: bool,
+    },
+    /// Snapshot the work of an agent session to park and resume it
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Bundle instructions, rules and issues into one file for an agent
+    Context {
+        #[command(subcommand)]
+        command: ContextCommand,
+    },
+    /// Package the flowmates checkout as a versioned rule pack
+    Pack {
+        #[command(subcommand)]
+        command: PackCommand,
+    },
+    /// Check that installed files, hooks, issues and AGENT.md match their
+    /// sources; fails on any drift
+    Check {
+        /// Print problems as GitHub Actions `::error` annotations (implied in
+        /// GitHub Actions)
+        #[arg(long)]
+        ci: bool,
+        /// Write a JSON report of the problems and warnings to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+    },
+    /// Manage agent instruction files
+    Agent {
+        #[command(subcommand)]
+        command: AgentCommand,
+    },
+    /// Manage git hooks from the hooks manifest
+    #[command(alias = "hook")]
+    Hooks {
+        #[command(subcommand)]
+        command: HooksCommand,
+    },
+    /// Manage issue projects
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommand,
+    },
+    /// Manage Cursor rules in .cursor/rules/
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+    /// Presets of rules, templates, hooks and settings for `init --preset`
+    Preset {
+        #[command(subcommand)]
+        command: PresetCommand,
+    },
+    /// Work with flowmates checkouts
+    Source {
+        #[command(subcommand)]
+        command: SourceCommand,
+    },
+    /// Manage issue templates in issues/shared/templates/
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+    /// Work with issue files under issues/
+    Issue {
+        #[command(subcommand)]
+        command: IssueCommand,
+    },
+    /// Run the echo-<name> plugin
+    #[command(external_subcommand)]
+    Plugin(Vec<String>),
+}
+
+#[derive(Subcommand)]
+enum AgentCommand {
+    /// Give an issue its own git worktree and branch, with the rules
+    /// installed, and move it to in_progress
+    Spawn {
+        /// Issue id (file stem) or file name
+        issue: String,
+        #[arg(long)]
+        project: Option<String>,
+        /// Agent recorded in the issue's **Agent:** field
+        #[arg(long)]
+        agent: Option<String>,
+        /// Worktree directory; `<repo>-<issue>` next to the repository by
+        /// default
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Take over an issue assigned to someone else
+        #[arg(long)]
+        steal: bool,
+    },
+    /// Remove the worktrees of issues moved to done
+    Reap,
+    /// Render the agent instructions into the file each tool expects
+    Generate {
+        /// Target file; repeatable
+        #[arg(long = "target", value_enum, required = true)]
+        targets: Vec<TargetArg>,
+        /// Project used for PROJECT_NAME
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Re-render the managed section of AGENT.md, showing a diff first
+    Regen {
+        /// File to regenerate
+        #[arg(long, value_enum, default_value_t = TargetArg::Agent)]
+        target: TargetArg,
+        /// Write without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+        /// Project used for PROJECT_NAME
+        #[arg(long)]
+        project: Option<String>,
+        #[command(flatten)]
+        template_vars: TemplateVars,
+    },
+}
+
+#[derive(Subcommand)]
+enum HooksCommand {
+    /// Install the hooks of the manifest into the git hooks directory
+    Install {
+        /// Only install this hook (pre-commit, commit-msg, pre-push, post-checkout)
+        hook: Option<String>,
+        /// Rewrite hooks that are already up to date
+        #[arg(long)]
+        force: bool,
+        /// Leave this hook alone; repeatable
+        #[arg(long = "skip")]
+        skip: Vec<String>,
+        /// Link to the manifest scripts instead of copying them, so edits
+        /// apply without reinstalling; falls back to a copy
+        #[arg(long, value_enum, default_value_t = LinkArg::Copy)]
+        link: LinkArg,
+    },
+    /// Remove the hooks echo installed
+    Uninstall { hook: Option<String> },
+    /// List the hooks available in the manifest
+    List,
+    /// Show which hooks are installed, outdated or available
+    Status { hook: Option<String> },
+    /// Check that installed hooks are still the ones echo wrote
+    Verify { hook: Option<String> },
+    /// Run the checks of a hook; called by the installed hook scripts
+    Run {
+        hook: String,
+        /// Arguments git passed to the hook
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectsCommand {
+    /// Register a repository; the current one by default
+    Add { path: Option<PathBuf> },
+    /// Unregister a repository; the current one by default
+    Remove { path: Option<PathBuf> },
+    /// List the registered repositories
+    List,
+}
+
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// Record the branch, AGENT.md and issue states in
+    /// .cursor/sessions/<name>.json; resuming a parked session reports what
+    /// changed since
+    Start { name: String },
+    /// Park a session, recording where it left off
+    End { name: String },
+    /// List sessions with the issues they have in progress
+    List,
+}
+
+#[derive(Subcommand)]
+enum ContextCommand {
+    /// Write context.md from AGENT.md, the rules and the open issues,
+    /// within a token budget
+    Build {
+        /// Token budget; `[context] max-tokens` or 8000 by default
+        #[arg(long)]
+        max_tokens: Option<usize>,
+        /// Sections to include, comma-separated; `[context] include` or
+        /// all by default
+        #[arg(long, value_enum, value_delimiter = ',')]
+        include: Vec<ContextSectionArg>,
+        /// Write here instead of context.md
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PackCommand {
+    /// Archive the rules, templates, scripts and manifests of the checkout
+    /// and upload them to a registry
+    Publish {
+        /// Where to upload: an http(s)://, file:// or s3:// URL
+        #[arg(long)]
+        registry: String,
+        /// Keep the archive in this directory
+        #[arg(long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum McpCommand {
+    /// Serve rules, AGENT.md and issues over stdio
+    Serve,
+}
+
+#[derive(Subcommand)]
+enum DaemonCommand {
+    /// Show whether the daemon runs and when each repository was synced
+    Status,
+}
+
+#[derive(Subcommand)]
+enum AuditCommand {
+    /// Print the logged runs, oldest first, with the files each changed
+    Show {
+        /// Only runs on or after this date, YYYY-MM-DD
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+        /// Only changes to files at or under this path, e.g. .cursor/rules
+        #[arg(long, value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommand {
+    /// Record each invocation of echo to ~/.cache/echo/stats.jsonl
+    Enable,
+    /// Stop recording and delete the recorded statistics
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum ProjectCommand {
+    /// Rename issues/<old>/ to issues/<new>/ and update references
+    Rename { old: String, new: String },
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// List installed rules with their source and front-matter
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Move a rule to .cursor/rules/disabled/ and keep it from being reinstalled
+    Disable { name: String },
+    /// Restore a disabled rule
+    Enable { name: String },
+    /// Check rule front-matter: missing descriptions, invalid globs, unknown keys
+    Lint,
+    /// Create a rule with valid front-matter in .cursor/rules/
+    New {
+        /// Rule name, e.g. `rust/errors` for a namespaced rule
+        name: String,
+        /// File pattern the rule attaches to; repeatable
+        #[arg(long = "glob")]
+        globs: Vec<String>,
+        #[arg(long)]
+        description: String,
+        /// Apply the rule to every request
+        #[arg(long)]
+        always_apply: bool,
+        /// Create the rule in the flowmates checkout instead
+        #[arg(long)]
+        upstream: bool,
+    },
+    /// Split a legacy .cursorrules file into .mdc rules and move it aside
+    MigrateCursorrules,
+    /// Show what `init --force` would change in installed rules
+    Diff {
+        /// Rule to compare; every installed rule by default
+        name: Option<String>,
+    },
+    /// Commit a locally modified rule to a branch of the flowmates checkout
+    Contribute {
+        name: String,
+        /// Push the branch to origin
+        #[arg(long)]
+        push: bool,
+    },
+    /// Write the enabled rules into other agents' instruction files
+    Export {
+        /// Target tool; repeatable
+        #[arg(long = "target", value_enum, required = true)]
+        targets: Vec<ExportTargetArg>,
+    },
+    /// Flag installed rules covering the same files and topics
+    CheckConflicts {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Install bundles of the flowmates checkout with the rules they
+    /// require, and keep them installed on `update`
+    Install {
+        #[arg(required = true)]
+        bundles: Vec<String>,
+    },
+    /// List installed rules whose source changed since they were installed,
+    /// with the upstream commits behind each change
+    Outdated {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PresetCommand {
+    /// List the presets of the flowmates checkout, or the embedded ones
+    List,
+}
+
+#[derive(Subcommand)]
+enum SourceCommand {
+    /// Check a flowmates checkout before using it: its rules/ and templates/
+    /// layout, rule front-matter, templates, and hooks and presets manifests.
+    /// Fails on any problem
+    Validate {
+        /// Checkout to check; the current repository by default
+        path: Option<PathBuf>,
+        /// Print problems as GitHub Actions `::error` annotations (implied in
+        /// GitHub Actions)
+        #[arg(long)]
+        ci: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    /// Create a template from the built-in skeleton
+    New { name: String },
+    /// List available templates
+    List,
+    /// Check templates for unknown variables, broken includes, malformed
+    /// headers and the sections their schema.toml requires
+    Lint {
+        /// Templates directory, e.g. `templates` in a flowmates checkout;
+        /// issues/shared/templates by default
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Schema file; schema.toml of the templates directory by default
+        #[arg(long)]
+        schema: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IssueCommand {
+    /// Create an issue from a shared template, in todo/ unless its status names another state
+    New {
+        /// Template to use, e.g. feature, bug, enhancement
+        #[arg(long = "type", default_value = "feature")]
+        kind: String,
+        title: String,
+        #[arg(long)]
+        project: Option<String>,
+        /// Owner recorded in the **Assignee:** field; `me` for yourself
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Label recorded in the **Labels:** field; repeatable
+        #[arg(long = "label")]
+        labels: Vec<String>,
+        #[command(flatten)]
+        template_vars: TemplateVars,
+    },
+    /// List issues grouped by state
+    List {
+        /// Only list this project; all projects by default
+        #[arg(long)]
+        project: Option<String>,
+        /// Only list issues assigned to this owner; `me` for yourself
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Only list issues carrying this label
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Move an issue to another state directory
+    Move {
+        /// Issue id (file name without .md)
+        id: String,
+        #[arg(value_enum)]
+        to: StateArg,
+        #[arg(long)]
+        project: Option<String>,
+        /// Owner recorded in the **Assignee:** field; `me` for yourself
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Take over an issue assigned to someone else
+        #[arg(long)]
+        steal: bool,
+    },
+    /// Check that issue files match their state directory
+    Validate {
+        /// Only validate this project; all projects by default
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Check issue structure against the [lint] rules of .echo.toml
+    Lint {
+        /// Issue files to lint; every issue of the selected projects by default
+        paths: Vec<PathBuf>,
+        #[arg(long, conflicts_with = "paths")]
+        project: Option<String>,
+    },
+    /// Render the workflow state as a markdown board or mermaid diagram
+    Render {
+        #[arg(long, value_enum, default_value_t = RenderFormat::Markdown)]
+        format: RenderFormat,
+        /// Write to a file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Move every issue matching the filters to another state
+    BulkMove {
+        /// Only move issues in this state
+        #[arg(long, value_enum)]
+        from: Option<StateArg>,
+        #[arg(long, value_enum)]
+        to: StateArg,
+        #[command(flatten)]
+        filter: BulkFilter,
+        /// List the issues that would move without moving them
+        #[arg(long)]
+        dry_run: bool,
+        /// Take over issues assigned to someone else
+        #[arg(long)]
+        steal: bool,
+    },
+    /// Set header fields on every issue matching the filters
+    BulkEdit {
+        /// Field to set, e.g. `assignee=alice`; repeatable
+        #[arg(long = "set", required = true)]
+        set: Vec<String>,
+        /// Only edit issues in this state
+        #[arg(long, value_enum)]
+        state: Option<StateArg>,
+        #[command(flatten)]
+        filter: BulkFilter,
+        /// List the issues that would change without editing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the commits that created, moved and edited an issue
+    History {
+        /// Issue id (file name without .md)
+        id: String,
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// List issues in progress left untouched, and open issues past due
+    Stale {
+        /// Days without change after which an issue in progress is stale
+        #[arg(long, default_value_t = stale::DEFAULT_DAYS)]
+        days: u64,
+        /// Only check this project; all projects by default
+        #[arg(long)]
+        project: Option<String>,
+        /// Also POST the list as JSON to this URL, when not empty
+        #[arg(long)]
+        webhook: Option<String>,
+    },
+    /// Render the dependencies declared in **Depends on:** fields as a graph
+    Graph {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Write to a file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Filters shared by `echo issue bulk-move` and `echo issue bulk-edit`.
+#[derive(Args)]
+struct BulkFilter {
+    /// Only issues of this project; all projects by default
+    #[arg(long)]
+    project: Option<String>,
+    /// Only issues carrying this label
+    #[arg(long)]
+    label: Option<String>,
+    /// Only issues assigned to this owner; `me` for yourself
+    #[arg(long)]
+    assignee: Option<String>,
+}
+
+/// Values of template variables echo does not define, shared by
+/// `echo issue new` and `echo agent regen`. Missing ones are asked for on
+/// the terminal.
+#[derive(Args)]
+struct TemplateVars {
+    /// Template variable, as NAME=value; repeatable
+    #[arg(long = "var")]
+    vars: Vec<String>,
+    /// Store the values given and asked for in [variables] of .echo.toml
+    #[arg(long)]
+    save_vars: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RenderFormat {
+    Markdown,
+    Mermaid,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TargetArg {
+    /// AGENT.md
+    Agent,
+    /// AGENTS.md
+    AgentsMd,
+    /// CLAUDE.md
+    Claude,
+    /// .github/copilot-instructions.md
+    Copilot,
+    /// .windsurfrules
+    Windsurf,
+}
+
+impl From<TargetArg> for agent::Target {
+    fn from(target: TargetArg) -> Self {
+        match target {
+            TargetArg::Agent => agent::Target::Agent,
+            TargetArg::AgentsMd => agent::Target::AgentsMd,
+            TargetArg::Claude => agent::Target::Claude,
+            TargetArg::Copilot => agent::Target::Copilot,
+            TargetArg::Windsurf => agent::Target::Windsurf,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportTargetArg {
+    /// CLAUDE.md
+    Claude,
+    /// .github/copilot-instructions.md
+    Copilot,
+    /// .windsurfrules
+    Windsurf,
+    /// .rules (Zed)
+    Zed,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ContextSectionArg {
+    /// AGENT.md
+    Agent,
+    /// The enabled installed rules
+    Rules,
+    /// Issues in todo/ and in_progress/
+    OpenIssues,
+}
+
+impl From<ContextSectionArg> for context::Section {
+    fn from(section: ContextSectionArg) -> Self {
+        match section {
+            ContextSectionArg::Agent => context::Section::Agent,
+            ContextSectionArg::Rules => context::Section::Rules,
+            ContextSectionArg::OpenIssues => context::Section::OpenIssues,
+        }
+    }
+}
+
+impl From<ExportTargetArg> for rules::export::Target {
+    fn from(target: ExportTargetArg) -> Self {
+        match target {
+            ExportTargetArg::Claude => rules::export::Target::Claude,
+            ExportTargetArg::Copilot => rules::export::Target::Copilot,
+            ExportTargetArg::Windsurf => rules::export::Target::Windsurf,
+            ExportTargetArg::Zed => rules::export::Target::Zed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StrategyArg {
+    /// Take the source version
+    Theirs,
+    /// Keep the local version
+    Ours,
+    /// Ask for each file: keep, take, view the diff or merge
+    Ask,
+    /// Merge the source changes in, three-way against the contents echo
+    /// last wrote, leaving conflict markers where both sides changed the
+    /// same lines; files without a recorded base are kept
+    Merge,
+}
+
+impl StrategyArg {
+    /// `strategy`, or the default given `force` and whether echo may
+    /// prompt.
+    fn or_default(strategy: Option<StrategyArg>, force: bool) -> StrategyArg {
+        strategy.unwrap_or(if force {
+            StrategyArg::Theirs
+        } else if interactive() {
+            StrategyArg::Ask
+        } else {
+            StrategyArg::Ours
+        })
+    }
+
+    fn resolve(self, conflict: &Conflict) -> Result<Resolution> {
+        match self {
+            StrategyArg::Theirs => Ok(Resolution::Take),
+            StrategyArg::Ours => Ok(Resolution::Keep),
+            StrategyArg::Ask => ask_resolution(conflict),
+            StrategyArg::Merge => Ok(Resolution::Merge),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExcludeStrategyArg {
+    /// .gitignore, committed with the repository
+    Gitignore,
+    /// .git/info/exclude, local to the clone
+    InfoExclude,
+    /// Leave ignore files alone
+    None,
+}
+
+impl From<ExcludeStrategyArg> for ExcludeStrategy {
+    fn from(strategy: ExcludeStrategyArg) -> Self {
+        match strategy {
+            ExcludeStrategyArg::Gitignore => ExcludeStrategy::Gitignore,
+            ExcludeStrategyArg::InfoExclude => ExcludeStrategy::InfoExclude,
+            ExcludeStrategyArg::None => ExcludeStrategy::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LinkArg {
+    Copy,
+    Symlink,
+    Hardlink,
+}
+
+impl From<LinkArg> for hooks::LinkMode {
+    fn from(link: LinkArg) -> Self {
+        match link {
+            LinkArg::Copy => hooks::LinkMode::Copy,
+            LinkArg::Symlink => hooks::LinkMode::Symlink,
+            LinkArg::Hardlink => hooks::LinkMode::Hardlink,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StateArg {
+    Proposal,
+    Todo,
+    InProgress,
+    Done,
+}
+
+impl From<StateArg> for State {
+    fn from(state: StateArg) -> Self {
+        match state {
+            StateArg::Proposal => State::Proposal,
+            StateArg::Todo => State::Todo,
+            StateArg::InProgress => State::InProgress,
+            StateArg::Done => State::Done,
+        }
+    }
+}
+
+impl From<GraphFormat> for graph::Format {
+    fn from(format: GraphFormat) -> Self {
+        match format {
+            GraphFormat::Dot => graph::Format::Dot,
+            GraphFormat::Mermaid => graph::Format::Mermaid,
+        }
+    }
+}
+
+impl From<RenderFormat> for render::Format {
+    fn from(format: RenderFormat) -> Self {
+        match format {
+            RenderFormat::Markdown => render::Format::Markdown,
+            RenderFormat::Mermaid => render::Format::Mermaid,
+        }
+    }
+}
+
+/// Tell the webhook of `[notifications]` about `transition`; a failure
+/// only warns, as the issue has moved already.
+fn notify_transition(config: &notify::NotificationsConfig, transition: &notify::Transition) {
+    match notify::notify(config, transition) {
+        Ok(true) => println!("notified {}", transition.text()),
+        Ok(false) => {}
+        Err(err) => eprintln!("warning: {:#}", err),
+    }
+}
+
+/// `name`, with `me` resolved from `$ECHO_ASSIGNEE` or git `user.name`.
+fn resolve_assignee(env: &Environment, root: &Path, name: &str) -> Result<String> {
+    let current = ops::current_assignee(root, env.var(ops::ASSIGNEE_VAR));
+    ops::resolve_assignee(name, current.as_deref())
+}
+
+/// Issues of `filter`'s project matching it and `state`.
+fn select_issues(
+    env: &Environment,
+    root: &Path,
+    filter: &BulkFilter,
+    state: Option<State>,
+) -> Result<Vec<issues::Issue>> {
+    let filter_by = bulk::Filter {
+        state,
+        label: filter.label.clone(),
+        assignee: filter
+            .assignee
+            .as_deref()
+            .map(|name| resolve_assignee(env, root, name))
+            .transpose()?,
+    };
+    Ok(filter_by.select(scan_selected(root, filter.project.as_deref())?))
+}
+
+/// Issues of one project when `--project` is given, of all projects otherwise.
+fn scan_selected(root: &Path, project: Option<&str>) -> Result<Vec<issues::Issue>> {
+    match project {
+        Some(name) => issues::scan_project(root, &issues::resolve_project(root, Some(name))?),
+        None => issues::scan(root),
+    }
+}
+
+/// Refresh the agent files, rules and templates of the repository at
+/// `root`, as `echo update` does.
+fn update(
+    env: &Environment,
+    root: &Path,
+    project: Option<&str>,
+    options: UpdateOptions,
+    report: &mut Report,
+) -> Result<()> {
+    let vars = agent_variables(env, root, project)?;
+    let config = env.load_config(root)?;
+    let template = agent::load_template(config.rules.flowmates_dir().as_deref())?;
+    for target in agent::Target::ALL {
+        if target == agent::Target::Agent || root.join(target.path()).exists() {
+            let update = agent::update(root, target, &template, &vars)?;
+            report.agent.push(report::AgentFile {
+                path: target.path().into(),
+                update,
+            });
+            report_agent_update(target, update)?;
+        }
+    }
+    if !options.offline {
+        sync_pinned_source(env, &config, report)?;
+    }
+    let post_update = config.post_update.clone();
+    let write_changelog = config.rules.changelog;
+    let from = Lock::load(root)?.revision;
+    let outdated = match config.rules.flowmates_dir() {
+        Some(dir) if dir.is_dir() && !options.offline => {
+            rules::outdated::outdated(root, &config.rules)?
+        }
+        _ => Vec::new(),
+    };
+    install_presets(
+        env,
+        root,
+        config,
+        options.strategy,
+        options.offline,
+        options.allow_major,
+        report,
+    )?;
+    summarize_install(env, "echo update", &report.files)?;
+    let changed: Vec<_> = outdated
+        .into_iter()
+        .filter(|rule| {
+            report.files.iter().any(|file| {
+                file.path == rule.path
+                    && matches!(
+                        file.outcome,
+                        Outcome::Updated | Outcome::Merged | Outcome::Conflicted
+                    )
+            })
+        })
+        .collect();
+    if !changed.is_empty() {
+        let entry = changelog::Entry {
+            date: environment::date(env.now()),
+            from,
+            to: Lock::load(root)?.revision,
+            rules: changed,
+        };
+        if write_changelog {
+            changelog::prepend(root, &entry)?;
+            progress!("updated {}", changelog::CHANGELOG_FILE);
+        } else {
+            progress!("upstream changes:");
+            for rule in &entry.rules {
+                progress!("  {}", rule.path.display());
+                for commit in &rule.commits {
+                    progress!("    {}", commit);
+                }
+            }
+        }
+    }
+    enforce_strict(options.strict, report)?;
+    if options.run_hooks {
+        run_post_commands(root, "update", &post_update, report)?;
+    }
+    Ok(())
+}
+
+/// Flags of `echo update`.
+#[derive(Clone, Copy)]
+struct UpdateOptions {
+    strategy: StrategyArg,
+    run_hooks: bool,
+    strict: bool,
+    offline: bool,
+    allow_major: bool,
+}
+
+/// Run the post-run commands of `.echo.toml`, printing their output.
+fn run_post_commands(
+    root: &Path,
+    stage: &str,
+    commands: &[String],
+    report: &mut Report,
+) -> Result<()> {
+    for ran in post_run::run(root, stage, commands)? {
+        progress!("ran `{}`", ran.command);
+        for line in ran.output.lines() {
+            progress!("  {}", line);
+        }
+        let failed = (!ran.success).then(|| ran.command.clone());
+        report.post_run.push(ran);
+        if let Some(command) = failed {
+            bail!("post-{} command `{}` failed", stage, command);
+        }
+    }
+    Ok(())
+}
+
+/// Take the run lock of the repository at `root`.
+fn lock_repo(root: &Path, wait: bool) -> Result<RunLock> {
+    RunLock::acquire(root, wait, |holder| {
+        eprintln!(
+            "waiting for another echo run (pid {}) to finish...",
+            holder.pid
+        )
+    })
+}
+
+fn flowmates_config_dir(env: &Environment) -> Result<PathBuf> {
+    env.config_dir()
+        .context("cannot locate the flowmates configuration; set $FLOWMATES_CONFIG_DIR")
+}
+
+/// Warn on stderr when `~/.cursor/rules/` was last synced too long ago.
+fn warn_stale_cursor_sync(env: &Environment) -> Result<()> {
+    let Some(config_dir) = env.config_dir() else {
+        return Ok(());
+    };
+    let state = cursor_sync::State::load(&config_dir)?;
+    if let Some(warning) = state.staleness(env.now()) {
+        if verbosity() == Verbosity::Normal {
+            eprintln!("warning: {}", warning);
+        }
+    }
+    Ok(())
+}
+
+/// Repositories of the registry, failing when there are none.
+fn registered_projects(env: &Environment) -> Result<Vec<PathBuf>> {
+    let projects = registry::Registry::load(&flowmates_config_dir(env)?)?.projects;
+    if projects.is_empty() {
+        bail!("no repository registered; add them with `echo projects add`");
+    }
+    Ok(projects)
+}
+
+/// Template variables for agent files. PROJECT_NAME is `--project`, else the
+/// only issue project, else the repository directory name.
+fn agent_variables(
+    env: &Environment,
+    root: &Path,
+    project: Option<&str>,
+) -> Result<template::Variables> {
+    let project = match project {
+        Some(name) => issues::resolve_project(root, Some(name))?,
+        None => match issues::projects(root)?.as_slice() {
+            [only] => only.clone(),
+            _ => ops::detect_project_name(root)?,
+        },
+    };
+    let config = env.load_config(root)?;
+    Ok(template::variables(
+        root,
+        &project,
+        env.now(),
+        &config.variables,
+    ))
+}
+
+/// Add the `--var` values of `args` to `vars`, then ask on the terminal
+/// for the variables `undefined` still reports; with `--save-vars`, both
+/// are stored in `[variables]` of `.echo.toml`.
+fn complete_variables(
+    root: &Path,
+    vars: &mut template::Variables,
+    args: &TemplateVars,
+    undefined: impl Fn(&template::Variables) -> Result<Vec<String>>,
+) -> Result<()> {
+    let mut given = template::Variables::new();
+    for assignment in &args.vars {
+        let (name, value) = template::parse_assignment(assignment)?;
+        given.insert(name, value);
+    }
+    vars.extend(given.clone());
+    let missing = undefined(vars)?;
+    if !missing.is_empty() {
+        if !interactive() {
+            bail!(
+                "the template needs {}; pass --var NAME=value",
+                missing.join(", ")
+            );
+        }
+        for name in missing {
+            print!("{}: ", name);
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            if io::stdin().read_line(&mut answer)? == 0 {
+                bail!("no value for {}", name);
+            }
+            given.insert(name, answer.trim().to_string());
+        }
+        vars.extend(given.clone());
+    }
+    if args.save_vars && !given.is_empty() {
+        config::edit(root, |doc| {
+            let table = doc
+                .entry("variables")
+                .or_insert(toml_edit::table())
+                .as_table_mut()
+                .ok_or_else(|| anyhow!("[variables] of {} is not a table", config::CONFIG_FILE))?;
+            for (name, value) in &given {
+                table.insert(name, toml_edit::value(value.as_str()));
+            }
+            Ok(())
+        })?;
+        println!(
+            "saved {} in {}",
+            given.keys().cloned().collect::<Vec<_>>().join(", "),
+            config::CONFIG_FILE
+        );
+    }
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal; refuses when echo may not
+/// prompt.
+fn confirm(question: &str) -> Result<bool> {
+    if !interactive() {
+        bail!("cannot ask for confirmation non-interactively; pass --yes to confirm");
+    }
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn report_agent_update(target: agent::Target, update: agent::Update) -> Result<()> {
+    let message = match update {
+        agent::Update::Created => "wrote",
+        agent::Update::Updated => "updated the managed section of",
+        agent::Update::Unchanged => "up to date:",
+        agent::Update::Merged => "merged the generated section into",
+        agent::Update::Conflict => bail!(
+            "{} has merge conflicts; resolve them and wrap the generated text in {} / {}",
+            target.path(),
+            agent::BEGIN_MARKER,
+            agent::END_MARKER
+        ),
+    };
+    progress!("{} {}", message, target.path());
+    Ok(())
+}
+
+/// Keep `.cursor/` in or out of the ignore files according to vendor mode
+/// and the exclude strategy, and `.echo/objects/` out when the audit log
+/// keeps contents there.
+fn apply_vendor_mode(root: &Path, config: &Config) -> Result<()> {
+    let mut entries = Vec::new();
+    if !config.vendor {
+        entries.push(gitignore::CURSOR_ENTRY);
+    }
+    if config.audit_log {
+        entries.push(gitignore::OBJECTS_ENTRY);
+    }
+    let changes = gitignore::ensure_ignored(root, config.exclude_strategy, &entries)?;
+    for change in changes {
+        if change.added {
+            progress!("updated the echo block in {}", change.file);
+        } else {
+            progress!("removed the echo block from {}", change.file);
+        }
+    }
+    Ok(())
+}
+
+/// Install the rules and issue templates selected by the configured or
+/// detected presets, recording what happened to each file in `report`.
+fn install_presets(
+    env: &Environment,
+    root: &Path,
+    mut config: Config,
+    strategy: StrategyArg,
+    offline: bool,
+    allow_major: bool,
+    report: &mut Report,
+) -> Result<()> {
+    apply_vendor_mode(root, &config)?;
+    let cache_dir = env.cache_dir();
+    let cached = match &cache_dir {
+        Some(dir) => source_cache::load(dir)?,
+        None => None,
+    };
+    if offline {
+        let (dir, cached) = cached.clone().context(
+            "no cached flowmates checkout; run `echo init` or `echo update` once without --offline",
+        )?;
+        warn(
+            report,
+            format!(
+                "installing from the cache of {} from {}",
+                cached.source.display(),
+                cached.date()
+            ),
+        );
+        config.rules.flowmates_dir = Some(dir);
+    }
+    let flowmates_dir = config.rules.flowmates_dir();
+    if let Some(dir) = flowmates_dir
+        .as_ref()
+        .filter(|dir| rules::is_source_repo(root, dir))
+    {
+        progress!(
+            "{} is the flowmates checkout; not installing its rules and templates into itself",
+            dir.display()
+        );
+        return Ok(());
+    }
+    if let Some(dir) = flowmates_dir.as_ref().filter(|dir| !dir.is_dir()) {
+        let mut warning = format!(
+            "flowmates checkout {} not found; installed the embedded rules and templates only",
+            dir.display()
+        );
+        if let Some((_, cached)) = &cached {
+            warning.push_str(&format!(
+                "; --offline installs from the cache from {}",
+                cached.date()
+            ));
+        }
+        warn(report, warning);
+    }
+    let cache = flowmates_dir
+        .as_ref()
+        .filter(|dir| dir.is_dir())
+        .map(|dir| scan_cache(env, dir));
+    if let Some(dir) = flowmates_dir.as_ref().filter(|dir| dir.is_dir()) {
+        let verified = checksums::verify_source(&config.rules, cache.as_ref())?;
+        if let Some(count) = verified {
+            progress!(
+                "verified {} file(s) against {}",
+                count,
+                checksums::SUMS_FILE
+            );
+        }
+        let installed = Lock::load(root)?.pack;
+        let pack = pack::check(
+            dir,
+            config.rules_pack.as_deref(),
+            installed.as_deref(),
+            allow_major,
+        )?;
+        if let Some(version) = pack {
+            progress!("rule pack {}", version);
+            report.pack = Some(version.to_string());
+        }
+    }
+    let selection = select_presets(root, &mut config)?;
+    let audit = Audit::new(RealFs);
+    let mut all = Vec::new();
+    // Verified again as rules are read, through the cache warmed above.
+    let synced = rules::sync_with(&audit, root, &config.rules, cache.as_ref(), |c| {
+        strategy.resolve(c)
+    })?;
+    if let Some(cache) = &cache {
+        save_scan_cache(cache);
+    }
+    for (target, installed) in synced {
+        for (path, outcome) in installed {
+            report_install(env, &format!("{}: ", target.name()), &path, outcome);
+            all.push((path, outcome));
+        }
+    }
+    let stamp = config
+        .rules
+        .provenance
+        .then(|| rules::Stamp::new(flowmates_dir.as_deref(), env.now()));
+    let installed = templates::install_with(
+        &audit,
+        root,
+        flowmates_dir.as_deref(),
+        selection.templates.as_deref(),
+        config.rules.preserve_metadata,
+        stamp.as_ref(),
+        |c| strategy.resolve(c),
+    )?;
+    for (path, outcome) in installed {
+        report_install(env, "", &path, outcome);
+        all.push((path, outcome));
+    }
+    if let Some(dir) = flowmates_dir.as_ref().filter(|dir| dir.is_dir()) {
+        let installed = scripts::install(&audit, root, dir, |c| strategy.resolve(c))?;
+        for (path, outcome) in installed.scripts {
+            report_install(env, "", &path, outcome);
+            all.push((path, outcome));
+        }
+        for path in installed.extra {
+            warn(
+                report,
+                format!(
+                    "{} is not a script of the flowmates checkout",
+                    path.display()
+                ),
+            );
+        }
+    }
+    report.add_installed(all);
+    report.add_changes(root, audit.into_changes());
+    if let (Some(cache_dir), Some(dir), false) = (cache_dir, flowmates_dir, offline) {
+        if dir.is_dir() {
+            if let Err(err) = source_cache::store(&cache_dir, &dir, env.now()) {
+                eprintln!("warning: {:#}", err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Bring the checkout `[source]` of `config` pins, if any, to its ref. A
+/// failed fetch is only a warning when the ref is known already.
+fn sync_pinned_source(env: &Environment, config: &Config, report: &mut Report) -> Result<()> {
+    let Some(url) = &config.source.url else {
+        return Ok(());
+    };
+    let data_dir = env
+        .data_dir()
+        .context("cannot locate echo's data directory; set $XDG_DATA_HOME")?;
+    let rev = config.source.rev.as_deref();
+    let synced = source_pin::sync(&data_dir, url, rev)?;
+    if let Some(err) = synced.fetch_error {
+        warn(
+            report,
+            format!("could not fetch {}, using it as last fetched: {}", url, err),
+        );
+    }
+    progress!(
+        "source {} at {} ({})",
+        url,
+        rev.unwrap_or("HEAD"),
+        &synced.commit[..12.min(synced.commit.len())]
+    );
+    Ok(())
+}
+
+/// `echo init --source-repo`: check the flowmates checkout at `root`, set
+/// up its issues and hooks, and register it as the canonical source.
+fn init_source_repo(env: &Environment, root: &Path, mut projects: Vec<String>) -> Result<()> {
+    validate_source(
+        env,
+        root,
+        "echo init --source-repo",
+        github::is_actions(env),
+    )?;
+    if projects.is_empty() {
+        projects.push(ops::detect_project_name(root)?);
+    }
+    for project in &projects {
+        for dir in ops::create_issue_workflow_structure(root, project)? {
+            progress!("created {}", dir.display());
+        }
+    }
+    if gitutil::is_repo(root) {
+        let available = hooks::available(Some(root))?;
+        let installed = hooks::install(root, &available, None, false, &[], hooks::LinkMode::Copy)?;
+        for (name, action, _) in installed {
+            if matches!(action, hooks::Action::Installed | hooks::Action::Updated) {
+                progress!("installed hook {}", name);
+            }
+        }
+    }
+    let config_dir = flowmates_config_dir(env)?;
+    let mut registry = registry::Registry::load(&config_dir)?;
+    if registry.set_source(root)? {
+        registry.save(&config_dir)?;
+        progress!("registered {} as the flowmates source", root.display());
+    }
+    Ok(())
+}
+
+/// Set up the flowmates checkout of a new machine: clone `url` under the
+/// data directory and register the clone as the source. Without `url`,
+/// one is asked for on a terminal when no checkout is configured and
+/// `~/.cursor/` does not exist. Returns the checkout set up, if any.
+fn first_run_setup(
+    env: &Environment,
+    config: &Config,
+    url: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    let url = match url {
+        Some(url) => url.to_string(),
+        None => {
+            let first_run = config.rules.flowmates_dir().is_none()
+                && env
+                    .home()
+                    .is_some_and(|home| !home.join(".cursor").exists());
+            if !first_run || verbosity() == Verbosity::Quiet || !interactive() {
+                return Ok(None);
+            }
+            println!("No flowmates checkout is configured on this machine.");
+            print!("URL of the flowmates repository to clone (empty to skip): ");
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            match answer.trim() {
+                "" => return Ok(None),
+                url => url.to_string(),
+            }
+        }
+    };
+    let data_dir = env
+        .data_dir()
+        .context("cannot locate echo's data directory; set $XDG_DATA_HOME")?;
+    let dir = data_dir.join("flowmates");
+    if dir.exists() {
+        match gitutil::remote_url(&dir, "origin") {
+            Some(origin) if origin == url => {
+                progress!("using the clone of {} in {}", url, dir.display())
+            }
+            origin => bail!(
+                "{} already holds {}; remove it to clone {}",
+                dir.display(),
+                origin.map_or("another checkout".to_string(), |origin| format!(
+                    "a clone of {}",
+                    origin
+                )),
+                url
+            ),
+        }
+    } else {
+        fs::create_dir_all(&data_dir)
+            .with_context(|| format!("failed to create {}", data_dir.display()))?;
+        git::clone(&url, &dir)?;
+        if !dir.join(rules::FLOWMATES_RULES_DIR).is_dir() {
+            fs::remove_dir_all(&dir)?;
+            bail!(
+                "{} has no {}/ directory; it is not a flowmates repository",
+                url,
+                rules::FLOWMATES_RULES_DIR
+            );
+        }
+        progress!("cloned {} to {}", url, dir.display());
+    }
+    let config_dir = flowmates_config_dir(env)?;
+    let mut registry = registry::Registry::load(&config_dir)?;
+    if registry.set_source(&dir)? {
+        registry.save(&config_dir)?;
+        progress!("registered {} as the flowmates source", dir.display());
+    }
+    Ok(Some(dir))
+}
+
+/// Fail unless `dir` passes [`source_repo::check`], reporting its
+/// problems under `title`.
+fn validate_source(env: &Environment, dir: &Path, title: &str, annotate: bool) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    let config = env.load_config(dir)?;
+    let vars = template::variables(dir, "project", env.now(), &config.variables);
+    let violations = source_repo::check(dir, &vars)?;
+    report_violations(env, dir, title, &violations, annotate)?;
+    if !violations.is_empty() {
+        bail!("{} problem(s) found in {}", violations.len(), dir.display());
+    }
+    progress!("{} is a valid flowmates checkout", dir.display());
+    Ok(())
+}
+
+/// [`status::status`] through the scan cache of the flowmates checkout.
+fn cached_status(env: &Environment, root: &Path, config: &Config) -> Result<status::Status> {
+    let dir = config.rules.flowmates_dir().filter(|dir| dir.is_dir());
+    let cache = dir.map(|dir| scan_cache(env, &dir));
+    let status = status::status(root, config, cache.as_ref())?;
+    if let Some(cache) = &cache {
+        save_scan_cache(cache);
+    }
+    Ok(status)
+}
+
+/// Record `warning` in `report`, printing it unless told to be quiet.
+fn warn(report: &mut Report, warning: String) {
+    if verbosity() == Verbosity::Normal {
+        eprintln!("warning: {}", warning);
+    }
+    report.warnings.push(warning);
+}
+
+/// With `strict`, fail when the run left warnings.
+fn enforce_strict(strict: bool, report: &Report) -> Result<()> {
+    if strict && !report.warnings.is_empty() {
+        bail!(
+            "{} warning(s) and --strict is set; the setup is incomplete",
+            report.warnings.len()
+        );
+    }
+    Ok(())
+}
+
+/// In GitHub Actions, add the files an install changed or kept to the step
+/// summary.
+fn summarize_install(
+    env: &Environment,
+    title: &str,
+    installed: &[report::InstalledFile],
+) -> Result<()> {
+    if !github::is_actions(env) {
+        return Ok(());
+    }
+    let rows: Vec<_> = installed
+        .iter()
+        .filter(|file| file.outcome != Outcome::UpToDate)
+        .map(|file| {
+            (
+                file.path.display().to_string(),
+                file.outcome.as_str().to_string(),
+            )
+        })
+        .collect();
+    github::append_summary(env, &github::summary_table(title, &rows))
+}
+
+/// Print `violations`, as error annotations with `annotate`, and in GitHub
+/// Actions list them in the step summary.
+fn report_violations(
+    env: &Environment,
+    root: &Path,
+    title: &str,
+    violations: &[validate::Violation],
+    annotate: bool,
+) -> Result<()> {
+    for violation in violations {
+        if !annotate {
+            eprintln!("{}", violation);
+        } else if root.join(&violation.path).is_file() {
+            let path = Some(violation.path.as_path());
+            println!(
+                "{}",
+                github::annotation(Level::Error, path, &violation.message)
+            );
+        } else {
+            println!(
+                "{}",
+                github::annotation(Level::Error, None, &violation.to_string())
+            );
+        }
+    }
+    if github::is_actions(env) {
+        let rows: Vec<_> = violations
+            .iter()
+            .map(|v| (v.path.display().to_string(), v.message.clone()))
+            .collect();
+        github::append_summary(env, &github::summary_table(title, &rows))?;
+    }
+    Ok(())
+}
+
+/// Print what installing `path` did; files already up to date are not
+/// mentioned. In GitHub Actions, files left for the user are annotated.
+fn report_install(env: &Environment, prefix: &str, path: &Path, outcome: Outcome) {
+    let level = match outcome {
+        Outcome::Modified => Some(Level::Warning),
+        Outcome::Protected => Some(Level::Notice),
+        Outcome::Conflicted => Some(Level::Error),
+        _ => None,
+    };
+    if let Some(level) = level.filter(|_| github::is_actions(env)) {
+        let message = match outcome {
+            Outcome::Modified => "kept: modified locally; use --force to overwrite",
+            Outcome::Protected => "kept: listed in .echoignore",
+            _ => "merged with conflicts; resolve the <<<<<<< markers",
+        };
+        progress!("{}", github::annotation(level, Some(path), message));
+        return;
+    }
+    match outcome {
+        Outcome::UpToDate => {}
+        Outcome::Modified => progress!(
+            "{}kept {} (modified locally; use --force to overwrite)",
+            prefix,
+            path.display()
+        ),
+        Outcome::Protected => progress!("{}kept {} (in .echoignore)", prefix, path.display()),
+        Outcome::Conflicted => progress!(
+            "{}merged {} with conflicts; resolve the <<<<<<< markers",
+            prefix,
+            path.display()
+        ),
+        _ => progress!("{}{} {}", prefix, outcome.as_str(), path.display()),
+    }
+}
+
+/// Ask on the terminal how to settle `conflict`, showing the diff on
+/// request. Merging is offered when a merge base was kept.
+fn ask_resolution(conflict: &Conflict) -> Result<Resolution> {
+    if !interactive() {
+        bail!(
+            "cannot ask how to settle conflicts non-interactively; pass --strategy theirs or ours"
+        );
+    }
+    let merge = if conflict.base.is_some() {
+        ", [m]erge"
+    } else {
+        ""
+    };
+    loop {
+        print!(
+            "{} was edited locally and its source changed: [k]eep local, [t]ake source, [d]iff{}? ",
+            conflict.path.display(),
+            merge
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            bail!("no answer for {}", conflict.path.display());
+        }
+        match answer.trim() {
+            "k" | "keep" => return Ok(Resolution::Keep),
+            "t" | "take" => return Ok(Resolution::Take),
+            "m" | "merge" if conflict.base.is_some() => return Ok(Resolution::Merge),
+            "d" | "diff" => print!("{}", conflict.diff()),
+            _ => {}
+        }
+    }
+}
+
+/// Presets `echo init` records for the repository at `root`: the detected
+/// ones with `auto`, or when confirmed on a terminal; none otherwise.
+fn suggest_presets(root: &Path, config: &Config, auto: bool) -> Result<Vec<String>> {
+    if !auto && !interactive() {
+        return Ok(Vec::new());
+    }
+    let manifest = presets::load(config.rules.flowmates_dir().as_deref())?;
+    let detected = presets::detections(root, &manifest);
+    if detected.is_empty() {
+        return Ok(Vec::new());
+    }
+    for preset in &detected {
+        progress!("detected preset {} ({})", preset.name, preset.reason());
+    }
+    let names: Vec<String> = detected.into_iter().map(|preset| preset.name).collect();
+    let accepted = auto
+        || confirm(&format!(
+            "Use preset {} with its hooks and settings?",
+            names.join(", ")
+        ))?;
+    Ok(if accepted { names } else { Vec::new() })
+}
+
+/// Resolve the presets of `config`, narrowing its rules to them, and
+/// return the selection.
+fn select_presets(root: &Path, config: &mut Config) -> Result<presets::Selection> {
+    let manifest = presets::load(config.rules.flowmates_dir().as_deref())?;
+    let names = if config.presets.is_empty() {
+        let detected = presets::detections(root, &manifest);
+        for preset in &detected {
+            progress!("using preset {} ({})", preset.name, preset.reason());
+        }
+        detected.into_iter().map(|preset| preset.name).collect()
+    } else {
+        progress!("using preset {}", config.presets.join(", "));
+        config.presets.clone()
+    };
+    let mut selection = presets::select(&manifest, &names)?;
+    config.rules.preset = std::mem::take(&mut selection.rules);
+    Ok(selection)
+}
+
+fn main() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let _ = VERBOSITY.set(cli.command.verbosity());
+    let _ = NO_CACHE.set(cli.no_cache);
+    source_scan::set_scan_ignored(cli.scan_ignored);
+    let mut env = Environment::from_process()?;
+    if let Some(profile) = &cli.profile {
+        env = env.with_var(profiles::PROFILE_VAR, profile.as_str());
+    }
+    let ci = env
+        .var("CI")
+        .is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1");
+    let _ = INTERACTIVE.set(!cli.non_interactive && !ci && io::stdin().is_terminal());
+    let mut command = Vec::new();
+    let mut sub = &matches;
+    while let Some((name, next)) = sub.subcommand() {
+        command.push(name);
+        sub = next;
+    }
+    let (cache_dir, at, started) = (env.cache_dir(), env.now(), Instant::now());
+    let root = env.repository_root(cli.root.as_deref());
+    let result = if cli.command.is_mutating() {
+        run_mutating(cli, env, root, &command.join(" "))
+    } else {
+        run(cli, env, root)
+    };
+    if let Some(cache_dir) = cache_dir.filter(|_| command.first() != Some(&"stats")) {
+        let entry = stats::Entry {
+            command: command.join(" "),
+            at,
+            duration_ms: started.elapsed().as_millis() as u64,
+            ok: result.is_ok(),
+        };
+        if let Err(err) = stats::record(&cache_dir, &entry) {
+            eprintln!("warning: {:#}", err);
+        }
+    }
+    result
+}
+
+/// Run the mutating `command` of `cli` under the lock of `root`, logging
+/// the files it changes when `audit-log` is set.
+fn run_mutating(cli: Cli, env: Environment, root: PathBuf, command: &str) -> Result<()> {
+    let _lock = lock_repo(&root, cli.wait)?;
+    let audited = env.load_config(&root).is_ok_and(|config| config.audit_log);
+    let before = audited
+        .then(|| audit_log::Snapshot::take_keeping(&root))
+        .transpose()?;
+    let (at, by) = (
+        env.now(),
+        ops::current_assignee(&root, env.var(ops::ASSIGNEE_VAR)),
+    );
+    let result = run(cli, env, root.clone());
+    if let Some(before) = before {
+        let logged = audit_log::Snapshot::take(&root).and_then(|after| {
+            let files = after.changes_since(&before);
+            before.keep(&root, &files)?;
+            if !files.is_empty() {
+                let entry = audit_log::Entry {
+                    at,
+                    command: command.to_string(),
+                    by,
+                    ok: result.is_ok(),
+                    files,
+                };
+                audit_log::append(&root, &entry)?;
+            }
+            audit_log::prune(&root).map(|_| ())
+        });
+        if let Err(err) = logged {
+            eprintln!("warning: {:#}", err);
+        }
+    }
+    result
+}
+
+/// Run the command of `cli` in the repository at `root`.
+fn run(cli: Cli, env: Environment, root: PathBuf) -> Result<()> {
+    match cli.command {
+        Command::Init {
+            mut projects,
+            workspace,
+            mut presets,
+            auto_preset,
+            vendor,
+            link,
+            source_repo,
+            source_url,
+            exclude_strategy,
+            force,
+            strategy,
+            no_run_hooks,
+            report: report_path,
+            strict,
+            offline,
+            ..
+        } => {
+            if source_repo {
+                return init_source_repo(&env, &root, projects);
+            }
+            let mut report = Report::new(report::Command::Init);
+            let mut config = env.load_config(&root)?;
+            if !offline {
+                sync_pinned_source(&env, &config, &mut report)?;
+            }
+            if let Some(dir) = first_run_setup(&env, &config, source_url.as_deref())? {
+                config.rules.flowmates_dir = Some(dir);
+            }
+            if presets.is_empty() && config.presets.is_empty() {
+                presets = suggest_presets(&root, &config, auto_preset)?;
+            }
+            let manifest = if presets.is_empty() {
+                None
+            } else {
+                let manifest = presets::load(config.rules.flowmates_dir().as_deref())?;
+                presets::select(&manifest, &presets)?;
+                Some(manifest)
+            };
+            if workspace {
+                let Some(found) = workspace::detect(&root, &config.workspace)? else {
+                    bail!("no workspace found; list packages under [workspace] in .echo.toml");
+                };
+                progress!(
+                    "workspace: {}, {} package(s)",
+                    found.kind.as_str(),
+                    found.packages.len()
+                );
+                for package in found.packages {
+                    progress!("  {:<20} {}", package.name, package.path.display());
+                    if !projects.contains(&package.name) {
+                        projects.push(package.name);
+                    }
+                }
+            }
+            if projects.is_empty() {
+                projects.push(ops::detect_project_name(&root)?);
+            }
+            for project in &projects {
+                let created = ops::create_issue_workflow_structure(&root, project)?;
+                if created.is_empty() {
+                    progress!("{}: already initialized", project);
+                }
+                for dir in created {
+                    progress!("created {}", dir.display());
+                }
+            }
+            let mut preset_hooks = Vec::new();
+            if let Some(manifest) = &manifest {
+                presets::record(&root, &presets)?;
+                if presets::apply_settings(&root, manifest, &presets)? {
+                    progress!("applied the settings of preset {}", presets.join(", "));
+                    config = env.load_config(&root)?;
+                }
+                preset_hooks = presets::hooks(manifest, &presets);
+                config.presets = presets;
+            }
+            if vendor && !config.vendor {
+                config::edit(&root, |doc| {
+                    doc.insert("vendor", toml_edit::value(true));
+                    Ok(())
+                })?;
+                config.vendor = true;
+            }
+            if link && !config.rules.link {
+                config::edit(&root, |doc| {
+                    doc.entry("rules")
+                        .or_insert_with(toml_edit::table)
+                        .as_table_like_mut()
+                        .context("[rules] is not a table")?
+                        .insert("link", toml_edit::value(true));
+                    Ok(())
+                })?;
+                config.rules.link = true;
+            }
+            if let Some(strategy) = exclude_strategy.map(ExcludeStrategy::from) {
+                if strategy != config.exclude_strategy {
+                    config::edit(&root, |doc| {
+                        doc.insert("exclude-strategy", toml_edit::value(strategy.name()));
+                        Ok(())
+                    })?;
+                    config.exclude_strategy = strategy;
+                }
+            }
+            let vars = template::variables(&root, &projects[0], env.now(), &config.variables);
+            let template = agent::load_template(config.rules.flowmates_dir().as_deref())?;
+            let update = agent::create_agent_md(&root, &template, &vars, force)?;
+            report.agent.push(report::AgentFile {
+                path: agent::AGENT_MD.into(),
+                update,
+            });
+            report_agent_update(agent::Target::Agent, update)?;
+            let post_init = config.post_init.clone();
+            let plugins = config.plugins.clone();
+            let available_hooks = if preset_hooks.is_empty() {
+                Vec::new()
+            } else {
+                hooks::available(config.rules.flowmates_dir().as_deref())?
+            };
+            install_presets(
+                &env,
+                &root,
+                config,
+                StrategyArg::or_default(strategy, force),
+                offline,
+                true,
+                &mut report,
+            )?;
+            summarize_install(&env, "echo init", &report.files)?;
+            for name in &preset_hooks {
+                let installed = hooks::install(
+                    &root,
+                    &available_hooks,
+                    Some(name),
+                    false,
+                    &[],
+                    hooks::LinkMode::default(),
+                )?;
+                if installed
+                    .iter()
+                    .any(|(_, action, _)| *action == hooks::Action::Installed)
+                {
+                    progress!("installed hook {}", name);
+                }
+            }
+            warn_stale_cursor_sync(&env)?;
+            let request = plugins::InitRequest {
+                protocol: plugins::PROTOCOL_VERSION,
+                root: root.clone(),
+                projects,
+                force,
+            };
+            for (name, response) in plugins::init(&env, &root, &plugins, &request)? {
+                for file in &response.files {
+                    progress!("{}: installed {}", name, file.display());
+                }
+                for message in &response.messages {
+                    progress!("{}: {}", name, message);
+                }
+            }
+            let ran = enforce_strict(strict, &report).and_then(|()| {
+                if no_run_hooks {
+                    return Ok(());
+                }
+                run_post_commands(&root, "init", &post_init, &mut report)
+            });
+            print_summary("", &report);
+            if let Some(path) = report_path {
+                report.write(&path)?;
+            }
+            ran?;
+        }
+        Command::Update {
+            project,
+            all: false,
+            strategy,
+            no_run_hooks,
+            report: report_path,
+            strict,
+            offline,
+            allow_major,
+            ..
+        } => {
+            let mut report = Report::new(report::Command::Update);
+            let options = UpdateOptions {
+                strategy: StrategyArg::or_default(strategy, false),
+                run_hooks: !no_run_hooks,
+                strict,
+                offline,
+                allow_major,
+            };
+            let updated = update(&env, &root, project.as_deref(), options, &mut report);
+            print_summary("", &report);
+            if let Some(path) = report_path {
+                report.write(&path)?;
+            }
+            updated?;
+        }
+        Command::Update {
+            all: true,
+            strategy,
+            no_run_hooks,
+            strict,
+            offline,
+            allow_major,
+            ..
+        } => {
+            let options = UpdateOptions {
+                strategy: StrategyArg::or_default(strategy, false),
+                run_hooks: !no_run_hooks,
+                strict,
+                offline,
+                allow_major,
+            };
+            let mut failed = 0;
+            for repo in registered_projects(&env)? {
+                progress!("{}:", repo.display());
+                let updated = lock_repo(&repo, cli.wait).and_then(|_lock| {
+                    let mut report = Report::new(report::Command::Update);
+                    let updated = update(&env, &repo, None, options, &mut report);
+                    print_summary(&format!("{}: ", repo.display()), &report);
+                    updated
+                });
+                if let Err(err) = updated {
+                    eprintln!("error: {:#}", err);
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                bail!("{} repositor(ies) failed to update", failed);
+            }
+        }
+        Command::Status { all: true } => {
+            println!(
+                "{:<40} {:>5} {:>7} {:>8}",
+                "REPOSITORY", "RULES", "DRIFTED", "WARNINGS"
+            );
+            for repo in registered_projects(&env)? {
+                if !repo.is_dir() {
+                    println!("{:<40} missing", repo.display());
+                    continue;
+                }
+                let summary = (|| -> Result<(usize, usize, usize)> {
+                    let config = env.load_config(&repo)?;
+                    let status = cached_status(&env, &repo, &config)?;
+                    let drifted = rules::diff(&repo, &config.rules, None)?.len();
+                    Ok((status.rules, drifted, status.warnings.len()))
+                })();
+                match summary {
+                    Ok((rules, drifted, warnings)) => println!(
+                        "{:<40} {:>5} {:>7} {:>8}",
+                        repo.display(),
+                        rules,
+                        drifted,
+                        warnings
+                    ),
+                    Err(err) => println!("{:<40} error: {:#}", repo.display(), err),
+                }
+            }
+        }
+        Command::Projects { command } => {
+            let config_dir = flowmates_config_dir(&env)?;
+            let mut registry = registry::Registry::load(&config_dir)?;
+            match command {
+                ProjectsCommand::Add { path } => {
+                    let path = path.unwrap_or_else(|| root.clone());
+                    if registry.add(&path)? {
+                        registry.save(&config_dir)?;
+                        println!("registered {}", path.display());
+                    } else {
+                        println!("{} is already registered", path.display());
+                    }
+                }
+                ProjectsCommand::Remove { path } => {
+                    let path = path.unwrap_or_else(|| root.clone());
+                    if !registry.remove(&path) {
+                        bail!("{} is not registered", path.display());
+                    }
+                    registry.save(&config_dir)?;
+                    println!("unregistered {}", path.display());
+                }
+                ProjectsCommand::List => {
+                    for repo in registry.projects {
+                        if repo.is_dir() {
+                            println!("{}", repo.display());
+                        } else {
+                            println!("{} (missing)", repo.display());
+                        }
+                    }
+                }
+            }
+        }
+        Command::Status { all: false } => {
+            let status = cached_status(&env, &root, &env.load_config(&root)?)?;
+            println!(
+                "mode:     {}",
+                match (status.vendor, status.exclude_strategy) {
+                    (true, _) => "vendor (.cursor/ committed)",
+                    (false, ExcludeStrategy::Gitignore) => {
+                        "installed per checkout (.cursor/ in .gitignore)"
+                    }
+                    (false, ExcludeStrategy::InfoExclude) => {
+                        "installed per checkout (.cursor/ in .git/info/exclude)"
+                    }
+                    (false, ExcludeStrategy::None) =>
+                        "installed per checkout (not ignored by echo)",
+                }
+            );
+            println!(
+                "rules:    {} installed, {} disabled",
+                status.rules, status.disabled_rules
+            );
+            println!("lockfile: {} file(s)", status.locked_files);
+            if let Some(profile) = env.var(profiles::PROFILE_VAR) {
+                println!("profile:  {}", profile);
+            }
+            for warning in &status.warnings {
+                eprintln!("warning: {}", warning);
+            }
+        }
+        Command::Audit {
+            command: AuditCommand::Show { since, path },
+        } => {
+            let since = match since {
+                Some(date) => environment::parse_date(&date)
+                    .with_context(|| format!("invalid date '{}', expected YYYY-MM-DD", date))?,
+                None => 0,
+            };
+            let mut shown = 0;
+            for entry in audit_log::load(&root)? {
+                let files: Vec<_> = entry
+                    .files
+                    .iter()
+                    .filter(|touched| {
+                        path.as_ref()
+                            .is_none_or(|path| touched.path.starts_with(path))
+                    })
+                    .collect();
+                if entry.at < since || files.is_empty() {
+                    continue;
+                }
+                shown += 1;
+                let by = entry
+                    .by
+                    .as_ref()
+                    .map_or(String::new(), |by| format!(" by {}", by));
+                let failed = if entry.ok { "" } else { " (failed)" };
+                println!(
+                    "{}  {}{}{}",
+                    template::format_time(entry.at),
+                    entry.command,
+                    by,
+                    failed
+                );
+                let short = |hash: &Option<String>| {
+                    hash.as_deref()
+                        .map_or("-".to_string(), |hash| hash.chars().take(12).collect())
+                };
+                for touched in files {
+                    println!(
+                        "  {} {}  {} -> {}",
+                        touched.mark(),
+                        touched.path.display(),
+                        short(&touched.before),
+                        short(&touched.after)
+                    );
+                }
+            }
+            if shown == 0 {
+                if env.load_config(&root)?.audit_log {
+                    println!("no logged change");
+                } else {
+                    println!(
+                        "nothing logged; set `audit-log = true` in {}",
+                        config::CONFIG_FILE
+                    );
+                }
+            }
+        }
+        Command::Undo { list, force } => {
+            let entries = audit_log::load(&root)?;
+            let revertible = audit_log::revertible(&entries);
+            if list {
+                if revertible.is_empty() {
+                    println!("nothing to undo");
+                }
+                for entry in revertible {
+                    println!(
+                        "{}  {}: {} file(s)",
+                        template::format_time(entry.at),
+                        entry.command,
+                        entry.files.len()
+                    );
+                }
+                return Ok(());
+            }
+            let Some(entry) = revertible.first() else {
+                if env.load_config(&root)?.audit_log {
+                    bail!("nothing to undo");
+                }
+                bail!(
+                    "nothing to undo; set `audit-log = true` in {} to record commands",
+                    config::CONFIG_FILE
+                );
+            };
+            audit_log::revert(&root, entry, force)?;
+            for touched in &entry.files {
+                let action = match touched.mark() {
+                    'A' => "removed",
+                    'D' => "restored",
+                    _ => "reverted",
+                };
+                progress!("{} {}", action, touched.path.display());
+            }
+            println!(
+                "undid `{}` of {}",
+                entry.command,
+                template::format_time(entry.at)
+            );
+        }
+        Command::Stats { command } => {
+            let cache_dir = env
+                .cache_dir()
+                .context("cannot locate echo's cache directory; set $XDG_CACHE_HOME")?;
+            match command {
+                Some(StatsCommand::Enable) => {
+                    stats::enable(&cache_dir)?;
+                    println!(
+                        "recording usage statistics to {}",
+                        cache_dir.join(stats::STATS_FILE).display()
+                    );
+                }
+                Some(StatsCommand::Disable) => {
+                    if stats::disable(&cache_dir)? {
+                        println!("stopped recording and deleted the usage statistics");
+                    } else {
+                        println!("usage statistics are not recorded");
+                    }
+                }
+                None => {
+                    if !stats::enabled(&cache_dir) {
+                        println!("usage statistics are not recorded; run `echo stats enable`");
+                        return Ok(());
+                    }
+                    let entries = stats::load(&cache_dir)?;
+                    let Some(first) = entries.first() else {
+                        println!("no invocation recorded yet");
+                        return Ok(());
+                    };
+                    println!(
+                        "{} run(s) since {}",
+                        entries.len(),
+                        environment::date(first.at)
+                    );
+                    println!(
+                        "{:<24} {:>6} {:>7} {:>10}",
+                        "command", "runs", "failed", "avg time"
+                    );
+                    for summary in stats::summarize(&entries) {
+                        println!(
+                            "{:<24} {:>6} {:>7} {:>8}ms",
+                            summary.command,
+                            summary.runs,
+                            summary.failures,
+                            summary.average_ms()
+                        );
+                    }
+                }
+            }
+        }
+        Command::Daemon { command, interval } => {
+            let config_dir = flowmates_config_dir(&env)?;
+            match command {
+                None => {
+                    println!(
+                        "syncing the repositories of {} every {}s (Ctrl-C to stop)",
+                        config_dir.join(registry::PROJECTS_FILE).display(),
+                        interval
+                    );
+                    daemon::run(&env, &config_dir, interval, |line| println!("{}", line))?;
+                }
+                Some(DaemonCommand::Status) => {
+                    let state = daemon::State::load(&config_dir)?;
+                    match (state.is_running(env.now()), state.last_round) {
+                        (true, Some(last)) => println!(
+                            "daemon:   running (pid {}), last round {}",
+                            state.pid.unwrap_or_default(),
+                            template::format_time(last)
+                        ),
+                        (false, Some(last)) => println!(
+                            "daemon:   not running, last round {}",
+                            template::format_time(last)
+                        ),
+                        (_, None) => println!("daemon:   never ran"),
+                    }
+                    for root in registry::Registry::load(&config_dir)?.projects {
+                        let repo = state.repos.get(&root.display().to_string());
+                        let synced = repo
+                            .and_then(|r| r.last_sync)
+                            .map_or("never synced".to_string(), template::format_time);
+                        match repo.and_then(|r| r.error.as_deref()) {
+                            Some(error) => {
+                                println!("{}  {}  failed: {}", root.display(), synced, error)
+                            }
+                            None => println!("{}  {}", root.display(), synced),
+                        }
+                    }
+                }
+            }
+        }
+        Command::Mcp {
+            command: McpCommand::Serve,
+        } => mcp::serve(&env, &root, io::stdin().lock(), io::stdout().lock())?,
+        Command::Watch { global } => {
+            let mut config = env.load_config(&root)?;
+            let Some(flowmates_dir) = config.rules.flowmates_dir() else {
+                bail!("no flowmates checkout configured (set [rules] flowmates-dir or $FLOWMATES_DIR)");
+            };
+            let global = if global {
+                Some(watch::Global {
+                    home: env
+                        .home()
+                        .context("cannot locate the home directory; set $HOME")?,
+                    config_dir: flowmates_config_dir(&env)?,
+                })
+            } else {
+                None
+            };
+            let selection = select_presets(&root, &mut config)?;
+            println!("watching {} (Ctrl-C to stop)", flowmates_dir.display());
+            watch::watch(
+                &root,
+                &flowmates_dir,
+                &config.rules,
+                selection.templates.as_deref(),
+                global.as_ref(),
+                |line| println!("{}", line),
+            )?;
+        }
+        Command::Uninstall => {
+            let report = uninstall::uninstall(&root)?;
+            for path in &report.removed {
+                println!("removed {}", path.display());
+            }
+            for path in &report.modified {
+                println!("kept {} (modified)", path.display());
+            }
+            for path in &report.protected {
+                println!("kept {} (in .echoignore)", path.display());
+            }
+            for file in &report.ignore_files {
+                println!("removed the echo block from {}", file);
+            }
+            for name in &report.hooks {
+                println!("removed the {} hook", name);
+            }
+        }
+        Command::Prune { yes, .. } => {
+            let config = env.load_config(&root)?;
+            let report = prune::prune(&root, &config.rules, yes)?;
+            let verb = if yes { "removed" } else { "would remove" };
+            for path in &report.removed {
+                println!("{} {}", verb, path.display());
+            }
+            for path in &report.modified {
+                println!("kept {} (modified)", path.display());
+            }
+            for path in &report.protected {
+                println!("kept {} (in .echoignore)", path.display());
+            }
+            if !yes && !report.removed.is_empty() {
+                println!("run `echo prune --yes` to remove them");
+            }
+        }
+        Command::Session { command } => match command {
+            SessionCommand::Start { name } => {
+                let changes = session::start(&root, &name, env.now())?;
+                if !changes.is_empty() {
+                    println!("since session {} was parked:", name);
+                    for change in &changes {
+                        println!("  {}", change);
+                    }
+                }
+                println!("started session {}", name);
+            }
+            SessionCommand::End { name } => {
+                let changes = session::end(&root, &name, env.now())?;
+                for change in &changes {
+                    println!("  {}", change);
+                }
+                println!("parked session {}", name);
+            }
+            SessionCommand::List => {
+                for session in session::list(&root)? {
+                    let (status, snapshot) = match &session.ended {
+                        None => ("active", &session.started),
+                        Some(ended) => ("parked", ended),
+                    };
+                    println!(
+                        "{:<16} {:<7} {} {}",
+                        session.name,
+                        status,
+                        session::date(snapshot),
+                        snapshot.branch.as_deref().unwrap_or("-")
+                    );
+                    for issue in session.issues_in_progress() {
+                        println!("  {}", issue.display());
+                    }
+                }
+            }
+        },
+        Command::Context {
+            command:
+                ContextCommand::Build {
+                    max_tokens,
+                    include,
+                    output,
+                },
+        } => {
+            let config = env.load_config(&root)?;
+            let sections: Vec<context::Section> = if !include.is_empty() {
+                include.into_iter().map(Into::into).collect()
+            } else if !config.context.include.is_empty() {
+                config.context.include.clone()
+            } else {
+                context::Section::ALL.to_vec()
+            };
+            let max_tokens = max_tokens
+                .or(config.context.max_tokens)
+                .unwrap_or(context::DEFAULT_MAX_TOKENS);
+            let built =
+                context::build(&root, &config.rules, &config.context, &sections, max_tokens)?;
+            let path = context::output_path(&root, output.as_deref());
+            context::write(&path, &built)?;
+            for title in &built.truncated {
+                eprintln!("truncated {}", title);
+            }
+            for title in &built.omitted {
+                eprintln!("left out {}", title);
+            }
+            println!(
+                "wrote {} (~{} of {} tokens)",
+                path.strip_prefix(&root).unwrap_or(&path).display(),
+                built.tokens,
+                max_tokens
+            );
+        }
+        Command::Pack {
+            command: PackCommand::Publish { registry, output },
+        } => {
+            let config = env.load_config(&root)?;
+            let flowmates_dir = config
+                .rules
+                .flowmates_dir()
+                .filter(|dir| dir.is_dir())
+                .context("no flowmates checkout; set [rules] flowmates-dir or $FLOWMATES_DIR")?;
+            let out_dir = match &output {
+                Some(dir) => dir.clone(),
+                None => std::env::temp_dir().join(format!("echo-pack-{}", std::process::id())),
+            };
+            fs::create_dir_all(&out_dir)
+                .with_context(|| format!("failed to create {}", out_dir.display()))?;
+            let archive = pack::publish::archive(&flowmates_dir, &out_dir)?;
+            let uploaded = pack::publish::upload(&env, &archive, &registry);
+            if output.is_none() {
+                fs::remove_dir_all(&out_dir).ok();
+            }
+            println!("published {}", uploaded?);
+        }
+        Command::SyncCursor { check, prune } => {
+            let config = env.load_config(&root)?;
+            let flowmates_dir = config
+                .rules
+                .flowmates_dir()
+                .filter(|dir| dir.is_dir())
+                .context("no flowmates checkout; set [rules] flowmates-dir or $FLOWMATES_DIR")?;
+            let home = env
+                .home()
+                .context("cannot locate the home directory; set $HOME")?;
+            let config_dir = flowmates_config_dir(&env)?;
+            let mut state = cursor_sync::State::load(&config_dir)?;
+            if check {
+                let drift = cursor_sync::check(&home, &flowmates_dir, &state)?;
+                for (path, drift) in &drift {
+                    println!("~/{}: {}", path.display(), drift.as_str());
+                }
+                if let Some(warning) = state.staleness(env.now()) {
+                    eprintln!("warning: {}", warning);
+                }
+                if !drift.is_empty() {
+                    bail!(
+                        "{} global rule(s) differ from {}",
+                        drift.len(),
+                        flowmates_dir.display()
+                    );
+                }
+                println!(
+                    "~/.cursor/rules is in step with {}",
+                    flowmates_dir.display()
+                );
+            } else {
+                let synced =
+                    cursor_sync::sync(&home, &flowmates_dir, &mut state, prune, env.now())?;
+                state.save(&config_dir)?;
+                for (path, outcome) in &synced.installed {
+                    match outcome {
+                        Outcome::UpToDate => {}
+                        Outcome::Modified => println!("kept ~/{} (modified)", path.display()),
+                        _ => println!("{} ~/{}", outcome.as_str(), path.display()),
+                    }
+                }
+                for path in &synced.removed {
+                    println!("removed ~/{}", path.display());
+                }
+                for path in &synced.kept {
+                    println!("kept ~/{} (modified, deleted upstream)", path.display());
+                }
+            }
+        }
+        Command::Plugin(args) => {
+            let config = env.load_config(&root)?;
+            let (name, args) = args.split_first().context("missing plugin name")?;
+            let status = plugins::exec(&env, &root, &config.plugins, name, args)?;
+            if !status.success() {
+                std::process::exit(status.code().unwrap_or(1));
+            }
+        }
+        Command::Check {
+            ci,
+            report: report_path,
+        } => {
+            let config = env.load_config(&root)?;
+            let vars = agent_variables(&env, &root, None)?;
+            let violations = check::check(&root, &config, &vars)?;
+            let warnings = check::warnings(&root, &config)?;
+            let annotate = ci || github::is_actions(&env);
+            report_violations(&env, &root, "echo check", &violations, annotate)?;
+            for warning in &warnings {
+                if annotate {
+                    println!("{}", github::annotation(Level::Warning, None, warning));
+                } else {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            if let Some(path) = report_path {
+                let mut report = Report::new(report::Command::Check);
+                report.problems = violations.clone();
+                report.warnings = warnings;
+                report.write(&path)?;
+            }
+            if !violations.is_empty() {
+                bail!("{} problem(s) found", violations.len());
+            }
+            println!("everything matches its source");
+        }
+        Command::Hooks { command } => {
+            let config = env.load_config(&root)?;
+            let available = hooks::available(config.rules.flowmates_dir().as_deref())?;
+            match command {
+                HooksCommand::Install {
+                    hook,
+                    force,
+                    skip,
+                    link,
+                } => {
+                    for name in &skip {
+                        hooks::check_name(name)?;
+                    }
+                    let link = hooks::LinkMode::from(link);
+                    let report =
+                        hooks::install(&root, &available, hook.as_deref(), force, &skip, link)?;
+                    for (name, action, placed) in report {
+                        if placed.is_some_and(|placed| placed != link) {
+                            eprintln!(
+                                "warning: could not {} the {} script; copied it instead",
+                                link.name(),
+                                name
+                            );
+                        }
+                        match action {
+                            hooks::Action::Installed => println!("installed {}", name),
+                            hooks::Action::Updated => println!("updated {}", name),
+                            hooks::Action::Unchanged => println!("up to date: {}", name),
+                            hooks::Action::Skipped => println!("skipped {}", name),
+                            hooks::Action::Protected => {
+                                println!("kept {} (in .echoignore)", name)
+                            }
+                            hooks::Action::MadeExecutable => {
+                                println!("up to date: {} (made executable)", name)
+                            }
+                            hooks::Action::Chained(tool) => println!(
+                                "installed {}; moved the existing {}hook to {}.d/",
+                                name,
+                                tool.map(|t| format!("{} ", t)).unwrap_or_default(),
+                                name
+                            ),
+                        }
+                    }
+                }
+                HooksCommand::Uninstall { hook } => {
+                    for name in hooks::uninstall(&root, hook.as_deref())? {
+                        println!("removed {}", name);
+                    }
+                }
+                HooksCommand::List => {
+                    for hook in &available {
+                        println!("{:<14} {:<40} {}", hook.name, hook.source, hook.description);
+                    }
+                }
+                HooksCommand::Run { hook, args } => {
+                    let report = hooks::runner::run(&env, &root, &config, &hook, &args)?;
+                    for warning in &report.warnings {
+                        eprintln!("warning: {}", warning);
+                    }
+                    for error in &report.errors {
+                        eprintln!("error: {}", error);
+                    }
+                    if !report.errors.is_empty() {
+                        bail!(
+                            "{} check(s) failed; in an emergency, bypass with --no-verify",
+                            report.errors.len()
+                        );
+                    }
+                }
+                HooksCommand::Status { hook } => {
+                    if let Some(name) = &hook {
+                        hooks::check_name(name)?;
+                    }
+                    for status in hooks::status(&root, &available, hook.as_deref())? {
+                        println!(
+                            "{:<14} {:<10} {}",
+                            status.name,
+                            status.state.as_str(),
+                            if status.available {
+                                "available"
+                            } else {
+                                "not in manifest"
+                            }
+                        );
+                    }
+                }
+                HooksCommand::Verify { hook } => {
+                    if let Some(name) = &hook {
+                        hooks::check_name(name)?;
+                    }
+                    let report = hooks::verify::verify(&root, hook.as_deref())?;
+                    for hook in &report {
+                        let mut notes = Vec::new();
+                        if let hooks::verify::Integrity::Modified(reason) = hook.integrity {
+                            notes.push(reason.to_string());
+                        }
+                        if let Some(provenance) = &hook.provenance {
+                            notes.push(format!("from {}", provenance.source));
+                        }
+                        if notes.is_empty() {
+                            println!("{:<14} {}", hook.name, hook.integrity.as_str());
+                        } else {
+                            println!(
+                                "{:<14} {} ({})",
+                                hook.name,
+                                hook.integrity.as_str(),
+                                notes.join(", ")
+                            );
+                        }
+                    }
+                    let problems = report.iter().filter(|h| h.is_problem()).count();
+                    if problems > 0 {
+                        bail!(
+                            "{} hook(s) changed since echo installed them; reinstall with `echo hooks install --force`",
+                            problems
+                        );
+                    }
+                }
+            }
+        }
+        Command::Agent { command } => match command {
+            AgentCommand::Spawn {
+                issue,
+                project,
+                agent,
+                path,
+                steal,
+            } => {
+                let project = issues::resolve_project(&root, project.as_deref())?;
+                let id = issue.strip_suffix(".md").unwrap_or(&issue);
+                let path = match path {
+                    Some(path) => path,
+                    None => worktree::default_path(&root, id)?,
+                };
+                let config = env.load_config(&root)?;
+                let by = agent
+                    .clone()
+                    .or_else(|| ops::current_assignee(&root, env.var(ops::ASSIGNEE_VAR)));
+                let claim = ops::Claim {
+                    by: by.as_deref(),
+                    steal,
+                };
+                let spawned = worktree::spawn(
+                    &root,
+                    &config.rules,
+                    &project,
+                    id,
+                    agent.as_deref(),
+                    claim,
+                    &path,
+                )?;
+                for warning in &spawned.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                println!("moved to {}", spawned.issue.display());
+                println!(
+                    "created {} on branch {}",
+                    spawned.worktree.display(),
+                    spawned.branch
+                );
+                let title = issues::read_issue_at(&root, &spawned.issue)?.title;
+                notify_transition(
+                    &config.notifications,
+                    &notify::Transition {
+                        issue: spawned.issue,
+                        title,
+                        from: spawned.from,
+                        to: State::InProgress,
+                        by,
+                    },
+                );
+            }
+            AgentCommand::Reap => {
+                for reaped in worktree::reap(&root)? {
+                    match reaped {
+                        worktree::Reaped::Removed(path) => println!("removed {}", path.display()),
+                        worktree::Reaped::Dirty(path) => {
+                            eprintln!("kept {} (uncommitted changes)", path.display())
+                        }
+                    }
+                }
+            }
+            AgentCommand::Generate { targets, project } => {
+                let vars = agent_variables(&env, &root, project.as_deref())?;
+                let config = env.load_config(&root)?;
+                let template = agent::load_template(config.rules.flowmates_dir().as_deref())?;
+                for target in targets {
+                    let target = target.into();
+                    report_agent_update(target, agent::update(&root, target, &template, &vars)?)?;
+                }
+            }
+            AgentCommand::Regen {
+                target,
+                yes,
+                project,
+                template_vars,
+            } => {
+                let target: agent::Target = target.into();
+                let mut vars = agent_variables(&env, &root, project.as_deref())?;
+                let config = env.load_config(&root)?;
+                let agent_template = agent::load_template(config.rules.flowmates_dir().as_deref())?;
+                complete_variables(&root, &mut vars, &template_vars, |vars| {
+                    template::undefined_variables(&agent_template, vars)
+                })?;
+                let plan = agent::plan_update(&root, target, &agent_template, &vars)?;
+                if plan.update == agent::Update::Unchanged {
+                    println!("{} is up to date", target.path());
+                    return Ok(());
+                }
+                print!("{}", plan.diff());
+                if !yes && !confirm(&format!("Write {}?", target.path()))? {
+                    println!("aborted, {} left unchanged", target.path());
+                    return Ok(());
+                }
+                plan.write(&root)?;
+                report_agent_update(target, plan.update)?;
+            }
+        },
+        Command::Project { command } => match command {
+            ProjectCommand::Rename { old, new } => {
+                let rewritten = ops::rename_project(&root, &old, &new)?;
+                println!("renamed issues/{}/ to issues/{}/", old, new);
+                for path in rewritten {
+                    println!("updated {}", path.display());
+                }
+            }
+        },
+        Command::Rules { command } => match command {
+            RulesCommand::List { json } => {
+                let installed = rules::list(&root, &env.load_config(&root)?.rules)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&installed)?);
+                    return Ok(());
+                }
+                for rule in installed {
+                    let modified = match rule.modified {
+                        Some(true) => "modified",
+                        Some(false) => "same",
+                        None => "-",
+                    };
+                    println!(
+                        "{:<20} {:<10} {:<8} {:<9} {}  {}{}",
+                        rule.name,
+                        rule.source.name(),
+                        if rule.disabled { "disabled" } else { "enabled" },
+                        modified,
+                        &rule.hash[..12],
+                        rule.description.as_deref().unwrap_or("-"),
+                        if rule.globs.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" [{}]", rule.globs.join(", "))
+                        }
+                    );
+                }
+            }
+            RulesCommand::Disable { name } => {
+                rules::disable(&root, &env.load_config(&root)?.rules, &name)?;
+                println!("disabled {}", name);
+            }
+            RulesCommand::Enable { name } => {
+                rules::enable(&root, &env.load_config(&root)?.rules, &name)?;
+                println!("enabled {}", name);
+            }
+            RulesCommand::Install { bundles } => {
+                let mut config = env.load_config(&root)?;
+                let rules = rules::bundles::add(&root, &config.rules, &bundles)?;
+                progress!(
+                    "bundle {}: {}",
+                    bundles.join(", "),
+                    rules.into_iter().collect::<Vec<_>>().join(", ")
+                );
+                config.rules.bundles = env.load_config(&root)?.rules.bundles;
+                select_presets(&root, &mut config)?;
+                for (target, installed) in rules::sync(&root, &config.rules, false)? {
+                    for (path, outcome) in installed {
+                        report_install(&env, &format!("{}: ", target.name()), &path, outcome);
+                    }
+                }
+            }
+            RulesCommand::Lint => {
+                let violations = rules::lint(&root, &env.load_config(&root)?.rules)?;
+                for violation in &violations {
+                    eprintln!("{}", violation);
+                }
+                if !violations.is_empty() {
+                    bail!("{} rule problem(s) found", violations.len());
+                }
+                println!("rules clean");
+            }
+            RulesCommand::New {
+                name,
+                globs,
+                description,
+                always_apply,
+                upstream,
+            } => {
+                let config = env.load_config(&root)?;
+                let front_matter = rules::mdc::FrontMatter {
+                    description: Some(description),
+                    globs,
+                    always_apply: Some(always_apply),
+                    unknown: Vec::new(),
+                };
+                let path = rules::create(&root, &config.rules, &name, &front_matter, upstream)?;
+                println!("created {}", path.display());
+            }
+            RulesCommand::MigrateCursorrules => {
+                let migration = rules::migrate::migrate(&root)?;
+                for path in &migration.rules {
+                    println!("created {}", path.display());
+                }
+                println!(
+                    "moved {} to {}",
+                    rules::migrate::CURSORRULES,
+                    migration.moved_to.display()
+                );
+            }
+            RulesCommand::Diff { name } => {
+                let config = env.load_config(&root)?;
+                for (_, patch) in rules::diff(&root, &config.rules, name.as_deref())? {
+                    print!("{}", patch);
+                }
+            }
+            RulesCommand::Contribute { name, push } => {
+                let config = env.load_config(&root)?;
+                let contribution = rules::upstream::contribute(&root, &config.rules, &name, push)?;
+                println!(
+                    "committed {} to branch {} of {}",
+                    contribution.path.display(),
+                    contribution.branch,
+                    contribution.repo.display()
+                );
+                if !contribution.pushed {
+                    println!(
+                        "push it with: git -C {} push -u origin {}",
+                        contribution.repo.display(),
+                        contribution.branch
+                    );
+                }
+                if let Some(url) = contribution.url {
+                    println!("open a pull request: {}", url);
+                }
+            }
+            RulesCommand::Export { targets } => {
+                let config = env.load_config(&root)?;
+                for target in targets {
+                    let target: rules::export::Target = target.into();
+                    let message = match rules::export::export(&root, &config.rules, target)? {
+                        agent::Update::Unchanged => "rules already current in",
+                        agent::Update::Created => "exported rules to",
+                        _ => "updated rules in",
+                    };
+                    println!("{} {}", message, target.path());
+                }
+            }
+            RulesCommand::CheckConflicts { json } => {
+                let conflicts = rules::check_conflicts(&root)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&conflicts)?);
+                } else {
+                    for conflict in &conflicts {
+                        eprintln!("{}", conflict);
+                    }
+                }
+                if !conflicts.is_empty() {
+                    bail!("{} possible rule conflict(s) found", conflicts.len());
+                }
+                if !json {
+                    println!("no rule conflicts");
+                }
+            }
+            RulesCommand::Outdated { json } => {
+                let config = env.load_config(&root)?;
+                let outdated = rules::outdated::outdated(&root, &config.rules)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&outdated)?);
+                    return Ok(());
+                }
+                for rule in &outdated {
+                    let note = if rule.modified {
+                        ", modified locally"
+                    } else {
+                        ""
+                    };
+                    println!("{} ({}{})", rule.path.display(), rule.source.name(), note);
+                    for commit in &rule.commits {
+                        println!("  {}", commit);
+                    }
+                }
+                if outdated.is_empty() {
+                    println!("every installed rule matches its source");
+                } else {
+                    println!("run `echo update` to apply the changes");
+                }
+            }
+        },
+        Command::Preset { command } => match command {
+            PresetCommand::List => {
+                let config = env.load_config(&root)?;
+                let manifest = presets::load(config.rules.flowmates_dir().as_deref())?;
+                let detected = presets::detect(&root, &manifest);
+                for (name, preset) in &manifest {
+                    let description = match preset.description.as_str() {
+                        "" => "-",
+                        text => text,
+                    };
+                    let marker = if detected.contains(name) {
+                        " (detected)"
+                    } else {
+                        ""
+                    };
+                    println!("{:<16} {}{}", name, description, marker);
+                }
+            }
+        },
+        Command::Source { command } => match command {
+            SourceCommand::Validate { path, ci } => {
+                let dir = path.unwrap_or_else(|| root.clone());
+                validate_source(
+                    &env,
+                    &dir,
+                    "echo source validate",
+                    ci || github::is_actions(&env),
+                )?;
+            }
+        },
+        Command::Template { command } => match command {
+            TemplateCommand::New { name } => {
+                let path = templates::create(&root, &name)?;
+                println!("created {}", path.display());
+            }
+            TemplateCommand::List => {
+                for template in templates::list(&root)? {
+                    let description = match template.description.as_str() {
+                        "" => "-",
+                        text => text,
+                    };
+                    println!("{:<16} {}", template.name, description);
+                }
+            }
+            TemplateCommand::Lint { dir, schema } => {
+                let dir = dir.unwrap_or_else(|| {
+                    Path::new(issues::ISSUES_DIR)
+                        .join(issues::SHARED_DIR)
+                        .join(templates::TEMPLATES_DIR)
+                });
+                let schema = schema.unwrap_or_else(|| dir.join(template_lint::SCHEMA_FILE));
+                let schema = template_lint::Schema::load(&root.join(schema))?;
+                let config = env.load_config(&root)?;
+                let vars = template::variables(&root, "project", env.now(), &config.variables);
+                let violations = template_lint::lint(&root, &dir, &schema, &vars)?;
+                report_violations(
+                    &env,
+                    &root,
+                    "echo template lint",
+                    &violations,
+                    github::is_actions(&env),
+                )?;
+                if !violations.is_empty() {
+                    bail!("{} template lint error(s) found", violations.len());
+                }
+                println!("templates in {} clean", dir.display());
+            }
+        },
+        Command::Issue { command } => match command {
+            IssueCommand::New {
+                kind,
+                title,
+                project,
+                assignee,
+                labels,
+                template_vars,
+            } => {
+                let project = issues::resolve_project(&root, project.as_deref())?;
+                let assignee = assignee
+                    .map(|name| resolve_assignee(&env, &root, &name))
+                    .transpose()?;
+                let config = env.load_config(&root)?;
+                let mut vars = template::variables(&root, &project, env.now(), &config.variables);
+                complete_variables(&root, &mut vars, &template_vars, |vars| {
+                    ops::undefined_variables(&root, &kind, vars)
+                })?;
+                let path = ops::new_issue(&root, &project, &kind, &title, &vars)?;
+                let labels = labels.join(", ");
+                let mut fields = Vec::new();
+                if let Some(assignee) = &assignee {
+                    fields.push((ops::ASSIGNEE_FIELD, assignee.as_str()));
+                }
+                if !labels.is_empty() {
+                    fields.push((issues::LABELS_FIELD, labels.as_str()));
+                }
+                ops::set_fields(&root, &path, &fields)?;
+                println!("created {}", path.display());
+            }
+            IssueCommand::List {
+                project,
+                assignee,
+                label,
+            } => {
+                let filter = BulkFilter {
+                    project,
+                    label,
+                    assignee,
+                };
+                for issue in select_issues(&env, &root, &filter, None)? {
+                    println!(
+                        "{:<12} {:<16} {}  {}",
+                        issue.state.dir_name(),
+                        issue.project,
+                        issue.id(),
+                        issue.title
+                    );
+                }
+            }
+            IssueCommand::Move {
+                id,
+                to,
+                project,
+                assignee,
+                steal,
+            } => {
+                let project = issues::resolve_project(&root, project.as_deref())?;
+                // Stealing assigns the issue to yourself unless told otherwise.
+                let assignee = match assignee {
+                    Some(name) => Some(resolve_assignee(&env, &root, &name)?),
+                    None if steal => Some(resolve_assignee(&env, &root, "me")?),
+                    None => None,
+                };
+                let current = ops::current_assignee(&root, env.var(ops::ASSIGNEE_VAR));
+                let claim = ops::Claim {
+                    by: assignee.as_deref().or(current.as_deref()),
+                    steal,
+                };
+                let (transition, path) = ops::transition(&root, &project, &id, to.into(), claim)?;
+                for warning in &transition.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                if let Some(assignee) = &assignee {
+                    ops::set_fields(&root, &path, &[(ops::ASSIGNEE_FIELD, assignee)])?;
+                }
+                println!("moved to {}", path.display());
+                let config = env.load_config(&root)?;
+                notify_transition(
+                    &config.notifications,
+                    &notify::Transition {
+                        issue: path,
+                        title: transition.issue.title,
+                        from: transition.issue.state,
+                        to: transition.to,
+                        by: assignee.or(current),
+                    },
+                );
+            }
+            IssueCommand::Validate { project } => {
+                let issues = scan_selected(&root, project.as_deref())?;
+                let violations = validate::validate(&issues);
+                report_violations(
+                    &env,
+                    &root,
+                    "echo issue validate",
+                    &violations,
+                    github::is_actions(&env),
+                )?;
+                if !violations.is_empty() {
+                    bail!("{} workflow violation(s) found", violations.len());
+                }
+                println!("{} issue(s) valid", issues.len());
+            }
+            IssueCommand::Lint { paths, project } => {
+                let config = env.load_config(&root)?;
+                let issues = if paths.is_empty() {
+                    scan_selected(&root, project.as_deref())?
+                } else {
+                    paths
+                        .iter()
+                        .map(|path| issues::read_issue_at(&root, path))
+                        .collect::<Result<_>>()?
+                };
+                let mut violations = Vec::new();
+                for issue in &issues {
+                    let content = fs::read_to_string(root.join(&issue.path))
+                        .with_context(|| format!("failed to read {}", issue.path.display()))?;
+                    violations.extend(lint::lint(issue, &content, &config.lint));
+                }
+                report_violations(
+                    &env,
+                    &root,
+                    "echo issue lint",
+                    &violations,
+                    github::is_actions(&env),
+                )?;
+                if !violations.is_empty() {
+                    bail!("{} lint error(s) found", violations.len());
+                }
+                println!("{} issue(s) clean", issues.len());
+            }
+            IssueCommand::BulkMove {
+                from,
+                to,
+                filter,
+                dry_run,
+                steal,
+            } => {
+                let selected = select_issues(&env, &root, &filter, from.map(Into::into))?;
+                let by = ops::current_assignee(&root, env.var(ops::ASSIGNEE_VAR));
+                let claim = ops::Claim {
+                    by: by.as_deref(),
+                    steal,
+                };
+                let moved = bulk::move_all(&root, &selected, to.into(), claim, dry_run)?;
+                let config = env.load_config(&root)?;
+                for (transition, new) in &moved {
+                    let old = &transition.issue.path;
+                    if dry_run {
+                        println!("would move {} -> {}", old.display(), new.display());
+                        continue;
+                    }
+                    for warning in &transition.warnings {
+                        eprintln!("warning: {}", warning);
+                    }
+                    println!("moved {} -> {}", old.display(), new.display());
+                    notify_transition(
+                        &config.notifications,
+                        &notify::Transition {
+                            issue: new.clone(),
+                            title: transition.issue.title.clone(),
+                            from: transition.issue.state,
+                            to: transition.to,
+                            by: by.clone(),
+                        },
+                    );
+                }
+                println!(
+                    "{} issue(s) {}",
+                    moved.len(),
+                    if dry_run { "would move" } else { "moved" }
+                );
+            }
+            IssueCommand::BulkEdit {
+                set,
+                state,
+                filter,
+                dry_run,
+            } => {
+                let mut fields = Vec::new();
+                for assignment in &set {
+                    let (name, value) = bulk::parse_assignment(assignment)?;
+                    let value = if name.eq_ignore_ascii_case(ops::ASSIGNEE_FIELD) {
+                        resolve_assignee(&env, &root, &value)?
+                    } else {
+                        value
+                    };
+                    fields.push((name, value));
+                }
+                let selected = select_issues(&env, &root, &filter, state.map(Into::into))?;
+                let edited = bulk::edit_all(&root, &selected, &fields, dry_run)?;
+                for path in &edited {
+                    if dry_run {
+                        println!("would edit {}", path.display());
+                    } else {
+                        println!("edited {}", path.display());
+                    }
+                }
+                println!(
+                    "{} issue(s) {}",
+                    edited.len(),
+                    if dry_run { "would change" } else { "edited" }
+                );
+            }
+            IssueCommand::History { id, project } => {
+                let project = issues::resolve_project(&root, project.as_deref())?;
+                let issue = ops::find_issue(&root, &project, &id)?;
+                let events = history::history(&root, &issue)?;
+                if events.is_empty() {
+                    println!("{} has no commits yet", issue.path.display());
+                }
+                for event in events {
+                    println!("{}", event.line());
+                }
+            }
+            IssueCommand::Stale {
+                days,
+                project,
+                webhook,
+            } => {
+                let issues = scan_selected(&root, project.as_deref())?;
+                let reports = stale::find(&root, &issues, env.now(), days);
+                for report in &reports {
+                    println!("{}", stale::describe(report));
+                }
+                if reports.is_empty() {
+                    println!("no stale or overdue issues");
+                } else if let Some(url) = webhook {
+                    stale::post(&url, &reports)?;
+                    println!("posted {} issue(s) to {}", reports.len(), url);
+                }
+            }
+            IssueCommand::Graph { format, output } => {
+                let issues = issues::scan(&root)?;
+                if let Some(cycle) = graph::cycle(&issues) {
+                    eprintln!("warning: dependency cycle: {}", cycle.join(" -> "));
+                }
+                let rendered = graph::render(&issues, format.into());
+                match output {
+                    Some(path) => fs::write(&path, rendered)
+                        .with_context(|| format!("failed to write {}", path.display()))?,
+                    None => print!("{}", rendered),
+                }
+            }
+            IssueCommand::Render { format, output } => {
+                let issues = issues::scan(&root)?;
+                let rendered = render::render(&issues, format.into());
+                match output {
+                    Some(path) => fs::write(&path, rendered)
+                        .with_context(|| format!("failed to write {}", path.display()))?,
+                    None => print!("{}", rendered),
+                }
+            }
+        },
+    }
+    Ok(())
+}
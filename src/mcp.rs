@@ -0,0 +1,385 @@
+//! `echo mcp serve`: a Model Context Protocol server on stdio.
+//!
+//! Agents that speak MCP call tools to list rules, read AGENT.md, query
+//! issues and move them between states, and read AGENT.md and the
+//! installed rules as resources, instead of shelling out to echo. Messages
+//! are JSON-RPC 2.0, one per line.
+
+use crate::agent::AGENT_MD;
+use crate::environment::Environment;
+use crate::issues::{self, ops, validate, Issue, State};
+use crate::rules::{self, RULES_DIR};
+use crate::run_lock::RunLock;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Protocol revision implemented.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// URI of the AGENT.md resource; rules are `echo://rules/<name>`.
+pub const AGENT_MD_URI: &str = "echo://agent-md";
+
+const RULES_URI_PREFIX: &str = "echo://rules/";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Answer the requests read from `input` on `output` until `input` ends.
+/// The configuration of `root` is loaded through `env`.
+pub fn serve(
+    env: &Environment,
+    root: &Path,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<()> {
+    for line in input.lines() {
+        let line = line.context("failed to read from stdin")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(env, root, &message),
+            Err(err) => Some(error(Value::Null, PARSE_ERROR, &err.to_string())),
+        };
+        if let Some(response) = response {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to `message`; `None` for notifications.
+pub fn handle(env: &Environment, root: &Path, message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let method = message["method"].as_str().unwrap_or_default();
+    let params = &message["params"];
+    let result = match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {}, "resources": {} },
+            "serverInfo": { "name": "echo", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let arguments = &params["arguments"];
+            if !tools().iter().any(|tool| tool["name"] == name) {
+                return Some(error(
+                    id,
+                    INVALID_PARAMS,
+                    &format!("unknown tool '{}'", name),
+                ));
+            }
+            // Tool failures are results the agent can read and act upon.
+            Ok(match call_tool(env, root, name, arguments) {
+                Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+                Err(err) => json!({
+                    "content": [{ "type": "text", "text": format!("{:#}", err) }],
+                    "isError": true,
+                }),
+            })
+        }
+        "resources/list" => resources(env, root).map(|resources| json!({ "resources": resources })),
+        "resources/read" => {
+            let uri = params["uri"].as_str().unwrap_or_default();
+            return Some(match read_resource(root, uri) {
+                Ok(text) => response(
+                    id,
+                    json!({
+                        "contents": [{ "uri": uri, "mimeType": "text/markdown", "text": text }],
+                    }),
+                ),
+                Err(err) => error(id, INVALID_PARAMS, &format!("{:#}", err)),
+            });
+        }
+        _ => {
+            return Some(error(
+                id,
+                METHOD_NOT_FOUND,
+                &format!("unknown method '{}'", method),
+            ))
+        }
+    };
+    Some(match result {
+        Ok(result) => response(id, result),
+        Err(err) => error(id, INVALID_PARAMS, &format!("{:#}", err)),
+    })
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Tools offered, with their input schemas.
+fn tools() -> Vec<Value> {
+    let project = json!({ "type": "string", "description": "Project under issues/; optional when there is only one" });
+    let states: Vec<&str> = State::ALL.iter().map(|s| s.dir_name()).collect();
+    vec![
+        json!({
+            "name": "list_rules",
+            "description": "List the installed Cursor rules with their source, description and globs",
+            "inputSchema": { "type": "object", "properties": {} },
+        }),
+        json!({
+            "name": "read_agent_md",
+            "description": "Read the AGENT.md instructions of the repository",
+            "inputSchema": { "type": "object", "properties": {} },
+        }),
+        json!({
+            "name": "list_issues",
+            "description": "List issues, optionally of one project or state",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "project": project,
+                    "state": { "type": "string", "enum": states },
+                },
+            },
+        }),
+        json!({
+            "name": "move_issue",
+            "description": "Move an issue to another workflow state",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "Issue id, the file name without .md" },
+                    "to": { "type": "string", "enum": states },
+                    "project": project,
+                    "steal": { "type": "boolean", "description": "Take over an issue assigned to someone else" },
+                },
+                "required": ["id", "to"],
+            },
+        }),
+        json!({
+            "name": "validate_issues",
+            "description": "Check that every issue lives in the directory matching its status",
+            "inputSchema": { "type": "object", "properties": {} },
+        }),
+    ]
+}
+
+fn call_tool(env: &Environment, root: &Path, name: &str, arguments: &Value) -> Result<String> {
+    let string = |key: &str| arguments[key].as_str();
+    let state = |key: &str| -> Result<Option<State>> {
+        string(key)
+            .map(|name| State::from_name(name).ok_or_else(|| anyhow!("unknown state '{}'", name)))
+            .transpose()
+    };
+    match name {
+        "list_rules" => {
+            let config = env.load_config(root)?;
+            Ok(serde_json::to_string_pretty(&rules::list(
+                root,
+                &config.rules,
+            )?)?)
+        }
+        "read_agent_md" => read_resource(root, AGENT_MD_URI),
+        "list_issues" => {
+            let wanted = state("state")?;
+            let found = match string("project") {
+                Some(project) => {
+                    issues::scan_project(root, &issues::resolve_project(root, Some(project))?)?
+                }
+                None => issues::scan(root)?,
+            };
+            let listed: Vec<Value> = found
+                .iter()
+                .filter(|issue| wanted.is_none_or(|state| issue.state == state))
+                .map(issue_json)
+                .collect();
+            Ok(serde_json::to_string_pretty(&listed)?)
+        }
+        "move_issue" => {
+            let (Some(id), Some(to)) = (string("id"), state("to")?) else {
+                bail!("`id` and `to` are required");
+            };
+            let project = issues::resolve_project(root, string("project"))?;
+            // Waits for a run writing to the repository, as `--wait` does.
+            let _lock = RunLock::acquire(root, true, |_| {})?;
+            let current = ops::current_assignee(root, env.var(ops::ASSIGNEE_VAR));
+            let claim = ops::Claim {
+                by: current.as_deref(),
+                steal: arguments["steal"].as_bool().unwrap_or(false),
+            };
+            let (transition, path) = ops::transition(root, &project, id, to, claim)?;
+            let mut lines = vec![format!("moved to {}", path.display())];
+            lines.extend(
+                transition
+                    .warnings
+                    .iter()
+                    .map(|warning| format!("warning: {}", warning)),
+            );
+            Ok(lines.join("\n"))
+        }
+        "validate_issues" => {
+            let violations = validate::validate(&issues::scan(root)?);
+            if violations.is_empty() {
+                return Ok("no workflow violations".to_string());
+            }
+            Ok(violations
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        _ => bail!("unknown tool '{}'", name),
+    }
+}
+
+fn issue_json(issue: &Issue) -> Value {
+    json!({
+        "id": issue.id(),
+        "project": issue.project,
+        "state": issue.state.dir_name(),
+        "title": issue.title,
+        "type": issue.field("Type"),
+        "path": issue.path.display().to_string(),
+    })
+}
+
+/// AGENT.md, when present, and the enabled installed rules.
+fn resources(env: &Environment, root: &Path) -> Result<Vec<Value>> {
+    let mut resources = Vec::new();
+    if root.join(AGENT_MD).is_file() {
+        resources.push(json!({
+            "uri": AGENT_MD_URI,
+            "name": AGENT_MD,
+            "description": "Instructions for agents working in this repository",
+            "mimeType": "text/markdown",
+        }));
+    }
+    let config = env.load_config(root)?;
+    for rule in rules::list(root, &config.rules)? {
+        if rule.disabled {
+            continue;
+        }
+        resources.push(json!({
+            "uri": format!("{}{}", RULES_URI_PREFIX, rule.name),
+            "name": rule.name,
+            "description": rule.description,
+            "mimeType": "text/markdown",
+        }));
+    }
+    Ok(resources)
+}
+
+fn read_resource(root: &Path, uri: &str) -> Result<String> {
+    let path = if uri == AGENT_MD_URI {
+        root.join(AGENT_MD)
+    } else if let Some(name) = uri.strip_prefix(RULES_URI_PREFIX) {
+        if name.split('/').any(|part| part.is_empty() || part == "..") {
+            bail!("invalid rule name '{}'", name);
+        }
+        root.join(RULES_DIR)
+            .join(format!("{}.{}", name, rules::RULE_EXTENSION))
+    } else {
+        bail!("unknown resource '{}'", uri);
+    };
+    fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+    }
+
+    #[test]
+    fn serves_tools_and_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let env = Environment::new(root);
+        ops::create_issue_workflow_structure(root, "app").unwrap();
+        fs::write(
+            root.join("issues/app/todo/bug-crash.md"),
+            "# Crash\n\n**Type:** Bug\n",
+        )
+        .unwrap();
+        fs::write(root.join(AGENT_MD), "# Agent\n").unwrap();
+
+        let mut output = Vec::new();
+        let input = "{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"initialize\",\"params\":{}}\n\
+                     {\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}\n\
+                     not json\n";
+        serve(&env, root, input.as_bytes(), &mut output).unwrap();
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], 7);
+        assert_eq!(lines[0]["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(lines[1]["error"]["code"], PARSE_ERROR);
+
+        let call = |name: &str, arguments: Value| {
+            handle(
+                &env,
+                root,
+                &request(
+                    "tools/call",
+                    json!({ "name": name, "arguments": arguments }),
+                ),
+            )
+            .unwrap()["result"]
+                .clone()
+        };
+        let listed = call("list_issues", json!({ "state": "todo" }));
+        let text = listed["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("\"id\": \"bug-crash\""));
+        fs::write(
+            root.join("issues/app/todo/bug-owned.md"),
+            "# Owned\n\n**Assignee:** someone-else\n",
+        )
+        .unwrap();
+        let owned = call(
+            "move_issue",
+            json!({ "id": "bug-owned", "to": "in_progress" }),
+        );
+        assert!(owned["isError"].as_bool().unwrap());
+        let stolen = call(
+            "move_issue",
+            json!({ "id": "bug-owned", "to": "in_progress", "steal": true }),
+        );
+        assert!(stolen.get("isError").is_none());
+        assert!(
+            call("move_issue", json!({ "id": "bug-crash", "to": "doing" }))["isError"]
+                .as_bool()
+                .unwrap()
+        );
+        let moved = call(
+            "move_issue",
+            json!({ "id": "bug-crash", "to": "in_progress" }),
+        );
+        assert!(moved.get("isError").is_none());
+        assert!(root.join("issues/app/in_progress/bug-crash.md").is_file());
+
+        let resources = handle(&env, root, &request("resources/list", json!({}))).unwrap();
+        assert_eq!(resources["result"]["resources"][0]["uri"], AGENT_MD_URI);
+        let read = handle(
+            &env,
+            root,
+            &request("resources/read", json!({ "uri": AGENT_MD_URI })),
+        )
+        .unwrap();
+        assert_eq!(read["result"]["contents"][0]["text"], "# Agent\n");
+        let escape = json!({ "uri": "echo://rules/../../etc/passwd" });
+        assert!(
+            handle(&env, root, &request("resources/read", escape)).unwrap()["error"].is_object()
+        );
+        let unknown = handle(&env, root, &request("prompts/list", json!({}))).unwrap();
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,288 @@
+//! Versioned rule packs: a flowmates checkout declares its version in
+//! [`PACK_FILE`], and `.echo.toml` can pin a range of versions with
+//! `rules-pack = "^2.1"`.
+//!
+//! Versions are `MAJOR.MINOR.PATCH`; a range is a comma-separated list of
+//! comparators (`^2.1`, `~2.1.3`, `>=2.0, <4`, `=2.1.0`, `*`), a bare
+//! version meaning `^`, as in Cargo.
+
+pub mod publish;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Pack description, at the root of a flowmates checkout.
+pub const PACK_FILE: &str = "pack.toml";
+
+/// Contents of [`PACK_FILE`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pack {
+    pub name: Option<String>,
+    pub version: String,
+}
+
+impl Pack {
+    /// The pack of `flowmates_dir`; `None` without a [`PACK_FILE`].
+    pub fn load(flowmates_dir: &Path) -> Result<Option<Pack>> {
+        let path = flowmates_dir.join(PACK_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let pack: Pack =
+            toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))?;
+        pack.version()
+            .with_context(|| format!("invalid {}", path.display()))?;
+        Ok(Some(pack))
+    }
+
+    pub fn version(&self) -> Result<Version> {
+        self.version.parse()
+    }
+}
+
+/// Check the pack of `flowmates_dir` against the `rules-pack` range and
+/// the version `installed` before: moving to a new major version needs
+/// `allow_major`. Returns the version of the pack, if it has one.
+pub fn check(
+    flowmates_dir: &Path,
+    requirement: Option<&str>,
+    installed: Option<&str>,
+    allow_major: bool,
+) -> Result<Option<Version>> {
+    let Some(pack) = Pack::load(flowmates_dir)? else {
+        if let Some(requirement) = requirement {
+            bail!(
+                "rules-pack = \"{}\" is set but {} has no {}",
+                requirement,
+                flowmates_dir.display(),
+                PACK_FILE
+            );
+        }
+        return Ok(None);
+    };
+    let version = pack.version()?;
+    if let Some(requirement) = requirement {
+        let requirement: Requirement = requirement.parse()?;
+        if !requirement.matches(version) {
+            bail!(
+                "rule pack {} does not satisfy rules-pack = \"{}\"; change the range in .echo.toml to install it",
+                version,
+                requirement
+            );
+        }
+    }
+    if let Some(installed) = installed.and_then(|v| v.parse::<Version>().ok()) {
+        if version.major > installed.major && !allow_major {
+            bail!(
+                "rule pack moves from {} to {}, a new major version; rerun with --allow-major",
+                installed,
+                version
+            );
+        }
+    }
+    Ok(Some(version))
+}
+
+/// A `MAJOR.MINOR.PATCH` version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Version> {
+        let (version, parts) = partial(s)?;
+        if parts < 3 {
+            bail!("invalid version '{}': expected MAJOR.MINOR.PATCH", s);
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A version with up to three parts, missing ones as 0, and the number
+/// of parts given.
+fn partial(s: &str) -> Result<(Version, usize)> {
+    let s = s.trim();
+    let parts: Vec<&str> = s.split('.').collect();
+    if parts.len() > 3 {
+        bail!("invalid version '{}'", s);
+    }
+    let mut numbers = [0; 3];
+    for (number, part) in numbers.iter_mut().zip(&parts) {
+        *number = part
+            .parse()
+            .with_context(|| format!("invalid version '{}'", s))?;
+    }
+    let [major, minor, patch] = numbers;
+    Ok((
+        Version {
+            major,
+            minor,
+            patch,
+        },
+        parts.len(),
+    ))
+}
+
+/// A range of versions, e.g. `^2.1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    text: String,
+    /// Bounds of each comparator: inclusive lower, exclusive upper.
+    bounds: Vec<(Version, Option<Version>)>,
+}
+
+impl FromStr for Requirement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Requirement> {
+        let mut bounds = Vec::new();
+        for comparator in s.split(',') {
+            bounds.push(
+                comparator_bounds(comparator.trim())
+                    .with_context(|| format!("invalid version requirement '{}'", s))?,
+            );
+        }
+        Ok(Requirement {
+            text: s.trim().to_string(),
+            bounds,
+        })
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Requirement {
+    pub fn matches(&self, version: Version) -> bool {
+        self.bounds
+            .iter()
+            .all(|(low, high)| version >= *low && high.is_none_or(|high| version < high))
+    }
+}
+
+fn comparator_bounds(comparator: &str) -> Result<(Version, Option<Version>)> {
+    const ZERO: Version = Version {
+        major: 0,
+        minor: 0,
+        patch: 0,
+    };
+    if comparator == "*" {
+        return Ok((ZERO, None));
+    }
+    let bump = |v: Version, parts: usize| match parts {
+        1 => Version {
+            major: v.major + 1,
+            minor: 0,
+            patch: 0,
+        },
+        2 => Version {
+            minor: v.minor + 1,
+            patch: 0,
+            ..v
+        },
+        _ => Version {
+            patch: v.patch + 1,
+            ..v
+        },
+    };
+    if let Some(rest) = comparator.strip_prefix(">=") {
+        return Ok((partial(rest)?.0, None));
+    }
+    if let Some(rest) = comparator.strip_prefix("<=") {
+        let (v, parts) = partial(rest)?;
+        return Ok((ZERO, Some(bump(v, parts))));
+    }
+    if let Some(rest) = comparator.strip_prefix('>') {
+        let (v, parts) = partial(rest)?;
+        return Ok((bump(v, parts), None));
+    }
+    if let Some(rest) = comparator.strip_prefix('<') {
+        return Ok((ZERO, Some(partial(rest)?.0)));
+    }
+    if let Some(rest) = comparator.strip_prefix('=') {
+        let (v, parts) = partial(rest)?;
+        return Ok((v, Some(bump(v, parts))));
+    }
+    if let Some(rest) = comparator.strip_prefix('~') {
+        let (v, parts) = partial(rest)?;
+        return Ok((v, Some(bump(v, parts.min(2)))));
+    }
+    let (v, parts) = partial(comparator.strip_prefix('^').unwrap_or(comparator))?;
+    // The first non-zero part given may not change.
+    let significant = if v.major > 0 || parts == 1 {
+        1
+    } else if v.minor > 0 || parts == 2 {
+        2
+    } else {
+        3
+    };
+    Ok((v, Some(bump(v, significant))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(requirement: &str, version: &str) -> bool {
+        requirement
+            .parse::<Requirement>()
+            .unwrap()
+            .matches(version.parse().unwrap())
+    }
+
+    #[test]
+    fn matches_version_ranges() {
+        assert!(matches("^2.1", "2.1.0"));
+        assert!(matches("^2.1", "2.9.3"));
+        assert!(!matches("^2.1", "2.0.9"));
+        assert!(!matches("^2.1", "3.0.0"));
+        assert!(matches("2", "2.5.0"));
+        assert!(!matches("^0.3", "0.4.0"));
+        assert!(matches("~2.1.3", "2.1.9"));
+        assert!(!matches("~2.1.3", "2.2.0"));
+        assert!(matches(">=2.0, <4", "3.9.9"));
+        assert!(!matches(">=2.0, <4", "4.0.0"));
+        assert!(matches("=2.1", "2.1.7"));
+        assert!(!matches(">2.1", "2.1.7"));
+        assert!(matches("*", "9.0.0"));
+        assert!("^x".parse::<Requirement>().is_err());
+        assert!("2.1".parse::<Version>().is_err());
+        assert_eq!("2.1.0".parse::<Version>().unwrap().to_string(), "2.1.0");
+    }
+
+    #[test]
+    fn checks_the_pack_against_the_pin_and_the_installed_version() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(check(dir.path(), None, None, false).unwrap(), None);
+        assert!(check(dir.path(), Some("^2"), None, false).is_err());
+
+        fs::write(dir.path().join(PACK_FILE), "version = \"3.0.1\"\n").unwrap();
+        let version = check(dir.path(), Some(">=2"), Some("3.0.0"), false).unwrap();
+        assert_eq!(version.unwrap().to_string(), "3.0.1");
+        assert!(check(dir.path(), Some("^2.1"), None, true).is_err());
+        let err = check(dir.path(), None, Some("2.4.0"), false).unwrap_err();
+        assert!(err.to_string().contains("--allow-major"));
+        assert!(check(dir.path(), None, Some("2.4.0"), true).is_ok());
+    }
+}
@@ -0,0 +1,213 @@
+//! `echo pack publish`: archive a flowmates checkout as a rule pack and
+//! upload it to a registry, so teams can distribute rules without a git
+//! checkout.
+//!
+//! The archive is a `<name>-<version>.tar.gz` of the rules, templates,
+//! hook scripts and manifests of the checkout, with a [`SUMS_FILE`]
+//! listing them so the unpacked pack passes [`checksums::verify`]. `tar`
+//! builds it; `curl` uploads it with an HTTP PUT to `http(s)://` and
+//! `file://` registries, and the `aws` CLI copies it to `s3://` ones
+//! (`AWS_ENDPOINT_URL` selects an S3-compatible service).
+
+use super::Pack;
+use crate::checksums::{self, SUMS_FILE};
+use crate::environment::Environment;
+use crate::hooks;
+use crate::issues::templates::FLOWMATES_TEMPLATES_DIR;
+use crate::lock;
+use crate::presets;
+use crate::rules::{bundles, FLOWMATES_RULES_DIR};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directories of the checkout packed.
+const DIRS: [&str; 4] = [
+    FLOWMATES_RULES_DIR,
+    FLOWMATES_TEMPLATES_DIR,
+    presets::PRESETS_DIR,
+    "scripts",
+];
+
+/// Bearer token sent to HTTP registries, when set.
+pub const TOKEN_VAR: &str = "ECHO_REGISTRY_TOKEN";
+
+/// Files of `flowmates_dir` that go in the pack, relative to it.
+pub fn files(flowmates_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = [
+        super::PACK_FILE,
+        presets::MANIFEST_FILE,
+        bundles::MANIFEST_FILE,
+        hooks::MANIFEST_FILE,
+    ]
+    .into_iter()
+    .filter(|file| flowmates_dir.join(file).is_file())
+    .map(PathBuf::from)
+    .collect();
+    for dir in DIRS {
+        checksums::collect(flowmates_dir, Path::new(dir), &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Archive `flowmates_dir` into `out_dir`, returning the archive path.
+/// The checkout must have a `pack.toml`; the pack name defaults to the
+/// name of its directory.
+pub fn archive(flowmates_dir: &Path, out_dir: &Path) -> Result<PathBuf> {
+    let Some(pack) = Pack::load(flowmates_dir)? else {
+        bail!(
+            "{} has no {}; a pack needs a version to be published",
+            flowmates_dir.display(),
+            super::PACK_FILE
+        );
+    };
+    let name = match &pack.name {
+        Some(name) => name.clone(),
+        None => flowmates_dir
+            .canonicalize()?
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context("cannot name the pack; set `name` in pack.toml")?
+            .to_string(),
+    };
+    let base = format!("{}-{}", name, pack.version()?);
+    let staging = out_dir.join(&base);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .with_context(|| format!("failed to remove {}", staging.display()))?;
+    }
+    let mut sums = String::new();
+    for file in files(flowmates_dir)? {
+        let content = fs::read(flowmates_dir.join(&file))
+            .with_context(|| format!("failed to read {}", file.display()))?;
+        let target = staging.join(&file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, &content)
+            .with_context(|| format!("failed to write {}", target.display()))?;
+        sums.push_str(&format!(
+            "{}  {}\n",
+            lock::hash_bytes(&content),
+            file.display()
+        ));
+    }
+    fs::write(staging.join(SUMS_FILE), sums)?;
+    let archive = out_dir.join(format!("{}.tar.gz", base));
+    let result = run(
+        Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(out_dir)
+            .arg(&base),
+        "tar",
+    );
+    fs::remove_dir_all(&staging).ok();
+    result?;
+    Ok(archive)
+}
+
+/// Upload `archive` under `registry`, returning where it went.
+pub fn upload(env: &Environment, archive: &Path, registry: &str) -> Result<String> {
+    let file_name = archive
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("invalid archive name")?;
+    let url = format!("{}/{}", registry.trim_end_matches('/'), file_name);
+    if url.starts_with("s3://") {
+        run(
+            Command::new("aws")
+                .args(["s3", "cp"])
+                .arg(archive)
+                .arg(&url),
+            "aws",
+        )?;
+    } else if ["http://", "https://", "file://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+    {
+        let mut curl = Command::new("curl");
+        curl.args(["--fail", "--silent", "--show-error", "--upload-file"])
+            .arg(archive);
+        if let Some(token) = env.var(TOKEN_VAR) {
+            curl.arg("--header")
+                .arg(format!("Authorization: Bearer {}", token));
+        }
+        run(curl.arg(&url), "curl")?;
+    } else {
+        bail!(
+            "unsupported registry '{}': expected an http(s)://, file:// or s3:// URL",
+            registry
+        );
+    }
+    Ok(url)
+}
+
+fn run(command: &mut Command, program: &str) -> Result<()> {
+    let output = command
+        .output()
+        .with_context(|| format!("failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives_and_uploads_a_pack() {
+        let flowmates = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let registry = tempfile::tempdir().unwrap();
+        fs::create_dir_all(flowmates.path().join("rules/rust")).unwrap();
+        fs::write(flowmates.path().join("rules/rust/style.mdc"), "style\n").unwrap();
+        fs::write(flowmates.path().join("README.md"), "not packed\n").unwrap();
+        assert!(archive(flowmates.path(), out.path()).is_err());
+
+        fs::write(
+            flowmates.path().join("pack.toml"),
+            "name = \"acme\"\nversion = \"1.2.0\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            files(flowmates.path()).unwrap(),
+            vec![
+                PathBuf::from("pack.toml"),
+                PathBuf::from("rules/rust/style.mdc")
+            ]
+        );
+        let archive = archive(flowmates.path(), out.path()).unwrap();
+        assert_eq!(archive, out.path().join("acme-1.2.0.tar.gz"));
+
+        let unpacked = tempfile::tempdir().unwrap();
+        run(
+            Command::new("tar")
+                .arg("-xzf")
+                .arg(&archive)
+                .arg("-C")
+                .arg(unpacked.path()),
+            "tar",
+        )
+        .unwrap();
+        let pack = unpacked.path().join("acme-1.2.0");
+        assert!(!pack.join("README.md").exists());
+        assert_eq!(checksums::verify(&pack, true).unwrap(), Some(2));
+
+        let env = Environment::new(out.path()).with_var(TOKEN_VAR, "secret");
+        let url = format!("file://{}", registry.path().display());
+        let uploaded = upload(&env, &archive, &url).unwrap();
+        assert!(uploaded.ends_with("/acme-1.2.0.tar.gz"));
+        assert!(registry.path().join("acme-1.2.0.tar.gz").is_file());
+        assert!(upload(&env, &archive, "ftp://example.com").is_err());
+    }
+}
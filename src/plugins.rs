@@ -0,0 +1,190 @@
+//! Plugins: executables extending echo without forking it.
+//!
+//! `echo <name> ...` runs `echo-<name>` from `PATH`, or the executable
+//! declared under `[plugins.<name>]` in `.echo.toml`, when `<name>` is not
+//! a built-in command. Plugins declared with `init = true` also take part
+//! in `echo init`: they are run as `echo-<name> init` with an
+//! [`InitRequest`] as JSON on stdin, and answer an [`InitResponse`] as JSON
+//! on stdout.
+
+use crate::environment::Environment;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Prefix of plugin executables.
+pub const PREFIX: &str = "echo-";
+
+/// Version of the JSON protocol, sent in each request.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// `[plugins]` section of `.echo.toml`, by plugin name.
+pub type PluginsConfig = BTreeMap<String, PluginConfig>;
+
+/// `[plugins.<name>]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginConfig {
+    /// Executable, relative to the repository root; `echo-<name>` from
+    /// `PATH` when unset.
+    pub path: Option<PathBuf>,
+    /// Run the plugin during `echo init`.
+    pub init: bool,
+}
+
+/// What `echo init` tells a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InitRequest {
+    pub protocol: u32,
+    pub root: PathBuf,
+    pub projects: Vec<String>,
+    pub force: bool,
+}
+
+/// What a plugin did during `echo init`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct InitResponse {
+    /// Files written, relative to the repository root.
+    pub files: Vec<PathBuf>,
+    /// Lines to show the user.
+    pub messages: Vec<String>,
+}
+
+/// Executable of plugin `name`: the configured one, else `echo-<name>`
+/// found in `PATH`.
+pub fn find(env: &Environment, root: &Path, config: &PluginsConfig, name: &str) -> Option<PathBuf> {
+    if let Some(path) = config.get(name).and_then(|plugin| plugin.path.as_ref()) {
+        return Some(root.join(path));
+    }
+    let file = format!("{}{}", PREFIX, name);
+    env::split_paths(env.var("PATH")?)
+        .map(|dir| dir.join(&file))
+        .find(|path| path.is_file())
+}
+
+/// Run plugin `name` with `args` for an unknown subcommand, with the
+/// terminal attached. `ECHO_ROOT` is set to the repository root.
+pub fn exec(
+    env: &Environment,
+    root: &Path,
+    config: &PluginsConfig,
+    name: &str,
+    args: &[String],
+) -> Result<ExitStatus> {
+    let Some(path) = find(env, root, config, name) else {
+        bail!(
+            "no such command '{}' (no {}{} in PATH nor [plugins.{}] in .echo.toml)",
+            name,
+            PREFIX,
+            name,
+            name
+        );
+    };
+    Command::new(&path)
+        .args(args)
+        .env("ECHO_ROOT", root)
+        .status()
+        .with_context(|| format!("failed to run {}", path.display()))
+}
+
+/// Run the plugins declared with `init = true`, in name order, and return
+/// their responses. A plugin that is missing, fails or answers invalid
+/// JSON fails the run.
+pub fn init(
+    env: &Environment,
+    root: &Path,
+    config: &PluginsConfig,
+    request: &InitRequest,
+) -> Result<Vec<(String, InitResponse)>> {
+    let input = serde_json::to_vec(request)?;
+    let mut responses = Vec::new();
+    for (name, _) in config.iter().filter(|(_, plugin)| plugin.init) {
+        let Some(path) = find(env, root, config, name) else {
+            bail!("plugin {} not found: no {}{} in PATH", name, PREFIX, name);
+        };
+        let mut child = Command::new(&path)
+            .arg("init")
+            .env("ECHO_ROOT", root)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {}", path.display()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin may not read its request; it then closed stdin.
+            let _ = stdin.write_all(&input);
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!("plugin {} failed ({})", name, output.status);
+        }
+        let response = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("plugin {} answered invalid JSON", name))?;
+        responses.push((name.clone(), response));
+    }
+    Ok(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn script(path: &Path, body: &str) {
+        fs::write(path, format!("#!/bin/sh\n{}", body)).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn runs_init_plugins_over_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        script(
+            &root.join("secrets"),
+            "[ \"$1\" = init ] || exit 2\ncat > request.json\n\
+             echo '{\"files\": [\".env.example\"], \"messages\": [\"secrets ready\"]}'\n",
+        );
+        script(&root.join("broken"), "echo nope\n");
+        let mut config = PluginsConfig::new();
+        config.insert(
+            "secrets".into(),
+            PluginConfig {
+                path: Some("secrets".into()),
+                init: true,
+            },
+        );
+        config.insert(
+            "broken".into(),
+            PluginConfig {
+                path: Some("broken".into()),
+                init: false,
+            },
+        );
+        let env = Environment::new(root);
+        let request = InitRequest {
+            protocol: PROTOCOL_VERSION,
+            root: root.to_path_buf(),
+            projects: vec!["app".into()],
+            force: false,
+        };
+        let responses = init(&env, root, &config, &request).unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].0, "secrets");
+        assert_eq!(responses[0].1.files, vec![PathBuf::from(".env.example")]);
+        assert_eq!(responses[0].1.messages, vec!["secrets ready"]);
+        let sent: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(root.join("request.json")).unwrap()).unwrap();
+        assert_eq!(sent["projects"][0], "app");
+
+        config.get_mut("broken").unwrap().init = true;
+        let err = init(&env, root, &config, &request).unwrap_err();
+        assert!(err.to_string().contains("invalid JSON"));
+        assert!(find(&env, root, &config, "missing-plugin-xyz").is_none());
+    }
+}
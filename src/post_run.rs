@@ -0,0 +1,70 @@
+//! Commands of `.echo.toml` run after a successful `echo init` or
+//! `echo update`, e.g. `post-update = ["cargo fmt"]`.
+//!
+//! Each command runs through `sh -c` from the repository root, in order;
+//! the first failure stops the others.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+/// A command that ran, with its combined stdout and stderr.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ran {
+    pub command: String,
+    pub success: bool,
+    pub output: String,
+}
+
+/// Run `commands` in `root`, stopping after the first that fails.
+/// `ECHO_COMMAND` is set to `stage`, e.g. `init`, for the commands.
+pub fn run(root: &Path, stage: &str, commands: &[String]) -> Result<Vec<Ran>> {
+    let mut ran = Vec::new();
+    for command in commands {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(root)
+            .env("ECHO_COMMAND", stage)
+            .output()
+            .with_context(|| format!("failed to run `{}`", command))?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        let success = output.status.success();
+        ran.push(Ran {
+            command: command.clone(),
+            success,
+            output: text.trim_end().to_string(),
+        });
+        if !success {
+            break;
+        }
+    }
+    Ok(ran)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_in_the_root_until_a_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let commands = [
+            "echo $ECHO_COMMAND > stage; echo done",
+            "echo oops >&2; exit 3",
+            "touch never",
+        ]
+        .map(String::from);
+        let ran = run(dir.path(), "update", &commands).unwrap();
+        assert_eq!(ran.len(), 2);
+        assert!(ran[0].success);
+        assert_eq!(ran[0].output, "done");
+        assert!(!ran[1].success);
+        assert_eq!(ran[1].output, "oops");
+        let stage = std::fs::read_to_string(dir.path().join("stage")).unwrap();
+        assert_eq!(stage, "update\n");
+        assert!(!dir.path().join("never").exists());
+    }
+}
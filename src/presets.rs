@@ -0,0 +1,348 @@
+//! Rule and template presets by language or stack.
+//!
+//! Presets are defined by the configured flowmates checkout, in its
+//! `presets.toml` manifest and in `presets/<name>.toml` files, one preset
+//! each; without either, by the manifest embedded in echo. Besides rules and
+//! templates, a preset names hooks to install and settings to write to
+//! `.echo.toml` when `echo init --preset` selects it.
+
+use crate::config;
+use crate::source_scan::SourceFilter;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Manifest file name at the root of a flowmates checkout.
+pub const MANIFEST_FILE: &str = "presets.toml";
+
+/// Directory of a flowmates checkout holding one `<name>.toml` per preset.
+pub const PRESETS_DIR: &str = "presets";
+
+/// Manifest shipped with echo.
+pub const DEFAULT_MANIFEST: &str = include_str!("../templates/presets.toml");
+
+/// One preset of the manifest.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    /// One line for `echo preset list`.
+    pub description: String,
+    /// Files whose presence at the repository root selects this preset.
+    pub detect: Vec<String>,
+    /// Glob patterns on rule paths, as in `rules.include`.
+    pub rules: Vec<String>,
+    /// Issue template names; empty means every template.
+    pub templates: Vec<String>,
+    /// Hooks of the hooks manifest to install.
+    pub hooks: Vec<String>,
+    /// `.echo.toml` settings, written where the file does not set them.
+    pub settings: toml::Table,
+}
+
+/// Presets by name.
+pub type Manifest = BTreeMap<String, Preset>;
+
+/// What a set of presets installs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    /// Rule patterns; empty means every rule.
+    pub rules: Vec<String>,
+    /// Template names; `None` means every template.
+    pub templates: Option<Vec<String>>,
+}
+
+/// Load the presets of the flowmates checkout if it defines any, else the
+/// embedded ones. A `presets/<name>.toml` file replaces the preset of the
+/// same name of `presets.toml`.
+pub fn load(flowmates_dir: Option<&Path>) -> Result<Manifest> {
+    let Some(dir) = flowmates_dir else {
+        return parse(DEFAULT_MANIFEST);
+    };
+    let path = dir.join(MANIFEST_FILE);
+    let mut manifest = if path.is_file() {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Some(parse(&content).with_context(|| format!("invalid {}", path.display()))?)
+    } else {
+        None
+    };
+    let presets_dir = dir.join(PRESETS_DIR);
+    if presets_dir.is_dir() {
+        let manifest = manifest.get_or_insert_with(Manifest::new);
+        let filter = SourceFilter::load(dir)?;
+        for entry in fs::read_dir(&presets_dir)? {
+            let path = entry?.path();
+            if filter.skips(path.strip_prefix(dir).unwrap_or(&path)) {
+                continue;
+            }
+            let Some(name) = path
+                .file_stem()
+                .filter(|_| path.extension().is_some_and(|ext| ext == "toml"))
+                .map(|stem| stem.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let preset =
+                toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))?;
+            manifest.insert(name, preset);
+        }
+    }
+    match manifest {
+        Some(manifest) => Ok(manifest),
+        None => parse(DEFAULT_MANIFEST),
+    }
+}
+
+/// Parse a presets manifest.
+pub fn parse(content: &str) -> Result<Manifest> {
+    Ok(toml::from_str(content)?)
+}
+
+/// A preset selected by the files of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detected {
+    pub name: String,
+    /// The `detect` entries that matched.
+    pub found: Vec<String>,
+}
+
+impl Detected {
+    /// Why the preset was selected, e.g. `found Cargo.toml`.
+    pub fn reason(&self) -> String {
+        format!("found {}", self.found.join(", "))
+    }
+}
+
+/// Presets whose `detect` files exist under `root`, sorted by name. An
+/// entry joining files with `+`, e.g. `package.json + tsconfig.json`,
+/// matches when all of them exist.
+pub fn detections(root: &Path, manifest: &Manifest) -> Vec<Detected> {
+    manifest
+        .iter()
+        .filter_map(|(name, preset)| {
+            let found: Vec<String> = preset
+                .detect
+                .iter()
+                .filter(|entry| entry.split('+').all(|file| root.join(file.trim()).exists()))
+                .cloned()
+                .collect();
+            (!found.is_empty()).then(|| Detected {
+                name: name.clone(),
+                found,
+            })
+        })
+        .collect()
+}
+
+/// Names of the [`detections`].
+pub fn detect(root: &Path, manifest: &Manifest) -> Vec<String> {
+    detections(root, manifest)
+        .into_iter()
+        .map(|detected| detected.name)
+        .collect()
+}
+
+/// Presets to apply: the configured ones, else the detected ones.
+pub fn resolve(root: &Path, configured: &[String], manifest: &Manifest) -> Vec<String> {
+    if configured.is_empty() {
+        detect(root, manifest)
+    } else {
+        configured.to_vec()
+    }
+}
+
+/// Combine the named presets. No presets select everything.
+pub fn select(manifest: &Manifest, names: &[String]) -> Result<Selection> {
+    let mut selection = Selection::default();
+    let mut templates = Vec::new();
+    let mut all_templates = names.is_empty();
+    for name in names {
+        let Some(preset) = manifest.get(name) else {
+            bail!(
+                "unknown preset '{}' (available: {})",
+                name,
+                manifest.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+        };
+        for pattern in &preset.rules {
+            if !selection.rules.contains(pattern) {
+                selection.rules.push(pattern.clone());
+            }
+        }
+        all_templates |= preset.templates.is_empty();
+        for template in &preset.templates {
+            if !templates.contains(template) {
+                templates.push(template.clone());
+            }
+        }
+    }
+    if !all_templates {
+        selection.templates = Some(templates);
+    }
+    Ok(selection)
+}
+
+/// Hooks of the named presets, without duplicates.
+pub fn hooks(manifest: &Manifest, names: &[String]) -> Vec<String> {
+    let mut hooks = Vec::new();
+    for preset in names.iter().filter_map(|name| manifest.get(name)) {
+        for hook in &preset.hooks {
+            if !hooks.contains(hook) {
+                hooks.push(hook.clone());
+            }
+        }
+    }
+    hooks
+}
+
+/// Write the settings of the named presets to `.echo.toml`, keeping the
+/// values it already has; the first preset setting a key wins. Returns
+/// whether anything was added.
+pub fn apply_settings(root: &Path, manifest: &Manifest, names: &[String]) -> Result<bool> {
+    let mut settings = toml_edit::Table::new();
+    for preset in names.iter().filter_map(|name| manifest.get(name)) {
+        let table: toml_edit::DocumentMut = toml::to_string(&preset.settings)?.parse()?;
+        merge(&mut settings, table.as_table());
+    }
+    if settings.is_empty() {
+        return Ok(false);
+    }
+    let mut added = false;
+    config::edit(root, |doc| {
+        added = merge(doc.as_table_mut(), &settings);
+        Ok(())
+    })?;
+    Ok(added)
+}
+
+/// Add the keys of `from` missing from `into`, recursing into tables.
+fn merge(into: &mut toml_edit::Table, from: &toml_edit::Table) -> bool {
+    let mut added = false;
+    for (key, item) in from.iter() {
+        match (into.get_mut(key), item.as_table()) {
+            (None, _) => {
+                into.insert(key, item.clone());
+                added = true;
+            }
+            (Some(existing), Some(table)) => {
+                if let Some(existing) = existing.as_table_mut() {
+                    added |= merge(existing, table);
+                }
+            }
+            (Some(_), None) => {}
+        }
+    }
+    added
+}
+
+/// Record `names` as the `presets` of `.echo.toml`.
+pub fn record(root: &Path, names: &[String]) -> Result<()> {
+    config::edit(root, |doc| {
+        doc.insert(
+            "presets",
+            toml_edit::value(names.iter().collect::<toml_edit::Array>()),
+        );
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_manifest_parses() {
+        let manifest = parse(DEFAULT_MANIFEST).unwrap();
+        assert!(manifest.contains_key("minimal"));
+        assert_eq!(manifest["rust"].detect, vec!["Cargo.toml"]);
+    }
+
+    #[test]
+    fn detects_presets_from_manifest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = parse(DEFAULT_MANIFEST).unwrap();
+        assert!(detect(dir.path(), &manifest).is_empty());
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        fs::write(dir.path().join("package.json"), "{}").unwrap();
+        assert_eq!(detect(dir.path(), &manifest), vec!["rust", "web"]);
+        fs::write(dir.path().join("tsconfig.json"), "{}").unwrap();
+        fs::write(dir.path().join("Dockerfile"), "").unwrap();
+        let detected = detections(dir.path(), &manifest);
+        let reasons: Vec<_> = detected
+            .iter()
+            .map(|d| format!("{}: {}", d.name, d.reason()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "infra: found Dockerfile",
+                "rust: found Cargo.toml",
+                "typescript: found package.json + tsconfig.json",
+                "web: found package.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn records_presets_in_config() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".echo.toml"), "[lint]\ntitle = false\n").unwrap();
+        record(dir.path(), &["rust".into(), "web".into()]).unwrap();
+        let config = config::Config::load(dir.path()).unwrap();
+        assert_eq!(config.presets, vec!["rust", "web"]);
+        assert!(!config.lint.title);
+        assert_eq!(
+            resolve(dir.path(), &config.presets, &Manifest::new()),
+            vec!["rust", "web"]
+        );
+    }
+
+    #[test]
+    fn loads_presets_from_the_flowmates_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(MANIFEST_FILE), "[a]\nrules = [\"x\"]\n").unwrap();
+        fs::create_dir(dir.path().join(PRESETS_DIR)).unwrap();
+        fs::write(
+            dir.path().join(PRESETS_DIR).join("platform.toml"),
+            "description = \"Platform services\"\nrules = [\"rust/*\"]\nhooks = [\"pre-commit\"]\n\
+             [settings]\nvendor = true\n[settings.lint]\nmax-title-length = 100\ntitle = false\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join(PRESETS_DIR).join("README.md"), "").unwrap();
+        let manifest = load(Some(dir.path())).unwrap();
+        assert_eq!(manifest.keys().collect::<Vec<_>>(), vec!["a", "platform"]);
+        assert_eq!(manifest["platform"].description, "Platform services");
+        let names = vec!["platform".to_string(), "a".to_string()];
+        assert_eq!(hooks(&manifest, &names), vec!["pre-commit"]);
+
+        let repo = tempfile::tempdir().unwrap();
+        fs::write(repo.path().join(".echo.toml"), "[lint]\ntitle = true\n").unwrap();
+        assert!(apply_settings(repo.path(), &manifest, &names).unwrap());
+        let config = config::Config::load(repo.path()).unwrap();
+        assert!(config.vendor);
+        assert!(config.lint.title);
+        assert_eq!(config.lint.max_title_length, 100);
+        assert!(!apply_settings(repo.path(), &manifest, &names).unwrap());
+    }
+
+    #[test]
+    fn combines_presets() {
+        let manifest = parse(
+            "[a]\nrules = [\"x\", \"y\"]\ntemplates = [\"bug\"]\n\
+             [b]\nrules = [\"y\", \"z\"]\ntemplates = [\"feature\"]\n\
+             [all]\nrules = [\"x\"]\n",
+        )
+        .unwrap();
+        let both = select(&manifest, &["a".into(), "b".into()]).unwrap();
+        assert_eq!(both.rules, vec!["x", "y", "z"]);
+        assert_eq!(both.templates, Some(vec!["bug".into(), "feature".into()]));
+        let with_all = select(&manifest, &["a".into(), "all".into()]).unwrap();
+        assert_eq!(with_all.templates, None);
+        assert_eq!(select(&manifest, &[]).unwrap(), Selection::default());
+        assert!(select(&manifest, &["c".into()]).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Named sets of sources, presets and template variables, listed in
+//! [`PROFILES_FILE`] of the user's flowmates configuration directory, so
+//! one machine can serve several organizations without per-repository
+//! configuration.
+//!
+//! A profile is selected with `--profile <name>` or [`PROFILE_VAR`]. Its
+//! values stand in for those `.echo.toml` leaves unset: the flowmates
+//! checkout when the repository names none, the presets when it lists
+//! none, and each template variable it does not define.
+
+use crate::config::Config;
+use crate::template::Variables;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Profiles file name, in the flowmates configuration directory.
+pub const PROFILES_FILE: &str = "profiles.toml";
+
+/// Variable naming the profile to use, as `--profile` does.
+pub const PROFILE_VAR: &str = "ECHO_PROFILE";
+
+/// One profile, a table of [`PROFILES_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// The flowmates checkout of the profile.
+    pub source: Option<PathBuf>,
+    pub presets: Vec<String>,
+    pub variables: Variables,
+}
+
+impl Profile {
+    /// Fill what `config` leaves unset from the profile.
+    pub fn apply(&self, config: &mut Config) {
+        if config.rules.flowmates_dir.is_none() {
+            config.rules.flowmates_dir = self.source.clone();
+        }
+        if config.presets.is_empty() {
+            config.presets = self.presets.clone();
+        }
+        for (name, value) in &self.variables {
+            config
+                .variables
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// The profiles of `config_dir`, by name; none without a profiles file.
+pub fn load(config_dir: &Path) -> Result<BTreeMap<String, Profile>> {
+    let path = config_dir.join(PROFILES_FILE);
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+}
+
+/// The profile `name` of `config_dir`.
+pub fn find(config_dir: &Path, name: &str) -> Result<Profile> {
+    pick(load(config_dir)?, name, &config_dir.join(PROFILES_FILE))
+}
+
+/// The profile `name` of `profiles`, read from `path`.
+pub(crate) fn pick(
+    mut profiles: BTreeMap<String, Profile>,
+    name: &str,
+    path: &Path,
+) -> Result<Profile> {
+    if let Some(profile) = profiles.remove(name) {
+        return Ok(profile);
+    }
+    if profiles.is_empty() {
+        bail!("unknown profile '{}': {} lists none", name, path.display());
+    }
+    bail!(
+        "unknown profile '{}'; {} lists {}",
+        name,
+        path.display(),
+        profiles.into_keys().collect::<Vec<_>>().join(", ")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_what_the_repository_leaves_unset() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+        fs::write(
+            dir.path().join(PROFILES_FILE),
+            "[work]\nsource = \"/work/flowmates\"\npresets = [\"rust\"]\n\
+             variables = { TEAM = \"platform\", ORG = \"acme\" }\n\n[oss]\n",
+        )
+        .unwrap();
+        let work = find(dir.path(), "work").unwrap();
+
+        let mut config = Config::default();
+        config.variables.insert("TEAM".into(), "core".into());
+        work.apply(&mut config);
+        assert_eq!(
+            config.rules.flowmates_dir,
+            Some(PathBuf::from("/work/flowmates"))
+        );
+        assert_eq!(config.presets, vec!["rust"]);
+        assert_eq!(config.variables["TEAM"], "core");
+        assert_eq!(config.variables["ORG"], "acme");
+
+        let err = find(dir.path(), "personal").unwrap_err().to_string();
+        assert!(err.ends_with("lists oss, work"), "{}", err);
+    }
+}
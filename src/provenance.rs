@@ -0,0 +1,168 @@
+//! Provenance header stamped into installed files, so a reviewer can tell
+//! where a vendored rule, template or hook script came from.
+//!
+//! The header is a single comment line, placed after the front-matter of
+//! markdown files and after the shebang of scripts:
+//!
+//! ```text
+//! <!-- managed by echo, do not edit; source: flowmates:rules/style.mdc; version: 1a2b3c4; sha256: …; installed: 2024-03-01 -->
+//! ```
+//!
+//! `sha256` hashes the contents without the header, so comparisons with
+//! the source ignore it: see [`strip`].
+
+use std::borrow::Cow;
+
+/// Start of every header, after the comment opener.
+pub const MARKER: &str = "managed by echo, do not edit;";
+
+/// How the header is commented out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comment {
+    /// `<!-- … -->`, for markdown and `.mdc` files.
+    Html,
+    /// `# …`, for shell scripts.
+    Hash,
+}
+
+/// What a header records.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// Where the file came from, e.g. `flowmates:rules/style.mdc`.
+    pub source: String,
+    /// Revision of the source, e.g. a commit or `echo 0.1.0`.
+    pub version: Option<String>,
+    /// SHA-256 of the contents installed, without the header.
+    pub hash: String,
+    /// Day of the install, `YYYY-MM-DD`.
+    pub installed: Option<String>,
+}
+
+impl Provenance {
+    /// The header line, without its newline.
+    pub fn line(&self, comment: Comment) -> String {
+        let mut fields = vec![format!("source: {}", self.source)];
+        if let Some(version) = &self.version {
+            fields.push(format!("version: {}", version));
+        }
+        fields.push(format!("sha256: {}", self.hash));
+        if let Some(installed) = &self.installed {
+            fields.push(format!("installed: {}", installed));
+        }
+        let text = format!("{} {}", MARKER, fields.join("; "));
+        match comment {
+            Comment::Html => format!("<!-- {} -->", text),
+            Comment::Hash => format!("# {}", text),
+        }
+    }
+
+    /// Parse a header line.
+    pub fn parse(line: &str) -> Option<Provenance> {
+        let text = line.trim();
+        let text = match text.strip_prefix("<!--") {
+            Some(rest) => rest.strip_suffix("-->")?,
+            None => text.strip_prefix('#')?,
+        };
+        let fields = text.trim().strip_prefix(MARKER)?;
+        let mut provenance = Provenance::default();
+        for field in fields.split(';') {
+            let Some((key, value)) = field.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().to_string();
+            match key.trim() {
+                "source" => provenance.source = value,
+                "version" => provenance.version = Some(value),
+                "sha256" => provenance.hash = value,
+                "installed" => provenance.installed = Some(value),
+                _ => {}
+            }
+        }
+        Some(provenance)
+    }
+}
+
+/// `content` with the header of `provenance` inserted after its
+/// front-matter or shebang.
+pub fn stamp(content: &str, comment: Comment, provenance: &Provenance) -> String {
+    let at = header_offset(content);
+    format!(
+        "{}{}\n{}",
+        &content[..at],
+        provenance.line(comment),
+        &content[at..]
+    )
+}
+
+/// Where the header goes: after a leading front-matter block or shebang
+/// line, else at the top.
+fn header_offset(content: &str) -> usize {
+    if content.starts_with("#!") {
+        return content.find('\n').map_or(content.len(), |end| end + 1);
+    }
+    if let Some(rest) = content.strip_prefix("---\n") {
+        if let Some(end) = rest.find("\n---\n") {
+            return 4 + end + 5;
+        }
+    }
+    0
+}
+
+/// The header of `content`, if it has one.
+pub fn find(content: &str) -> Option<Provenance> {
+    content
+        .lines()
+        .take(HEADER_LINES)
+        .find_map(Provenance::parse)
+}
+
+/// `content` without its header.
+pub fn strip(content: &str) -> Cow<'_, str> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n').take(HEADER_LINES) {
+        if Provenance::parse(line).is_some() && line.ends_with('\n') {
+            let mut stripped = String::with_capacity(content.len() - line.len());
+            stripped.push_str(&content[..offset]);
+            stripped.push_str(&content[offset + line.len()..]);
+            return Cow::Owned(stripped);
+        }
+        offset += line.len();
+    }
+    Cow::Borrowed(content)
+}
+
+/// Lines searched for a header: front-matter rarely runs longer.
+const HEADER_LINES: usize = 30;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_after_front_matter_and_strips_back() {
+        let provenance = Provenance {
+            source: "flowmates:rules/style.mdc".into(),
+            version: Some("1a2b3c4".into()),
+            hash: "ab".repeat(32),
+            installed: Some("2024-03-01".into()),
+        };
+        let rule = "---\ndescription: Style\n---\n# Style\n";
+        let stamped = stamp(rule, Comment::Html, &provenance);
+        assert!(stamped.starts_with("---\ndescription: Style\n---\n<!-- managed by echo"));
+        assert!(stamped.ends_with("installed: 2024-03-01 -->\n# Style\n"));
+        assert_eq!(find(&stamped), Some(provenance.clone()));
+        assert_eq!(strip(&stamped), rule);
+
+        let script = "#!/bin/sh\nexit 0\n";
+        let stamped = stamp(script, Comment::Hash, &provenance);
+        assert!(stamped.starts_with("#!/bin/sh\n# managed by echo, do not edit; source: "));
+        assert_eq!(strip(&stamped), script);
+
+        assert_eq!(
+            stamp("plain\n", Comment::Html, &provenance).lines().count(),
+            2
+        );
+        assert!(matches!(strip(rule), Cow::Borrowed(_)));
+        assert_eq!(find(rule), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! `echo prune`: remove installed rules and scripts whose source is gone.
+//!
+//! Only files recorded in the lockfile are considered, so rules and
+//! scripts created in the repository are never touched. Orphans still
+//! matching the recorded hash are removed; edited ones and those listed in
+//! `.echoignore` are kept and reported.
+
+use crate::echoignore::EchoIgnore;
+use crate::hooks;
+use crate::lock::{self, Lock};
+use crate::rules::{self, RulesConfig};
+use crate::scripts;
+use crate::uninstall::prune_empty_parents;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What [`prune`] did, or would do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prune {
+    /// Orphans removed, relative to the root.
+    pub removed: Vec<PathBuf>,
+    /// Orphans kept because they were edited since.
+    pub modified: Vec<PathBuf>,
+    /// Orphans kept because they are listed in `.echoignore`.
+    pub protected: Vec<PathBuf>,
+}
+
+/// Find the installed rules and scripts of `root` whose source no longer
+/// exists, or that the scripts manifest no longer selects, and, with
+/// `apply`, remove them along with their lockfile entries.
+pub fn prune(root: &Path, config: &RulesConfig, apply: bool) -> Result<Prune> {
+    let flowmates_dir = config.flowmates_dir();
+    let sources = rules::sources(flowmates_dir.as_deref())?;
+    let scripts = scripts::sources(flowmates_dir.as_deref())?;
+    let ignore = EchoIgnore::load(root)?;
+    let mut lock = Lock::load(root)?;
+    let mut report = Prune::default();
+    let mut pruned = Vec::new();
+    for file in &lock.files {
+        let orphan = match file.target.as_str() {
+            hooks::LOCK_TARGET => false,
+            scripts::LOCK_TARGET => !scripts.contains(&file.path),
+            _ => !sources.contains_key(&file.name),
+        };
+        if !orphan {
+            continue;
+        }
+        let path = root.join(&file.path);
+        if !path.is_file() {
+            pruned.push(file.path.clone());
+            continue;
+        }
+        if ignore.is_protected(&file.path) {
+            report.protected.push(file.path.clone());
+            continue;
+        }
+        let content =
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        if lock::hash_installed_bytes(&content) != file.hash {
+            report.modified.push(file.path.clone());
+            continue;
+        }
+        report.removed.push(file.path.clone());
+        pruned.push(file.path.clone());
+    }
+    if !apply {
+        return Ok(report);
+    }
+    for rel_path in &pruned {
+        // The link echo made to a deleted source is broken.
+        let echo_link = lock.get(rel_path).is_some_and(rules::is_link);
+        for path in [root.join(rel_path), root.join(lock::base_path(rel_path))] {
+            if path.is_file() || echo_link && path.is_symlink() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?;
+                prune_empty_parents(root, &path);
+            }
+        }
+        lock.remove(rel_path);
+    }
+    if !pruned.is_empty() {
+        lock.save(root)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_ops::RealFs;
+    use crate::rules::rule_path;
+
+    #[test]
+    fn removes_unedited_rules_and_scripts_deleted_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let sources = flowmates.path().join("rules");
+        fs::create_dir_all(&sources).unwrap();
+        for name in ["kept", "gone", "edited"] {
+            fs::write(sources.join(format!("{}.mdc", name)), name).unwrap();
+        }
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            include: vec!["kept.mdc".into(), "gone.mdc".into(), "edited.mdc".into()],
+            ..RulesConfig::default()
+        };
+        rules::sync(dir.path(), &config, false).unwrap();
+        let scripts_dir = flowmates.path().join(scripts::SCRIPTS_DIR);
+        fs::create_dir_all(&scripts_dir).unwrap();
+        fs::write(scripts_dir.join("kept.sh"), "kept").unwrap();
+        fs::write(scripts_dir.join("old.sh"), "old").unwrap();
+        let keep = |_: &lock::Conflict| Ok(lock::Resolution::Keep);
+        scripts::install(&RealFs, dir.path(), flowmates.path(), keep).unwrap();
+        fs::remove_file(scripts_dir.join("old.sh")).unwrap();
+        fs::write(dir.path().join(rule_path("local")), "mine").unwrap();
+        fs::write(dir.path().join(rule_path("edited")), "changed").unwrap();
+        fs::remove_file(sources.join("gone.mdc")).unwrap();
+        fs::remove_file(sources.join("edited.mdc")).unwrap();
+
+        let dry_run = prune(dir.path(), &config, false).unwrap();
+        assert_eq!(
+            dry_run.removed,
+            vec![rule_path("gone"), PathBuf::from("scripts/old.sh")]
+        );
+        assert_eq!(dry_run.modified, vec![rule_path("edited")]);
+        assert!(dir.path().join(rule_path("gone")).is_file());
+
+        assert_eq!(prune(dir.path(), &config, true).unwrap(), dry_run);
+        assert!(!dir.path().join(rule_path("gone")).exists());
+        assert!(!dir
+            .path()
+            .join(lock::base_path(&rule_path("gone")))
+            .exists());
+        assert!(dir.path().join(rule_path("local")).is_file());
+        assert!(dir.path().join(rule_path("kept")).is_file());
+        assert!(!dir.path().join("scripts/old.sh").exists());
+        assert!(dir.path().join("scripts/kept.sh").is_file());
+        let lock = Lock::load(dir.path()).unwrap();
+        assert!(lock.get(&rule_path("gone")).is_none());
+        assert!(lock.get(&rule_path("edited")).is_some());
+    }
+}
@@ -0,0 +1,106 @@
+//! Repositories registered with flowmates, listed in [`PROJECTS_FILE`] of
+//! the user's flowmates configuration directory.
+
+use crate::fs_ops;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Registry file name, in the flowmates configuration directory (see
+/// [`Environment::config_dir`](crate::environment::Environment::config_dir)).
+pub const PROJECTS_FILE: &str = "projects.toml";
+
+/// Contents of the registry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Registry {
+    /// The canonical flowmates checkout, registered by `echo init
+    /// --source-repo`; used when neither `.echo.toml` nor `$FLOWMATES_DIR`
+    /// names one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    /// Absolute paths of the repositories' roots.
+    pub projects: Vec<PathBuf>,
+    /// Minisign public keys trusted to sign flowmates checkouts, for
+    /// every repository (see [`crate::checksums`]).
+    #[serde(rename = "trusted-keys", skip_serializing_if = "Vec::is_empty")]
+    pub trusted_keys: Vec<String>,
+}
+
+impl Registry {
+    /// Load the registry of `config_dir`; empty when absent.
+    pub fn load(config_dir: &Path) -> Result<Registry> {
+        let path = config_dir.join(PROJECTS_FILE);
+        if !path.is_file() {
+            return Ok(Registry::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    /// Write the registry to `config_dir`, creating the directory.
+    pub fn save(&self, config_dir: &Path) -> Result<()> {
+        fs::create_dir_all(config_dir)
+            .with_context(|| format!("failed to create {}", config_dir.display()))?;
+        let path = config_dir.join(PROJECTS_FILE);
+        fs_ops::write_atomic(&path, toml::to_string(self)?.as_bytes(), true)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Register the repository at `root`; returns false if it already was.
+    pub fn add(&mut self, root: &Path) -> Result<bool> {
+        let root = fs::canonicalize(root)
+            .with_context(|| format!("cannot register {}", root.display()))?;
+        if self.projects.contains(&root) {
+            return Ok(false);
+        }
+        self.projects.push(root);
+        self.projects.sort();
+        Ok(true)
+    }
+
+    /// Register the flowmates checkout at `dir` as the canonical source;
+    /// returns false if it already was.
+    pub fn set_source(&mut self, dir: &Path) -> Result<bool> {
+        let dir =
+            fs::canonicalize(dir).with_context(|| format!("cannot register {}", dir.display()))?;
+        if self.source.as_ref() == Some(&dir) {
+            return Ok(false);
+        }
+        self.source = Some(dir);
+        Ok(true)
+    }
+
+    /// Unregister `root`, which may no longer exist; returns whether it
+    /// was registered.
+    pub fn remove(&mut self, root: &Path) -> bool {
+        let resolved = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let count = self.projects.len();
+        self.projects.retain(|p| p != root && *p != resolved);
+        self.projects.len() < count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_and_removes_repositories() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let mut registry = Registry::load(config_dir.path()).unwrap();
+        assert!(registry.add(repo.path()).unwrap());
+        assert!(!registry.add(&repo.path().join(".")).unwrap());
+        assert!(registry.add(&repo.path().join("missing")).is_err());
+        registry.save(config_dir.path()).unwrap();
+
+        let mut loaded = Registry::load(config_dir.path()).unwrap();
+        assert_eq!(loaded, registry);
+        assert!(loaded.remove(repo.path()));
+        assert!(!loaded.remove(repo.path()));
+        assert!(loaded.projects.is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+//! Machine-readable report of `echo init`, `echo update` and `echo check`,
+//! written as JSON with `--report <file>`.
+//!
+//! The layout is versioned by [`SCHEMA_VERSION`]: fields may be added
+//! within a version, anything else bumps it.
+
+use crate::agent::Update;
+use crate::fs_ops::Change;
+use crate::issues::validate::Violation;
+use crate::lock::Outcome;
+use crate::post_run::Ran;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the report layout.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Command a report is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    Init,
+    Update,
+    Check,
+}
+
+/// What a run did, file by file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub schema_version: u32,
+    pub command: Command,
+    /// Agent instruction files written.
+    pub agent: Vec<AgentFile>,
+    /// Rules and templates installed, including those already up to date.
+    pub files: Vec<InstalledFile>,
+    /// Drift and workflow violations found.
+    pub problems: Vec<Violation>,
+    /// Every file written and directory created, in order.
+    pub changes: Vec<Change>,
+    /// What kept the run from provisioning everything, e.g. files kept
+    /// because they were edited locally.
+    pub warnings: Vec<String>,
+    /// Post-run commands of `.echo.toml` that ran.
+    pub post_run: Vec<Ran>,
+    /// Version of the rule pack installed from, when it declares one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
+}
+
+/// An agent instruction file and what writing it did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AgentFile {
+    pub path: PathBuf,
+    pub update: Update,
+}
+
+/// A rule or template and what installing it did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstalledFile {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+}
+
+impl Report {
+    pub fn new(command: Command) -> Report {
+        Report {
+            schema_version: SCHEMA_VERSION,
+            command,
+            agent: Vec::new(),
+            files: Vec::new(),
+            problems: Vec::new(),
+            changes: Vec::new(),
+            warnings: Vec::new(),
+            post_run: Vec::new(),
+            pack: None,
+        }
+    }
+
+    /// Record the rules and templates of an install, with a warning for
+    /// each left different from its source.
+    pub fn add_installed(&mut self, installed: Vec<(PathBuf, Outcome)>) {
+        for (path, outcome) in installed {
+            match outcome {
+                Outcome::Modified => self
+                    .warnings
+                    .push(format!("{} kept: modified locally", path.display())),
+                Outcome::Conflicted => self
+                    .warnings
+                    .push(format!("{} merged with conflicts", path.display())),
+                _ => {}
+            }
+            self.files.push(InstalledFile { path, outcome });
+        }
+    }
+
+    /// Record `changes`, with paths made relative to `root`.
+    pub fn add_changes(&mut self, root: &Path, changes: Vec<Change>) {
+        self.changes.extend(changes.into_iter().map(|change| {
+            Change {
+                path: change
+                    .path
+                    .strip_prefix(root)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(change.path),
+                ..change
+            }
+        }));
+    }
+
+    /// One line counting the files by what happened to them and the
+    /// warnings, e.g. `3 installed, 12 up to date, 1 kept, 1 warning(s)`.
+    pub fn summary(&self) -> String {
+        let count = |outcomes: &[Outcome]| {
+            self.files
+                .iter()
+                .filter(|file| outcomes.contains(&file.outcome))
+                .count()
+        };
+        let counts = [
+            (count(&[Outcome::Installed]), "installed"),
+            (count(&[Outcome::Updated]), "updated"),
+            (count(&[Outcome::Merged, Outcome::Conflicted]), "merged"),
+            (count(&[Outcome::UpToDate]), "up to date"),
+            (count(&[Outcome::Modified, Outcome::Protected]), "kept"),
+            (self.warnings.len(), "warning(s)"),
+        ];
+        let parts: Vec<_> = counts
+            .iter()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, label)| format!("{} {}", n, label))
+            .collect();
+        if parts.is_empty() {
+            return "nothing to install".to_string();
+        }
+        parts.join(", ")
+    }
+
+    /// Write the report as pretty-printed JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n").with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_a_stable_layout() {
+        let mut report = Report::new(Command::Update);
+        report.agent.push(AgentFile {
+            path: "AGENT.md".into(),
+            update: Update::Unchanged,
+        });
+        report.files.push(InstalledFile {
+            path: ".cursor/rules/style.mdc".into(),
+            outcome: Outcome::UpToDate,
+        });
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "command": "update",
+                "agent": [{"path": "AGENT.md", "update": "unchanged"}],
+                "files": [{"path": ".cursor/rules/style.mdc", "outcome": "up_to_date"}],
+                "problems": [],
+                "changes": [],
+                "warnings": [],
+                "post_run": [],
+            })
+        );
+    }
+
+    #[test]
+    fn warns_about_files_left_different() {
+        let mut report = Report::new(Command::Init);
+        report.add_installed(vec![
+            ("a.mdc".into(), Outcome::Installed),
+            ("b.mdc".into(), Outcome::Modified),
+            ("c.mdc".into(), Outcome::Protected),
+        ]);
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(report.warnings, vec!["b.mdc kept: modified locally"]);
+        assert_eq!(report.summary(), "1 installed, 2 kept, 1 warning(s)");
+        assert_eq!(Report::new(Command::Init).summary(), "nothing to install");
+    }
+}
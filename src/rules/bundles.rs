@@ -0,0 +1,157 @@
+//! Rule bundles: named sets of rules a flowmates checkout defines in
+//! [`MANIFEST_FILE`], with the rules each rule depends on.
+//!
+//! ```toml
+//! [bundles]
+//! rust-backend = ["core", "rust", "sqlx"]
+//!
+//! [requires]
+//! sqlx = ["rust"]
+//! ```
+//!
+//! Installing a bundle installs the closure of its rules under
+//! `requires`, and records the bundle in `[rules] bundles` of
+//! `.echo.toml` so `echo update` keeps it installed.
+
+use super::RulesConfig;
+use crate::config;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Manifest file name at the root of a flowmates checkout.
+pub const MANIFEST_FILE: &str = "bundles.toml";
+
+/// Contents of [`MANIFEST_FILE`]. Rules are named as in `echo rules
+/// list`, with or without their `.mdc` extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Manifest {
+    /// Rules of each bundle, by bundle name.
+    pub bundles: BTreeMap<String, Vec<String>>,
+    /// Rules each rule needs installed alongside it.
+    pub requires: BTreeMap<String, Vec<String>>,
+}
+
+impl Manifest {
+    /// Load the manifest of `flowmates_dir`; empty when there is none.
+    pub fn load(flowmates_dir: Option<&Path>) -> Result<Manifest> {
+        let Some(path) = flowmates_dir.map(|dir| dir.join(MANIFEST_FILE)) else {
+            return Ok(Manifest::default());
+        };
+        if !path.is_file() {
+            return Ok(Manifest::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    /// Rules of the bundles `names` and every rule they require, sorted.
+    pub fn resolve(&self, names: &[String]) -> Result<BTreeSet<String>> {
+        let mut pending = Vec::new();
+        for name in names {
+            let Some(rules) = self.bundles.get(name) else {
+                bail!(
+                    "unknown bundle '{}' (available: {})",
+                    name,
+                    self.bundles.keys().cloned().collect::<Vec<_>>().join(", ")
+                );
+            };
+            pending.extend(rules.iter().map(|rule| rule_name(rule)));
+        }
+        let requires: BTreeMap<String, &Vec<String>> = self
+            .requires
+            .iter()
+            .map(|(rule, required)| (rule_name(rule), required))
+            .collect();
+        let mut closure = BTreeSet::new();
+        while let Some(rule) = pending.pop() {
+            if let Some(required) = requires.get(&rule).filter(|_| !closure.contains(&rule)) {
+                pending.extend(required.iter().map(|rule| rule_name(rule)));
+            }
+            closure.insert(rule);
+        }
+        Ok(closure)
+    }
+}
+
+/// Check that the bundles `names` of the flowmates checkout resolve to
+/// available rules, and record them in `.echo.toml`. Returns the rules.
+pub fn add(root: &Path, config: &RulesConfig, names: &[String]) -> Result<BTreeSet<String>> {
+    let flowmates_dir = config.flowmates_dir();
+    let rules = Manifest::load(flowmates_dir.as_deref())?.resolve(names)?;
+    let sources = super::sources(flowmates_dir.as_deref())?;
+    let missing: Vec<&str> = rules
+        .iter()
+        .filter(|rule| !sources.contains_key(*rule))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        bail!("bundled rule(s) not found: {}", missing.join(", "));
+    }
+    record(root, names)?;
+    Ok(rules)
+}
+
+/// `name` without its `.mdc` extension.
+fn rule_name(name: &str) -> String {
+    name.strip_suffix(".mdc").unwrap_or(name).to_string()
+}
+
+/// Add `names` to the `bundles` of `[rules]` in `.echo.toml`.
+pub fn record(root: &Path, names: &[String]) -> Result<()> {
+    config::edit(root, |doc| {
+        let rules = doc
+            .entry("rules")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .context("[rules] is not a table")?;
+        let list = rules
+            .entry("bundles")
+            .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .context("rules.bundles is not an array")?;
+        for name in names {
+            if !list.iter().any(|v| v.as_str() == Some(name)) {
+                list.push(name.as_str());
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_the_closure_of_bundles() {
+        let manifest: Manifest = toml::from_str(
+            "[bundles]\nrust-backend = [\"core.mdc\", \"rust\", \"sqlx.mdc\"]\nweb = [\"web/react\"]\n\
+             [requires]\n\"sqlx.mdc\" = [\"rust\", \"db/migrations\"]\n\"db/migrations\" = [\"db/style\"]\n\
+             \"web/react\" = [\"web/react\"]\n",
+        )
+        .unwrap();
+        let rules = manifest.resolve(&["rust-backend".into()]).unwrap();
+        assert_eq!(
+            rules.into_iter().collect::<Vec<_>>(),
+            vec!["core", "db/migrations", "db/style", "rust", "sqlx"]
+        );
+        assert_eq!(manifest.resolve(&["web".into()]).unwrap().len(), 1);
+        let err = manifest.resolve(&["go".into()]).unwrap_err().to_string();
+        assert!(err.contains("available: rust-backend, web"));
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            Manifest::load(Some(dir.path())).unwrap(),
+            Manifest::default()
+        );
+        record(dir.path(), &["web".into()]).unwrap();
+        record(dir.path(), &["web".into(), "rust-backend".into()]).unwrap();
+        let config = config::Config::load(dir.path()).unwrap();
+        assert_eq!(config.rules.bundles, vec!["web", "rust-backend"]);
+    }
+}
@@ -0,0 +1,98 @@
+//! Entries of [`CHANGELOG_FILE`], listing the rules an `echo update`
+//! changed and the upstream commits behind each change, for reviewers of
+//! the pull request that bumps vendored rules.
+
+use super::outdated::Outdated;
+use crate::fs_ops;
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Changelog path, relative to the repository root.
+pub const CHANGELOG_FILE: &str = "CHANGELOG.echo.md";
+
+const HEADING: &str = "# Rule changes\n";
+
+/// One update's worth of rule changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Day of the update, `YYYY-MM-DD`.
+    pub date: String,
+    /// Revisions of the flowmates checkout before and after, when known.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub rules: Vec<Outdated>,
+}
+
+impl Entry {
+    /// The entry as markdown: a heading with the date and revision range,
+    /// then each rule with its commits.
+    pub fn render(&self) -> String {
+        let short = |revision: &str| revision.chars().take(7).collect::<String>();
+        let mut out = format!("## {}", self.date);
+        if let (Some(from), Some(to)) = (&self.from, &self.to) {
+            let _ = write!(out, " (flowmates {}..{})", short(from), short(to));
+        }
+        out.push_str("\n\n");
+        for rule in &self.rules {
+            let _ = writeln!(out, "- `{}`", rule.path.display());
+            for commit in &rule.commits {
+                let _ = writeln!(out, "  - {}", commit);
+            }
+        }
+        out
+    }
+}
+
+/// Add `entry` at the top of the changelog of `root`, creating it.
+pub fn prepend(root: &Path, entry: &Entry) -> Result<()> {
+    let path = root.join(CHANGELOG_FILE);
+    let current = if path.is_file() {
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", CHANGELOG_FILE))?
+    } else {
+        String::new()
+    };
+    let rest = current.strip_prefix(HEADING).unwrap_or(&current);
+    let rest = rest.trim_start_matches('\n');
+    let mut content = format!("{}\n{}", HEADING, entry.render());
+    if !rest.is_empty() {
+        content.push('\n');
+        content.push_str(rest);
+    }
+    fs_ops::write_atomic(&path, content.as_bytes(), false)
+        .with_context(|| format!("failed to write {}", CHANGELOG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{rule_path, Source};
+
+    #[test]
+    fn prepends_entries_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = |date: &str, commit: &str| Entry {
+            date: date.to_string(),
+            from: Some("1111111aaaa".into()),
+            to: Some("2222222bbbb".into()),
+            rules: vec![Outdated {
+                name: "style".into(),
+                path: rule_path("style"),
+                source: Source::Flowmates,
+                modified: false,
+                commits: vec![commit.to_string()],
+            }],
+        };
+        prepend(dir.path(), &entry("2024-03-01", "1234567 Prefer tabs")).unwrap();
+        prepend(dir.path(), &entry("2024-03-02", "89abcde Wrap at 100")).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(CHANGELOG_FILE)).unwrap(),
+            "# Rule changes\n\n\
+             ## 2024-03-02 (flowmates 1111111..2222222)\n\n\
+             - `.cursor/rules/style.mdc`\n  - 89abcde Wrap at 100\n\n\
+             ## 2024-03-01 (flowmates 1111111..2222222)\n\n\
+             - `.cursor/rules/style.mdc`\n  - 1234567 Prefer tabs\n"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Detection of rules that Cursor may apply together with contradictory
+//! guidance.
+//!
+//! Two rules conflict when their scopes overlap and they cover the same
+//! topic, i.e. share a section heading such as `## Formatting`. Scopes
+//! overlap when both rules are always applied, or when a glob of one rule
+//! matches a path built from a glob of the other: `**/*.rs` and
+//! `src/**/*.rs` overlap, `*.rs` and `*.py` do not. The check is a
+//! heuristic; it cannot read the instructions themselves.
+
+use super::mdc::{self, FrontMatter};
+use globset::Glob;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Two rules with overlapping scopes and common topics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub first: String,
+    pub second: String,
+    /// What makes the scopes overlap: `every file (alwaysApply)` or
+    /// `<glob> ~ <glob>`.
+    pub scope: String,
+    /// Section headings found in both rules, lower-cased.
+    pub topics: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} both apply to {} and cover: {}",
+            self.first,
+            self.second,
+            self.scope,
+            self.topics.join(", ")
+        )
+    }
+}
+
+/// Find conflicts among `(name, content)` rules, in input order.
+pub fn check(rules: &[(String, String)]) -> Vec<Conflict> {
+    let parsed: Vec<_> = rules
+        .iter()
+        .map(|(name, content)| {
+            let front_matter = mdc::parse(content)
+                .ok()
+                .and_then(|mdc| mdc.front_matter)
+                .unwrap_or_default();
+            (name, front_matter, topics(content))
+        })
+        .collect();
+    let mut conflicts = Vec::new();
+    for (i, (first, first_fm, first_topics)) in parsed.iter().enumerate() {
+        for (second, second_fm, second_topics) in &parsed[i + 1..] {
+            let Some(scope) = overlap(first_fm, second_fm) else {
+                continue;
+            };
+            let topics: Vec<_> = first_topics.intersection(second_topics).cloned().collect();
+            if !topics.is_empty() {
+                conflicts.push(Conflict {
+                    first: first.to_string(),
+                    second: second.to_string(),
+                    scope,
+                    topics,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+fn overlap(a: &FrontMatter, b: &FrontMatter) -> Option<String> {
+    if a.always_apply == Some(true) && b.always_apply == Some(true) {
+        return Some("every file (alwaysApply)".to_string());
+    }
+    a.globs.iter().find_map(|x| {
+        b.globs
+            .iter()
+            .find(|y| globs_overlap(x, y))
+            .map(|y| format!("{} ~ {}", x, y))
+    })
+}
+
+fn globs_overlap(a: &str, b: &str) -> bool {
+    let matches = |glob: &str, path: &str| {
+        Glob::new(glob).is_ok_and(|glob| glob.compile_matcher().is_match(path))
+    };
+    a == b || matches(a, &sample(b)) || matches(b, &sample(a))
+}
+
+/// A path matched by `glob`: wildcards become `x`, classes and
+/// alternations their first option.
+fn sample(glob: &str) -> String {
+    let mut out = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                while chars.next_if_eq(&'*').is_some() {}
+                out.push('x');
+            }
+            '?' => out.push('x'),
+            '\\' => out.extend(chars.next()),
+            '[' => {
+                let negated = chars.next_if(|c| *c == '!' || *c == '^').is_some();
+                let mut class = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' && !class.is_empty() {
+                        break;
+                    }
+                    class.push(c);
+                }
+                if negated {
+                    out.extend("xyz_0".chars().find(|c| !class.contains(*c)));
+                } else {
+                    out.extend(class.chars().next());
+                }
+            }
+            '{' => {
+                let mut depth = 1;
+                let mut first = true;
+                for c in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' if depth == 1 => break,
+                        '}' => depth -= 1,
+                        ',' if depth == 1 => first = false,
+                        _ if first => out.push(c),
+                        _ => {}
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Normalized `##`-and-deeper headings: lower case, letters and digits.
+fn topics(content: &str) -> BTreeSet<String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("##"))
+        .map(|heading| {
+            heading
+                .chars()
+                .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        })
+        .filter(|topic| !topic.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, front_matter: &str, body: &str) -> (String, String) {
+        (
+            name.to_string(),
+            format!("---\n{}\n---\n# {}\n{}", front_matter, name, body),
+        )
+    }
+
+    #[test]
+    fn samples_globs() {
+        assert_eq!(sample("**/*.rs"), "x/x.rs");
+        assert_eq!(sample("src/{lib,bin}/[!x]?.rs"), "src/lib/yx.rs");
+        assert_eq!(sample("[ab]\\*"), "a*");
+        assert!(globs_overlap("**/*.rs", "src/**/*.rs"));
+        assert!(globs_overlap("*.{ts,tsx}", "web/*.tsx"));
+        assert!(!globs_overlap("*.rs", "*.py"));
+    }
+
+    #[test]
+    fn flags_overlapping_rules_with_common_topics() {
+        let rules = vec![
+            rule("rust", "globs: **/*.rs", "## Formatting\nUse rustfmt.\n"),
+            rule("style", "globs: src/**/*.rs", "## 🎨 Formatting\nTabs.\n"),
+            rule("python", "globs: *.py", "## Formatting\nUse black.\n"),
+            rule("errors", "globs: **/*.rs", "## Errors\nUse anyhow.\n"),
+            rule("a", "alwaysApply: true", "## Commits\nSmall.\n"),
+            rule("b", "alwaysApply: true", "## Commits\nSquash.\n"),
+        ];
+        let conflicts = check(&rules);
+        assert_eq!(
+            conflicts,
+            vec![
+                Conflict {
+                    first: "rust".into(),
+                    second: "style".into(),
+                    scope: "**/*.rs ~ src/**/*.rs".into(),
+                    topics: vec!["formatting".into()],
+                },
+                Conflict {
+                    first: "a".into(),
+                    second: "b".into(),
+                    scope: "every file (alwaysApply)".into(),
+                    topics: vec!["commits".into()],
+                },
+            ]
+        );
+        assert_eq!(
+            conflicts[1].to_string(),
+            "a and b both apply to every file (alwaysApply) and cover: commits"
+        );
+    }
+}
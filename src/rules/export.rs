@@ -0,0 +1,247 @@
+//! Export of installed rules to the instruction files of other agents.
+//!
+//! The enabled rules are rendered as one markdown section between
+//! [`BEGIN_MARKER`] and [`END_MARKER`]; re-exporting replaces that section
+//! and keeps the rest of the file, including the AGENT.md section written
+//! by `echo agent generate`. `[rules.export.<target>]` in `.echo.toml`
+//! selects the rules of each target with `include` / `exclude` globs.
+
+use super::{mdc, rule_files, Patterns, RulesConfig, DISABLED_DIR, RULES_DIR, RULE_EXTENSION};
+use crate::agent::{self, Update};
+use crate::fs_ops;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Start of the exported rules section.
+pub const BEGIN_MARKER: &str = "<!-- echo:rules:begin -->";
+
+/// End of the exported rules section.
+pub const END_MARKER: &str = "<!-- echo:rules:end -->";
+
+/// A tool to export rules to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// `CLAUDE.md`.
+    Claude,
+    /// `.github/copilot-instructions.md`.
+    Copilot,
+    /// `.windsurfrules`.
+    Windsurf,
+    /// `.rules`, read by the Zed assistant.
+    Zed,
+}
+
+impl Target {
+    /// Key of the target under `[rules.export]`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Claude => "claude",
+            Target::Copilot => "copilot",
+            Target::Windsurf => "windsurf",
+            Target::Zed => "zed",
+        }
+    }
+
+    /// File path relative to the repository root.
+    pub fn path(self) -> &'static str {
+        match self {
+            Target::Claude => agent::Target::Claude.path(),
+            Target::Copilot => agent::Target::Copilot.path(),
+            Target::Windsurf => agent::Target::Windsurf.path(),
+            Target::Zed => ".rules",
+        }
+    }
+}
+
+/// `[rules.export.<target>]`: rules exported to one target, matched like
+/// the `[rules]` filters.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Render the rules section for `target` from the enabled installed rules.
+pub fn render(root: &Path, config: &RulesConfig, target: Target) -> Result<String> {
+    let filter = match config.export.get(target.name()) {
+        Some(export) => Patterns::new(
+            &export.include,
+            &export.exclude,
+            &format!("rules.export.{}", target.name()),
+        )?,
+        None => Patterns::new(&[], &[], "rules.export")?,
+    };
+    let mut out = format!(
+        "{}\n# Project rules\n\nExported from `{}/` by `echo rules export`.\n",
+        BEGIN_MARKER, RULES_DIR
+    );
+    for (name, path) in rule_files(&root.join(RULES_DIR), Some(DISABLED_DIR))? {
+        let rel_path = format!("{}.{}", name, RULE_EXTENSION);
+        let file_name = rel_path.rsplit('/').next().unwrap_or(&rel_path);
+        if !filter.matches(&[&rel_path, file_name]) {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let (front_matter, body) = match mdc::parse(&content) {
+            Ok(mdc) => (mdc.front_matter.unwrap_or_default(), mdc.body),
+            Err(_) => (mdc::FrontMatter::default(), content.as_str()),
+        };
+        out.push_str(&format!("\n## {}\n\n", name));
+        if let Some(description) = &front_matter.description {
+            out.push_str(&format!("{}\n\n", description));
+        }
+        if !front_matter.globs.is_empty() {
+            let globs: Vec<_> = front_matter
+                .globs
+                .iter()
+                .map(|g| format!("`{}`", g))
+                .collect();
+            out.push_str(&format!("Applies to {}.\n\n", globs.join(", ")));
+        }
+        let body = demote(body.trim());
+        if !body.is_empty() {
+            out.push_str(&body);
+            out.push('\n');
+        }
+    }
+    out.push('\n');
+    out.push_str(END_MARKER);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Write the rules section into the file of `target`.
+pub fn export(root: &Path, config: &RulesConfig, target: Target) -> Result<Update> {
+    let section = render(root, config, target)?;
+    let path = root.join(target.path());
+    let current = if path.is_file() {
+        Some(
+            fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", target.path()))?,
+        )
+    } else {
+        None
+    };
+    let (content, update) = apply(current.as_deref(), &section);
+    if update != Update::Unchanged {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs_ops::write_atomic(&path, content.as_bytes(), false)
+            .with_context(|| format!("failed to write {}", target.path()))?;
+    }
+    Ok(update)
+}
+
+/// Replace the rules section of `current`, or append it.
+fn apply(current: Option<&str>, section: &str) -> (String, Update) {
+    let Some(current) = current else {
+        return (section.to_string(), Update::Created);
+    };
+    let span = current.find(BEGIN_MARKER).and_then(|start| {
+        let end = start + current[start..].find(END_MARKER)? + END_MARKER.len();
+        Some((start, end))
+    });
+    if let Some((start, end)) = span {
+        let section = section.trim_end_matches('\n');
+        if &current[start..end] == section {
+            return (current.to_string(), Update::Unchanged);
+        }
+        return (
+            format!("{}{}{}", &current[..start], section, &current[end..]),
+            Update::Updated,
+        );
+    }
+    let separator = match current {
+        "" => "",
+        c if c.ends_with("\n\n") => "",
+        c if c.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    (
+        format!("{}{}{}", current, separator, section),
+        Update::Updated,
+    )
+}
+
+/// Push markdown headings two levels down, outside code fences, so rule
+/// titles nest under the `## <rule>` heading.
+fn demote(body: &str) -> String {
+    let mut fenced = false;
+    let mut out = Vec::new();
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        if !fenced && line.starts_with('#') {
+            out.push(format!("##{}", line));
+        } else {
+            out.push(line.to_string());
+        }
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(root: &Path, name: &str, content: &str) {
+        let path = root.join(crate::rules::rule_path(name));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn renders_enabled_rules_with_target_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        install(
+            dir.path(),
+            "rust/style",
+            "---\ndescription: Rust style\nglobs: **/*.rs\n---\n# Style\n\n```sh\n# comment\n```\n",
+        );
+        install(dir.path(), "web", "---\ndescription: Web\n---\nUse TS.\n");
+        install(dir.path(), "disabled/old", "---\ndescription: Old\n---\n");
+        let config: RulesConfig =
+            toml::from_str("[export.zed]\nexclude = [\"web.mdc\"]\n").unwrap();
+
+        let claude = render(dir.path(), &config, Target::Claude).unwrap();
+        assert!(claude.starts_with(BEGIN_MARKER));
+        assert!(claude.contains(
+            "\n## rust/style\n\nRust style\n\nApplies to `**/*.rs`.\n\n### Style\n\n```sh\n# comment\n```\n"
+        ));
+        assert!(claude.contains("\n## web\n\nWeb\n\nUse TS.\n"));
+        assert!(!claude.contains("old"));
+        let zed = render(dir.path(), &config, Target::Zed).unwrap();
+        assert!(!zed.contains("## web"));
+    }
+
+    #[test]
+    fn keeps_the_rest_of_the_file() {
+        let section = format!("{}\nnew\n{}\n", BEGIN_MARKER, END_MARKER);
+        assert_eq!(apply(None, &section), (section.clone(), Update::Created));
+        let (appended, update) = apply(Some("# Mine\n"), &section);
+        assert_eq!(update, Update::Updated);
+        assert_eq!(appended, format!("# Mine\n\n{}", section));
+        let old = format!("# Mine\n\n{}\nold\n{}\nOutro\n", BEGIN_MARKER, END_MARKER);
+        let (replaced, _) = apply(Some(&old), &section);
+        assert_eq!(replaced, format!("# Mine\n\n{}Outro\n", section));
+        assert_eq!(apply(Some(&replaced), &section).1, Update::Unchanged);
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = RulesConfig::default();
+        assert_eq!(
+            export(dir.path(), &config, Target::Copilot).unwrap(),
+            Update::Created
+        );
+        assert!(dir.path().join(".github/copilot-instructions.md").is_file());
+        assert_eq!(
+            export(dir.path(), &config, Target::Copilot).unwrap(),
+            Update::Unchanged
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! `.mdc` rule files: a front-matter block between `---` lines followed by
+//! markdown.
+//!
+//! Cursor writes a small subset of YAML: `key: value` lines, with `globs`
+//! either a comma-separated string (`*.rs, *.toml`, usually unquoted) or a
+//! list. Unquoted globs such as `*.rs` are not valid YAML, so the
+//! front-matter is parsed here rather than by a YAML library.
+
+use anyhow::{bail, Result};
+use globset::Glob;
+
+/// Front-matter keys understood by Cursor.
+pub const KEYS: [&str; 3] = ["description", "globs", "alwaysApply"];
+
+/// Typed front-matter of a rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    /// Shown to the agent to decide whether the rule applies; `None` when
+    /// absent or empty.
+    pub description: Option<String>,
+    /// File patterns that attach the rule automatically.
+    pub globs: Vec<String>,
+    pub always_apply: Option<bool>,
+    /// Keys outside [`KEYS`], in file order.
+    pub unknown: Vec<String>,
+}
+
+/// A parsed rule file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mdc<'a> {
+    /// `None` when the file does not start with `---`.
+    pub front_matter: Option<FrontMatter>,
+    pub body: &'a str,
+}
+
+/// Parse a rule file. Fails on an unterminated front-matter block, lines
+/// that are not `key: value` pairs or list items, and non-boolean
+/// `alwaysApply` values.
+pub fn parse(content: &str) -> Result<Mdc<'_>> {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return Ok(Mdc {
+            front_matter: None,
+            body: content,
+        });
+    };
+    let mut front_matter = FrontMatter::default();
+    let mut key: Option<&str> = None;
+    let mut offset = 0;
+    let mut body = None;
+    for (number, line) in rest.split_inclusive('\n').enumerate() {
+        offset += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+        let trimmed = line.trim();
+        if trimmed == "---" {
+            body = Some(&rest[offset..]);
+            break;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // Line 1 is the opening `---`.
+        let number = number + 2;
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            match key {
+                Some("globs") => front_matter.globs.push(unquote(item).to_string()),
+                Some(_) => {}
+                None => bail!("line {}: list item outside of a key", number),
+            }
+            continue;
+        }
+        if line.starts_with([' ', '\t']) {
+            if key == Some("description") {
+                let description = front_matter.description.get_or_insert_with(String::new);
+                if !description.is_empty() {
+                    description.push(' ');
+                }
+                description.push_str(unquote(trimmed));
+            }
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            bail!("line {}: expected `key: value`, found '{}'", number, line);
+        };
+        let name = name.trim();
+        let value = value.trim();
+        key = Some(name);
+        match name {
+            "description" => {
+                let value = unquote(value);
+                front_matter.description = match value {
+                    "" | ">" | "|" | ">-" | "|-" => None,
+                    _ => Some(value.to_string()),
+                };
+            }
+            "globs" => front_matter.globs = split_globs(value),
+            "alwaysApply" => {
+                front_matter.always_apply = match unquote(value) {
+                    "" => None,
+                    "true" => Some(true),
+                    "false" => Some(false),
+                    other => bail!(
+                        "line {}: alwaysApply must be true or false, found '{}'",
+                        number,
+                        other
+                    ),
+                }
+            }
+            _ => front_matter.unknown.push(name.to_string()),
+        }
+    }
+    let Some(body) = body else {
+        bail!("front-matter is not closed by `---`");
+    };
+    if front_matter.description.as_deref() == Some("") {
+        front_matter.description = None;
+    }
+    Ok(Mdc {
+        front_matter: Some(front_matter),
+        body,
+    })
+}
+
+/// Problems that make a rule unfit for installation: unparsable or missing
+/// front-matter, a missing description, invalid globs and unknown keys.
+pub fn lint(content: &str) -> Vec<String> {
+    let front_matter = match parse(content) {
+        Ok(Mdc {
+            front_matter: Some(front_matter),
+            ..
+        }) => front_matter,
+        Ok(_) => return vec!["missing front-matter".to_string()],
+        Err(err) => return vec![format!("invalid front-matter: {}", err)],
+    };
+    let mut problems = Vec::new();
+    if front_matter.description.is_none() {
+        problems.push("missing description".to_string());
+    }
+    for glob in &front_matter.globs {
+        if let Err(err) = Glob::new(glob) {
+            problems.push(format!("invalid glob '{}': {}", glob, err.kind()));
+        }
+    }
+    for key in &front_matter.unknown {
+        problems.push(format!(
+            "unknown front-matter key '{}' (expected one of {})",
+            key,
+            KEYS.join(", ")
+        ));
+    }
+    problems
+}
+
+/// Render `front_matter` followed by `body`, in the form [`parse`] reads
+/// back. Unknown keys are not written.
+pub fn render(front_matter: &FrontMatter, body: &str) -> String {
+    let mut out = String::from("---\n");
+    out.push_str("description:");
+    if let Some(description) = &front_matter.description {
+        out.push(' ');
+        out.push_str(&quote(description));
+    }
+    out.push_str("\nglobs:");
+    if !front_matter.globs.is_empty() {
+        out.push(' ');
+        out.push_str(&front_matter.globs.join(", "));
+    }
+    out.push_str("\nalwaysApply: ");
+    out.push_str(if front_matter.always_apply == Some(true) {
+        "true"
+    } else {
+        "false"
+    });
+    out.push_str("\n---\n");
+    out.push_str(body);
+    out
+}
+
+/// Quote values that would otherwise be misread.
+fn quote(value: &str) -> String {
+    let plain = !value.contains(": ")
+        && !value.starts_with([
+            '"', '\'', '[', '{', '>', '|', '*', '&', '!', '%', '@', '#', '-',
+        ]);
+    if plain {
+        value.to_string()
+    } else if !value.contains('"') {
+        format!("\"{}\"", value)
+    } else {
+        format!("'{}'", value)
+    }
+}
+
+/// `*.rs, *.toml`, `"*.rs"` or `["*.rs", "*.toml"]`; commas inside
+/// `{a,b}` alternations do not separate globs.
+fn split_globs(value: &str) -> Vec<String> {
+    let value = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or_else(|| unquote(value));
+    let mut globs = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                globs.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    globs.push(&value[start..]);
+    globs
+        .into_iter()
+        .map(|glob| unquote(glob.trim()))
+        .filter(|glob| !glob.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cursor_front_matter() {
+        let mdc =
+            parse("---\ndescription: \"Rust style\"\nglobs: *.rs, src/**/*.toml\nalwaysApply: false\n---\n# Body\n")
+                .unwrap();
+        assert_eq!(mdc.body, "# Body\n");
+        assert_eq!(
+            mdc.front_matter,
+            Some(FrontMatter {
+                description: Some("Rust style".into()),
+                globs: vec!["*.rs".into(), "src/**/*.toml".into()],
+                always_apply: Some(false),
+                unknown: vec![],
+            })
+        );
+
+        let list = parse("---\ndescription: >\n  Long\n  text\nglobs:\n  - \"*.py\"\n---\n")
+            .unwrap()
+            .front_matter
+            .unwrap();
+        assert_eq!(list.description.as_deref(), Some("Long text"));
+        assert_eq!(list.globs, vec!["*.py"]);
+        let braces = parse("---\nglobs: *.{ts,tsx}, *.css\n---\n").unwrap();
+        assert_eq!(
+            braces.front_matter.unwrap().globs,
+            vec!["*.{ts,tsx}", "*.css"]
+        );
+        let inline = parse("---\nglobs: [\"*.ts\", '*.tsx']\n---\n").unwrap();
+        assert_eq!(inline.front_matter.unwrap().globs, vec!["*.ts", "*.tsx"]);
+
+        assert_eq!(parse("# Plain\n").unwrap().front_matter, None);
+        assert!(parse("---\ndescription: x\n").is_err());
+        assert!(parse("---\nalwaysApply: yes\n---\n").is_err());
+        assert!(parse("---\nnot a pair\n---\n").is_err());
+    }
+
+    #[test]
+    fn renders_front_matter_that_parses_back() {
+        let front_matter = FrontMatter {
+            description: Some("Note: \"quoted\" text".into()),
+            globs: vec!["**/*.rs".into(), "Cargo.toml".into()],
+            always_apply: Some(false),
+            unknown: vec![],
+        };
+        let content = render(&front_matter, "# Body\n");
+        assert_eq!(
+            content,
+            "---\ndescription: 'Note: \"quoted\" text'\nglobs: **/*.rs, Cargo.toml\n\
+             alwaysApply: false\n---\n# Body\n"
+        );
+        let parsed = parse(&content).unwrap();
+        assert_eq!(parsed.front_matter, Some(front_matter));
+        assert_eq!(parsed.body, "# Body\n");
+        assert!(lint(&render(&FrontMatter::default(), "")) == vec!["missing description"]);
+    }
+
+    #[test]
+    fn lints_descriptions_globs_and_keys() {
+        assert!(lint("---\ndescription: Ok\nglobs: *.rs\n---\n").is_empty());
+        assert_eq!(lint("# Plain\n"), vec!["missing front-matter"]);
+        let problems = lint("---\nglobs: src/[a.rs\nalways: true\n---\n");
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert_eq!(problems[0], "missing description");
+        assert!(problems[1].starts_with("invalid glob 'src/[a.rs'"));
+        assert!(problems[2].starts_with("unknown front-matter key 'always'"));
+        assert!(lint("---\ndescription: x\n")[0].starts_with("invalid front-matter"));
+    }
+}
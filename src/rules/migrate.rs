@@ -0,0 +1,221 @@
+//! Conversion of a legacy `.cursorrules` file into `.mdc` rules.
+//!
+//! The file is split on its `##` sections (or `#` sections when it has no
+//! `##` headings). Each section becomes an always-applied rule under the
+//! [`NAMESPACE`] namespace, described by its heading; text before the first
+//! section becomes `cursorrules/general`.
+
+use super::{mdc, rule_path};
+use crate::git;
+use crate::issues::ops::slugify;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Legacy rules file, relative to the repository root.
+pub const CURSORRULES: &str = ".cursorrules";
+
+/// Where the legacy file is moved once migrated.
+pub const MIGRATED: &str = ".cursorrules.migrated";
+
+/// Namespace of the generated rules.
+pub const NAMESPACE: &str = "cursorrules";
+
+/// A section of the legacy file, as a rule to write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Rule name, e.g. `cursorrules/testing`.
+    pub name: String,
+    pub description: String,
+    pub body: String,
+}
+
+/// Result of [`migrate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    /// Written rules, relative to the repository root.
+    pub rules: Vec<PathBuf>,
+    /// New location of the legacy file.
+    pub moved_to: PathBuf,
+}
+
+/// Split `.cursorrules` contents into rules. Headings inside fenced code
+/// blocks are ignored.
+pub fn split(content: &str) -> Vec<Section> {
+    let headings = |prefix: &str| {
+        let mut fenced = false;
+        content
+            .lines()
+            .filter(|line| {
+                if line.trim_start().starts_with("```") {
+                    fenced = !fenced;
+                }
+                !fenced && line.starts_with(prefix)
+            })
+            .count()
+    };
+    let level = if headings("## ") > 0 {
+        "## "
+    } else if headings("# ") > 1 {
+        "# "
+    } else {
+        ""
+    };
+
+    let mut parts: Vec<(Option<&str>, String)> = vec![(None, String::new())];
+    let mut fenced = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        if !fenced && !level.is_empty() && line.starts_with(level) {
+            parts.push((Some(line[level.len()..].trim()), String::new()));
+        }
+        let body = &mut parts.last_mut().expect("parts is never empty").1;
+        body.push_str(line);
+        body.push('\n');
+    }
+
+    let mut sections: Vec<Section> = Vec::new();
+    for (heading, body) in parts {
+        let body = body.trim().to_string();
+        // A lone title, such as the file's `# Heading`, carries no rule.
+        let title_only = body.lines().count() == 1 && body.starts_with('#');
+        if body.is_empty() || title_only {
+            continue;
+        }
+        let (slug, description) = match heading {
+            Some(heading) => (slugify(heading), heading.to_string()),
+            None => (
+                "general".to_string(),
+                format!("General rules migrated from {}", CURSORRULES),
+            ),
+        };
+        let slug = if slug.is_empty() {
+            format!("section-{}", sections.len() + 1)
+        } else {
+            slug
+        };
+        let mut name = format!("{}/{}", NAMESPACE, slug);
+        let mut n = 2;
+        while sections.iter().any(|s| s.name == name) {
+            name = format!("{}/{}-{}", NAMESPACE, slug, n);
+            n += 1;
+        }
+        sections.push(Section {
+            name,
+            description,
+            body: body + "\n",
+        });
+    }
+    sections
+}
+
+/// Write the rules split from `.cursorrules` and move the file to
+/// [`MIGRATED`]. Nothing is written if a target already exists.
+pub fn migrate(root: &Path) -> Result<Migration> {
+    let source = root.join(CURSORRULES);
+    if !source.is_file() {
+        bail!("no {} file to migrate", CURSORRULES);
+    }
+    if root.join(MIGRATED).exists() {
+        bail!("{} already exists", MIGRATED);
+    }
+    let content =
+        fs::read_to_string(&source).with_context(|| format!("failed to read {}", CURSORRULES))?;
+    let sections = split(&content);
+    if sections.is_empty() {
+        bail!("{} is empty", CURSORRULES);
+    }
+    if let Some(existing) = sections
+        .iter()
+        .map(|s| rule_path(&s.name))
+        .find(|path| root.join(path).exists())
+    {
+        bail!("{} already exists", existing.display());
+    }
+
+    let mut rules = Vec::new();
+    for section in sections {
+        let path = rule_path(&section.name);
+        let front_matter = mdc::FrontMatter {
+            description: Some(section.description),
+            always_apply: Some(true),
+            ..mdc::FrontMatter::default()
+        };
+        let full = root.join(&path);
+        if let Some(parent) = full.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            &full,
+            mdc::render(&front_matter, &format!("\n{}", section.body)),
+        )
+        .with_context(|| format!("failed to write {}", path.display()))?;
+        rules.push(path);
+    }
+    git::move_path(root, Path::new(CURSORRULES), Path::new(MIGRATED))?;
+    Ok(Migration {
+        rules,
+        moved_to: PathBuf::from(MIGRATED),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_second_level_sections() {
+        let content = "# Project rules\n\nBe concise.\n\n## Testing\nWrite tests.\n\n\
+                       ```md\n## Not a heading\n```\n\n## Style\nUse rustfmt.\n## Style\nMore.\n";
+        let sections = split(content);
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "cursorrules/general",
+                "cursorrules/testing",
+                "cursorrules/style",
+                "cursorrules/style-2"
+            ]
+        );
+        assert_eq!(sections[0].body, "# Project rules\n\nBe concise.\n");
+        assert_eq!(sections[1].description, "Testing");
+        assert!(sections[1].body.contains("## Not a heading"));
+
+        let flat = split("Always answer in English.\n");
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat[0].name, "cursorrules/general");
+        let titled = split("# Rules\n\n# Naming\nShort.\n# Errors\nanyhow.\n");
+        assert_eq!(titled.len(), 2);
+        assert_eq!(titled[0].name, "cursorrules/naming");
+    }
+
+    #[test]
+    fn migrates_and_moves_the_legacy_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(migrate(dir.path()).is_err());
+        fs::write(
+            dir.path().join(CURSORRULES),
+            "Intro.\n\n## Testing\nWrite tests.\n",
+        )
+        .unwrap();
+        let migration = migrate(dir.path()).unwrap();
+        assert_eq!(
+            migration.rules,
+            vec![
+                rule_path("cursorrules/general"),
+                rule_path("cursorrules/testing")
+            ]
+        );
+        assert!(!dir.path().join(CURSORRULES).exists());
+        assert!(dir.path().join(MIGRATED).is_file());
+        let testing = fs::read_to_string(dir.path().join(&migration.rules[1])).unwrap();
+        assert_eq!(
+            testing,
+            "---\ndescription: Testing\nglobs:\nalwaysApply: true\n---\n\n## Testing\nWrite tests.\n"
+        );
+        assert!(mdc::lint(&testing).is_empty());
+    }
+}
@@ -0,0 +1,1678 @@
+//! Cursor rules (`.mdc` files) installed in `.cursor/rules/`.
+//!
+//! Rules come from two sources: the set embedded in echo and, when
+//! configured, a flowmates checkout whose `rules/` directory overrides
+//! embedded rules of the same name. Installed rules matching neither were
+//! created in Cursor directly.
+//!
+//! Rules may be grouped in namespaces, i.e. subdirectories such as
+//! `rules/rust/style.mdc`; the rule is then named `rust/style` and installed
+//! to `.cursor/rules/rust/style.mdc`.
+//!
+//! Disabled rules live in `.cursor/rules/disabled/`, where Cursor ignores
+//! them, and are listed under `[rules] disabled` in `.echo.toml` so
+//! installs do not bring them back.
+
+pub mod bundles;
+pub mod changelog;
+pub mod conflicts;
+pub mod export;
+pub mod mdc;
+pub mod migrate;
+pub mod outdated;
+pub mod targets;
+pub mod upstream;
+
+use crate::checksums;
+use crate::config;
+use crate::echoignore::EchoIgnore;
+use crate::environment;
+use crate::fs_ops::{self, FsOps, RealFs};
+use crate::gitutil;
+use crate::hooks::LinkMode;
+use crate::issues::validate::Violation;
+use crate::lock::{self, Conflict, Lock, LockedFile, Outcome, Resolution};
+use crate::pack::Pack;
+use crate::parallel;
+use crate::provenance::{self, Comment, Provenance};
+use crate::scan_cache::ScanCache;
+use crate::source_scan::SourceFilter;
+use anyhow::{bail, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use targets::RuleTarget;
+
+/// Directory holding installed rules, relative to the repository root.
+pub const RULES_DIR: &str = ".cursor/rules";
+
+/// Directory under [`RULES_DIR`] holding disabled rules.
+pub const DISABLED_DIR: &str = "disabled";
+
+/// Extension of rule files.
+pub const RULE_EXTENSION: &str = "mdc";
+
+/// Directory of a flowmates checkout holding its rules.
+pub const FLOWMATES_RULES_DIR: &str = "rules";
+
+/// Rules shipped with echo, as `(name, contents)`.
+pub const EMBEDDED: [(&str, &str); 2] = [
+    (
+        "echo-workflow",
+        include_str!("../../templates/rules/echo-workflow.mdc"),
+    ),
+    (
+        "issue-files",
+        include_str!("../../templates/rules/issue-files.mdc"),
+    ),
+];
+
+/// `[rules]` section of `.echo.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RulesConfig {
+    /// Path of a flowmates checkout; when unset,
+    /// [`crate::environment::Environment::load_config`] looks further.
+    pub flowmates_dir: Option<PathBuf>,
+    /// Rules turned off with `echo rules disable`.
+    pub disabled: Vec<String>,
+    /// Glob patterns on rule files (`rust-*.mdc`, `web/*.mdc`), matched
+    /// against both the path under `rules/` and the bare file name; when
+    /// non-empty, only matching rules are installed.
+    pub include: Vec<String>,
+    /// Glob patterns on rule files excluded from installation.
+    pub exclude: Vec<String>,
+    /// Extra filters per namespace, `[rules.namespaces.<name>]`.
+    pub namespaces: BTreeMap<String, NamespaceConfig>,
+    /// Where `init` and `update` install rules: `cursor`, `windsurf`,
+    /// `copilot` or a directory; Cursor alone when empty.
+    pub targets: Vec<String>,
+    /// Rules exported to each agent, `[rules.export.<target>]`.
+    pub export: BTreeMap<String, export::ExportConfig>,
+    /// Extra rules installed in the `.cursor/rules/` of a subdirectory,
+    /// `[rules.overlays."<path>"]`.
+    pub overlays: BTreeMap<String, OverlayConfig>,
+    /// Bundles installed with `echo rules install` (see [`bundles`]);
+    /// their rules are installed whatever the patterns above say.
+    pub bundles: Vec<String>,
+    /// Rules for issues carrying a label, `[rules.labels.<label>]`,
+    /// installed in the worktree `echo agent spawn` creates for them.
+    pub labels: BTreeMap<String, OverlayConfig>,
+    /// Labels whose rules are installed whatever the patterns above say;
+    /// set by `echo agent spawn` from the issue's labels.
+    #[serde(skip)]
+    pub active_labels: Vec<String>,
+    /// Rule patterns of the selected presets (see [`crate::presets`]);
+    /// when non-empty, a rule must also match one of them.
+    #[serde(skip)]
+    pub preset: Vec<String>,
+    /// Give rules and issue templates installed from flowmates the
+    /// permissions and modification time of their source.
+    pub preserve_metadata: bool,
+    /// Refuse to install from a flowmates checkout without a
+    /// `SHA256SUMS` file (see [`crate::checksums`]).
+    pub require_checksums: bool,
+    /// Minisign public keys, one of which must have signed the
+    /// `SHA256SUMS` of the flowmates checkout; the registry's are added
+    /// by [`crate::environment::Environment::load_config`].
+    pub trusted_keys: Vec<String>,
+    /// Record the rules `echo update` changes in `CHANGELOG.echo.md`.
+    pub changelog: bool,
+    /// Stamp rules and issue templates with a provenance header (see
+    /// [`crate::provenance`]).
+    pub provenance: bool,
+    /// Install flowmates rules in `.cursor/rules/` as symbolic links to
+    /// their source, so upstream edits apply at once; set by `echo init
+    /// --link`. Embedded rules are still copied.
+    pub link: bool,
+    /// Time provenance headers are dated with, in seconds since the
+    /// epoch; set from the environment by
+    /// [`crate::environment::Environment::load_config`], the system clock
+    /// otherwise.
+    #[serde(skip)]
+    pub now: Option<u64>,
+}
+
+/// `[rules.namespaces.<name>]`: filters on the rule files of one namespace,
+/// matched against their path inside the namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamespaceConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// `[rules.overlays."<path>"]`: rules installed to
+/// `<path>/.cursor/rules/`, which Cursor applies to files under `<path>`.
+/// Patterns match like those of `[rules]`; the top-level filters and
+/// presets do not apply. `[rules.labels.<label>]` takes the same patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverlayConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl RulesConfig {
+    /// Install targets, `[rules] targets` or Cursor by default.
+    pub fn targets(&self) -> Vec<RuleTarget> {
+        if self.targets.is_empty() {
+            return vec![RuleTarget::Cursor];
+        }
+        self.targets.iter().map(|t| RuleTarget::parse(t)).collect()
+    }
+
+    /// The flowmates checkout to read rules from, if any.
+    pub fn flowmates_dir(&self) -> Option<PathBuf> {
+        self.flowmates_dir.clone()
+    }
+
+    /// Seconds since the epoch, from [`RulesConfig::now`] when set.
+    pub fn now(&self) -> u64 {
+        self.now.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Compile the `include` / `exclude` patterns.
+    pub fn filter(&self) -> Result<RuleFilter> {
+        let mut namespaces = BTreeMap::new();
+        for (name, config) in &self.namespaces {
+            let key = format!("rules.namespaces.{}", name);
+            namespaces.insert(
+                name.clone(),
+                Patterns::new(&config.include, &config.exclude, &key)?,
+            );
+        }
+        let bundled = if self.bundles.is_empty() {
+            BTreeSet::new()
+        } else {
+            bundles::Manifest::load(self.flowmates_dir().as_deref())?
+                .resolve(&self.bundles)
+                .context("invalid rules.bundles")?
+        };
+        let mut labelled = Vec::new();
+        for label in &self.active_labels {
+            // A label without include patterns adds no rules.
+            if let Some(config) = self.labels.get(label).filter(|c| !c.include.is_empty()) {
+                let key = format!("rules.labels.{}", label);
+                labelled.push(Patterns::new(&config.include, &config.exclude, &key)?);
+            }
+        }
+        Ok(RuleFilter {
+            top: Patterns::new(&self.include, &self.exclude, "rules")?,
+            preset: Patterns::new(&self.preset, &[], "preset rules")?,
+            namespaces,
+            bundled,
+            labelled,
+        })
+    }
+}
+
+/// Compiled `include` / `exclude` patterns of `[rules]` and its namespaces.
+#[derive(Debug, Clone)]
+pub struct RuleFilter {
+    top: Patterns,
+    preset: Patterns,
+    namespaces: BTreeMap<String, Patterns>,
+    /// Rules of the installed bundles, by name.
+    bundled: BTreeSet<String>,
+    /// Patterns of the active labels.
+    labelled: Vec<Patterns>,
+}
+
+impl RuleFilter {
+    /// Whether the rule file at `rel_path` (`rust/style.mdc`, relative to
+    /// the rules directory) should be installed.
+    pub fn matches(&self, rel_path: &str) -> bool {
+        let file_name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+        let name = rel_path.strip_suffix(".mdc").unwrap_or(rel_path);
+        if self.bundled.contains(name)
+            || self
+                .labelled
+                .iter()
+                .any(|patterns| patterns.matches(&[rel_path, file_name]))
+        {
+            return true;
+        }
+        if !self.top.matches(&[rel_path, file_name]) || !self.preset.matches(&[rel_path, file_name])
+        {
+            return false;
+        }
+        match rel_path.split_once('/') {
+            Some((namespace, inner)) => self
+                .namespaces
+                .get(namespace)
+                .is_none_or(|patterns| patterns.matches(&[inner, file_name])),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Patterns {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl Patterns {
+    fn new(include: &[String], exclude: &[String], key: &str) -> Result<Patterns> {
+        Ok(Patterns {
+            include: (!include.is_empty())
+                .then(|| glob_set(include, &format!("{}.include", key)))
+                .transpose()?,
+            exclude: glob_set(exclude, &format!("{}.exclude", key))?,
+        })
+    }
+
+    /// Included when any candidate matches `include` (or it is empty) and
+    /// no candidate matches `exclude`.
+    fn matches(&self, candidates: &[&str]) -> bool {
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|set| candidates.iter().any(|c| set.is_match(c)));
+        included && !candidates.iter().any(|c| self.exclude.is_match(c))
+    }
+}
+
+fn glob_set(patterns: &[String], key: &str) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .with_context(|| format!("invalid pattern '{}' in {}", pattern, key))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .with_context(|| format!("invalid patterns in {}", key))
+}
+
+/// Where a rule comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Flowmates,
+    Embedded,
+    /// Created in Cursor; echo has no copy of it.
+    Cursor,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Flowmates => "flowmates",
+            Source::Embedded => "embedded",
+            Source::Cursor => "cursor",
+        }
+    }
+}
+
+/// A rule available from echo or flowmates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceRule {
+    pub name: String,
+    pub source: Source,
+    pub content: String,
+    /// File the rule was read from; `None` for embedded rules.
+    pub path: Option<PathBuf>,
+}
+
+/// An installed rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    /// Path under the rules directory without extension, e.g. `rust/style`.
+    pub name: String,
+    /// First directory of the name, if the rule is namespaced.
+    pub namespace: Option<String>,
+    /// Path relative to the repository root.
+    pub path: PathBuf,
+    pub source: Source,
+    /// SHA-256 of the installed contents, hex encoded.
+    pub hash: String,
+    /// Whether the installed file differs from its source; `None` for
+    /// rules created in Cursor.
+    pub modified: Option<bool>,
+    pub description: Option<String>,
+    pub globs: Vec<String>,
+    /// Whether the rule sits in the disabled directory.
+    pub disabled: bool,
+}
+
+/// Rules available for installation, by name. Flowmates rules override
+/// embedded ones; files the checkout does not consider sources are
+/// skipped (see [`crate::source_scan`]).
+pub fn sources(flowmates_dir: Option<&Path>) -> Result<BTreeMap<String, SourceRule>> {
+    let mut rules = BTreeMap::new();
+    for (name, content) in EMBEDDED {
+        rules.insert(
+            name.to_string(),
+            SourceRule {
+                name: name.to_string(),
+                source: Source::Embedded,
+                content: content.to_string(),
+                path: None,
+            },
+        );
+    }
+    if let Some(dir) = flowmates_dir {
+        let filter = SourceFilter::load(dir)?;
+        let mut files = rule_files(&dir.join(FLOWMATES_RULES_DIR), None)?;
+        files.retain(|(_, path)| !filter.skips(path.strip_prefix(dir).unwrap_or(path)));
+        let read = parallel::map(&files, |(name, path)| -> Result<SourceRule> {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            Ok(SourceRule {
+                name: name.clone(),
+                source: Source::Flowmates,
+                content,
+                path: Some(path.clone()),
+            })
+        });
+        for rule in read {
+            let rule = rule?;
+            rules.insert(rule.name.clone(), rule);
+        }
+    }
+    Ok(rules)
+}
+
+/// Hashes of the rules available for installation, by name, as
+/// [`sources`] finds them. The flowmates rules are hashed through `cache`,
+/// which must be of the flowmates checkout.
+pub fn source_hashes(
+    flowmates_dir: Option<&Path>,
+    cache: Option<&ScanCache>,
+) -> Result<BTreeMap<String, (Source, String)>> {
+    let mut hashes: BTreeMap<String, (Source, String)> = EMBEDDED
+        .iter()
+        .map(|(name, content)| (name.to_string(), (Source::Embedded, lock::hash(content))))
+        .collect();
+    if let Some(dir) = flowmates_dir {
+        let filter = SourceFilter::load(dir)?;
+        let mut files = rule_files(&dir.join(FLOWMATES_RULES_DIR), None)?;
+        files.retain(|(_, path)| !filter.skips(path.strip_prefix(dir).unwrap_or(path)));
+        let hashed = parallel::map(&files, |(name, path)| -> Result<(String, String)> {
+            let hash = match cache {
+                Some(cache) => cache.hash(path.strip_prefix(dir).unwrap_or(path))?,
+                None => lock::hash_bytes(
+                    &fs::read(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?,
+                ),
+            };
+            Ok((name.clone(), hash))
+        });
+        for entry in hashed {
+            let (name, hash) = entry?;
+            hashes.insert(name, (Source::Flowmates, hash));
+        }
+    }
+    Ok(hashes)
+}
+
+/// List installed rules, disabled ones included, with their provenance.
+/// Sorted by name.
+pub fn list(root: &Path, config: &RulesConfig) -> Result<Vec<Rule>> {
+    list_with(root, config, None)
+}
+
+/// Like [`list`], hashing the flowmates rules through `cache`.
+pub fn list_with(
+    root: &Path,
+    config: &RulesConfig,
+    cache: Option<&ScanCache>,
+) -> Result<Vec<Rule>> {
+    let sources = source_hashes(config.flowmates_dir().as_deref(), cache)?;
+    let dir = root.join(RULES_DIR);
+    let mut files: Vec<_> = rule_files(&dir, Some(DISABLED_DIR))?
+        .into_iter()
+        .map(|(name, path)| (name, path, false))
+        .collect();
+    files.extend(
+        rule_files(&dir.join(DISABLED_DIR), None)?
+            .into_iter()
+            .map(|(name, path)| (name, path, true)),
+    );
+    // Broken links are reported by `check_links`.
+    files.retain(|(_, path, _)| path.exists());
+    files.sort();
+    parallel::map(&files, |(name, path, disabled)| {
+        let (name, disabled) = (name.clone(), *disabled);
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let content = provenance::strip(&content);
+        let front_matter = mdc::parse(&content)
+            .ok()
+            .and_then(|mdc| mdc.front_matter)
+            .unwrap_or_default();
+        let source = sources.get(&name);
+        let hash = lock::hash(&content);
+        Ok(Rule {
+            path: if disabled {
+                disabled_path(&name)
+            } else {
+                rule_path(&name)
+            },
+            source: source.map_or(Source::Cursor, |(source, _)| *source),
+            modified: source.map(|(_, source_hash)| *source_hash != hash),
+            hash,
+            description: front_matter.description,
+            globs: front_matter.globs,
+            disabled,
+            namespace: name.split_once('/').map(|(ns, _)| ns.to_string()),
+            name,
+        })
+    })
+    .into_iter()
+    .collect()
+}
+
+/// Paths of the rules installed for a target, with their outcome.
+pub type Installed = Vec<(PathBuf, Outcome)>;
+
+/// Install the source rules to `.cursor/rules/`. Missing rules are
+/// written; installed ones that differ from their source are overwritten
+/// only with `force`. Disabled rules and rules filtered out by `include` /
+/// `exclude` are skipped. Returns each rule path with its outcome.
+pub fn install(root: &Path, config: &RulesConfig, force: bool) -> Result<Installed> {
+    install_target(
+        root,
+        config,
+        &RuleTarget::Cursor,
+        &mut overwrite(force),
+        None,
+        Some(&RealFs),
+    )
+}
+
+/// Resolve every conflict by overwriting with `force`, else by keeping.
+fn overwrite(force: bool) -> impl FnMut(&Conflict) -> Result<Resolution> {
+    move |_| {
+        Ok(if force {
+            Resolution::Take
+        } else {
+            Resolution::Keep
+        })
+    }
+}
+
+/// Install the source rules to every configured target as [`install`]
+/// does for Cursor, then to the `.cursor/rules/` of each overlay,
+/// recording the installed files in the lockfile. Installed rules not
+/// edited since echo wrote them follow their source without `force`.
+/// Returns the rule paths and outcomes of each target.
+pub fn sync(
+    root: &Path,
+    config: &RulesConfig,
+    force: bool,
+) -> Result<Vec<(RuleTarget, Installed)>> {
+    sync_with(&RealFs, root, config, None, overwrite(force))
+}
+
+/// Like [`sync`], writing through `fs` and asking `resolve` what to do
+/// with each installed rule edited locally whose source changed. The
+/// contents echo writes are kept under `.echo/` as the base of merges (see
+/// [`lock::base_path`]). The flowmates checkout is verified first (see
+/// [`checksums::verify_source`]), hashing through `cache` when given.
+pub fn sync_with(
+    fs: &dyn FsOps,
+    root: &Path,
+    config: &RulesConfig,
+    cache: Option<&ScanCache>,
+    mut resolve: impl FnMut(&Conflict) -> Result<Resolution>,
+) -> Result<Vec<(RuleTarget, Installed)>> {
+    checksums::verify_source(config, cache)?;
+    sync_targets(root, config, &mut resolve, Some(fs))
+}
+
+/// What [`sync`] would do, without writing anything: rules edited locally
+/// whose source changed are reported as [`Outcome::Modified`].
+pub fn plan(root: &Path, config: &RulesConfig) -> Result<Vec<(RuleTarget, Installed)>> {
+    sync_targets(root, config, &mut |_| Ok(Resolution::Keep), None)
+}
+
+/// Sync every target through `fs`, or only plan it without one.
+fn sync_targets(
+    root: &Path,
+    config: &RulesConfig,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Resolution>,
+    fs: Option<&dyn FsOps>,
+) -> Result<Vec<(RuleTarget, Installed)>> {
+    let mut lock = Lock::load(root)?;
+    let mut report = Vec::new();
+    for target in config.targets() {
+        let installed = install_target(root, config, &target, resolve, Some(&mut lock), fs)?;
+        report.push((target, installed));
+    }
+    for (path, overlay) in &config.overlays {
+        let dir = Path::new(path);
+        if dir.as_os_str().is_empty()
+            || !dir
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            bail!("invalid overlay path '{}' in [rules.overlays]", path);
+        }
+        let patterns = Patterns::new(
+            &overlay.include,
+            &overlay.exclude,
+            &format!("rules.overlays.\"{}\"", path),
+        )?;
+        let target = RuleTarget::Dir(dir.join(RULES_DIR));
+        let installed = install_matching(
+            root,
+            config,
+            &target,
+            |file_name| {
+                let bare = file_name.rsplit('/').next().unwrap_or(file_name);
+                patterns.matches(&[file_name, bare])
+            },
+            resolve,
+            Some(&mut lock),
+            fs,
+        )?;
+        report.push((target, installed));
+    }
+    if let Some(fs) = fs {
+        lock.revision = config
+            .flowmates_dir()
+            .filter(|dir| gitutil::is_repo(dir))
+            .and_then(|dir| gitutil::head(&dir));
+        lock.pack = match config.flowmates_dir().filter(|dir| dir.is_dir()) {
+            Some(dir) => Pack::load(&dir)?.map(|pack| pack.version),
+            None => None,
+        };
+        lock.save_in(fs, root)?;
+    }
+    Ok(report)
+}
+
+fn install_target(
+    root: &Path,
+    config: &RulesConfig,
+    target: &RuleTarget,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Resolution>,
+    lock: Option<&mut Lock>,
+    fs: Option<&dyn FsOps>,
+) -> Result<Installed> {
+    let filter = config.filter()?;
+    install_matching(
+        root,
+        config,
+        target,
+        |file_name| filter.matches(file_name),
+        resolve,
+        lock,
+        fs,
+    )
+}
+
+/// Install the enabled source rules whose file name passes `matches`.
+fn install_matching(
+    root: &Path,
+    config: &RulesConfig,
+    target: &RuleTarget,
+    matches: impl Fn(&str) -> bool,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Resolution>,
+    mut lock: Option<&mut Lock>,
+    fs: Option<&dyn FsOps>,
+) -> Result<Installed> {
+    let rules: Vec<SourceRule> = sources(config.flowmates_dir().as_deref())?
+        .into_values()
+        .filter(|rule| {
+            let file_name = format!("{}.{}", rule.name, RULE_EXTENSION);
+            !config.disabled.contains(&rule.name) && matches(&file_name)
+        })
+        .collect();
+    // Files are hashed and written in parallel; the lock is only read
+    // until every write is done. Conflicts are resolved one at a time
+    // afterwards, as resolving may ask the user. Without `fs`, nothing is
+    // written nor resolved.
+    let ignore = EchoIgnore::load(root)?;
+    let stamp = config
+        .provenance
+        .then(|| Stamp::new(config.flowmates_dir().as_deref(), config.now()));
+    let installed = {
+        let lock = lock.as_deref();
+        parallel::map(&rules, |rule| -> Result<_> {
+            let rel_path = target.path(&rule.name);
+            let path = root.join(&rel_path);
+            let content = target.convert(&rule.content);
+            let locked = lock.and_then(|lock| lock.get(&rel_path));
+            let echo_link = locked.is_some_and(is_link) && path.is_symlink();
+            let link = rule
+                .path
+                .as_ref()
+                .filter(|_| config.link && *target == RuleTarget::Cursor);
+            let outcome = match link {
+                Some(source) if links_to(&path, source) => Outcome::UpToDate,
+                _ => {
+                    let locked = locked.map(|locked| locked.hash.as_str());
+                    match Outcome::of(root, &rel_path, &content, false, locked) {
+                        // A copy becomes a link and a link a copy.
+                        Outcome::UpToDate if link.is_some() || echo_link => Outcome::Updated,
+                        outcome => outcome,
+                    }
+                }
+            };
+            let outcome = ignore.guard(&rel_path, outcome);
+            let mut mtime = None;
+            let mut linked = link.is_some() && outcome == Outcome::UpToDate;
+            if let Some(fs) = fs.filter(|_| outcome.is_written()) {
+                // Writing through a link would change its target.
+                if echo_link || link.is_some() && path.symlink_metadata().is_ok() {
+                    fs.remove_file(&path)
+                        .with_context(|| format!("failed to remove {}", rel_path.display()))?;
+                }
+                if let Some(source) = link {
+                    linked = link_rule(fs, &path, source).is_ok();
+                }
+                match &stamp {
+                    _ if linked => {}
+                    Some(stamp) => {
+                        let file =
+                            format!("{}/{}.{}", FLOWMATES_RULES_DIR, rule.name, RULE_EXTENSION);
+                        let stamped = stamp.apply(rule.source, &file, &content);
+                        write_rule(fs, root, &rel_path, &stamped)?;
+                    }
+                    None => write_rule(fs, root, &rel_path, &content)?,
+                }
+                if let Some(source) = rule
+                    .path
+                    .as_ref()
+                    .filter(|_| config.preserve_metadata && !linked)
+                {
+                    mtime = Some(
+                        fs_ops::preserve_metadata(fs, source, &root.join(&rel_path)).with_context(
+                            || format!("failed to copy metadata to {}", rel_path.display()),
+                        )?,
+                    );
+                }
+            }
+            Ok((rel_path, content, outcome, mtime, linked))
+        })
+    };
+    let mut report = Vec::new();
+    for (rule, installed) in rules.iter().zip(installed) {
+        let (rel_path, content, mut outcome, mut mtime, linked) = installed?;
+        let Some(fs) = fs else {
+            report.push((rel_path, outcome));
+            continue;
+        };
+        if outcome == Outcome::Modified {
+            let local = fs::read_to_string(root.join(&rel_path))
+                .with_context(|| format!("failed to read {}", rel_path.display()))?;
+            let local = provenance::strip(&local);
+            let base = lock
+                .as_deref()
+                .and_then(|lock| lock.get(&rel_path))
+                .and_then(|locked| lock::read_base(root, &rel_path, &locked.hash));
+            let conflict = Conflict {
+                path: &rel_path,
+                local: &local,
+                source: &content,
+                base: base.as_deref(),
+            };
+            let (resolved, resolution) = conflict.resolve(resolve(&conflict)?);
+            if let Some(resolved) = resolved {
+                write_rule(fs, root, &rel_path, &resolved)?;
+            }
+            outcome = resolution;
+        }
+        let kept = matches!(outcome, Outcome::Modified | Outcome::Protected);
+        if outcome == Outcome::UpToDate {
+            mtime = lock
+                .as_deref()
+                .and_then(|lock| lock.get(&rel_path))
+                .and_then(|locked| locked.mtime);
+        }
+        if let Some(lock) = lock.as_deref_mut().filter(|_| !kept) {
+            lock::write_base(fs, root, &rel_path, &content)?;
+            lock.record(LockedFile {
+                target: target.name(),
+                path: rel_path.clone(),
+                name: rule.name.clone(),
+                source: rule.source.name().to_string(),
+                hash: lock::hash(&content),
+                link: linked.then(|| LinkMode::Symlink.name().to_string()),
+                mtime,
+            });
+        }
+        report.push((rel_path, outcome));
+    }
+    Ok(report)
+}
+
+/// Provenance headers for one install: the revision of the source and
+/// the day, shared by every file.
+pub struct Stamp {
+    revision: Option<String>,
+    installed: String,
+}
+
+impl Stamp {
+    /// A stamp for files installed from `flowmates_dir` at `now`, in
+    /// seconds since the epoch.
+    pub fn new(flowmates_dir: Option<&Path>, now: u64) -> Stamp {
+        Stamp {
+            revision: flowmates_dir
+                .filter(|dir| gitutil::is_repo(dir))
+                .and_then(gitutil::head)
+                .map(|head| head.chars().take(7).collect()),
+            installed: environment::date(now),
+        }
+    }
+
+    /// `content`, installed from `file` of `source`, with its header.
+    pub fn apply(&self, source: Source, file: &str, content: &str) -> String {
+        let version = match source {
+            Source::Flowmates => self.revision.clone(),
+            _ => Some(format!("echo {}", env!("CARGO_PKG_VERSION"))),
+        };
+        let provenance = Provenance {
+            source: format!("{}:{}", source.name(), file),
+            version,
+            hash: lock::hash(content),
+            installed: Some(self.installed.clone()),
+        };
+        provenance::stamp(content, Comment::Html, &provenance)
+    }
+}
+
+/// What is wrong with a symbolic link among the installed rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkProblem {
+    /// Its target is gone.
+    Broken,
+    /// echo did not make it.
+    Foreign,
+}
+
+/// The installed rules, disabled ones included, that are broken links or
+/// links echo did not make, sorted by name.
+pub fn check_links(root: &Path) -> Result<Vec<(PathBuf, LinkProblem)>> {
+    let lock = Lock::load(root)?;
+    let mut problems = Vec::new();
+    for (_, path) in rule_files(&root.join(RULES_DIR), None)? {
+        if !path.is_symlink() {
+            continue;
+        }
+        let rel_path = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        if !path.exists() {
+            problems.push((rel_path, LinkProblem::Broken));
+        } else if !lock.get(&rel_path).is_some_and(is_link) {
+            problems.push((rel_path, LinkProblem::Foreign));
+        }
+    }
+    Ok(problems)
+}
+
+/// Whether the repository at `root` is the flowmates checkout
+/// `flowmates_dir` or lies inside it, where installing would copy its
+/// sources onto themselves.
+pub fn is_source_repo(root: &Path, flowmates_dir: &Path) -> bool {
+    match (fs::canonicalize(root), fs::canonicalize(flowmates_dir)) {
+        (Ok(root), Ok(dir)) => root.starts_with(dir),
+        _ => false,
+    }
+}
+
+/// Whether the lockfile entry `locked` is of a link echo made.
+pub fn is_link(locked: &LockedFile) -> bool {
+    locked.link.as_deref() == Some(LinkMode::Symlink.name())
+}
+
+/// Whether `path` is a symbolic link resolving to `source`.
+pub fn links_to(path: &Path, source: &Path) -> bool {
+    path.is_symlink()
+        && matches!(
+            (fs::canonicalize(path), fs::canonicalize(source)),
+            (Ok(path), Ok(source)) if path == source
+        )
+}
+
+fn link_rule(fs: &dyn FsOps, path: &Path, source: &Path) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs.create_dir_all(parent)?;
+    }
+    fs.symlink(&fs::canonicalize(source)?, path)
+}
+
+fn write_rule(fs: &dyn FsOps, root: &Path, rel_path: &Path, content: &str) -> Result<()> {
+    fs_ops::write_file(fs, &root.join(rel_path), content.as_bytes())
+        .with_context(|| format!("failed to write {}", rel_path.display()))
+}
+
+/// Create the rule `name` with the given front-matter and a heading, in
+/// `.cursor/rules/` or, with `upstream`, in the flowmates checkout. Fails
+/// if the rule exists or its front-matter does not pass [`mdc::lint`].
+/// Returns the written path, relative to the repository root when local.
+pub fn create(
+    root: &Path,
+    config: &RulesConfig,
+    name: &str,
+    front_matter: &mdc::FrontMatter,
+    upstream: bool,
+) -> Result<PathBuf> {
+    let valid = |segment: &str| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    };
+    if !name.split('/').all(valid) || name.split('/').next() == Some(DISABLED_DIR) {
+        bail!(
+            "invalid rule name '{}' (use lowercase letters, digits, '-' and '_', with '/' between namespaces)",
+            name
+        );
+    }
+    let title = name
+        .rsplit('/')
+        .next()
+        .unwrap_or(name)
+        .replace(['-', '_'], " ");
+    let mut title_chars = title.chars();
+    let title: String = title_chars
+        .next()
+        .map(|c| c.to_uppercase().chain(title_chars).collect())
+        .unwrap_or_default();
+    let content = mdc::render(front_matter, &format!("\n# {}\n\n", title));
+    if let Some(problem) = mdc::lint(&content).into_iter().next() {
+        bail!("{}", problem);
+    }
+    let (path, full) = if upstream {
+        let Some(dir) = config.flowmates_dir() else {
+            bail!("no flowmates checkout configured (set [rules] flowmates-dir or $FLOWMATES_DIR)");
+        };
+        let path = dir
+            .join(FLOWMATES_RULES_DIR)
+            .join(format!("{}.{}", name, RULE_EXTENSION));
+        (path.clone(), path)
+    } else {
+        if root.join(disabled_path(name)).exists() {
+            bail!("rule '{}' exists and is disabled", name);
+        }
+        (rule_path(name), root.join(rule_path(name)))
+    };
+    if full.exists() {
+        bail!("{} already exists", path.display());
+    }
+    if let Some(parent) = full.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&full, content).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Disable a rule: move it to the disabled directory and record it in
+/// `.echo.toml`. Known rules that are not installed are only recorded.
+pub fn disable(root: &Path, config: &RulesConfig, name: &str) -> Result<()> {
+    let active = root.join(rule_path(name));
+    let disabled = root.join(disabled_path(name));
+    if active.is_file() {
+        if disabled.exists() {
+            bail!("{} already exists", disabled_path(name).display());
+        }
+        if let Some(parent) = disabled.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&active, &disabled)
+            .with_context(|| format!("failed to move {}", rule_path(name).display()))?;
+    } else if !disabled.is_file() && !sources(config.flowmates_dir().as_deref())?.contains_key(name)
+    {
+        bail!("unknown rule '{}'", name);
+    }
+    record_disabled(root, name, true)
+}
+
+/// Enable a rule: move it back from the disabled directory, or install it
+/// from its source, and drop it from the disabled list in `.echo.toml`.
+pub fn enable(root: &Path, config: &RulesConfig, name: &str) -> Result<()> {
+    let active = root.join(rule_path(name));
+    let disabled = root.join(disabled_path(name));
+    if disabled.is_file() {
+        if active.exists() {
+            bail!("{} already exists", rule_path(name).display());
+        }
+        if let Some(parent) = active.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&disabled, &active)
+            .with_context(|| format!("failed to move {}", disabled_path(name).display()))?;
+    } else if !active.is_file() {
+        let sources = sources(config.flowmates_dir().as_deref())?;
+        let Some(rule) = sources.get(name) else {
+            bail!("unknown rule '{}'", name);
+        };
+        write_rule(&RealFs, root, &rule_path(name), &rule.content)?;
+    }
+    record_disabled(root, name, false)
+}
+
+/// Add `name` to, or remove it from, `[rules] disabled` in `.echo.toml`.
+fn record_disabled(root: &Path, name: &str, disabled: bool) -> Result<()> {
+    config::edit(root, |doc| {
+        let rules = doc
+            .entry("rules")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .context("[rules] is not a table")?;
+        let list = rules
+            .entry("disabled")
+            .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .context("rules.disabled is not an array")?;
+        let present = list.iter().any(|v| v.as_str() == Some(name));
+        if disabled && !present {
+            list.push(name);
+        } else if !disabled {
+            list.retain(|v| v.as_str() != Some(name));
+        }
+        Ok(())
+    })
+}
+
+/// Path of a disabled rule, relative to the repository root.
+pub fn disabled_path(name: &str) -> PathBuf {
+    Path::new(RULES_DIR)
+        .join(DISABLED_DIR)
+        .join(format!("{}.{}", name, RULE_EXTENSION))
+}
+
+/// Path of the installed rule `name`, relative to the repository root.
+pub fn rule_path(name: &str) -> PathBuf {
+    Path::new(RULES_DIR).join(format!("{}.{}", name, RULE_EXTENSION))
+}
+
+/// `.mdc` files below `dir`, as `(name, path)` sorted by name, where the
+/// name is the path relative to `dir` without extension. A top-level
+/// directory called `skip` is not descended into.
+fn rule_files(dir: &Path, skip: Option<&str>) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![(dir.to_path_buf(), String::new())];
+    while let Some((current, prefix)) = pending.pop() {
+        if !current.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir() {
+                let skipped = prefix.is_empty() && Some(file_name.as_str()) == skip;
+                if !skipped && !file_name.starts_with('.') {
+                    pending.push((path, format!("{}{}/", prefix, file_name)));
+                }
+                continue;
+            }
+            if path.extension() != Some(RULE_EXTENSION.as_ref()) {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                files.push((format!("{}{}", prefix, stem), path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Find conflicting rules among the enabled installed ones (see
+/// [`conflicts`]).
+pub fn check_conflicts(root: &Path) -> Result<Vec<conflicts::Conflict>> {
+    let mut rules = Vec::new();
+    for (name, path) in rule_files(&root.join(RULES_DIR), Some(DISABLED_DIR))? {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        rules.push((name, content));
+    }
+    Ok(conflicts::check(&rules))
+}
+
+/// Unified diffs from installed rules to their source, i.e. what
+/// `install --force` would change, as `(name, patch)`. Without `name`,
+/// every enabled rule that differs from its source is compared; a named
+/// rule that is not installed yet is diffed against an empty file.
+pub fn diff(
+    root: &Path,
+    config: &RulesConfig,
+    name: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    let flowmates_dir = config.flowmates_dir();
+    let sources = sources(flowmates_dir.as_deref())?;
+    let names: Vec<String> = match name {
+        Some(name) => {
+            if !sources.contains_key(name) {
+                if root.join(rule_path(name)).is_file() {
+                    bail!("rule '{}' was created in Cursor and has no source", name);
+                }
+                bail!("unknown rule '{}'", name);
+            }
+            if root.join(disabled_path(name)).is_file() {
+                bail!("rule '{}' is disabled", name);
+            }
+            vec![name.to_string()]
+        }
+        None => rule_files(&root.join(RULES_DIR), Some(DISABLED_DIR))?
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| sources.contains_key(name))
+            .collect(),
+    };
+    let mut patches = Vec::new();
+    for name in names {
+        let rule = &sources[&name];
+        let installed = rule_path(&name);
+        let path = root.join(&installed);
+        let current = if path.is_file() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", installed.display()))?
+        } else {
+            String::new()
+        };
+        let current = provenance::strip(&current);
+        if current == rule.content {
+            continue;
+        }
+        let patch = diffy::DiffOptions::new()
+            .set_original_filename(installed.display().to_string())
+            .set_modified_filename(
+                source_path(rule, flowmates_dir.as_deref())
+                    .display()
+                    .to_string(),
+            )
+            .create_patch(&current, &rule.content)
+            .to_string();
+        patches.push((name, patch));
+    }
+    Ok(patches)
+}
+
+/// Where a source rule comes from: its file in the flowmates checkout, or
+/// `embedded/<name>.mdc`.
+fn source_path(rule: &SourceRule, flowmates_dir: Option<&Path>) -> PathBuf {
+    let file_name = format!("{}.{}", rule.name, RULE_EXTENSION);
+    match (rule.source, flowmates_dir) {
+        (Source::Flowmates, Some(dir)) => dir.join(FLOWMATES_RULES_DIR).join(file_name),
+        _ => Path::new("embedded").join(file_name),
+    }
+}
+
+/// Check the front-matter of the source rules and of the installed rules
+/// that differ from their source (see [`mdc::lint`]). Embedded rules are
+/// reported under `embedded/`.
+pub fn lint(root: &Path, config: &RulesConfig) -> Result<Vec<Violation>> {
+    let flowmates_dir = config.flowmates_dir();
+    let sources = sources(flowmates_dir.as_deref())?;
+    let mut files = Vec::new();
+    for rule in sources.values() {
+        files.push((
+            source_path(rule, flowmates_dir.as_deref()),
+            rule.content.clone(),
+        ));
+    }
+    for (name, path) in rule_files(&root.join(RULES_DIR), Some(DISABLED_DIR))? {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if sources.get(&name).is_none_or(|s| s.content != content) {
+            files.push((rule_path(&name), content));
+        }
+    }
+    Ok(files
+        .into_iter()
+        .flat_map(|(path, content)| {
+            mdc::lint(&content)
+                .into_iter()
+                .map(move |message| Violation {
+                    path: path.clone(),
+                    message,
+                })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(installed: &[(PathBuf, Outcome)]) -> Vec<PathBuf> {
+        installed
+            .iter()
+            .filter(|(_, outcome)| outcome.is_written())
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn tells_the_flowmates_checkout_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("rules")).unwrap();
+        assert!(is_source_repo(&repo, &repo));
+        assert!(is_source_repo(&repo.join("rules"), &repo));
+        assert!(is_source_repo(&repo.join("rules/.."), &repo));
+        assert!(!is_source_repo(&repo, &repo.join("rules")));
+        assert!(!is_source_repo(&repo, &dir.path().join("missing")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_flowmates_rules_to_their_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let flowmates = tempfile::tempdir().unwrap();
+        let source = flowmates.path().join("rules/style.mdc");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "four spaces\n").unwrap();
+        let mut config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            link: true,
+            ..RulesConfig::default()
+        };
+        let installed = root.join(rule_path("style"));
+
+        sync(root, &config, false).unwrap();
+        assert!(links_to(&installed, &source));
+        assert!(!root.join(rule_path("echo-workflow")).is_symlink());
+        fs::write(&source, "tabs\n").unwrap();
+        assert_eq!(fs::read_to_string(&installed).unwrap(), "tabs\n");
+        assert!(written(&sync(root, &config, false).unwrap()[0].1).is_empty());
+        assert!(is_link(
+            Lock::load(root).unwrap().get(&rule_path("style")).unwrap()
+        ));
+
+        config.link = false;
+        assert_eq!(
+            written(&sync(root, &config, false).unwrap()[0].1),
+            vec![rule_path("style")]
+        );
+        assert!(!installed.is_symlink());
+        assert_eq!(fs::read_to_string(&source).unwrap(), "tabs\n");
+        config.link = true;
+        sync(root, &config, false).unwrap();
+        assert!(links_to(&installed, &source));
+
+        std::os::unix::fs::symlink(&source, root.join(rule_path("mine"))).unwrap();
+        fs::remove_file(&source).unwrap();
+        assert_eq!(
+            check_links(root).unwrap(),
+            vec![
+                (rule_path("mine"), LinkProblem::Broken),
+                (rule_path("style"), LinkProblem::Broken),
+            ]
+        );
+        fs::write(&source, "back\n").unwrap();
+        assert_eq!(
+            check_links(root).unwrap(),
+            vec![(rule_path("mine"), LinkProblem::Foreign)]
+        );
+    }
+
+    #[test]
+    fn embedded_rules_pass_lint() {
+        for (name, content) in EMBEDDED {
+            assert!(mdc::lint(content).is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn lints_sources_and_changed_installs() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        fs::create_dir_all(flowmates.path().join("rules")).unwrap();
+        fs::write(
+            flowmates.path().join("rules/rust.mdc"),
+            "---\nglobs: *.rs\n---\n",
+        )
+        .unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            ..RulesConfig::default()
+        };
+        install(dir.path(), &config, false).unwrap();
+        fs::write(dir.path().join(rule_path("local")), "---\nglob: x\n---\n").unwrap();
+
+        let violations = lint(dir.path(), &config).unwrap();
+        let found: Vec<_> = violations
+            .iter()
+            .map(|v| (v.path.clone(), v.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (flowmates.path().join("rules/rust.mdc"), "missing description"),
+                (rule_path("local"), "missing description"),
+                (
+                    rule_path("local"),
+                    "unknown front-matter key 'glob' (expected one of description, globs, alwaysApply)"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_provenance_and_modifications() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        fs::create_dir_all(flowmates.path().join("rules")).unwrap();
+        fs::write(
+            flowmates.path().join("rules/rust.mdc"),
+            "---\nglobs: *.rs\n---\n",
+        )
+        .unwrap();
+
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            ..RulesConfig::default()
+        };
+        let installed = install(dir.path(), &config, false).unwrap();
+        assert_eq!(written(&installed).len(), EMBEDDED.len() + 1);
+        fs::write(dir.path().join(rule_path("issue-files")), "edited").unwrap();
+        fs::write(dir.path().join(rule_path("local")), "mine").unwrap();
+        fs::write(dir.path().join(".cursor/rules/notes.txt"), "x").unwrap();
+
+        let rules = list(dir.path(), &config).unwrap();
+        let summary: Vec<_> = rules
+            .iter()
+            .map(|r| (r.name.as_str(), r.source, r.modified))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("echo-workflow", Source::Embedded, Some(false)),
+                ("issue-files", Source::Embedded, Some(true)),
+                ("local", Source::Cursor, None),
+                ("rust", Source::Flowmates, Some(false)),
+            ]
+        );
+        assert_eq!(rules[3].globs, vec!["*.rs"]);
+        assert_eq!(rules[2].hash.len(), 64);
+
+        let embedded_only = RulesConfig::default();
+        let outcomes: Vec<_> = install(dir.path(), &embedded_only, false)
+            .unwrap()
+            .into_iter()
+            .map(|(_, outcome)| outcome)
+            .collect();
+        assert_eq!(outcomes, vec![Outcome::UpToDate, Outcome::Modified]);
+        assert_eq!(
+            written(&install(dir.path(), &embedded_only, true).unwrap()),
+            vec![rule_path("issue-files")]
+        );
+    }
+
+    #[test]
+    fn include_and_exclude_filter_installs() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        fs::create_dir_all(flowmates.path().join("rules")).unwrap();
+        for name in ["rust-style", "frontend-react", "frontend-css"] {
+            fs::write(flowmates.path().join(format!("rules/{}.mdc", name)), "x").unwrap();
+        }
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            include: vec!["rust-*.mdc".into(), "frontend-*.mdc".into()],
+            exclude: vec!["frontend-css.mdc".into()],
+            ..RulesConfig::default()
+        };
+        let installed = install(dir.path(), &config, false).unwrap();
+        assert_eq!(
+            written(&installed),
+            vec![rule_path("frontend-react"), rule_path("rust-style")]
+        );
+
+        let invalid = RulesConfig {
+            exclude: vec!["[".into()],
+            ..RulesConfig::default()
+        };
+        let err = install(dir.path(), &invalid, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("rules.exclude"));
+    }
+
+    #[test]
+    fn active_labels_add_their_rules() {
+        let mut config: RulesConfig = toml::from_str(
+            "exclude = [\"frontend-*.mdc\"]\n\
+             [labels.css]\n\
+             include = [\"frontend-*.mdc\"]\n\
+             exclude = [\"frontend-react.mdc\"]\n\
+             [labels.none]\n",
+        )
+        .unwrap();
+        assert!(!config.filter().unwrap().matches("frontend-css.mdc"));
+        config.active_labels = vec!["css".into(), "none".into(), "unknown".into()];
+        let filter = config.filter().unwrap();
+        assert!(filter.matches("frontend-css.mdc"));
+        assert!(!filter.matches("frontend-react.mdc"));
+        assert!(filter.matches("rust-style.mdc"));
+    }
+
+    #[test]
+    fn installs_namespaces_with_their_own_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        for path in ["rust/style", "rust/unsafe", "web/react", "web/frontend-css"] {
+            let file = flowmates.path().join(format!("rules/{}.mdc", path));
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, "x").unwrap();
+        }
+        let config: RulesConfig = toml::from_str(&format!(
+            "flowmates-dir = {:?}\n\
+             exclude = [\"frontend-*.mdc\", \"echo-*\", \"issue-*\"]\n\
+             [namespaces.rust]\n\
+             exclude = [\"unsafe.mdc\"]\n",
+            flowmates.path()
+        ))
+        .unwrap();
+        let installed = install(dir.path(), &config, false).unwrap();
+        assert_eq!(
+            written(&installed),
+            vec![rule_path("rust/style"), rule_path("web/react")]
+        );
+        assert!(dir.path().join(".cursor/rules/rust/style.mdc").is_file());
+
+        disable(dir.path(), &config, "rust/style").unwrap();
+        assert!(dir
+            .path()
+            .join(".cursor/rules/disabled/rust/style.mdc")
+            .is_file());
+        let listed = list(dir.path(), &config).unwrap();
+        let names: Vec<_> = listed
+            .iter()
+            .map(|r| (r.name.as_str(), r.namespace.as_deref(), r.disabled))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("rust/style", Some("rust"), true),
+                ("web/react", Some("web"), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn diffs_installed_rules_against_their_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = RulesConfig::default();
+        assert_eq!(
+            diff(dir.path(), &config, Some("issue-files"))
+                .unwrap()
+                .len(),
+            1
+        );
+        install(dir.path(), &config, false).unwrap();
+        assert!(diff(dir.path(), &config, None).unwrap().is_empty());
+
+        let path = dir.path().join(rule_path("issue-files"));
+        let edited = fs::read_to_string(&path).unwrap() + "Local note.\n";
+        fs::write(&path, edited).unwrap();
+        fs::write(dir.path().join(rule_path("local")), "mine").unwrap();
+        let patches = diff(dir.path(), &config, None).unwrap();
+        assert_eq!(patches.len(), 1);
+        let (name, patch) = &patches[0];
+        assert_eq!(name, "issue-files");
+        assert!(
+            patch.starts_with("--- .cursor/rules/issue-files.mdc\n+++ embedded/issue-files.mdc\n")
+        );
+        assert!(patch.contains("\n-Local note.\n"));
+
+        assert!(diff(dir.path(), &config, Some("local")).is_err());
+        assert!(diff(dir.path(), &config, Some("nope")).is_err());
+    }
+
+    #[test]
+    fn creates_rules_locally_or_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            ..RulesConfig::default()
+        };
+        let front_matter = mdc::FrontMatter {
+            description: Some("Rust error handling".into()),
+            globs: vec!["**/*.rs".into()],
+            ..mdc::FrontMatter::default()
+        };
+        let path = create(
+            dir.path(),
+            &config,
+            "rust/error_handling",
+            &front_matter,
+            false,
+        )
+        .unwrap();
+        assert_eq!(path, rule_path("rust/error_handling"));
+        let content = fs::read_to_string(dir.path().join(&path)).unwrap();
+        assert!(
+            content.ends_with("---\n\n# Error handling\n\n"),
+            "{}",
+            content
+        );
+        assert!(create(
+            dir.path(),
+            &config,
+            "rust/error_handling",
+            &front_matter,
+            false
+        )
+        .is_err());
+
+        let upstream = create(dir.path(), &config, "python", &front_matter, true).unwrap();
+        assert_eq!(upstream, flowmates.path().join("rules/python.mdc"));
+        assert!(sources(Some(flowmates.path()))
+            .unwrap()
+            .contains_key("python"));
+
+        assert!(create(dir.path(), &config, "Bad Name", &front_matter, false).is_err());
+        assert!(create(dir.path(), &config, "disabled/x", &front_matter, false).is_err());
+        let no_description = mdc::FrontMatter::default();
+        assert!(create(dir.path(), &config, "other", &no_description, false).is_err());
+        let bad_glob = mdc::FrontMatter {
+            globs: vec!["src/[".into()],
+            ..front_matter
+        };
+        assert!(create(dir.path(), &config, "other", &bad_glob, false).is_err());
+    }
+
+    #[test]
+    fn syncs_every_target_and_records_the_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: RulesConfig = toml::from_str(
+            "targets = [\"cursor\", \"windsurf\", \"copilot\", \"shared/rules\"]\n\
+             disabled = [\"issue-files\"]\n",
+        )
+        .unwrap();
+        let report = sync(dir.path(), &config, false).unwrap();
+        let summary: Vec<_> = report
+            .iter()
+            .map(|(target, installed)| (target.name(), written(installed)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("cursor".into(), vec![rule_path("echo-workflow")]),
+                (
+                    "windsurf".into(),
+                    vec![PathBuf::from(".windsurf/rules/echo-workflow.md")]
+                ),
+                (
+                    "copilot".into(),
+                    vec![PathBuf::from(
+                        ".github/instructions/echo-workflow.instructions.md"
+                    )]
+                ),
+                (
+                    "shared/rules".into(),
+                    vec![PathBuf::from("shared/rules/echo-workflow.mdc")]
+                ),
+            ]
+        );
+        let lock = Lock::load(dir.path()).unwrap();
+        assert_eq!(lock.files.len(), 4);
+        let windsurf = lock
+            .get(Path::new(".windsurf/rules/echo-workflow.md"))
+            .unwrap();
+        assert_eq!(windsurf.target, "windsurf");
+        assert_eq!(windsurf.source, "embedded");
+        let content =
+            fs::read_to_string(dir.path().join(".windsurf/rules/echo-workflow.md")).unwrap();
+        assert_eq!(windsurf.hash, lock::hash(&content));
+
+        assert!(sync(dir.path(), &config, false)
+            .unwrap()
+            .iter()
+            .all(|(_, installed)| written(installed).is_empty()));
+        assert_eq!(Lock::load(dir.path()).unwrap().files.len(), 4);
+    }
+
+    #[test]
+    fn preserves_source_metadata_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let source = flowmates.path().join("rules/style.mdc");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "style\n").unwrap();
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            include: vec!["style.mdc".into()],
+            preserve_metadata: true,
+            ..RulesConfig::default()
+        };
+        sync(dir.path(), &config, false).unwrap();
+        let installed = dir.path().join(rule_path("style"));
+        assert_eq!(fs::metadata(&installed).unwrap().modified().unwrap(), time);
+        let mtime = |root: &Path| {
+            Lock::load(root)
+                .unwrap()
+                .get(&rule_path("style"))
+                .unwrap()
+                .mtime
+        };
+        assert_eq!(mtime(dir.path()), Some(1_000_000));
+
+        // Up-to-date rules keep the recorded time.
+        sync(dir.path(), &config, false).unwrap();
+        assert_eq!(mtime(dir.path()), Some(1_000_000));
+    }
+
+    #[test]
+    fn resolves_local_edits_when_the_source_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let source = flowmates.path().join("rules/style.mdc");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "a\nb\nc\n").unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            include: vec!["style.mdc".into()],
+            ..RulesConfig::default()
+        };
+        sync(dir.path(), &config, false).unwrap();
+        let installed = dir.path().join(rule_path("style"));
+        fs::write(&installed, "a\nb\nc\nmine\n").unwrap();
+        fs::write(&source, "A\nb\nc\n").unwrap();
+
+        let mut asked = Vec::new();
+        let kept = sync_with(&RealFs, dir.path(), &config, None, |conflict| {
+            asked.push((conflict.path.to_path_buf(), conflict.base.is_some()));
+            Ok(Resolution::Keep)
+        })
+        .unwrap();
+        assert_eq!(asked, vec![(rule_path("style"), true)]);
+        assert_eq!(kept[0].1, vec![(rule_path("style"), Outcome::Modified)]);
+
+        let merged = sync_with(&RealFs, dir.path(), &config, None, |_| {
+            Ok(Resolution::Merge)
+        })
+        .unwrap();
+        assert_eq!(merged[0].1, vec![(rule_path("style"), Outcome::Merged)]);
+        assert_eq!(fs::read_to_string(&installed).unwrap(), "A\nb\nc\nmine\n");
+        let lock = Lock::load(dir.path()).unwrap();
+        let locked = lock.get(&rule_path("style")).unwrap();
+        assert_eq!(
+            lock::read_base(dir.path(), &rule_path("style"), &locked.hash).as_deref(),
+            Some("A\nb\nc\n")
+        );
+    }
+
+    #[test]
+    fn installs_overlays_in_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: RulesConfig = toml::from_str(
+            "include = [\"echo-*.mdc\"]\n\
+             [overlays.\"services/api\"]\n\
+             include = [\"issue-*.mdc\"]\n",
+        )
+        .unwrap();
+        let report = sync(dir.path(), &config, false).unwrap();
+        assert_eq!(report[1].0.name(), "services/api/.cursor/rules");
+        let overlay = PathBuf::from("services/api/.cursor/rules/issue-files.mdc");
+        assert_eq!(written(&report[1].1), vec![overlay.clone()]);
+        assert!(!dir.path().join(rule_path("issue-files")).exists());
+        assert_eq!(
+            Lock::load(dir.path()).unwrap().get(&overlay).unwrap().name,
+            "issue-files"
+        );
+
+        let escaping: RulesConfig = toml::from_str("[overlays.\"../x\"]\n").unwrap();
+        assert!(sync(dir.path(), &escaping, false).is_err());
+    }
+
+    #[test]
+    fn preset_patterns_narrow_the_installed_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = RulesConfig {
+            preset: vec!["echo-*.mdc".into()],
+            ..RulesConfig::default()
+        };
+        assert_eq!(
+            install(dir.path(), &config, false).unwrap(),
+            vec![(rule_path("echo-workflow"), Outcome::Installed)]
+        );
+    }
+
+    #[test]
+    fn disabled_rules_stay_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = "# team settings\n[lint]\nplaceholders = false\n";
+        fs::write(dir.path().join(".echo.toml"), settings).unwrap();
+        install(dir.path(), &RulesConfig::default(), false).unwrap();
+
+        disable(dir.path(), &RulesConfig::default(), "issue-files").unwrap();
+        assert!(!dir.path().join(rule_path("issue-files")).exists());
+        assert!(dir.path().join(disabled_path("issue-files")).is_file());
+        let config = config::Config::load(dir.path()).unwrap().rules;
+        assert_eq!(config.disabled, vec!["issue-files"]);
+        let toml = fs::read_to_string(dir.path().join(".echo.toml")).unwrap();
+        assert!(toml.starts_with(settings), "{}", toml);
+
+        assert!(install(dir.path(), &config, true)
+            .unwrap()
+            .iter()
+            .all(|(p, _)| !p.ends_with("issue-files.mdc")));
+        assert!(!dir.path().join(rule_path("issue-files")).exists());
+        let listed = list(dir.path(), &config).unwrap();
+        assert!(listed.iter().any(|r| r.name == "issue-files" && r.disabled));
+
+        enable(dir.path(), &config, "issue-files").unwrap();
+        assert!(dir.path().join(rule_path("issue-files")).is_file());
+        let config = config::Config::load(dir.path()).unwrap().rules;
+        assert!(config.disabled.is_empty());
+
+        assert!(disable(dir.path(), &config, "nope").is_err());
+        assert!(enable(dir.path(), &config, "nope").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! `echo rules outdated`: installed rules whose source changed since echo
+//! wrote them, with the commits of the flowmates checkout behind each
+//! change when the lockfile knows the revision they were installed from.
+
+use super::{is_link, sources, RulesConfig, Source};
+use crate::git;
+use crate::gitutil;
+use crate::lock::{self, Lock};
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+/// An installed rule behind its source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Outdated {
+    pub name: String,
+    /// Path relative to the repository root.
+    pub path: PathBuf,
+    pub source: Source,
+    /// Whether the installed file was also edited since, so updating will
+    /// not overwrite it.
+    pub modified: bool,
+    /// Commits of the flowmates checkout touching the rule since the
+    /// locked revision, newest first, as `<short hash> <subject>`.
+    pub commits: Vec<String>,
+}
+
+/// Rules installed for the targets of `config` whose source no longer
+/// hashes to what the lockfile recorded. Sorted by path.
+pub fn outdated(root: &std::path::Path, config: &RulesConfig) -> Result<Vec<Outdated>> {
+    let lock = Lock::load(root)?;
+    let flowmates_dir = config.flowmates_dir();
+    let sources = sources(flowmates_dir.as_deref())?;
+    let revision = lock
+        .revision
+        .as_deref()
+        .filter(|_| flowmates_dir.as_deref().is_some_and(gitutil::is_repo));
+    let mut found = Vec::new();
+    for target in config.targets() {
+        for rule in sources.values() {
+            let path = target.path(&rule.name);
+            // Links follow their source already.
+            let Some(locked) = lock.get(&path).filter(|locked| !is_link(locked)) else {
+                continue;
+            };
+            if lock::hash(&target.convert(&rule.content)) == locked.hash {
+                continue;
+            }
+            let modified = fs::read_to_string(root.join(&path))
+                .is_ok_and(|installed| lock::hash_installed(&installed) != locked.hash);
+            let commits = match (revision, &flowmates_dir, &rule.path) {
+                (Some(revision), Some(dir), Some(source)) => {
+                    let range = format!("{}..HEAD", revision);
+                    let source = source.to_string_lossy();
+                    git::run(dir, &["log", "--format=%h %s", &range, "--", &source])?
+                        .lines()
+                        .map(str::to_string)
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+            found.push(Outdated {
+                name: rule.name.clone(),
+                path,
+                source: rule.source,
+                modified,
+                commits,
+            });
+        }
+    }
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{rule_path, sync};
+    use std::path::Path;
+    use std::process::Command;
+
+    fn commit(dir: &Path, message: &str) {
+        for args in [
+            &["add", "-A"][..],
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-qm",
+                message,
+            ],
+        ] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[test]
+    fn lists_rules_behind_their_source_with_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        git::run(flowmates.path(), &["init", "-q"]).unwrap();
+        let source = flowmates.path().join("rules/style.mdc");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "style\n").unwrap();
+        commit(flowmates.path(), "Add style");
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            include: vec!["style.mdc".into()],
+            ..RulesConfig::default()
+        };
+        sync(dir.path(), &config, false).unwrap();
+        assert!(Lock::load(dir.path()).unwrap().revision.is_some());
+        assert!(outdated(dir.path(), &config).unwrap().is_empty());
+
+        fs::write(&source, "tabs\n").unwrap();
+        commit(flowmates.path(), "Prefer tabs");
+        let found = outdated(dir.path(), &config).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, rule_path("style"));
+        assert!(!found[0].modified);
+        assert_eq!(found[0].commits.len(), 1);
+        assert!(found[0].commits[0].ends_with(" Prefer tabs"));
+    }
+}
@@ -0,0 +1,141 @@
+//! Destinations the source rules are installed to.
+//!
+//! `[rules] targets` in `.echo.toml` lists them; the default is Cursor
+//! alone. Each target gets its own file layout and front-matter:
+//!
+//! | Target     | Path                                          |
+//! |------------|-----------------------------------------------|
+//! | `cursor`   | `.cursor/rules/<name>.mdc`                    |
+//! | `windsurf` | `.windsurf/rules/<name>.md`                   |
+//! | `copilot`  | `.github/instructions/<name>.instructions.md` |
+//! | any path   | `<path>/<name>.mdc`, copied as-is             |
+
+use super::{mdc, RULES_DIR, RULE_EXTENSION};
+use std::path::{Path, PathBuf};
+
+/// Windsurf workspace rules directory.
+pub const WINDSURF_DIR: &str = ".windsurf/rules";
+
+/// GitHub Copilot path-specific instructions directory.
+pub const COPILOT_DIR: &str = ".github/instructions";
+
+/// A destination for installed rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleTarget {
+    Cursor,
+    Windsurf,
+    Copilot,
+    /// A directory relative to the repository root receiving `.mdc` files.
+    Dir(PathBuf),
+}
+
+impl RuleTarget {
+    /// Parse a `[rules] targets` entry; anything but a known name is a path.
+    pub fn parse(value: &str) -> RuleTarget {
+        match value {
+            "cursor" => RuleTarget::Cursor,
+            "windsurf" => RuleTarget::Windsurf,
+            "copilot" => RuleTarget::Copilot,
+            path => RuleTarget::Dir(PathBuf::from(path)),
+        }
+    }
+
+    /// Name used in reports and the lockfile.
+    pub fn name(&self) -> String {
+        match self {
+            RuleTarget::Cursor => "cursor".to_string(),
+            RuleTarget::Windsurf => "windsurf".to_string(),
+            RuleTarget::Copilot => "copilot".to_string(),
+            RuleTarget::Dir(path) => path.display().to_string(),
+        }
+    }
+
+    /// Path of the rule `name` for this target, relative to the root.
+    pub fn path(&self, name: &str) -> PathBuf {
+        match self {
+            RuleTarget::Cursor => Path::new(RULES_DIR).join(format!("{}.{}", name, RULE_EXTENSION)),
+            RuleTarget::Windsurf => Path::new(WINDSURF_DIR).join(format!("{}.md", name)),
+            RuleTarget::Copilot => Path::new(COPILOT_DIR).join(format!("{}.instructions.md", name)),
+            RuleTarget::Dir(dir) => dir.join(format!("{}.{}", name, RULE_EXTENSION)),
+        }
+    }
+
+    /// Convert `.mdc` contents to the format of this target.
+    pub fn convert(&self, content: &str) -> String {
+        if matches!(self, RuleTarget::Cursor | RuleTarget::Dir(_)) {
+            return content.to_string();
+        }
+        let (front_matter, body) = match mdc::parse(content) {
+            Ok(mdc) => (mdc.front_matter.unwrap_or_default(), mdc.body),
+            Err(_) => (mdc::FrontMatter::default(), content),
+        };
+        let mut out = String::from("---\n");
+        if self == &RuleTarget::Windsurf {
+            let trigger = if front_matter.always_apply == Some(true) {
+                "always_on"
+            } else if !front_matter.globs.is_empty() {
+                "glob"
+            } else if front_matter.description.is_some() {
+                "model_decision"
+            } else {
+                "manual"
+            };
+            out.push_str(&format!("trigger: {}\n", trigger));
+            if !front_matter.globs.is_empty() {
+                out.push_str(&format!("globs: {}\n", front_matter.globs.join(", ")));
+            }
+        } else {
+            let apply_to = if front_matter.always_apply == Some(true) {
+                "**".to_string()
+            } else {
+                front_matter.globs.join(",")
+            };
+            if !apply_to.is_empty() {
+                out.push_str(&format!("applyTo: \"{}\"\n", apply_to));
+            }
+        }
+        if let Some(description) = &front_matter.description {
+            out.push_str(&format!(
+                "description: \"{}\"\n",
+                description.replace('"', "'")
+            ));
+        }
+        out.push_str("---\n");
+        out.push_str(body);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_rules_to_each_target() {
+        let rule = "---\ndescription: Rust style\nglobs: **/*.rs, Cargo.toml\nalwaysApply: false\n---\n# Style\n";
+        assert_eq!(RuleTarget::parse("cursor").convert(rule), rule);
+        assert_eq!(
+            RuleTarget::Windsurf.convert(rule),
+            "---\ntrigger: glob\nglobs: **/*.rs, Cargo.toml\ndescription: \"Rust style\"\n---\n# Style\n"
+        );
+        assert_eq!(
+            RuleTarget::Copilot.convert(rule),
+            "---\napplyTo: \"**/*.rs,Cargo.toml\"\ndescription: \"Rust style\"\n---\n# Style\n"
+        );
+        let always = "---\ndescription: Workflow\nalwaysApply: true\n---\nBody\n";
+        assert!(RuleTarget::Windsurf
+            .convert(always)
+            .starts_with("---\ntrigger: always_on\n"));
+        assert!(RuleTarget::Copilot
+            .convert(always)
+            .starts_with("---\napplyTo: \"**\"\n"));
+
+        assert_eq!(
+            RuleTarget::Copilot.path("rust/style"),
+            Path::new(".github/instructions/rust/style.instructions.md")
+        );
+        let custom = RuleTarget::parse("docs/rules");
+        assert_eq!(custom.name(), "docs/rules");
+        assert_eq!(custom.path("x"), Path::new("docs/rules/x.mdc"));
+    }
+}
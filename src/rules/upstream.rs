@@ -0,0 +1,160 @@
+//! Sending rules edited in a project back to the flowmates checkout.
+
+use super::{rule_path, RulesConfig, FLOWMATES_RULES_DIR, RULE_EXTENSION};
+use crate::git;
+use crate::gitutil;
+use crate::provenance;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the branches created by [`contribute`].
+pub const BRANCH_PREFIX: &str = "rules/";
+
+/// A rule committed to the flowmates checkout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    /// Flowmates checkout the rule was committed to.
+    pub repo: PathBuf,
+    pub branch: String,
+    /// Path of the rule, relative to `repo`.
+    pub path: PathBuf,
+    /// Whether the branch was pushed to `origin`.
+    pub pushed: bool,
+    /// Page to open the pull request, for GitHub and GitLab remotes.
+    pub url: Option<String>,
+}
+
+/// Commit the installed rule `name` to a new `rules/<name>` branch of the
+/// flowmates checkout, pushing it to `origin` when `push` is set. The
+/// checkout is left on the branch it was on.
+pub fn contribute(
+    root: &Path,
+    config: &RulesConfig,
+    name: &str,
+    push: bool,
+) -> Result<Contribution> {
+    let Some(repo) = config.flowmates_dir() else {
+        bail!("no flowmates checkout configured (set [rules] flowmates-dir or $FLOWMATES_DIR)");
+    };
+    if !gitutil::is_repo(&repo) {
+        bail!("{} is not a git repository", repo.display());
+    }
+    let installed = rule_path(name);
+    let content = fs::read_to_string(root.join(&installed))
+        .with_context(|| format!("rule '{}' is not installed", name))?;
+    // The provenance header describes the install, not the rule.
+    let content = provenance::strip(&content).into_owned();
+    let path = Path::new(FLOWMATES_RULES_DIR).join(format!("{}.{}", name, RULE_EXTENSION));
+    let rel = path.to_string_lossy();
+    let upstream = repo.join(&path);
+    let exists = upstream.is_file();
+    if exists && fs::read_to_string(&upstream)? == content {
+        bail!("rule '{}' has no local changes", name);
+    }
+    if !git::run(&repo, &["status", "--porcelain", "--", &rel])?.is_empty() {
+        bail!("{} has uncommitted changes in {}", rel, repo.display());
+    }
+
+    let branch = format!("{}{}", BRANCH_PREFIX, name.replace('/', "-"));
+    let original = match gitutil::branch(&repo) {
+        Some(branch) if branch != "HEAD" => branch,
+        _ => gitutil::head(&repo).context("the flowmates checkout has no commit")?,
+    };
+    git::run(&repo, &["checkout", "-b", &branch])?;
+    let message = format!("{} rule {}", if exists { "Update" } else { "Add" }, name);
+    let committed = (|| -> Result<()> {
+        if let Some(parent) = upstream.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&upstream, &content)
+            .with_context(|| format!("failed to write {}", upstream.display()))?;
+        git::run(&repo, &["add", "--", &rel])?;
+        git::run(&repo, &["commit", "-m", &message, "--", &rel])?;
+        Ok(())
+    })();
+    if let Err(err) = committed {
+        // Put the checkout back as it was before failing.
+        let _ = git::run(&repo, &["checkout", "-f", &original]);
+        let _ = git::run(&repo, &["branch", "-D", &branch]);
+        return Err(err);
+    }
+    git::run(&repo, &["checkout", &original])?;
+    if push {
+        git::run(&repo, &["push", "-u", "origin", &branch])?;
+    }
+    let url = gitutil::remote_url(&repo, "origin")
+        .and_then(|remote| git::pull_request_url(&remote, &branch));
+    Ok(Contribution {
+        repo,
+        branch,
+        path,
+        pushed: push,
+        url,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Source, Stamp};
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        git::run(dir, args).unwrap()
+    }
+
+    #[test]
+    fn commits_local_rule_to_a_branch() {
+        let project = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let repo = flowmates.path();
+        git(repo, &["init", "-q", "-b", "main"]);
+        git(repo, &["config", "user.name", "Test"]);
+        git(repo, &["config", "user.email", "test@example.com"]);
+        fs::create_dir(repo.join("rules")).unwrap();
+        fs::write(repo.join("rules/rust.mdc"), "old\n").unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-q", "-m", "init"]);
+        git(
+            repo,
+            &[
+                "remote",
+                "add",
+                "origin",
+                "git@github.com:team/flowmates.git",
+            ],
+        );
+
+        let config = RulesConfig {
+            flowmates_dir: Some(repo.to_path_buf()),
+            ..RulesConfig::default()
+        };
+        assert!(contribute(project.path(), &config, "rust", false).is_err());
+        fs::create_dir_all(project.path().join(".cursor/rules")).unwrap();
+        let stamp = Stamp::new(Some(repo), 0);
+        let stamped = stamp.apply(Source::Flowmates, "rules/rust.mdc", "old\n");
+        fs::write(project.path().join(rule_path("rust")), stamped).unwrap();
+        let err = contribute(project.path(), &config, "rust", false).unwrap_err();
+        assert!(err.to_string().contains("no local changes"));
+
+        let stamped = stamp.apply(Source::Flowmates, "rules/rust.mdc", "new\n");
+        fs::write(project.path().join(rule_path("rust")), stamped).unwrap();
+        let contribution = contribute(project.path(), &config, "rust", false).unwrap();
+        assert_eq!(contribution.branch, "rules/rust");
+        assert_eq!(
+            contribution.url.as_deref(),
+            Some("https://github.com/team/flowmates/compare/rules/rust?expand=1")
+        );
+        assert_eq!(git(repo, &["rev-parse", "--abbrev-ref", "HEAD"]), "main");
+        assert_eq!(
+            fs::read_to_string(repo.join("rules/rust.mdc")).unwrap(),
+            "old\n"
+        );
+        assert_eq!(git(repo, &["show", "rules/rust:rules/rust.mdc"]), "new");
+        assert_eq!(
+            git(repo, &["log", "-1", "--format=%s", "rules/rust"]),
+            "Update rule rust"
+        );
+        assert!(contribute(project.path(), &config, "rust", false).is_err());
+    }
+}
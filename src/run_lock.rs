@@ -0,0 +1,269 @@
+//! Advisory lock taken by commands that write to the repository, so two
+//! runs (two agents, or an agent and a human) do not interleave writes.
+//!
+//! The lock is [`RUN_LOCK_FILE`], created exclusively and holding the pid
+//! and start time of its owner. A lock whose owner is gone, or older than
+//! [`STALE_AFTER`], is taken over, as is one that names no owner, left by
+//! a run that died creating it, once [`UNREADABLE_STALE_AFTER`] has passed.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Lock file path, relative to the repository root.
+pub const RUN_LOCK_FILE: &str = ".echo/run.lock";
+
+/// Age after which a lock is considered abandoned.
+pub const STALE_AFTER: Duration = Duration::from_secs(600);
+
+/// Age after which a lock naming no owner is considered abandoned; its
+/// owner writes it right after creating it.
+pub const UNREADABLE_STALE_AFTER: Duration = Duration::from_secs(5);
+
+/// Delay between two attempts when waiting.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Owner of a lock, as recorded in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    /// Seconds since the epoch.
+    pub started: u64,
+}
+
+impl Holder {
+    fn parse(content: &str) -> Option<Holder> {
+        let mut pid = None;
+        let mut started = None;
+        for line in content.lines() {
+            match line.split_once('=') {
+                Some(("pid", value)) => pid = value.trim().parse().ok(),
+                Some(("started", value)) => started = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Holder {
+            pid: pid?,
+            started: started?,
+        })
+    }
+
+    /// Whether the owner is gone: too old, or not running when that can
+    /// be told from `/proc`.
+    fn is_stale(&self, now: u64) -> bool {
+        if now.saturating_sub(self.started) > STALE_AFTER.as_secs() {
+            return true;
+        }
+        let proc = Path::new("/proc");
+        proc.join("self").exists() && !proc.join(self.pid.to_string()).exists()
+    }
+}
+
+/// A held lock, released when dropped.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    /// Take the lock of `root`. When another run holds it, fail or, with
+    /// `wait`, retry until it is released; `on_wait` is called once with
+    /// the holder before waiting.
+    pub fn acquire(root: &Path, wait: bool, mut on_wait: impl FnMut(&Holder)) -> Result<RunLock> {
+        let path = root.join(RUN_LOCK_FILE);
+        let mut waited = false;
+        loop {
+            // Recreated each time: a released lock takes an empty `.echo/`
+            // along with it.
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    if let Err(err) =
+                        write!(file, "pid={}\nstarted={}\n", std::process::id(), now())
+                    {
+                        let _ = fs::remove_file(&path);
+                        return Err(err)
+                            .with_context(|| format!("failed to write {}", RUN_LOCK_FILE));
+                    }
+                    return Ok(RunLock { path });
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("failed to create {}", RUN_LOCK_FILE))
+                }
+            }
+            let Some(seen) = LockFile::read(&path) else {
+                // Removed meanwhile.
+                continue;
+            };
+            let holder = Holder::parse(&seen.content);
+            let stale = match &holder {
+                Some(holder) => holder.is_stale(now()),
+                None => seen.age() > UNREADABLE_STALE_AFTER,
+            };
+            if stale {
+                take_over(&path, &seen);
+                continue;
+            }
+            let Some(holder) = holder else {
+                // Being written by its owner.
+                thread::sleep(RETRY_INTERVAL);
+                continue;
+            };
+            if !wait {
+                bail!(
+                    "another echo run (pid {}) is modifying this repository; retry with --wait, or remove {} if it is stuck",
+                    holder.pid,
+                    RUN_LOCK_FILE
+                );
+            }
+            if !waited {
+                on_wait(&holder);
+                waited = true;
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        // Only succeeds when the lock was all there was in `.echo/`.
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+}
+
+/// A lock file as read by a run waiting for it.
+#[derive(Debug, PartialEq, Eq)]
+struct LockFile {
+    content: String,
+    modified: SystemTime,
+}
+
+impl LockFile {
+    /// The lock at `path`; none once it is gone.
+    fn read(path: &Path) -> Option<LockFile> {
+        let modified = fs::metadata(path).ok()?.modified().ok()?;
+        let content = String::from_utf8_lossy(&fs::read(path).ok()?).into_owned();
+        Some(LockFile { content, modified })
+    }
+
+    fn age(&self) -> Duration {
+        self.modified.elapsed().unwrap_or_default()
+    }
+}
+
+/// Remove the stale lock `seen` at `path`. It is renamed aside first, so
+/// that of runs taking it over at once only one removes it, and put back
+/// when it turns out to be a lock another run took meanwhile.
+fn take_over(path: &Path, seen: &LockFile) {
+    static TAKEOVERS: AtomicUsize = AtomicUsize::new(0);
+    let aside = path.with_extension(format!(
+        "lock.stale-{}-{}",
+        std::process::id(),
+        TAKEOVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    if fs::rename(path, &aside).is_err() {
+        return;
+    }
+    if LockFile::read(&aside).as_ref() != Some(seen) {
+        // Fails, keeping the newer lock, if one was taken since.
+        let _ = fs::hard_link(&aside, path);
+    }
+    let _ = fs::remove_file(&aside);
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes_other_runs_and_recovers_stale_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let lock = RunLock::acquire(root, false, |_| {}).unwrap();
+        let content = fs::read_to_string(root.join(RUN_LOCK_FILE)).unwrap();
+        assert_eq!(Holder::parse(&content).unwrap().pid, std::process::id());
+        let err = RunLock::acquire(root, false, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("--wait"));
+        drop(lock);
+        assert!(!root.join(".echo").exists());
+
+        let path = root.join(RUN_LOCK_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("pid={}\nstarted=0\n", std::process::id())).unwrap();
+        let lock = RunLock::acquire(root, false, |_| {}).unwrap();
+        drop(lock);
+
+        // Left by a run that died between creating and writing it.
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - UNREADABLE_STALE_AFTER * 2)
+            .unwrap();
+        let _lock = RunLock::acquire(root, false, |_| {}).unwrap();
+        let names: Vec<_> = fs::read_dir(root.join(".echo"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["run.lock"]);
+    }
+
+    #[test]
+    fn puts_back_a_lock_taken_meanwhile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RUN_LOCK_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "pid=1\nstarted=0\n").unwrap();
+        let seen = LockFile::read(&path).unwrap();
+        // Another waiter took the stale lock over first and holds a new one.
+        fs::write(
+            &path,
+            format!("pid={}\nstarted={}\n", std::process::id(), now()),
+        )
+        .unwrap();
+        take_over(&path, &seen);
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(Holder::parse(&content).unwrap().pid, std::process::id());
+        take_over(&path, &LockFile::read(&path).unwrap());
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn waits_for_the_holder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let lock = RunLock::acquire(&root, false, |_| {}).unwrap();
+        let waiter = {
+            let root = root.clone();
+            thread::spawn(move || {
+                let mut waited = false;
+                let _lock = RunLock::acquire(&root, true, |_| waited = true).unwrap();
+                waited
+            })
+        };
+        thread::sleep(Duration::from_millis(300));
+        drop(lock);
+        assert!(waiter.join().unwrap());
+    }
+}
@@ -0,0 +1,223 @@
+//! Fingerprints of the files of a flowmates checkout, so repeated runs
+//! over a large one skip re-hashing the files that did not change.
+//!
+//! [`CACHE_FILE`] in echo's cache directory maps each file hashed to its
+//! size, modification time and SHA-256; a file whose size and time still
+//! match is not read again. The cache belongs to one checkout and starts
+//! over when the checkout path changes. Files modified in the last
+//! [`SETTLE_SECS`] are hashed but not cached, as a later write within the
+//! same clock tick would go unnoticed.
+
+use crate::lock;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Cache file, in the cache directory.
+pub const CACHE_FILE: &str = "scan.json";
+
+/// Age under which a file is not cached.
+pub const SETTLE_SECS: u64 = 2;
+
+/// What identifies a version of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    size: u64,
+    /// Modification time, in nanoseconds since the epoch.
+    modified: u128,
+    hash: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Stored {
+    source: PathBuf,
+    files: BTreeMap<PathBuf, Fingerprint>,
+}
+
+/// File hashes of one checkout, cached or not.
+#[derive(Debug)]
+pub struct ScanCache {
+    /// Cache file; `None` when caching is off.
+    path: Option<PathBuf>,
+    source: PathBuf,
+    files: Mutex<BTreeMap<PathBuf, Fingerprint>>,
+    /// Files hashed during this run, kept when saving.
+    seen: Mutex<Vec<PathBuf>>,
+    /// Whether a fingerprint was added or replaced.
+    dirty: AtomicBool,
+}
+
+impl ScanCache {
+    /// The cache of `source` in `cache_dir`. An unreadable cache, or one
+    /// of another checkout, starts empty.
+    pub fn load(cache_dir: &Path, source: &Path) -> ScanCache {
+        let path = cache_dir.join(CACHE_FILE);
+        let files = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Stored>(&content).ok())
+            .filter(|stored| stored.source == source)
+            .map(|stored| stored.files)
+            .unwrap_or_default();
+        ScanCache {
+            path: Some(path),
+            source: source.to_path_buf(),
+            files: Mutex::new(files),
+            seen: Mutex::new(Vec::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Hash every file of `source` afresh and remember nothing.
+    pub fn disabled(source: &Path) -> ScanCache {
+        ScanCache {
+            path: None,
+            source: source.to_path_buf(),
+            files: Mutex::new(BTreeMap::new()),
+            seen: Mutex::new(Vec::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// The checkout the cache is for.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// SHA-256 of the file `rel` of the checkout, as [`lock::hash_bytes`].
+    pub fn hash(&self, rel: &Path) -> Result<String> {
+        let path = self.source.join(rel);
+        let meta =
+            fs::metadata(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        let key = modified.map(|modified| (meta.len(), modified.as_nanos()));
+        if self.path.is_some() {
+            self.seen
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(rel.to_path_buf());
+            let files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+            if let (Some((size, modified)), Some(cached)) = (key, files.get(rel)) {
+                if cached.size == size && cached.modified == modified {
+                    return Ok(cached.hash.clone());
+                }
+            }
+        }
+        let content =
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let hash = lock::hash_bytes(&content);
+        let settled = modified.is_some_and(|modified| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .is_ok_and(|now| now.saturating_sub(modified) >= Duration::from_secs(SETTLE_SECS))
+        });
+        if let (Some(_), Some((size, modified)), true) = (&self.path, key, settled) {
+            self.dirty.store(true, Ordering::Relaxed);
+            self.files.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                rel.to_path_buf(),
+                Fingerprint {
+                    size,
+                    modified,
+                    hash: hash.clone(),
+                },
+            );
+        }
+        Ok(hash)
+    }
+
+    /// Write the fingerprints of the files hashed during this run, when
+    /// they changed.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let cached = files.len();
+        files.retain(|rel, _| seen.contains(rel));
+        if !self.dirty.load(Ordering::Relaxed) && files.len() == cached {
+            return Ok(());
+        }
+        let stored = Stored {
+            source: self.source.clone(),
+            files,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(&stored)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Number of cached fingerprints.
+    pub fn len(&self) -> usize {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn age(path: &Path) {
+        let past = SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+    }
+
+    #[test]
+    fn reuses_fingerprints_of_unchanged_files() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let source = tempfile::tempdir().unwrap();
+        let rule = source.path().join("style.mdc");
+        fs::write(&rule, "four spaces\n").unwrap();
+        fs::write(source.path().join("fresh.mdc"), "new\n").unwrap();
+        age(&rule);
+
+        let cache = ScanCache::load(cache_dir.path(), source.path());
+        let hash = cache.hash(Path::new("style.mdc")).unwrap();
+        assert_eq!(hash, lock::hash("four spaces\n"));
+        cache.hash(Path::new("fresh.mdc")).unwrap();
+        cache.save().unwrap();
+
+        // Same size and time: the cached hash wins over the content.
+        let stamp = fs::metadata(&rule).unwrap().modified().unwrap();
+        fs::write(&rule, "four SPACES\n").unwrap();
+        File::options()
+            .write(true)
+            .open(&rule)
+            .unwrap()
+            .set_modified(stamp)
+            .unwrap();
+        let cache = ScanCache::load(cache_dir.path(), source.path());
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.hash(Path::new("style.mdc")).unwrap(), hash);
+        assert_ne!(
+            ScanCache::disabled(source.path())
+                .hash(Path::new("style.mdc"))
+                .unwrap(),
+            hash
+        );
+
+        age(&rule);
+        assert_ne!(cache.hash(Path::new("style.mdc")).unwrap(), hash);
+        let other = tempfile::tempdir().unwrap();
+        assert!(ScanCache::load(cache_dir.path(), other.path()).is_empty());
+    }
+}
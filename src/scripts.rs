@@ -0,0 +1,402 @@
+//! Helper scripts of the flowmates checkout, such as `pre-work-hook`,
+//! installed into the `scripts/` directory of a repository by `echo init`
+//! and `echo update`.
+//!
+//! Every file under `scripts/` of the checkout is installed at the same
+//! path, recursively, but for what its `.gitignore` and the scan filters
+//! leave out (see [`crate::source_scan`]), so adding a helper to the
+//! checkout is enough to ship it. A `scripts/manifest.json` narrows the
+//! set down with globs relative to `scripts/`:
+//!
+//! ```json
+//! { "include": ["pre-work-hook", "ci/*"], "exclude": ["*.md"] }
+//! ```
+//!
+//! Scripts are copied byte for byte, keep the permissions of their
+//! source, and are recorded in the lockfile under the [`LOCK_TARGET`]
+//! target with a merge base, as rules are: `echo update` follows the
+//! source of unedited scripts, and `echo uninstall`, `echo prune` and
+//! `echo check` cover them. Files of the repository's `scripts/` the
+//! checkout does not install are reported, never removed. A checkout
+//! without `scripts/` installs nothing.
+
+use crate::checksums;
+use crate::echoignore::EchoIgnore;
+use crate::fs_ops::{self, FsOps};
+use crate::lock::{self, Conflict, Lock, LockedFile, Outcome, Resolution};
+use crate::provenance;
+use crate::rules::Source;
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the scripts, in the flowmates checkout and in the
+/// repository alike.
+pub const SCRIPTS_DIR: &str = "scripts";
+
+/// Manifest selecting the scripts to install, in [`SCRIPTS_DIR`] of the
+/// checkout. It is not installed itself.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Target of scripts in the lockfile.
+pub const LOCK_TARGET: &str = "scripts";
+
+/// Contents of [`MANIFEST_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Manifest {
+    /// Globs of the scripts to install, relative to `scripts/`; every
+    /// script when empty.
+    pub include: Vec<String>,
+    /// Globs of the scripts never installed.
+    pub exclude: Vec<String>,
+}
+
+impl Manifest {
+    /// Load the manifest of `flowmates_dir`; no file selects every script.
+    pub fn load(flowmates_dir: &Path) -> Result<Manifest> {
+        let path = flowmates_dir.join(SCRIPTS_DIR).join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(Manifest::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("invalid {}", path.display()))
+    }
+}
+
+/// What [`install`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Installed {
+    /// Each script, relative to the repository root, with its outcome.
+    pub scripts: Vec<(PathBuf, Outcome)>,
+    /// Files of the repository's `scripts/` the checkout does not install.
+    pub extra: Vec<PathBuf>,
+}
+
+/// The scripts `flowmates_dir` installs, sorted, as paths relative to it
+/// and to the repository root alike.
+pub fn sources(flowmates_dir: Option<&Path>) -> Result<Vec<PathBuf>> {
+    let Some(dir) = flowmates_dir.filter(|dir| dir.join(SCRIPTS_DIR).is_dir()) else {
+        return Ok(Vec::new());
+    };
+    let manifest = Manifest::load(dir)?;
+    let include = glob_set(&manifest.include)?;
+    let exclude = glob_set(&manifest.exclude)?;
+    let mut files = Vec::new();
+    checksums::collect(dir, Path::new(SCRIPTS_DIR), &mut files)?;
+    files.retain(|path| {
+        let rel = path.strip_prefix(SCRIPTS_DIR).unwrap_or(path);
+        rel != Path::new(MANIFEST_FILE)
+            && (manifest.include.is_empty() || include.is_match(rel))
+            && !exclude.is_match(rel)
+    });
+    files.sort();
+    Ok(files)
+}
+
+/// Install the scripts of `flowmates_dir` into `root` through `fs`,
+/// asking `resolve` what to do with each edited script whose source
+/// changed, and record them in the lockfile.
+pub fn install(
+    fs: &dyn FsOps,
+    root: &Path,
+    flowmates_dir: &Path,
+    mut resolve: impl FnMut(&Conflict) -> Result<Resolution>,
+) -> Result<Installed> {
+    if !flowmates_dir.join(SCRIPTS_DIR).is_dir() {
+        return Ok(Installed::default());
+    }
+    let sources = sources(Some(flowmates_dir))?;
+    let ignore = EchoIgnore::load(root)?;
+    let mut lock = Lock::load(root)?;
+    let mut installed = Installed::default();
+    for path in &sources {
+        let source = flowmates_dir.join(path);
+        let content =
+            fs::read(&source).with_context(|| format!("failed to read {}", source.display()))?;
+        let locked = lock.get(path).map(|locked| locked.hash.clone());
+        let outcome = Outcome::of_bytes(root, path, &content, false, locked.as_deref());
+        let mut outcome = ignore.guard(path, outcome);
+        let mut write = outcome.is_written().then(|| content.clone());
+        if outcome == Outcome::Modified {
+            (write, outcome) = settle(root, path, &content, locked.as_deref(), &mut resolve)?;
+        }
+        if let Some(written) = write {
+            let target = root.join(path);
+            fs_ops::write_file(fs, &target, &written)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            fs.set_permissions(&target, mode(&source)?)
+                .with_context(|| format!("failed to set the permissions of {}", path.display()))?;
+        }
+        if !matches!(outcome, Outcome::Modified | Outcome::Protected) {
+            // Binary scripts keep no base; they are never merged.
+            if let Ok(text) = std::str::from_utf8(&content) {
+                lock::write_base(fs, root, path, text)?;
+            }
+            lock.record(LockedFile {
+                target: LOCK_TARGET.to_string(),
+                path: path.clone(),
+                name: name(path),
+                source: Source::Flowmates.name().to_string(),
+                hash: lock::hash_bytes(&content),
+                link: None,
+                mtime: None,
+            });
+        }
+        installed.scripts.push((path.clone(), outcome));
+    }
+    // Entries of scripts the checkout dropped stay for `echo prune`.
+    lock.save_in(fs, root)?;
+    installed.extra = unknown(root, &sources)?;
+    Ok(installed)
+}
+
+/// What [`install`] would do, without writing anything.
+pub fn plan(root: &Path, flowmates_dir: Option<&Path>) -> Result<Vec<(PathBuf, Outcome)>> {
+    let Some(dir) = flowmates_dir else {
+        return Ok(Vec::new());
+    };
+    let ignore = EchoIgnore::load(root)?;
+    let lock = Lock::load(root)?;
+    sources(Some(dir))?
+        .into_iter()
+        .map(|path| {
+            let source = dir.join(&path);
+            let content = fs::read(&source)
+                .with_context(|| format!("failed to read {}", source.display()))?;
+            let locked = lock.get(&path).map(|locked| locked.hash.as_str());
+            let outcome = Outcome::of_bytes(root, &path, &content, false, locked);
+            Ok((path.clone(), ignore.guard(&path, outcome)))
+        })
+        .collect()
+}
+
+/// Files of the repository's `scripts/` that `flowmates_dir` does not
+/// install.
+pub fn extra(root: &Path, flowmates_dir: Option<&Path>) -> Result<Vec<PathBuf>> {
+    match flowmates_dir.filter(|dir| dir.join(SCRIPTS_DIR).is_dir()) {
+        Some(dir) => unknown(root, &sources(Some(dir))?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Name of the script at `path` in the lockfile, e.g. `ci/lint`.
+fn name(path: &Path) -> String {
+    path.strip_prefix(SCRIPTS_DIR)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Settle the script at `path`, edited locally, whose source is now
+/// `content`: returns the contents to write, if any, and the outcome.
+/// Binary scripts can only be kept or replaced.
+fn settle(
+    root: &Path,
+    path: &Path,
+    content: &[u8],
+    locked: Option<&str>,
+    resolve: &mut impl FnMut(&Conflict) -> Result<Resolution>,
+) -> Result<(Option<Vec<u8>>, Outcome)> {
+    let local =
+        fs::read(root.join(path)).with_context(|| format!("failed to read {}", path.display()))?;
+    match (std::str::from_utf8(&local), std::str::from_utf8(content)) {
+        (Ok(local), Ok(source)) => {
+            let local = provenance::strip(local);
+            let base = locked.and_then(|hash| lock::read_base(root, path, hash));
+            let conflict = Conflict {
+                path,
+                local: &local,
+                source,
+                base: base.as_deref(),
+            };
+            let (write, outcome) = conflict.resolve(resolve(&conflict)?);
+            Ok((write.map(String::into_bytes), outcome))
+        }
+        _ => {
+            let local = String::from_utf8_lossy(&local);
+            let source = String::from_utf8_lossy(content);
+            let conflict = Conflict {
+                path,
+                local: &local,
+                source: &source,
+                base: None,
+            };
+            Ok(match resolve(&conflict)? {
+                Resolution::Take => (Some(content.to_vec()), Outcome::Updated),
+                Resolution::Keep | Resolution::Merge => (None, Outcome::Modified),
+            })
+        }
+    }
+}
+
+/// Files of the repository's `scripts/` not among `sources`, sorted.
+fn unknown(root: &Path, sources: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut local = Vec::new();
+    collect_local(root, Path::new(SCRIPTS_DIR), &mut local)?;
+    local.retain(|path| !sources.contains(path));
+    local.sort();
+    Ok(local)
+}
+
+/// Add the files under `root/rel` to `files`, relative to `root`.
+fn collect_local(root: &Path, rel: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let dir = root.join(rel);
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let rel = rel.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_local(root, &rel, files)?;
+        } else {
+            files.push(rel);
+        }
+    }
+    Ok(())
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).with_context(|| {
+            format!(
+                "invalid pattern '{}' in {}/{}",
+                pattern, SCRIPTS_DIR, MANIFEST_FILE
+            )
+        })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .with_context(|| format!("invalid patterns in {}/{}", SCRIPTS_DIR, MANIFEST_FILE))
+}
+
+#[cfg(unix)]
+fn mode(path: &Path) -> Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata =
+        fs::metadata(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn mode(_path: &Path) -> Result<u32> {
+    Ok(0o644)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_ops::RealFs;
+
+    #[test]
+    fn installs_every_script_and_reports_extra_ones() {
+        let flowmates = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+        let scripts = flowmates.path().join(SCRIPTS_DIR);
+        fs::create_dir_all(scripts.join("ci")).unwrap();
+        fs::write(scripts.join("pre-work-hook"), "#!/bin/sh\nexit 0\n").unwrap();
+        fs::write(scripts.join("ci/lint"), "#!/bin/sh\n\nrun lint\n").unwrap();
+        fs::write(scripts.join("notes.md~"), "backup\n").unwrap();
+        fs::create_dir_all(root.join("scripts")).unwrap();
+        fs::write(root.join("scripts/mine"), "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let executable = fs::Permissions::from_mode(0o755);
+            fs::set_permissions(scripts.join("pre-work-hook"), executable).unwrap();
+        }
+
+        let keep = |_: &Conflict| Ok(Resolution::Keep);
+        let installed = install(&RealFs, root, flowmates.path(), keep).unwrap();
+        assert_eq!(
+            installed.scripts,
+            vec![
+                (PathBuf::from("scripts/ci/lint"), Outcome::Installed),
+                (PathBuf::from("scripts/pre-work-hook"), Outcome::Installed),
+            ]
+        );
+        assert_eq!(installed.extra, vec![PathBuf::from("scripts/mine")]);
+        assert_eq!(
+            fs::read_to_string(root.join("scripts/pre-work-hook")).unwrap(),
+            "#!/bin/sh\nexit 0\n"
+        );
+        let lock = Lock::load(root).unwrap();
+        let locked = lock.get(Path::new("scripts/ci/lint")).unwrap();
+        assert_eq!(
+            (locked.target.as_str(), locked.name.as_str()),
+            ("scripts", "ci/lint")
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(root.join("scripts/pre-work-hook"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+
+        // Unedited scripts follow their source; edited ones are settled.
+        fs::write(scripts.join("pre-work-hook"), "#!/bin/sh\nexit 1\n").unwrap();
+        fs::write(
+            root.join("scripts/ci/lint"),
+            "#!/bin/sh\n\nrun lint --fix\n",
+        )
+        .unwrap();
+        fs::write(scripts.join("ci/lint"), "#!/bin/bash\n\nrun lint\n").unwrap();
+        assert_eq!(
+            plan(root, Some(flowmates.path())).unwrap()[1].1,
+            Outcome::Updated
+        );
+        let installed = install(&RealFs, root, flowmates.path(), keep).unwrap();
+        assert_eq!(installed.scripts[0].1, Outcome::Modified);
+        assert_eq!(installed.scripts[1].1, Outcome::Updated);
+        let merge = |_: &Conflict| Ok(Resolution::Merge);
+        let installed = install(&RealFs, root, flowmates.path(), merge).unwrap();
+        assert_eq!(installed.scripts[0].1, Outcome::Merged);
+        assert_eq!(
+            fs::read_to_string(root.join("scripts/ci/lint")).unwrap(),
+            "#!/bin/bash\n\nrun lint --fix\n"
+        );
+    }
+
+    #[test]
+    fn selects_scripts_through_the_manifest_and_copies_bytes() {
+        let flowmates = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let scripts = flowmates.path().join(SCRIPTS_DIR);
+        fs::create_dir_all(scripts.join("dev")).unwrap();
+        fs::write(scripts.join("tool"), [0x7f, b'E', b'L', b'F', 0xff, 0x00]).unwrap();
+        fs::write(scripts.join("README.md"), "# Scripts\n").unwrap();
+        fs::write(scripts.join("dev/seed"), "#!/bin/sh\n").unwrap();
+        fs::write(
+            scripts.join(MANIFEST_FILE),
+            r#"{ "exclude": ["*.md", "dev/*"] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            sources(Some(flowmates.path())).unwrap(),
+            vec![PathBuf::from("scripts/tool")]
+        );
+        let take = |_: &Conflict| Ok(Resolution::Take);
+        install(&RealFs, repo.path(), flowmates.path(), take).unwrap();
+        assert_eq!(
+            fs::read(repo.path().join("scripts/tool")).unwrap(),
+            [0x7f, b'E', b'L', b'F', 0xff, 0x00]
+        );
+        fs::write(repo.path().join("scripts/tool"), [0xfe]).unwrap();
+        let installed = install(&RealFs, repo.path(), flowmates.path(), take).unwrap();
+        assert_eq!(installed.scripts[0].1, Outcome::Updated);
+
+        fs::write(scripts.join(MANIFEST_FILE), r#"{ "only": [] }"#).unwrap();
+        assert!(sources(Some(flowmates.path())).is_err());
+    }
+}
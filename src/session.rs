@@ -0,0 +1,297 @@
+//! `echo session`: snapshots of the work of an agent session, so several
+//! agents can park their work and resume it where they left off.
+//!
+//! A session is a JSON file in [`SESSIONS_DIR`] recording the branch,
+//! AGENT.md and the state of every issue when it started and, once parked
+//! with `echo session end`, when it ended. Starting an ended session again
+//! reports what changed while it was parked.
+
+use crate::agent::AGENT_MD;
+use crate::environment;
+use crate::fs_ops;
+use crate::gitutil;
+use crate::issues;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the session files, relative to the repository root.
+pub const SESSIONS_DIR: &str = ".cursor/sessions";
+
+/// The state of the repository at one point of a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Seconds since the epoch.
+    pub at: u64,
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    /// Contents of AGENT.md, when present.
+    pub agent_md: Option<String>,
+    /// Issue states by path, e.g. `in_progress`.
+    pub issues: BTreeMap<PathBuf, IssueState>,
+}
+
+/// An issue as recorded in a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueState {
+    pub title: String,
+    pub state: String,
+}
+
+/// Contents of a session file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    pub started: Snapshot,
+    /// Set by `echo session end`; `None` while the session is active.
+    pub ended: Option<Snapshot>,
+}
+
+impl Session {
+    pub fn is_active(&self) -> bool {
+        self.ended.is_none()
+    }
+
+    /// Issues in progress at the latest snapshot.
+    pub fn issues_in_progress(&self) -> Vec<&Path> {
+        let snapshot = self.ended.as_ref().unwrap_or(&self.started);
+        snapshot
+            .issues
+            .iter()
+            .filter(|(_, issue)| issue.state == issues::State::InProgress.dir_name())
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+}
+
+/// Take a snapshot of the repository at `root`.
+pub fn snapshot(root: &Path, now: u64) -> Result<Snapshot> {
+    let is_repo = gitutil::is_repo(root);
+    let agent_md = root.join(AGENT_MD);
+    Ok(Snapshot {
+        at: now,
+        branch: is_repo.then(|| gitutil::branch(root)).flatten(),
+        commit: is_repo.then(|| gitutil::head(root)).flatten(),
+        agent_md: agent_md
+            .is_file()
+            .then(|| fs::read_to_string(&agent_md))
+            .transpose()
+            .with_context(|| format!("failed to read {}", AGENT_MD))?,
+        issues: issues::scan(root)?
+            .into_iter()
+            .map(|issue| {
+                let state = IssueState {
+                    title: issue.title,
+                    state: issue.state.dir_name().to_string(),
+                };
+                (strip_state(&issue.path), state)
+            })
+            .collect(),
+    })
+}
+
+/// `path` without its state directory, so an issue keeps its key when it
+/// moves: `issues/app/todo/001.md` becomes `issues/app/001.md`.
+fn strip_state(path: &Path) -> PathBuf {
+    let file = path.file_name().unwrap_or_default();
+    match path.parent().and_then(Path::parent) {
+        Some(project) => project.join(file),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Path of the file of session `name`, relative to the repository root.
+pub fn path(name: &str) -> Result<PathBuf> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        bail!(
+            "invalid session name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        );
+    }
+    Ok(Path::new(SESSIONS_DIR).join(format!("{}.json", name)))
+}
+
+/// Load session `name`; `None` when there is none.
+pub fn load(root: &Path, name: &str) -> Result<Option<Session>> {
+    let path = root.join(path(name)?);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let session =
+        serde_json::from_str(&content).with_context(|| format!("invalid {}", path.display()))?;
+    Ok(Some(session))
+}
+
+fn save(root: &Path, session: &Session) -> Result<()> {
+    let path = root.join(path(&session.name)?);
+    let mut content = serde_json::to_string_pretty(session)?;
+    content.push('\n');
+    fs_ops::write_file(&fs_ops::RealFs, &path, content.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Start session `name` at `now`. Returns what changed since the session
+/// was parked, when it is resumed.
+pub fn start(root: &Path, name: &str, now: u64) -> Result<Vec<String>> {
+    let previous = load(root, name)?;
+    if previous.as_ref().is_some_and(Session::is_active) {
+        bail!("session '{}' is already active", name);
+    }
+    let started = snapshot(root, now)?;
+    let changes = previous
+        .and_then(|session| session.ended)
+        .map(|parked| changes(&parked, &started))
+        .unwrap_or_default();
+    save(
+        root,
+        &Session {
+            name: name.to_string(),
+            started,
+            ended: None,
+        },
+    )?;
+    Ok(changes)
+}
+
+/// Park session `name` at `now`. Returns what changed during the session.
+pub fn end(root: &Path, name: &str, now: u64) -> Result<Vec<String>> {
+    let Some(mut session) = load(root, name)? else {
+        bail!("no session '{}'; start it with `echo session start`", name);
+    };
+    if !session.is_active() {
+        bail!("session '{}' already ended", name);
+    }
+    let ended = snapshot(root, now)?;
+    let changes = changes(&session.started, &ended);
+    session.ended = Some(ended);
+    save(root, &session)?;
+    Ok(changes)
+}
+
+/// Every session, sorted by name.
+pub fn list(root: &Path) -> Result<Vec<Session>> {
+    let dir = root.join(SESSIONS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut sessions = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        sessions.extend(load(root, name)?);
+    }
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(sessions)
+}
+
+/// What differs from `before` to `after`, one line per change.
+pub fn changes(before: &Snapshot, after: &Snapshot) -> Vec<String> {
+    let mut changes = Vec::new();
+    if before.branch != after.branch {
+        changes.push(format!(
+            "branch: {} -> {}",
+            before.branch.as_deref().unwrap_or("none"),
+            after.branch.as_deref().unwrap_or("none")
+        ));
+    } else if before.commit != after.commit {
+        let short = |commit: &Option<String>| {
+            commit
+                .as_deref()
+                .map_or("none".to_string(), |c| c.chars().take(7).collect())
+        };
+        changes.push(format!(
+            "commit: {} -> {}",
+            short(&before.commit),
+            short(&after.commit)
+        ));
+    }
+    if before.agent_md != after.agent_md {
+        changes.push(format!("{} changed", AGENT_MD));
+    }
+    for (path, issue) in &after.issues {
+        match before.issues.get(path) {
+            Some(old) if old.state != issue.state => changes.push(format!(
+                "{}: {} -> {}",
+                path.display(),
+                old.state,
+                issue.state
+            )),
+            Some(_) => {}
+            None => changes.push(format!("{}: new in {}", path.display(), issue.state)),
+        }
+    }
+    for path in before.issues.keys() {
+        if !after.issues.contains_key(path) {
+            changes.push(format!("{}: removed", path.display()));
+        }
+    }
+    changes
+}
+
+/// Day of a snapshot, e.g. `2024-03-01`.
+pub fn date(snapshot: &Snapshot) -> String {
+    environment::date(snapshot.at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::State;
+
+    fn issue(root: &Path, state: State, file: &str) {
+        let dir = root.join("issues/app").join(state.dir_name());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(file), "# Add login\n\n**Type:** feature\n").unwrap();
+    }
+
+    #[test]
+    fn parks_and_resumes_a_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        issue(root, State::InProgress, "001-login.md");
+        fs::write(root.join(AGENT_MD), "# Agent\n").unwrap();
+        assert!(path("../x").is_err());
+        assert!(end(root, "alice", 1).is_err());
+
+        assert!(start(root, "alice", 1).unwrap().is_empty());
+        assert!(start(root, "alice", 2).is_err());
+        let sessions = list(root).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].is_active());
+        assert_eq!(
+            sessions[0].issues_in_progress(),
+            vec![Path::new("issues/app/001-login.md")]
+        );
+
+        fs::remove_file(root.join("issues/app/in_progress/001-login.md")).unwrap();
+        issue(root, State::Done, "001-login.md");
+        let during = end(root, "alice", 3).unwrap();
+        assert_eq!(during, vec!["issues/app/001-login.md: in_progress -> done"]);
+        assert!(!list(root).unwrap()[0].is_active());
+        assert!(end(root, "alice", 4).is_err());
+
+        fs::write(root.join(AGENT_MD), "# Agent, revised\n").unwrap();
+        issue(root, State::Todo, "002-logout.md");
+        let parked = start(root, "alice", 5).unwrap();
+        assert_eq!(
+            parked,
+            vec!["AGENT.md changed", "issues/app/002-logout.md: new in todo"]
+        );
+        assert!(load(root, "alice").unwrap().unwrap().is_active());
+    }
+}
@@ -0,0 +1,103 @@
+//! Copy of the last flowmates checkout `echo init` and `echo update`
+//! installed from, so `--offline` can install when the checkout is
+//! unreachable, e.g. on a network mount or moved away.
+//!
+//! The copy lives in [`SOURCE_DIR`] of echo's cache directory (see
+//! [`Environment::cache_dir`](crate::environment::Environment::cache_dir)),
+//! described by [`INFO_FILE`] next to it.
+
+use crate::environment;
+use crate::fs_ops;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the copy, in the cache directory.
+pub const SOURCE_DIR: &str = "source";
+
+/// Description of the copy, in the cache directory.
+pub const INFO_FILE: &str = "source.toml";
+
+/// Where and when the cached copy was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cached {
+    /// The checkout copied.
+    pub source: PathBuf,
+    /// When it was copied, in seconds since the epoch.
+    pub cached_at: u64,
+}
+
+impl Cached {
+    /// Day of the copy, e.g. `2024-03-01`.
+    pub fn date(&self) -> String {
+        environment::date(self.cached_at)
+    }
+}
+
+/// Replace the cached copy with `flowmates_dir`, taken at `now`. The
+/// `.git` directory is left out; files are cloned where the filesystem
+/// allows it, so a large checkout takes no extra space.
+pub fn store(cache_dir: &Path, flowmates_dir: &Path, now: u64) -> Result<()> {
+    let dir = cache_dir.join(SOURCE_DIR);
+    let staging = cache_dir.join(format!("{}.tmp", SOURCE_DIR));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .with_context(|| format!("failed to remove {}", staging.display()))?;
+    }
+    fs_ops::clone_dir(flowmates_dir, &staging, ".git")
+        .with_context(|| format!("failed to cache {}", flowmates_dir.display()))?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir).with_context(|| format!("failed to remove {}", dir.display()))?;
+    }
+    fs::rename(&staging, &dir).with_context(|| format!("failed to write {}", dir.display()))?;
+    let info = Cached {
+        source: flowmates_dir.to_path_buf(),
+        cached_at: now,
+    };
+    let path = cache_dir.join(INFO_FILE);
+    fs_ops::write_atomic(&path, toml::to_string(&info)?.as_bytes(), false)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The cached copy and its description; `None` when there is none.
+pub fn load(cache_dir: &Path) -> Result<Option<(PathBuf, Cached)>> {
+    let dir = cache_dir.join(SOURCE_DIR);
+    let path = cache_dir.join(INFO_FILE);
+    if !dir.is_dir() || !path.is_file() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let info = toml::from_str(&content).with_context(|| format!("invalid {}", path.display()))?;
+    Ok(Some((dir, info)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_and_loads_a_copy_without_git() {
+        let cache = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        fs::create_dir_all(flowmates.path().join("rules/rust")).unwrap();
+        fs::create_dir_all(flowmates.path().join(".git")).unwrap();
+        fs::write(flowmates.path().join("rules/rust/style.mdc"), "style\n").unwrap();
+        fs::write(flowmates.path().join(".git/HEAD"), "ref\n").unwrap();
+        assert_eq!(load(cache.path()).unwrap(), None);
+
+        store(cache.path(), flowmates.path(), 0).unwrap();
+        let (dir, _) = load(cache.path()).unwrap().unwrap();
+        assert!(dir.join("rules/rust/style.mdc").is_file());
+        assert!(!dir.join(".git").exists());
+
+        fs::remove_file(flowmates.path().join("rules/rust/style.mdc")).unwrap();
+        store(cache.path(), flowmates.path(), 1_709_251_200).unwrap();
+        let (dir, cached) = load(cache.path()).unwrap().unwrap();
+        assert!(!dir.join("rules/rust/style.mdc").exists());
+        assert_eq!(cached.source, flowmates.path());
+        assert_eq!(cached.date(), "2024-03-01");
+    }
+}
@@ -0,0 +1,142 @@
+//! A flowmates source pinned by the repository, `[source]` in
+//! `.echo.toml`: a git URL and a ref that every contributor installs from,
+//! whatever their own configuration names.
+//!
+//! The repository is cloned once per URL under `sources/` of echo's data
+//! directory. `echo init` and `echo update` fetch it and check out the
+//! ref, detached: a branch follows `origin`, a tag or commit stays put.
+//! Without a ref, the default branch of `origin` is followed.
+
+use crate::git;
+use crate::gitutil;
+use crate::lock;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the pinned checkouts, in echo's data directory.
+pub const SOURCES_DIR: &str = "sources";
+
+/// `[source]` section of `.echo.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceConfig {
+    /// Git URL of the flowmates repository.
+    pub url: Option<String>,
+    /// Branch, tag or commit to install from; the default branch when
+    /// unset.
+    #[serde(rename = "ref")]
+    pub rev: Option<String>,
+}
+
+/// A pinned checkout brought to its ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Synced {
+    pub dir: PathBuf,
+    /// Commit checked out.
+    pub commit: String,
+    /// Why fetching failed, when the ref was found without it.
+    pub fetch_error: Option<String>,
+}
+
+/// Where the checkout of `url` lives under `data_dir`.
+pub fn checkout_dir(data_dir: &Path, url: &str) -> PathBuf {
+    let hash = lock::hash(url);
+    data_dir.join(SOURCES_DIR).join(&hash[..16])
+}
+
+/// Clone or fetch the checkout of `url` under `data_dir` and check out
+/// `rev`.
+pub fn sync(data_dir: &Path, url: &str, rev: Option<&str>) -> Result<Synced> {
+    if let Some(rev) = rev.filter(|rev| rev.is_empty() || rev.starts_with('-')) {
+        bail!("invalid [source] ref '{}'", rev);
+    }
+    let dir = checkout_dir(data_dir, url);
+    let mut fetch_error = None;
+    if dir.join(".git").exists() {
+        if let Err(err) = git::run(&dir, &["fetch", "--quiet", "--tags", "--prune", "origin"]) {
+            fetch_error = Some(format!("{:#}", err));
+        }
+    } else {
+        let parent = data_dir.join(SOURCES_DIR);
+        fs::create_dir_all(&parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+        git::clone(url, &dir)?;
+    }
+    // A branch is taken from `origin`, not from the stale local one.
+    let candidates = match rev {
+        Some(rev) => vec![format!("origin/{}", rev), rev.to_string()],
+        None => vec!["origin/HEAD".to_string()],
+    };
+    let commit = candidates
+        .iter()
+        .find_map(|candidate| gitutil::resolve(&dir, candidate))
+        .with_context(|| {
+            let mut message = format!("{} has no ref '{}'", url, rev.unwrap_or("HEAD"));
+            if let Some(err) = &fetch_error {
+                message.push_str(&format!(" (fetching failed: {})", err));
+            }
+            message
+        })?;
+    git::run(&dir, &["checkout", "--quiet", "--detach", &commit])?;
+    Ok(Synced {
+        dir,
+        commit,
+        fetch_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &Path, content: &str) {
+        fs::create_dir_all(repo.join("rules")).unwrap();
+        fs::write(repo.join("rules/style.mdc"), content).unwrap();
+        git::run(repo, &["add", "-A"]).unwrap();
+        git::run(
+            repo,
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-qm",
+                content.trim(),
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn checks_out_the_pinned_ref() {
+        let upstream = tempfile::tempdir().unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        git::run(upstream.path(), &["init", "-q"]).unwrap();
+        commit(upstream.path(), "v1\n");
+        git::run(upstream.path(), &["tag", "v1"]).unwrap();
+        commit(upstream.path(), "v2\n");
+        let url = upstream.path().to_str().unwrap();
+        let read =
+            |synced: &Synced| fs::read_to_string(synced.dir.join("rules/style.mdc")).unwrap();
+
+        let pinned = sync(data_dir.path(), url, Some("v1")).unwrap();
+        assert_eq!(read(&pinned), "v1\n");
+        assert_eq!(pinned.dir, checkout_dir(data_dir.path(), url));
+        assert!(pinned.dir.starts_with(data_dir.path().join(SOURCES_DIR)));
+
+        commit(upstream.path(), "v3\n");
+        let head = sync(data_dir.path(), url, None).unwrap();
+        assert_eq!(read(&head), "v3\n");
+        assert_eq!(head.fetch_error, None);
+        assert_eq!(
+            read(&sync(data_dir.path(), url, Some("v1")).unwrap()),
+            "v1\n"
+        );
+        let err = sync(data_dir.path(), url, Some("v9")).unwrap_err();
+        assert!(err.to_string().contains("has no ref 'v9'"));
+        assert!(sync(data_dir.path(), url, Some("--help")).is_err());
+    }
+}
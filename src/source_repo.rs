@@ -0,0 +1,154 @@
+//! Checks on a flowmates checkout for `echo source validate` and `echo
+//! init --source-repo`: its layout, its hooks and presets manifests, and
+//! the lints of its rules and issue templates.
+//!
+//! A checkout needs a `rules/` directory holding at least one rule and a
+//! `templates/` directory. The scripts `hooks.toml` names must exist and be
+//! executable.
+
+use crate::hooks;
+use crate::issues::template_lint::{self, Schema};
+use crate::issues::templates::FLOWMATES_TEMPLATES_DIR;
+use crate::issues::validate::Violation;
+use crate::presets;
+use crate::rules::{self, RulesConfig, FLOWMATES_RULES_DIR};
+use crate::template::Variables;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Problems of the checkout at `root`, sorted by path.
+pub fn check(root: &Path, vars: &Variables) -> Result<Vec<Violation>> {
+    let mut violations = Vec::new();
+    let mut problem = |path: &str, message: String| {
+        violations.push(Violation {
+            path: PathBuf::from(path),
+            message,
+        })
+    };
+    let config = RulesConfig {
+        flowmates_dir: Some(root.to_path_buf()),
+        ..RulesConfig::default()
+    };
+    if !root.join(FLOWMATES_RULES_DIR).is_dir() {
+        problem(FLOWMATES_RULES_DIR, "missing rules directory".to_string());
+    } else if rules::sources(Some(root))?
+        .values()
+        .all(|rule| rule.path.is_none())
+    {
+        problem(FLOWMATES_RULES_DIR, "no .mdc rule".to_string());
+    }
+    let templates = root.join(FLOWMATES_TEMPLATES_DIR);
+    if !templates.is_dir() {
+        problem(
+            FLOWMATES_TEMPLATES_DIR,
+            "missing templates directory".to_string(),
+        );
+    }
+    match hooks::available(Some(root)) {
+        Ok(available) if root.join(hooks::MANIFEST_FILE).is_file() => {
+            for hook in available {
+                let Some(script) = hook.path.filter(|script| !is_executable(script)) else {
+                    continue;
+                };
+                let script = script.strip_prefix(root).unwrap_or(&script);
+                problem(
+                    hooks::MANIFEST_FILE,
+                    format!(
+                        "script {} of {} is not executable",
+                        script.display(),
+                        hook.name
+                    ),
+                );
+            }
+        }
+        Ok(_) => {}
+        Err(err) => problem(hooks::MANIFEST_FILE, format!("{:#}", err)),
+    }
+    if let Err(err) = presets::load(Some(root)) {
+        problem(presets::MANIFEST_FILE, format!("{:#}", err));
+    }
+    violations.extend(rules::lint(root, &config)?.into_iter().map(|violation| {
+        Violation {
+            path: violation
+                .path
+                .strip_prefix(root)
+                .map(Path::to_path_buf)
+                .unwrap_or(violation.path),
+            ..violation
+        }
+    }));
+    if templates.is_dir() {
+        let schema = Schema::load(&templates.join(template_lint::SCHEMA_FILE))?;
+        violations.extend(template_lint::lint(
+            root,
+            Path::new(FLOWMATES_TEMPLATES_DIR),
+            &schema,
+            vars,
+        )?);
+    }
+    violations.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(violations)
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn checks_the_layout_and_lints_the_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let vars = Variables::new();
+        let found = || -> Vec<String> {
+            check(root, &vars)
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(
+            found(),
+            vec![
+                "rules: missing rules directory",
+                "templates: missing templates directory"
+            ]
+        );
+
+        fs::create_dir_all(root.join("rules")).unwrap();
+        fs::create_dir_all(root.join("templates")).unwrap();
+        fs::create_dir_all(root.join("scripts")).unwrap();
+        assert_eq!(found(), vec!["rules: no .mdc rule"]);
+
+        fs::write(root.join("rules/style.mdc"), "---\nglob: x\n---\n").unwrap();
+        fs::write(root.join("templates/bug_template.md"), "# {{ TEAM }}\n").unwrap();
+        fs::write(root.join("scripts/pre-commit"), "#!/bin/sh\n").unwrap();
+        fs::write(
+            root.join(hooks::MANIFEST_FILE),
+            "[pre-commit]\nscript = \"scripts/pre-commit\"\n",
+        )
+        .unwrap();
+        let found = found();
+        assert_eq!(found.len(), 5, "{:?}", found);
+        assert_eq!(
+            found[0],
+            "hooks.toml: script scripts/pre-commit of pre-commit is not executable"
+        );
+        assert!(found[1..3]
+            .iter()
+            .all(|found| found.starts_with("rules/style.mdc: ")));
+        assert_eq!(found[3], "templates/bug_template.md: unknown variable TEAM");
+    }
+}
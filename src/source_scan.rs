@@ -0,0 +1,137 @@
+//! Which files of a flowmates checkout are sources: scans for rules,
+//! templates and presets skip the noise an editor or a misconfigured
+//! checkout leaves behind.
+//!
+//! Skipped are `.git` directories, hidden files and directories (Emacs
+//! lock files such as `.#style.mdc` among them), editor backups (`#x#`,
+//! `x~`) and whatever the `.gitignore` files of the checkout list, nested
+//! ones and `!` negations included. `--scan-ignored` turns every filter
+//! but `.git` off.
+
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Whether `--scan-ignored` is set, set once in `main`.
+static SCAN_IGNORED: OnceLock<bool> = OnceLock::new();
+
+/// Scan hidden, backup and ignored files too, for the rest of the run.
+pub fn set_scan_ignored(on: bool) {
+    let _ = SCAN_IGNORED.set(on);
+}
+
+/// The filter of one checkout.
+#[derive(Debug, Clone)]
+pub struct SourceFilter {
+    /// Every file and directory of the checkout that is a source,
+    /// relative to its root.
+    sources: BTreeSet<PathBuf>,
+}
+
+impl SourceFilter {
+    /// Filter of the checkout at `root`, honoring its `.gitignore` files
+    /// and `--scan-ignored`.
+    pub fn load(root: &Path) -> Result<SourceFilter> {
+        SourceFilter::scan(root, SCAN_IGNORED.get() == Some(&true))
+    }
+
+    fn scan(root: &Path, all: bool) -> Result<SourceFilter> {
+        let walk = WalkBuilder::new(root)
+            .standard_filters(false)
+            .hidden(!all)
+            .git_ignore(!all)
+            .require_git(false)
+            .follow_links(true)
+            .filter_entry(move |entry| {
+                let name = entry.file_name().to_string_lossy();
+                entry.depth() == 0
+                    || name != ".git" && (all || !name.starts_with('#') && !name.ends_with('~'))
+            })
+            .build();
+        let mut sources = BTreeSet::new();
+        for entry in walk {
+            let entry = entry.with_context(|| format!("failed to scan {}", root.display()))?;
+            if let Ok(rel) = entry.path().strip_prefix(root) {
+                if !rel.as_os_str().is_empty() {
+                    sources.insert(rel.to_path_buf());
+                }
+            }
+        }
+        Ok(SourceFilter { sources })
+    }
+
+    /// Whether `rel`, relative to the root of the checkout, is not a
+    /// source.
+    pub fn skips(&self, rel: &Path) -> bool {
+        !self.sources.contains(rel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn skips_noise_and_ignored_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            ".gitignore",
+            "rules/.#style.mdc",
+            "rules/#style.mdc#",
+            "rules/style.mdc~",
+            "rules/.hidden/style.mdc",
+            "rules/sub/.git/style.mdc",
+            "rules/style.mdc.bak",
+            "rules/keep.mdc.bak",
+            "rules/wip/style.mdc",
+            "rules/team/.gitignore",
+            "rules/team/drafts/style.mdc",
+            "rules/team/wip/style.mdc",
+            "rules/team/local.mdc",
+            "rules/style.mdc",
+            "templates/bug_template.md",
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), "x").unwrap();
+        }
+        fs::write(
+            root.join(".gitignore"),
+            "# drafts\n*.bak\n!keep.mdc.bak\n/rules/wip/\n",
+        )
+        .unwrap();
+        fs::write(root.join("rules/team/.gitignore"), "drafts/\nlocal.mdc\n").unwrap();
+
+        let filter = SourceFilter::scan(root, false).unwrap();
+        for noisy in [
+            "rules/.#style.mdc",
+            "rules/#style.mdc#",
+            "rules/style.mdc~",
+            "rules/.hidden/style.mdc",
+            "rules/sub/.git/style.mdc",
+            "rules/style.mdc.bak",
+            "rules/wip/style.mdc",
+            "rules/team/drafts/style.mdc",
+            "rules/team/local.mdc",
+        ] {
+            assert!(filter.skips(Path::new(noisy)), "{}", noisy);
+        }
+        for kept in [
+            "rules",
+            "rules/style.mdc",
+            "rules/keep.mdc.bak",
+            "rules/team/wip/style.mdc",
+            "templates/bug_template.md",
+        ] {
+            assert!(!filter.skips(Path::new(kept)), "{}", kept);
+        }
+
+        let all = SourceFilter::scan(root, true).unwrap();
+        assert!(!all.skips(Path::new("rules/.#style.mdc")));
+        assert!(!all.skips(Path::new("rules/team/drafts/style.mdc")));
+        assert!(all.skips(Path::new("rules/sub/.git/style.mdc")));
+    }
+}
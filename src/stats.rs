@@ -0,0 +1,170 @@
+//! Opt-in usage statistics, kept on this machine only: which commands
+//! run, how long they take and whether they fail.
+//!
+//! Each invocation appends a JSON line to [`STATS_FILE`] in echo's cache
+//! directory. Recording is on while the file exists: `echo stats enable`
+//! creates it and `echo stats disable` deletes it. Nothing is ever sent
+//! anywhere.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Stats file, in the cache directory.
+pub const STATS_FILE: &str = "stats.jsonl";
+
+/// One invocation of echo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Command and subcommands, e.g. `rules add`.
+    pub command: String,
+    /// Seconds since the epoch at the start.
+    pub at: u64,
+    pub duration_ms: u64,
+    /// Whether it succeeded.
+    pub ok: bool,
+}
+
+/// Invocations of one command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub command: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub total_ms: u64,
+}
+
+impl Summary {
+    /// Mean duration in milliseconds.
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms / self.runs.max(1) as u64
+    }
+}
+
+/// Whether invocations are recorded.
+pub fn enabled(cache_dir: &Path) -> bool {
+    cache_dir.join(STATS_FILE).is_file()
+}
+
+/// Start recording; returns false if it already was on.
+pub fn enable(cache_dir: &Path) -> Result<bool> {
+    if enabled(cache_dir) {
+        return Ok(false);
+    }
+    fs::create_dir_all(cache_dir)
+        .with_context(|| format!("failed to create {}", cache_dir.display()))?;
+    let path = cache_dir.join(STATS_FILE);
+    fs::write(&path, "").with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+/// Stop recording and delete what was recorded; returns false if it was
+/// off.
+pub fn disable(cache_dir: &Path) -> Result<bool> {
+    let path = cache_dir.join(STATS_FILE);
+    if !path.is_file() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    Ok(true)
+}
+
+/// Append `entry` when recording is on.
+pub fn record(cache_dir: &Path, entry: &Entry) -> Result<()> {
+    if !enabled(cache_dir) {
+        return Ok(());
+    }
+    let path = cache_dir.join(STATS_FILE);
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// The recorded invocations, oldest first. Lines that do not parse, as
+/// a write cut short leaves, are skipped.
+pub fn load(cache_dir: &Path) -> Result<Vec<Entry>> {
+    let path = cache_dir.join(STATS_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Invocations of `entries` by command, the most run first.
+pub fn summarize(entries: &[Entry]) -> Vec<Summary> {
+    let mut by_command: BTreeMap<&str, Summary> = BTreeMap::new();
+    for entry in entries {
+        let summary = by_command.entry(&entry.command).or_insert_with(|| Summary {
+            command: entry.command.clone(),
+            ..Summary::default()
+        });
+        summary.runs += 1;
+        summary.failures += usize::from(!entry.ok);
+        summary.total_ms += entry.duration_ms;
+    }
+    let mut summaries: Vec<Summary> = by_command.into_values().collect();
+    summaries.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.command.cmp(&b.command)));
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, duration_ms: u64, ok: bool) -> Entry {
+        Entry {
+            command: command.to_string(),
+            at: 1_700_000_000,
+            duration_ms,
+            ok,
+        }
+    }
+
+    #[test]
+    fn records_only_once_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("echo");
+        record(&cache_dir, &entry("init", 10, true)).unwrap();
+        assert!(!cache_dir.exists());
+
+        assert!(enable(&cache_dir).unwrap());
+        assert!(!enable(&cache_dir).unwrap());
+        record(&cache_dir, &entry("update", 30, true)).unwrap();
+        record(&cache_dir, &entry("update", 10, false)).unwrap();
+        record(&cache_dir, &entry("rules add", 5, true)).unwrap();
+        fs::write(
+            cache_dir.join(STATS_FILE),
+            fs::read_to_string(cache_dir.join(STATS_FILE)).unwrap() + "{\"command\":",
+        )
+        .unwrap();
+        let entries = load(&cache_dir).unwrap();
+        assert_eq!(entries.len(), 3);
+        let summaries = summarize(&entries);
+        assert_eq!(
+            summaries[0],
+            Summary {
+                command: "update".to_string(),
+                runs: 2,
+                failures: 1,
+                total_ms: 40,
+            }
+        );
+        assert_eq!(summaries[0].average_ms(), 20);
+        assert_eq!(summaries[1].command, "rules add");
+
+        assert!(disable(&cache_dir).unwrap());
+        assert!(!disable(&cache_dir).unwrap());
+        assert!(load(&cache_dir).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Summary of the echo setup of a repository, for `echo status`.
+
+use crate::config::Config;
+use crate::gitignore::{self, ExcludeStrategy};
+use crate::gitutil;
+use crate::hooks::verify::{self, Integrity};
+use crate::lock::Lock;
+use crate::provenance;
+use crate::rules::{self, LinkProblem, RULES_DIR};
+use crate::scan_cache::ScanCache;
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// State of the repository and the problems worth a warning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    /// Whether `.cursor/` is meant to be committed.
+    pub vendor: bool,
+    /// Where the `.cursor/` entry is meant to be written.
+    pub exclude_strategy: ExcludeStrategy,
+    /// Whether `.gitignore` or `.git/info/exclude` ignores `.cursor/`.
+    pub cursor_ignored: bool,
+    /// Installed rules, enabled and disabled.
+    pub rules: usize,
+    pub disabled_rules: usize,
+    /// Files recorded in the lockfile.
+    pub locked_files: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Inspect the repository at `root`, hashing the flowmates rules through
+/// `cache` (see [`rules::list_with`]).
+pub fn status(root: &Path, config: &Config, cache: Option<&ScanCache>) -> Result<Status> {
+    let installed = rules::list_with(root, &config.rules, cache)?;
+    let cursor_ignored = gitignore::is_cursor_ignored(root)?;
+    let mut warnings = Vec::new();
+    if config.vendor && cursor_ignored {
+        warnings.push(
+            "vendor mode is on but .cursor/ is ignored; remove the entry so rules are committed"
+                .to_string(),
+        );
+    }
+    if !config.vendor
+        && config.exclude_strategy != ExcludeStrategy::None
+        && !cursor_ignored
+        && root.join(RULES_DIR).is_dir()
+    {
+        warnings.push(
+            ".cursor/ is not ignored; run `echo update` or set `vendor = true` to commit it"
+                .to_string(),
+        );
+    }
+    for (path, problem) in rules::check_links(root)? {
+        warnings.push(match problem {
+            LinkProblem::Broken => format!(
+                "{} is a broken link; run `echo update` to reinstall it",
+                path.display()
+            ),
+            LinkProblem::Foreign => format!(
+                "{} is a link echo did not create; `echo update` and `echo uninstall` leave it alone",
+                path.display()
+            ),
+        });
+    }
+    for rule in &installed {
+        let Ok(content) = fs::read_to_string(root.join(&rule.path)) else {
+            continue;
+        };
+        if provenance::find(&content).is_some_and(|p| p.hash != rule.hash) {
+            warnings.push(format!(
+                "{} was edited since echo installed it; its provenance header no longer matches",
+                rule.path.display()
+            ));
+        }
+    }
+    if gitutil::is_repo(root) {
+        for hook in verify::verify(root, None)? {
+            if !hook.is_problem() {
+                continue;
+            }
+            warnings.push(match hook.integrity {
+                Integrity::Modified(reason) => format!(
+                    "the {} hook changed since echo installed it ({}); see `echo hooks verify`",
+                    hook.name, reason
+                ),
+                Integrity::Foreign => format!(
+                    "the {} hook echo installed was replaced by another; run `echo hooks install` to chain it",
+                    hook.name
+                ),
+                _ => format!(
+                    "the {} hook echo installed is gone; run `echo hooks install`",
+                    hook.name
+                ),
+            });
+        }
+    }
+    Ok(Status {
+        vendor: config.vendor,
+        exclude_strategy: config.exclude_strategy,
+        cursor_ignored,
+        disabled_rules: installed.iter().filter(|r| r.disabled).count(),
+        rules: installed.len(),
+        locked_files: Lock::load(root)?.files.len(),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_when_mode_and_gitignore_disagree() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        assert!(status(dir.path(), &config, None)
+            .unwrap()
+            .warnings
+            .is_empty());
+
+        rules::install(dir.path(), &config.rules, false).unwrap();
+        let ignored_mode = status(dir.path(), &config, None).unwrap();
+        assert_eq!(ignored_mode.rules, 2);
+        assert_eq!(ignored_mode.warnings.len(), 1);
+        assert!(ignored_mode.warnings[0].starts_with(".cursor/ is not ignored"));
+
+        gitignore::ensure_cursor_ignored(dir.path(), ExcludeStrategy::Gitignore).unwrap();
+        assert!(status(dir.path(), &config, None)
+            .unwrap()
+            .warnings
+            .is_empty());
+        config.vendor = true;
+        let vendored = status(dir.path(), &config, None).unwrap();
+        assert!(vendored.warnings[0].starts_with("vendor mode is on"));
+        fs::remove_file(dir.path().join(".gitignore")).unwrap();
+        assert!(status(dir.path(), &config, None)
+            .unwrap()
+            .warnings
+            .is_empty());
+
+        config.rules.provenance = true;
+        fs::remove_dir_all(dir.path().join(RULES_DIR)).unwrap();
+        rules::install(dir.path(), &config.rules, false).unwrap();
+        let rule = fs::read_dir(dir.path().join(RULES_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.is_file())
+            .unwrap();
+        let stamped = fs::read_to_string(&rule).unwrap();
+        assert!(provenance::find(&stamped).is_some());
+        assert!(status(dir.path(), &config, None)
+            .unwrap()
+            .warnings
+            .is_empty());
+        fs::write(&rule, format!("{}edited\n", stamped)).unwrap();
+        let edited = status(dir.path(), &config, None).unwrap();
+        assert!(edited.warnings[0].contains("provenance header no longer matches"));
+    }
+}
@@ -0,0 +1,328 @@
+//! Template rendering shared by AGENT.md and issue templates.
+//!
+//! Templates use minijinja syntax: `{{ VAR }}`, `{% if VAR %}...{% endif %}`.
+//! Built-in variables are upper case (`PROJECT_NAME`, `DATE`, `AUTHOR`,
+//! `GIT_REMOTE`, `ISSUE_ID`); user-defined ones come from the `[variables]`
+//! section of `.echo.toml`. Referencing an undefined variable is an error;
+//! [`undefined_variables`] lists them so commands can ask for their values.
+
+use crate::environment;
+use crate::gitutil;
+use anyhow::{anyhow, bail, Result};
+use minijinja::syntax::SyntaxConfig;
+use minijinja::{Environment, UndefinedBehavior};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Variables available to a template.
+pub type Variables = BTreeMap<String, String>;
+
+/// Built-in variables for the repository at `root`, merged with the
+/// user-defined `extra` ones (which win on conflicts). `DATE` is that of
+/// `now`, in seconds since the epoch.
+///
+/// `AUTHOR` and `GIT_REMOTE` are empty when git cannot provide them.
+pub fn variables(root: &Path, project: &str, now: u64, extra: &Variables) -> Variables {
+    let mut vars = Variables::new();
+    vars.insert("PROJECT_NAME".into(), project.to_string());
+    vars.insert("DATE".into(), environment::date(now));
+    vars.insert(
+        "AUTHOR".into(),
+        gitutil::config(root, "user.name").unwrap_or_default(),
+    );
+    vars.insert(
+        "GIT_REMOTE".into(),
+        gitutil::remote_url(root, "origin").unwrap_or_default(),
+    );
+    vars.extend(extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+    vars
+}
+
+/// Render `source`, failing on syntax errors and undefined variables.
+pub fn render(source: &str, vars: &Variables) -> Result<String> {
+    environment()?
+        .render_str(source, vars)
+        .map_err(|err| anyhow!("template error: {:#}", err))
+}
+
+fn environment() -> Result<Environment<'static>> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_syntax(
+        SyntaxConfig::builder()
+            .keep_trailing_newline(true)
+            .build()
+            .map_err(|err| anyhow!("template syntax: {}", err))?,
+    );
+    Ok(env)
+}
+
+/// Render an issue template.
+///
+/// Issue templates carry prose hints for the author such as
+/// `{{short background}}`. A placeholder starting with a lower-case name
+/// that is not a defined variable is such a hint and is kept verbatim;
+/// everything else is rendered as in [`render`].
+pub fn render_issue_template(source: &str, vars: &Variables) -> Result<String> {
+    render(&protect_hints(source, vars), vars)
+}
+
+/// Render an issue template that may build on others: `{% extends
+/// "base" %}` (or an `extends: base` first line) and `{% include
+/// "partials/criteria" %}` name templates `load` returns, `None` when there
+/// is no such template. Author hints are kept in all of them.
+pub fn render_issue_template_with(
+    source: &str,
+    vars: &Variables,
+    load: impl Fn(&str) -> Result<Option<String>> + Send + Sync + 'static,
+) -> Result<String> {
+    let (env, _) = issue_environment(vars, load)?;
+    env.render_str(&protect_hints(&expand_extends(source), vars), vars)
+        .map_err(|err| anyhow!("template error: {:#}", err))
+}
+
+/// Variables `source` references that `vars` does not define, sorted.
+pub fn undefined_variables(source: &str, vars: &Variables) -> Result<Vec<String>> {
+    undeclared(&[source.to_string()], vars)
+}
+
+/// [`undefined_variables`] of an issue template and of the templates it
+/// extends and includes. Author hints are not variables.
+pub fn undefined_issue_variables(
+    source: &str,
+    vars: &Variables,
+    load: impl Fn(&str) -> Result<Option<String>> + Send + Sync + 'static,
+) -> Result<Vec<String>> {
+    let (mut env, loaded) = issue_environment(vars, load)?;
+    // Rendering once, undefined variables left empty, loads every
+    // template this one builds on.
+    env.set_undefined_behavior(UndefinedBehavior::Chainable);
+    let source = protect_hints(&expand_extends(source), vars);
+    env.render_str(&source, vars)
+        .map_err(|err| anyhow!("template error: {:#}", err))?;
+    let mut sources = vec![source];
+    sources.extend(
+        loaded
+            .lock()
+            .map_err(|_| anyhow!("template loader panicked"))?
+            .drain(..),
+    );
+    undeclared(&sources, vars)
+}
+
+/// Parse a `NAME=value` assignment of `--var`.
+pub fn parse_assignment(assignment: &str) -> Result<(String, String)> {
+    let Some((name, value)) = assignment.split_once('=') else {
+        bail!("invalid --var '{}': expected NAME=value", assignment);
+    };
+    let name = name.trim();
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("invalid variable name '{}' in --var", name);
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// The environment rendering issue templates with `load`, and the
+/// templates it loaded so far, hints protected.
+fn issue_environment(
+    vars: &Variables,
+    load: impl Fn(&str) -> Result<Option<String>> + Send + Sync + 'static,
+) -> Result<(Environment<'static>, Arc<Mutex<Vec<String>>>)> {
+    let mut env = environment()?;
+    let loaded = Arc::new(Mutex::new(Vec::new()));
+    let hint_vars = vars.clone();
+    let record = Arc::clone(&loaded);
+    env.set_loader(move |name| {
+        let source = load(name).map_err(|err| {
+            minijinja::Error::new(minijinja::ErrorKind::TemplateNotFound, format!("{:#}", err))
+        })?;
+        let source = source.map(|source| protect_hints(&expand_extends(&source), &hint_vars));
+        if let (Some(source), Ok(mut loaded)) = (&source, record.lock()) {
+            loaded.push(source.clone());
+        }
+        Ok(source)
+    });
+    Ok((env, loaded))
+}
+
+/// Names `sources` read without setting them that are neither in `vars`
+/// nor engine globals such as `range`.
+fn undeclared(sources: &[String], vars: &Variables) -> Result<Vec<String>> {
+    let env = Environment::new();
+    let mut names = BTreeSet::new();
+    for source in sources {
+        let template = env
+            .template_from_str(source)
+            .map_err(|err| anyhow!("template error: {:#}", err))?;
+        names.extend(template.undeclared_variables(false));
+    }
+    Ok(names
+        .into_iter()
+        .filter(|name| !vars.contains_key(name) && env.globals().all(|(global, _)| global != name))
+        .collect())
+}
+
+/// Turn an `extends: <name>` first line into `{% extends "<name>" %}`.
+fn expand_extends(source: &str) -> String {
+    let (first, rest) = source.split_once('\n').unwrap_or((source, ""));
+    match first.trim().strip_prefix("extends:") {
+        Some(name) => format!(
+            "{{% extends \"{}\" %}}\n{}",
+            name.trim().trim_matches(['"', '\'']),
+            rest
+        ),
+        None => source.to_string(),
+    }
+}
+
+/// Wrap author hints in `{% raw %}` blocks so the engine leaves them alone.
+fn protect_hints(source: &str, vars: &Variables) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let end = start + len + 2;
+        let placeholder = &rest[start..end];
+        out.push_str(&rest[..start]);
+        if is_hint(&placeholder[2..placeholder.len() - 2], vars) {
+            out.push_str("{% raw %}");
+            out.push_str(placeholder);
+            out.push_str("{% endraw %}");
+        } else {
+            out.push_str(placeholder);
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn is_hint(inner: &str, vars: &Variables) -> bool {
+    let inner = inner.trim_matches(|c: char| c == '-' || c.is_whitespace());
+    let name: String = inner
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    name.starts_with(|c: char| c.is_lowercase()) && !vars.contains_key(&name)
+}
+
+/// `secs` since the epoch as `YYYY-MM-DD HH:MM UTC`.
+pub fn format_time(secs: u64) -> String {
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    let minutes = secs % 86_400 / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        y,
+        m,
+        d,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Variables {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn renders_variables_and_conditionals() {
+        let vars = vars(&[("PROJECT_NAME", "echo"), ("GIT_REMOTE", "")]);
+        let source = "# {{ PROJECT_NAME }}\n{% if GIT_REMOTE %}remote{% else %}local{% endif %}\n";
+        assert_eq!(render(source, &vars).unwrap(), "# echo\nlocal\n");
+    }
+
+    #[test]
+    fn undefined_variable_is_an_error() {
+        let err = render("{{ TEAM }}", &Variables::new()).unwrap_err();
+        assert!(err.to_string().contains("undefined"), "{}", err);
+    }
+
+    #[test]
+    fn issue_templates_keep_author_hints() {
+        let vars = vars(&[("title", "Crash"), ("ISSUE_ID", "bug-crash")]);
+        let source = "# {{title}}\n{{short background}} {{ scope }} ({{ISSUE_ID}})\n";
+        assert_eq!(
+            render_issue_template(source, &vars).unwrap(),
+            "# Crash\n{{short background}} {{ scope }} (bug-crash)\n"
+        );
+        assert!(render_issue_template("{{ UNKNOWN }}", &vars).is_err());
+    }
+
+    #[test]
+    fn issue_templates_extend_and_include_others() {
+        let vars = vars(&[("title", "Crash")]);
+        let load = |name: &str| {
+            Ok(match name {
+                "base" => Some(
+                    "# {{title}}\n{% block fields %}{% endblock %}\n{% include \"partials/criteria\" %}"
+                        .to_string(),
+                ),
+                "partials/criteria" => Some("## Criteria\n- [ ] {{criterion}}\n".to_string()),
+                _ => None,
+            })
+        };
+        let source = "extends: base\n{% block fields %}**Type:** bug{% endblock %}\n";
+        assert_eq!(
+            render_issue_template_with(source, &vars, load).unwrap(),
+            "# Crash\n**Type:** bug\n## Criteria\n- [ ] {{criterion}}\n"
+        );
+        let missing = "{% include \"partials/nope\" %}";
+        assert!(render_issue_template_with(missing, &vars, load).is_err());
+    }
+
+    #[test]
+    fn lists_undefined_variables() {
+        let vars = vars(&[("PROJECT_NAME", "echo"), ("title", "Crash")]);
+        let source = "{{ PROJECT_NAME }} {{ TEAM }}{% for x in range(2) %}{{ x }}{% endfor %}";
+        assert_eq!(undefined_variables(source, &vars).unwrap(), vec!["TEAM"]);
+
+        let load = |name: &str| {
+            Ok((name == "partials/owner").then(|| "{{ OWNER }} {{owner hint}}".to_string()))
+        };
+        let source = "# {{title}} {{ SPRINT }}\n{% include \"partials/owner\" %}\n";
+        assert_eq!(
+            undefined_issue_variables(source, &vars, load).unwrap(),
+            vec!["OWNER", "SPRINT"]
+        );
+
+        assert_eq!(
+            parse_assignment("TEAM=core=a").unwrap(),
+            ("TEAM".to_string(), "core=a".to_string())
+        );
+        assert!(parse_assignment("TEAM").is_err());
+        assert!(parse_assignment("1X=y").is_err());
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(format_time(19_782 * 86_400 + 3_725), "2024-02-29 01:02 UTC");
+    }
+}
@@ -0,0 +1,162 @@
+//! Removal of what echo installed, for `echo uninstall`.
+//!
+//! Files recorded in the lockfile are deleted when their contents still
+//! match the recorded hash; edited ones are kept and reported. Links echo
+//! made to flowmates rules are deleted, links made by others kept. The
+//! lockfile and the merge bases kept beside it, the ignore-file block and
+//! the git hooks echo installed go too. Issues and agent files are project
+//! content and are left alone.
+
+use crate::echoignore::EchoIgnore;
+use crate::gitignore;
+use crate::gitutil;
+use crate::hooks;
+use crate::lock::{self, Lock, LOCK_FILE};
+use crate::rules;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What [`uninstall`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Uninstall {
+    /// Installed files deleted, relative to the root.
+    pub removed: Vec<PathBuf>,
+    /// Installed files kept because they were edited since.
+    pub modified: Vec<PathBuf>,
+    /// Installed files kept because they are listed in `.echoignore`.
+    pub protected: Vec<PathBuf>,
+    /// Ignore files the managed block was removed from.
+    pub ignore_files: Vec<String>,
+    /// Git hooks removed.
+    pub hooks: Vec<String>,
+}
+
+/// Remove the files echo installed in `root`.
+pub fn uninstall(root: &Path) -> Result<Uninstall> {
+    let mut report = Uninstall::default();
+    // Hook scripts may be links whose source changed; `hooks` tells them
+    // apart and drops their lockfile entries.
+    if gitutil::is_repo(root) {
+        report.hooks = hooks::uninstall(root, None)?;
+    }
+    let ignore = EchoIgnore::load(root)?;
+    for file in Lock::load(root)?.files {
+        let base = root.join(lock::base_path(&file.path));
+        if base.is_file() {
+            fs::remove_file(&base)
+                .with_context(|| format!("failed to remove {}", base.display()))?;
+            prune_empty_parents(root, &base);
+        }
+        let path = root.join(&file.path);
+        // Links echo made go whatever their target became; other links,
+        // even to the same contents, are not echo's to remove.
+        let echo_link = rules::is_link(&file) && path.is_symlink();
+        if !echo_link && !path.is_file() {
+            continue;
+        }
+        if ignore.is_protected(&file.path) {
+            report.protected.push(file.path);
+            continue;
+        }
+        if echo_link {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+            prune_empty_parents(root, &path);
+            report.removed.push(file.path);
+            continue;
+        }
+        if path.is_symlink() {
+            report.modified.push(file.path);
+            continue;
+        }
+        let content =
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        if lock::hash_installed_bytes(&content) != file.hash {
+            report.modified.push(file.path);
+            continue;
+        }
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        prune_empty_parents(root, &path);
+        report.removed.push(file.path);
+    }
+    let lock_path = root.join(LOCK_FILE);
+    if lock_path.is_file() {
+        fs::remove_file(&lock_path).with_context(|| format!("failed to remove {}", LOCK_FILE))?;
+        prune_empty_parents(root, &lock_path);
+    }
+    report.ignore_files = gitignore::remove_managed_block(root)?
+        .into_iter()
+        .map(|change| change.file)
+        .collect();
+    Ok(report)
+}
+
+/// Remove the directories above `path` left empty, up to `root`.
+pub(crate) fn prune_empty_parents(root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitignore::ExcludeStrategy;
+    use crate::rules::RulesConfig;
+
+    #[test]
+    fn removes_unmodified_files_and_the_gitignore_block() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        gitignore::ensure_cursor_ignored(dir.path(), ExcludeStrategy::Gitignore).unwrap();
+        let synced = rules::sync(dir.path(), &RulesConfig::default(), false).unwrap();
+        let installed: Vec<_> = synced[0].1.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(installed.len(), 2);
+        let edited = dir.path().join(&installed[0]);
+        fs::write(&edited, "mine\n").unwrap();
+
+        let report = uninstall(dir.path()).unwrap();
+        assert_eq!(report.removed, vec![installed[1].clone()]);
+        assert_eq!(report.modified, vec![installed[0].clone()]);
+        assert_eq!(report.ignore_files, vec![".gitignore".to_string()]);
+        assert!(edited.is_file());
+        assert!(!dir.path().join(".echo").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join(".gitignore")).unwrap(),
+            "target/\n"
+        );
+        assert_eq!(uninstall(dir.path()).unwrap(), Uninstall::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn removes_only_the_links_echo_made() {
+        let dir = tempfile::tempdir().unwrap();
+        let flowmates = tempfile::tempdir().unwrap();
+        let source = flowmates.path().join("rules/style.mdc");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "four spaces\n").unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            link: true,
+            ..RulesConfig::default()
+        };
+        rules::sync(dir.path(), &config, false).unwrap();
+        // Someone else's link in place of an embedded rule echo copied.
+        let workflow = dir.path().join(rules::rule_path("echo-workflow"));
+        fs::remove_file(&workflow).unwrap();
+        std::os::unix::fs::symlink(&source, &workflow).unwrap();
+        fs::write(&source, "edited upstream\n").unwrap();
+
+        let report = uninstall(dir.path()).unwrap();
+        assert!(report.removed.contains(&rules::rule_path("style")));
+        assert_eq!(report.modified, vec![rules::rule_path("echo-workflow")]);
+        assert!(workflow.is_symlink());
+        assert!(source.is_file());
+    }
+}
@@ -0,0 +1,269 @@
+//! `echo watch`: keep a repository in step with a flowmates checkout while
+//! its rules and templates are being edited.
+//!
+//! The checkout is watched through `notify`; once a change has settled,
+//! rules are synced through [`rules::sync`] and templates through
+//! [`templates::install_with`], rewriting those still matching their
+//! previous source. Files edited in the repository are left alone. With
+//! [`Global`], the rules of `~/.cursor/rules/` follow as
+//! [`cursor_sync::sync`] keeps them.
+
+use crate::cursor_sync;
+use crate::fs_ops::RealFs;
+use crate::issues::templates::{self, FLOWMATES_TEMPLATES_DIR, PARTIALS_DIR, TEMPLATE_SUFFIX};
+use crate::lock::{Outcome, Resolution};
+use crate::rules::{self, RulesConfig, Stamp, FLOWMATES_RULES_DIR};
+use crate::run_lock::RunLock;
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// Quiet period after a change before it is applied, so saving several
+/// files applies once.
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Contents of the templates of a checkout, by installed path.
+pub type Snapshot = BTreeMap<PathBuf, String>;
+
+/// The global Cursor rules to keep in step too, as `echo sync-cursor`
+/// does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Global {
+    pub home: PathBuf,
+    /// Flowmates configuration directory, holding the sync state.
+    pub config_dir: PathBuf,
+}
+
+/// Read the templates of `flowmates_dir`, partials included.
+pub fn snapshot(flowmates_dir: &Path) -> Result<Snapshot> {
+    let mut files = Snapshot::new();
+    let dir = flowmates_dir.join(FLOWMATES_TEMPLATES_DIR);
+    for (dir, prefix) in [(dir.clone(), ""), (dir.join(PARTIALS_DIR), "partials/")] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(TEMPLATE_SUFFIX))
+            else {
+                continue;
+            };
+            // Editors briefly leave files unreadable or non-UTF-8.
+            if let Ok(content) = fs::read_to_string(&path) {
+                files.insert(
+                    templates::template_path(&format!("{}{}", prefix, name)),
+                    content,
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Bring `root` up to date with `flowmates_dir`, whose templates were
+/// `previous` when last applied. `templates` restricts the templates
+/// installed, as presets do. Returns a line per file written or kept.
+pub fn apply(
+    root: &Path,
+    flowmates_dir: &Path,
+    config: &RulesConfig,
+    templates: Option<&[String]>,
+    previous: &Snapshot,
+    global: Option<&Global>,
+) -> Result<Vec<String>> {
+    let mut log = Vec::new();
+    let mut report = |prefix: &str, path: &Path, outcome: Outcome| match outcome {
+        Outcome::UpToDate | Outcome::Protected => {}
+        Outcome::Modified => log.push(format!("{}kept {} (modified)", prefix, path.display())),
+        _ => log.push(format!("{}wrote {}", prefix, path.display())),
+    };
+    for (target, installed) in rules::sync(root, config, false)? {
+        let prefix = format!("{}: ", target.name());
+        for (path, outcome) in installed {
+            report(&prefix, &path, outcome);
+        }
+    }
+    let stamp = config
+        .provenance
+        .then(|| Stamp::new(Some(flowmates_dir), config.now()));
+    let installed = templates::install_with(
+        &RealFs,
+        root,
+        Some(flowmates_dir),
+        templates,
+        config.preserve_metadata,
+        stamp.as_ref(),
+        // Templates keep no lock: one still as its previous source was
+        // follows the source, as unedited.
+        |conflict| {
+            Ok(match previous.get(conflict.path) {
+                Some(content) if content.as_str() == conflict.local => Resolution::Take,
+                _ => Resolution::Keep,
+            })
+        },
+    )?;
+    for (path, outcome) in installed {
+        report("", &path, outcome);
+    }
+    if let Some(global) = global {
+        let mut state = cursor_sync::State::load(&global.config_dir)?;
+        let synced =
+            cursor_sync::sync(&global.home, flowmates_dir, &mut state, false, config.now())?;
+        state.save(&global.config_dir)?;
+        for (path, outcome) in synced.installed {
+            report("global: ", &Path::new("~").join(path), outcome);
+        }
+    }
+    Ok(log)
+}
+
+/// Watch `flowmates_dir` until interrupted, passing each line of
+/// [`apply`] to `report`. A change is applied once the checkout has been
+/// quiet for [`DEBOUNCE`], under the run lock of `root`.
+pub fn watch(
+    root: &Path,
+    flowmates_dir: &Path,
+    config: &RulesConfig,
+    templates: Option<&[String]>,
+    global: Option<&Global>,
+    mut report: impl FnMut(&str),
+) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .context("failed to start watching the flowmates checkout")?;
+    watcher
+        .watch(flowmates_dir, RecursiveMode::Recursive)
+        .with_context(|| format!("failed to watch {}", flowmates_dir.display()))?;
+    let watched = [
+        flowmates_dir.join(FLOWMATES_RULES_DIR),
+        flowmates_dir.join(FLOWMATES_TEMPLATES_DIR),
+    ];
+    let relevant = |event: &notify::Result<notify::Event>| match event {
+        Ok(event) => event
+            .paths
+            .iter()
+            .any(|path| watched.iter().any(|dir| path.starts_with(dir))),
+        // Events were lost: look again.
+        Err(_) => true,
+    };
+    let mut previous = snapshot(flowmates_dir)?;
+    loop {
+        let event = events.recv().context("stopped watching")?;
+        if !relevant(&event) {
+            continue;
+        }
+        loop {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+        let lock = RunLock::acquire(root, true, |holder| {
+            report(&format!(
+                "waiting for another echo run (pid {}) to finish...",
+                holder.pid
+            ))
+        })?;
+        let current = snapshot(flowmates_dir)?;
+        let lines = apply(root, flowmates_dir, config, templates, &previous, global)?;
+        drop(lock);
+        previous = current;
+        for line in lines {
+            report(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::Lock;
+    use crate::rules::rule_path;
+
+    #[test]
+    fn applies_source_changes_to_unedited_files() {
+        let flowmates = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let home = tempfile::tempdir().unwrap();
+        let root = repo.path();
+        let source_rule = flowmates.path().join("rules/style.mdc");
+        let source_template = flowmates.path().join("templates/spike_template.md");
+        fs::create_dir_all(source_rule.parent().unwrap()).unwrap();
+        fs::create_dir_all(source_template.parent().unwrap()).unwrap();
+        fs::write(&source_rule, "v1\n").unwrap();
+        fs::write(&source_template, "# Spike\n").unwrap();
+        let config = RulesConfig {
+            flowmates_dir: Some(flowmates.path().to_path_buf()),
+            include: vec!["style.mdc".into()],
+            ..RulesConfig::default()
+        };
+        rules::sync(root, &config, false).unwrap();
+        let before = snapshot(flowmates.path()).unwrap();
+        assert_eq!(before.len(), 1);
+
+        fs::write(&source_rule, "v2\n").unwrap();
+        let log = apply(root, flowmates.path(), &config, Some(&[]), &before, None).unwrap();
+        assert_eq!(log, vec!["cursor: wrote .cursor/rules/style.mdc"]);
+        assert_eq!(
+            fs::read_to_string(root.join(rule_path("style"))).unwrap(),
+            "v2\n"
+        );
+        assert!(!root.join(templates::template_path("spike")).exists());
+
+        let template = root.join(templates::template_path("spike"));
+        fs::create_dir_all(template.parent().unwrap()).unwrap();
+        fs::write(&template, "# Spike\n").unwrap();
+        fs::write(root.join(rule_path("style")), "mine\n").unwrap();
+        fs::write(&source_rule, "v3\n").unwrap();
+        fs::write(&source_template, "# Spike v2\n").unwrap();
+        let global = Global {
+            home: home.path().to_path_buf(),
+            config_dir: home.path().join(".config/flowmates"),
+        };
+        let log = apply(
+            root,
+            flowmates.path(),
+            &config,
+            Some(&["spike".to_string()]),
+            &before,
+            Some(&global),
+        )
+        .unwrap();
+        assert_eq!(
+            log,
+            vec![
+                "cursor: kept .cursor/rules/style.mdc (modified)",
+                "wrote issues/shared/templates/spike_template.md",
+                "global: wrote ~/.cursor/rules/style.mdc",
+            ]
+        );
+        assert_eq!(fs::read_to_string(&template).unwrap(), "# Spike v2\n");
+        assert_eq!(
+            fs::read_to_string(root.join(rule_path("style"))).unwrap(),
+            "mine\n"
+        );
+        assert!(Lock::load(root).unwrap().get(&rule_path("style")).is_some());
+
+        // Edited in the repository: kept, and `echo update` agrees.
+        fs::write(&template, "# My spike\n").unwrap();
+        let next = snapshot(flowmates.path()).unwrap();
+        fs::write(&source_template, "# Spike v3\n").unwrap();
+        let only = ["spike".to_string()];
+        let log = apply(root, flowmates.path(), &config, Some(&only), &next, None).unwrap();
+        assert_eq!(
+            log,
+            vec![
+                "cursor: kept .cursor/rules/style.mdc (modified)",
+                "kept issues/shared/templates/spike_template.md (modified)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Packages of a monorepo, for `echo init --workspace`.
+//!
+//! Packages come from the `[workspace]` section of `.echo.toml` when it
+//! lists any, else from a Cargo workspace, a `pnpm-workspace.yaml` or the
+//! `workspaces` of `package.json`. Each package becomes a project of
+//! `issues/`, named after its directory.
+
+use crate::issues::ops::validate_project_name;
+use anyhow::{bail, Context, Result};
+use globset::{GlobBuilder, GlobSetBuilder};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories never searched for packages.
+const SKIPPED_DIRS: [&str; 4] = [".git", "node_modules", "target", "issues"];
+
+/// `[workspace]` section of `.echo.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkspaceConfig {
+    /// Package directories or glob patterns, relative to the repository
+    /// root; `!` excludes. Overrides detection when not empty.
+    pub packages: Vec<String>,
+}
+
+/// Where the package list came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Config,
+    Cargo,
+    Pnpm,
+    Npm,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Config => ".echo.toml",
+            Kind::Cargo => "Cargo workspace",
+            Kind::Pnpm => "pnpm workspace",
+            Kind::Npm => "npm workspaces",
+        }
+    }
+}
+
+/// A package of the workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    /// Project name under `issues/`.
+    pub name: String,
+    /// Directory relative to the repository root.
+    pub path: PathBuf,
+}
+
+/// Packages found in a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    pub kind: Kind,
+    /// Sorted by path.
+    pub packages: Vec<Package>,
+}
+
+/// The workspace of `root`, if it is one.
+pub fn detect(root: &Path, config: &WorkspaceConfig) -> Result<Option<Workspace>> {
+    let (kind, patterns) = if !config.packages.is_empty() {
+        (Kind::Config, config.packages.clone())
+    } else if let Some(patterns) = cargo_members(root)? {
+        (Kind::Cargo, patterns)
+    } else if let Some(patterns) = pnpm_packages(root)? {
+        (Kind::Pnpm, patterns)
+    } else if let Some(patterns) = npm_workspaces(root)? {
+        (Kind::Npm, patterns)
+    } else {
+        return Ok(None);
+    };
+    let mut by_name: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for path in expand(root, &patterns)? {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(str::to_string)
+            .with_context(|| format!("cannot derive a project name from {}", path.display()))?;
+        validate_project_name(&name)?;
+        if let Some(other) = by_name.insert(name.clone(), path.clone()) {
+            bail!(
+                "packages {} and {} would share the project '{}'",
+                other.display(),
+                path.display(),
+                name
+            );
+        }
+    }
+    let mut packages: Vec<Package> = by_name
+        .into_iter()
+        .map(|(name, path)| Package { name, path })
+        .collect();
+    packages.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Some(Workspace { kind, packages }))
+}
+
+/// `members` minus `exclude` of the `[workspace]` of `Cargo.toml`.
+fn cargo_members(root: &Path) -> Result<Option<Vec<String>>> {
+    #[derive(Deserialize)]
+    struct Manifest {
+        workspace: Option<CargoWorkspace>,
+    }
+    #[derive(Deserialize)]
+    struct CargoWorkspace {
+        #[serde(default)]
+        members: Vec<String>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    }
+    let path = root.join("Cargo.toml");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let manifest: Manifest = toml::from_str(&fs::read_to_string(&path)?)
+        .with_context(|| format!("invalid {}", path.display()))?;
+    Ok(manifest.workspace.map(|workspace| {
+        let excluded = workspace.exclude.into_iter().map(|p| format!("!{}", p));
+        workspace.members.into_iter().chain(excluded).collect()
+    }))
+}
+
+/// `packages` of `pnpm-workspace.yaml`, a flat list of quoted or bare
+/// patterns.
+fn pnpm_packages(root: &Path) -> Result<Option<Vec<String>>> {
+    let path = root.join("pnpm-workspace.yaml");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)?;
+    let mut in_packages = false;
+    let mut patterns = Vec::new();
+    for line in content.lines() {
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+            patterns.push(item.trim().trim_matches(['\'', '"']).to_string());
+        }
+    }
+    Ok(Some(patterns))
+}
+
+/// `workspaces` of `package.json`, as a list or under `packages`.
+fn npm_workspaces(root: &Path) -> Result<Option<Vec<String>>> {
+    let path = root.join("package.json");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)
+        .with_context(|| format!("invalid {}", path.display()))?;
+    let workspaces = match &manifest["workspaces"] {
+        serde_json::Value::Object(object) => object.get("packages").cloned(),
+        other => Some(other.clone()),
+    };
+    Ok(workspaces.and_then(|w| w.as_array().cloned()).map(|items| {
+        items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect()
+    }))
+}
+
+/// Directories of `root` matching `patterns` and none of the `!` ones.
+fn expand(root: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut include = GlobSetBuilder::new();
+    let mut exclude = GlobSetBuilder::new();
+    let mut depth = 0;
+    for pattern in patterns {
+        let (builder, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (&mut exclude, pattern),
+            None => (&mut include, pattern.as_str()),
+        };
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        depth = depth.max(if pattern.contains("**") {
+            usize::MAX
+        } else {
+            pattern.split('/').count()
+        });
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid package pattern '{}'", pattern))?,
+        );
+    }
+    let (include, exclude) = (include.build()?, exclude.build()?);
+    let mut found = Vec::new();
+    let mut pending = vec![(PathBuf::new(), 0)];
+    while let Some((rel, level)) = pending.pop() {
+        if level > 0 && include.is_match(&rel) && !exclude.is_match(&rel) {
+            found.push(rel.clone());
+        }
+        if level == depth {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(root.join(&rel)) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_dir() && !SKIPPED_DIRS.iter().any(|s| name == *s) {
+                pending.push((rel.join(name), level + 1));
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mkdirs(root: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+    }
+
+    fn names(workspace: &Workspace) -> Vec<&str> {
+        workspace.packages.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn detects_cargo_pnpm_npm_and_configured_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let config = WorkspaceConfig::default();
+        assert_eq!(detect(root, &config).unwrap(), None);
+
+        mkdirs(
+            root,
+            &["crates/core", "crates/cli", "crates/old", "tools/gen"],
+        );
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\", \"tools/gen\"]\nexclude = [\"crates/old\"]\n",
+        )
+        .unwrap();
+        let cargo = detect(root, &config).unwrap().unwrap();
+        assert_eq!(cargo.kind, Kind::Cargo);
+        assert_eq!(names(&cargo), vec!["cli", "core", "gen"]);
+        assert_eq!(cargo.packages[0].path, Path::new("crates/cli"));
+
+        fs::remove_file(root.join("Cargo.toml")).unwrap();
+        mkdirs(root, &["packages/web", "apps/site/node_modules/x"]);
+        fs::write(
+            root.join("pnpm-workspace.yaml"),
+            "packages:\n  - 'packages/*'\n  - \"apps/*\"\ncatalog:\n  - ignored\n",
+        )
+        .unwrap();
+        let pnpm = detect(root, &config).unwrap().unwrap();
+        assert_eq!((pnpm.kind, names(&pnpm)), (Kind::Pnpm, vec!["site", "web"]));
+
+        fs::remove_file(root.join("pnpm-workspace.yaml")).unwrap();
+        fs::write(
+            root.join("package.json"),
+            r#"{"workspaces": {"packages": ["packages/*"]}}"#,
+        )
+        .unwrap();
+        let npm = detect(root, &config).unwrap().unwrap();
+        assert_eq!((npm.kind, names(&npm)), (Kind::Npm, vec!["web"]));
+
+        let config = WorkspaceConfig {
+            packages: vec!["crates/*".into(), "!crates/old".into(), "tools/gen".into()],
+        };
+        let configured = detect(root, &config).unwrap().unwrap();
+        assert_eq!(configured.kind, Kind::Config);
+        assert_eq!(names(&configured), vec!["cli", "core", "gen"]);
+
+        mkdirs(root, &["tools/core"]);
+        let config = WorkspaceConfig {
+            packages: vec!["crates/core".into(), "tools/core".into()],
+        };
+        assert!(detect(root, &config).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! `echo agent spawn` / `echo agent reap`: a git worktree per issue, so
+//! several agents can work in parallel without sharing a checkout.
+//!
+//! Spawning creates the branch `issue/<id>` in a worktree next to the
+//! repository, `<repo>-<id>`, installs the rules there, with those of
+//! `[rules.labels.<label>]` for the labels of the issue, and moves the
+//! issue to `in_progress/` with `**Agent:**` and `**Worktree:**` header
+//! fields. Reaping removes the worktrees of issues moved to `done/`,
+//! unless they have uncommitted changes.
+
+use crate::agent::BASE_DIR;
+use crate::git;
+use crate::issues::{self, ops, State};
+use crate::rules::{self, RulesConfig};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Prefix of the branches created for issues.
+pub const BRANCH_PREFIX: &str = "issue/";
+
+/// Header field recording the worktree of an issue.
+pub const WORKTREE_FIELD: &str = "Worktree";
+
+/// Header field recording the agent working on an issue.
+pub const AGENT_FIELD: &str = "Agent";
+
+/// What [`spawn`] set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spawned {
+    pub branch: String,
+    pub worktree: PathBuf,
+    /// The issue in `in_progress/`, relative to the repository root.
+    pub issue: PathBuf,
+    /// The state the issue was in.
+    pub from: State,
+    /// Unmet dependencies of the issue, see [`ops::Transition`].
+    pub warnings: Vec<String>,
+}
+
+/// Default worktree of issue `id`: `<repo>-<id>` next to `root`.
+pub fn default_path(root: &Path, id: &str) -> Result<PathBuf> {
+    let name = root
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("cannot name the worktree; pass --path")?;
+    let parent = root
+        .parent()
+        .context("cannot place the worktree; pass --path")?;
+    Ok(parent.join(format!("{}-{}", name, id)))
+}
+
+/// Give issue `id` of `project` a worktree at `path` on a new branch,
+/// with the rules of `config` installed, and move it to `in_progress/`
+/// on behalf of `claim`.
+pub fn spawn(
+    root: &Path,
+    config: &RulesConfig,
+    project: &str,
+    id: &str,
+    agent: Option<&str>,
+    claim: ops::Claim,
+    path: &Path,
+) -> Result<Spawned> {
+    let issue = ops::find_issue(root, project, id)?;
+    if !matches!(issue.state, State::Proposal | State::Todo) {
+        bail!("{} is already {}", issue.path.display(), issue.state);
+    }
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    let transition = ops::Transition::check(root, issue, State::InProgress, claim)?;
+    let issue = &transition.issue;
+    let branch = format!("{}{}", BRANCH_PREFIX, issue.id());
+    git::run(
+        root,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            &branch,
+            &path.to_string_lossy(),
+        ],
+    )?;
+    // The rules of the issue's labels go on top of the usual ones.
+    let config = RulesConfig {
+        active_labels: issue.labels().into_iter().map(String::from).collect(),
+        ..config.clone()
+    };
+    rules::sync(path, &config, false)
+        .with_context(|| format!("failed to install rules in {}", path.display()))?;
+    let moved = transition.apply(root)?;
+    let worktree = path.display().to_string();
+    let mut fields = vec![(WORKTREE_FIELD, worktree.as_str())];
+    if let Some(agent) = agent {
+        fields.insert(0, (AGENT_FIELD, agent));
+    }
+    ops::set_fields(root, &moved, &fields)?;
+    Ok(Spawned {
+        branch,
+        worktree: path.to_path_buf(),
+        issue: moved,
+        from: issue.state,
+        warnings: transition.warnings,
+    })
+}
+
+/// What [`reap`] did with the worktree of a finished issue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reaped {
+    Removed(PathBuf),
+    /// Kept, because it has uncommitted changes.
+    Dirty(PathBuf),
+}
+
+/// Remove the worktrees of the issues in `done/`. Their branches are
+/// deleted when merged, and kept otherwise.
+pub fn reap(root: &Path) -> Result<Vec<Reaped>> {
+    let worktrees = worktrees(root)?;
+    let mut reaped = Vec::new();
+    for issue in issues::scan(root)? {
+        if issue.state != State::Done {
+            continue;
+        }
+        let Some(path) = issue.field(WORKTREE_FIELD).map(PathBuf::from) else {
+            continue;
+        };
+        if !worktrees.contains(&path) {
+            continue;
+        }
+        if is_dirty(&path)? {
+            reaped.push(Reaped::Dirty(path));
+            continue;
+        }
+        // Forced, as the rules installed by `spawn` are untracked.
+        git::run(
+            root,
+            &["worktree", "remove", "--force", &path.to_string_lossy()],
+        )?;
+        let branch = format!("{}{}", BRANCH_PREFIX, issue.id());
+        // Fails, keeping the branch, when it has unmerged commits.
+        let _ = git::run(root, &["branch", "-d", &branch]);
+        reaped.push(Reaped::Removed(path));
+    }
+    Ok(reaped)
+}
+
+/// Whether the worktree at `path` has changes other than the rules
+/// installed by [`spawn`] and their records in `.echo/`.
+fn is_dirty(path: &Path) -> Result<bool> {
+    let status = git::run(path, &["status", "--porcelain", "--untracked-files=all"])?;
+    Ok(status.lines().any(|line| {
+        let file = line.get(3..).unwrap_or_default();
+        let installed = file.starts_with(rules::RULES_DIR) || file.starts_with(BASE_DIR);
+        !(line.starts_with("??") && installed)
+    }))
+}
+
+/// Linked worktrees of the repository at `root`.
+fn worktrees(root: &Path) -> Result<Vec<PathBuf>> {
+    Ok(git::run(root, &["worktree", "list", "--porcelain"])?
+        .lines()
+        .filter_map(|line| line.strip_prefix("worktree "))
+        .map(PathBuf::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::tests::write_issue;
+    use std::fs;
+
+    #[test]
+    fn spawns_and_reaps_a_worktree_per_issue() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        fs::create_dir_all(&root).unwrap();
+        git::run(&root, &["init", "-q", "-b", "main"]).unwrap();
+        git::run(&root, &["config", "user.email", "dev@example.com"]).unwrap();
+        git::run(&root, &["config", "user.name", "Dev"]).unwrap();
+        git::run(&root, &["commit", "-q", "--allow-empty", "-m", "init"]).unwrap();
+        write_issue(
+            &root,
+            "app",
+            "todo",
+            "001-login.md",
+            "# Login\n\n**Status:** todo  \n**Labels:** docs  \n",
+        );
+        let root = root.canonicalize().unwrap();
+        let path = default_path(&root, "001-login").unwrap();
+        assert_eq!(
+            path,
+            dir.path().canonicalize().unwrap().join("app-001-login")
+        );
+
+        let config: RulesConfig =
+            toml::from_str("exclude = [\"issue-*\"]\n[labels.docs]\ninclude = [\"issue-*\"]\n")
+                .unwrap();
+        let claim = ops::Claim {
+            by: Some("alice"),
+            steal: false,
+        };
+        let spawned = spawn(
+            &root,
+            &config,
+            "app",
+            "001-login",
+            Some("alice"),
+            claim,
+            &path,
+        )
+        .unwrap();
+        assert_eq!(spawned.branch, "issue/001-login");
+        assert!(path.join(rules::rule_path("issue-files")).is_file());
+        let issue = issues::read_issue_at(&root, &spawned.issue).unwrap();
+        assert_eq!(issue.state, State::InProgress);
+        assert_eq!(issue.field("Agent"), Some("alice"));
+        assert_eq!(issue.field("Worktree"), Some(path.to_str().unwrap()));
+        assert!(spawn(
+            &root,
+            &RulesConfig::default(),
+            "app",
+            "001-login",
+            None,
+            claim,
+            &path
+        )
+        .is_err());
+
+        assert!(reap(&root).unwrap().is_empty());
+        ops::move_issue(&root, "app", "001-login", State::Done).unwrap();
+        fs::write(path.join("notes.txt"), "wip\n").unwrap();
+        assert_eq!(reap(&root).unwrap(), vec![Reaped::Dirty(path.clone())]);
+        fs::remove_file(path.join("notes.txt")).unwrap();
+        assert_eq!(reap(&root).unwrap(), vec![Reaped::Removed(path.clone())]);
+        assert!(!path.exists());
+        assert!(git::run(&root, &["branch", "--list", "issue/001-login"])
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,744 @@
+//! `echo init`, `update`, `check` and `hooks` driven end to end against
+//! temporary repositories.
+
+mod testsupport;
+
+use echo::agent::AGENT_MD;
+use echo::check;
+use echo::config::Config;
+use echo::lock::{Lock, LOCK_FILE};
+use echo::rules::{self, rule_path};
+use echo::template;
+use testsupport::Fixture;
+
+#[test]
+fn init_sets_up_a_repository() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+
+    assert!(fixture.exists("issues/app/todo"));
+    assert!(fixture.read(AGENT_MD).contains("app"));
+    assert_eq!(fixture.read(rule_path("team/style")), "Use four spaces.\n");
+    assert!(fixture.exists(LOCK_FILE));
+    assert!(fixture.read(".gitignore").contains(".cursor/"));
+    fixture.echo(&["check"]).success();
+}
+
+#[test]
+fn update_applies_source_changes_and_keeps_local_edits() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.source_rule("team/review", "Ask for a review.\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+
+    fixture.source_rule("team/style", "Use tabs.\n");
+    fixture.source_rule("team/review", "Ask for two reviews.\n");
+    fixture.write(rule_path("team/review"), "Reviews are optional.\n");
+    let check = fixture.echo(&["check"]).failure();
+    assert!(check
+        .stderr()
+        .contains(".cursor/rules/team/style.mdc: outdated"));
+    assert!(check
+        .stderr()
+        .contains(".cursor/rules/team/review.mdc: differs from its source"));
+
+    let update = fixture.echo(&["update", "--strategy", "ours"]).success();
+    assert!(update
+        .stdout()
+        .contains("kept .cursor/rules/team/review.mdc"));
+    assert_eq!(fixture.read(rule_path("team/style")), "Use tabs.\n");
+    assert_eq!(
+        fixture.read(rule_path("team/review")),
+        "Reviews are optional.\n"
+    );
+    fixture.echo(&["update", "--strict"]).failure();
+
+    fixture.echo(&["update", "--strategy", "theirs"]).success();
+    assert_eq!(
+        fixture.read(rule_path("team/review")),
+        "Ask for two reviews.\n"
+    );
+    fixture.echo(&["check"]).success();
+}
+
+#[test]
+fn hooks_install_and_uninstall() {
+    let fixture = Fixture::new();
+    fixture.echo(&["hooks", "install", "pre-commit"]).success();
+    let hook = fixture.read(".git/hooks/pre-commit");
+    assert!(hook.contains("echo"));
+    fixture.echo(&["hooks", "verify"]).success();
+
+    fixture.echo(&["hooks", "uninstall"]).success();
+    assert!(!fixture.exists(".git/hooks/pre-commit"));
+}
+
+#[test]
+fn library_api_reports_drift() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    let config = Config {
+        rules: fixture.rules_config(),
+        ..Config::default()
+    };
+    rules::sync(fixture.root(), &config.rules, false).unwrap();
+    let lock = Lock::load(fixture.root()).unwrap();
+    assert!(lock.get(&rule_path("team/style")).is_some());
+
+    fixture.write(rule_path("team/style"), "Use tabs.\n");
+    let vars = template::variables(fixture.root(), "app", 0, &config.variables);
+    let problems = check::check(fixture.root(), &config, &vars).unwrap();
+    assert!(problems
+        .iter()
+        .any(|problem| problem.path == rule_path("team/style")));
+}
+
+#[test]
+fn root_flag_selects_the_repository() {
+    let fixture = Fixture::new();
+    let other = Fixture::new();
+    let other_root = other.root().to_str().unwrap();
+    fixture
+        .echo(&["init", "--project", "app", "--root", other_root])
+        .success();
+    assert!(other.exists("issues/app/todo"));
+    assert!(!fixture.exists("issues"));
+}
+
+#[test]
+fn offline_installs_from_the_cached_checkout() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+
+    std::fs::remove_dir_all(fixture.flowmates_dir().join("rules")).unwrap();
+    std::fs::remove_file(fixture.root().join(rule_path("team/style"))).unwrap();
+    let update = fixture.echo(&["update", "--offline"]).success();
+    assert!(update.stderr().contains("installing from the cache of"));
+    assert_eq!(fixture.read(rule_path("team/style")), "Use four spaces.\n");
+    fixture.echo(&["update", "--offline", "--strict"]).failure();
+}
+
+#[test]
+fn merge_strategy_combines_local_and_source_changes() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "one\ntwo\nthree\nfour\nfive\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+
+    fixture.write(
+        rule_path("team/style"),
+        "one\ntwo\nthree\nfour\nfive, locally\n",
+    );
+    fixture.source_rule("team/style", "one, upstream\ntwo\nthree\nfour\nfive\n");
+    let update = fixture.echo(&["update", "--strategy", "merge"]).success();
+    assert!(update.stdout().contains("merged"));
+    assert_eq!(
+        fixture.read(rule_path("team/style")),
+        "one, upstream\ntwo\nthree\nfour\nfive, locally\n"
+    );
+
+    fixture.write(
+        rule_path("team/style"),
+        "one, mine\ntwo\nthree\nfour\nfive, locally\n",
+    );
+    fixture.source_rule("team/style", "one, theirs\ntwo\nthree\nfour\nfive\n");
+    fixture.echo(&["update", "--strategy", "merge"]).success();
+    assert!(fixture.read(rule_path("team/style")).contains("<<<<<<<"));
+}
+
+#[test]
+fn rules_install_adds_a_bundle_and_its_requirements() {
+    let fixture = Fixture::new();
+    for name in ["core", "rust", "sqlx", "web"] {
+        fixture.source_rule(name, &format!("{}\n", name));
+    }
+    fixture.source_file(
+        "bundles.toml",
+        "[bundles]\nrust-backend = [\"sqlx.mdc\"]\n[requires]\nsqlx = [\"rust\"]\nrust = [\"core\"]\n",
+    );
+    fixture.write(".echo.toml", "[rules]\ninclude = [\"echo-*.mdc\"]\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    assert!(!fixture.exists(rule_path("core")));
+
+    fixture.echo(&["rules", "install", "go"]).failure();
+    fixture
+        .echo(&["rules", "install", "rust-backend"])
+        .success();
+    for name in ["core", "rust", "sqlx"] {
+        assert_eq!(fixture.read(rule_path(name)), format!("{}\n", name));
+    }
+    assert!(!fixture.exists(rule_path("web")));
+    assert!(fixture
+        .read(".echo.toml")
+        .contains("bundles = [\"rust-backend\"]"));
+
+    std::fs::remove_file(fixture.root().join(rule_path("core"))).unwrap();
+    fixture.echo(&["update"]).success();
+    assert!(fixture.exists(rule_path("core")));
+}
+
+#[test]
+fn update_refuses_a_new_major_pack_version() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.source_file("pack.toml", "version = \"2.1.0\"\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    assert_eq!(
+        Lock::load(fixture.root()).unwrap().pack.as_deref(),
+        Some("2.1.0")
+    );
+
+    fixture.source_file("pack.toml", "version = \"3.0.0\"\n");
+    fixture.source_rule("team/style", "Use tabs.\n");
+    let update = fixture.echo(&["update"]).failure();
+    assert!(update.stderr().contains("--allow-major"));
+    assert_eq!(fixture.read(rule_path("team/style")), "Use four spaces.\n");
+    fixture.echo(&["update", "--allow-major"]).success();
+    assert_eq!(fixture.read(rule_path("team/style")), "Use tabs.\n");
+
+    fixture.write(".echo.toml", "rules-pack = \"^2.1\"\n");
+    fixture.echo(&["update", "--allow-major"]).failure();
+}
+
+#[test]
+fn issue_new_asks_for_unknown_template_variables() {
+    let fixture = Fixture::new();
+    fixture.echo(&["init", "--project", "app"]).success();
+    fixture.write(
+        "issues/shared/templates/chore_template.md",
+        "# {{title}}\n\n**Team:** {{ TEAM }}\n\n{{what to do}}\n",
+    );
+
+    let new = ["issue", "new", "--type", "chore", "Bump deps"];
+    let refused = fixture.echo(&new).failure();
+    assert!(refused.stderr().contains("needs TEAM; pass --var"));
+
+    fixture
+        .echo(&[&new[..], &["--var", "TEAM=core", "--save-vars"]].concat())
+        .success();
+    assert!(fixture
+        .read("issues/app/todo/chore-bump-deps.md")
+        .contains("**Team:** core\n\n{{what to do}}\n"));
+    assert!(fixture.read(".echo.toml").contains("TEAM = \"core\""));
+    fixture
+        .echo(&["issue", "new", "--type", "chore", "Drop CI cache"])
+        .success();
+}
+
+#[test]
+fn new_issues_of_every_bundled_type_pass_lint() {
+    let fixture = Fixture::new();
+    fixture.echo(&["init", "--project", "app"]).success();
+    for kind in [
+        "adr",
+        "bug",
+        "enhancement",
+        "feature",
+        "proposal",
+        "roadmap",
+    ] {
+        fixture
+            .echo(&["issue", "new", "--type", kind, "Sample issue"])
+            .success();
+    }
+    assert!(fixture.exists("issues/app/proposal/proposal-sample-issue.md"));
+    let lint = fixture.echo(&["issue", "lint"]).success();
+    assert!(lint.stdout().contains("6 issue(s) clean"));
+    fixture.echo(&["issue", "validate"]).success();
+}
+
+#[test]
+fn init_applies_a_preset_of_the_flowmates_checkout() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.source_rule("web/react", "Use hooks.\n");
+    fixture.source_file(
+        "presets/platform.toml",
+        "description = \"Platform services\"\nrules = [\"team/*\"]\nhooks = [\"pre-commit\"]\n\
+         [settings.lint]\nmax-title-length = 100\n",
+    );
+    let list = fixture.echo(&["preset", "list"]).success();
+    assert!(list
+        .stdout()
+        .contains("platform         Platform services\n"));
+
+    fixture
+        .echo(&["init", "--project", "app", "--preset", "platform"])
+        .success();
+    assert!(fixture.exists(rule_path("team/style")));
+    assert!(!fixture.exists(rule_path("web/react")));
+    assert!(fixture.exists(".git/hooks/pre-commit"));
+    let config = Config::load(fixture.root()).unwrap();
+    assert_eq!(config.presets, vec!["platform"]);
+    assert_eq!(config.lint.max_title_length, 100);
+}
+
+#[test]
+fn init_applies_detected_presets_with_auto_preset() {
+    let fixture = Fixture::new();
+    fixture.source_rule("echo-workflow", "Follow the workflow.\n");
+    fixture.source_rule("infra/docker", "Pin base images.\n");
+    fixture.source_rule("web/react", "Use hooks.\n");
+    fixture.write("Dockerfile", "FROM scratch\n");
+
+    let init = fixture
+        .echo(&["init", "--project", "app", "--auto-preset"])
+        .success();
+    assert!(init
+        .stdout()
+        .contains("detected preset infra (found Dockerfile)"));
+    assert_eq!(Config::load(fixture.root()).unwrap().presets, vec!["infra"]);
+    assert!(fixture.exists(rule_path("infra/docker")));
+    assert!(!fixture.exists(rule_path("web/react")));
+}
+
+#[test]
+fn init_skips_noise_in_the_source_checkout() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.source_rule("team/.#style", "Emacs lock file.\n");
+    fixture.source_rule("drafts/wip", "Not ready.\n");
+    fixture.source_file("rules/vendor/.git/leak.mdc", "Nested repository.\n");
+    fixture.source_file("templates/.#bug_template.md", "# Lock file\n");
+    fixture.source_file("templates/spike_template.md~", "# Backup\n");
+    fixture.source_rule("team/local", "Personal.\n");
+    fixture.source_rule("team/shared", "Shared.\n");
+    fixture.source_file(".gitignore", "drafts/\n");
+    fixture.source_file("rules/team/.gitignore", "*.mdc\n!style.mdc\n!shared.mdc\n");
+
+    fixture.echo(&["init", "--project", "app"]).success();
+    assert!(fixture.exists(rule_path("team/style")));
+    assert!(fixture.exists(rule_path("team/shared")));
+    assert!(!fixture.exists(rule_path("team/local")));
+    assert!(!fixture.exists(rule_path("team/.#style")));
+    assert!(!fixture.exists(rule_path("drafts/wip")));
+    assert!(!fixture.exists(rule_path("vendor/.git/leak")));
+    assert!(!fixture.exists("issues/shared/templates/.#bug_template.md"));
+    assert!(fixture.exists("issues/shared/templates/bug_template.md"));
+    fixture.echo(&["check"]).success();
+
+    fixture.echo(&["update", "--scan-ignored"]).success();
+    assert!(fixture.exists(rule_path("drafts/wip")));
+    assert!(fixture.exists(rule_path("team/local")));
+    assert!(!fixture.exists(rule_path("vendor/.git/leak")));
+}
+
+#[test]
+fn init_installs_the_scripts_of_the_checkout() {
+    let fixture = Fixture::new();
+    fixture.source_file("scripts/pre-work-hook", "#!/bin/sh\nexit 0\n");
+    fixture.source_file("scripts/ci/lint", "#!/bin/sh\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    assert_eq!(fixture.read("scripts/pre-work-hook"), "#!/bin/sh\nexit 0\n");
+    assert!(fixture.exists("scripts/ci/lint"));
+
+    fixture.write("scripts/mine", "#!/bin/sh\n");
+    fixture.source_file("scripts/pre-work-hook", "#!/bin/sh\nexit 1\n");
+    let update = fixture.echo(&["update"]).success();
+    assert!(update
+        .stderr()
+        .contains("scripts/mine is not a script of the flowmates checkout"));
+    assert_eq!(fixture.read("scripts/pre-work-hook"), "#!/bin/sh\nexit 1\n");
+    let check = fixture.echo(&["check", "--report", "check.json"]).success();
+    assert!(check.stderr().contains("warning: scripts/mine"));
+    assert!(fixture.read("check.json").contains("scripts/mine"));
+
+    fixture.echo(&["uninstall"]).success();
+    assert!(!fixture.exists("scripts/pre-work-hook"));
+    assert!(fixture.exists("scripts/mine"));
+}
+
+#[test]
+fn status_caches_the_fingerprints_of_settled_sources() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    let source = fixture.flowmates_dir().join("rules/team/style.mdc");
+    let past = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+    std::fs::File::options()
+        .write(true)
+        .open(&source)
+        .unwrap()
+        .set_modified(past)
+        .unwrap();
+    let cache = fixture.cache_dir().join("scan.json");
+    let _ = std::fs::remove_file(&cache);
+
+    fixture.echo(&["status", "--no-cache"]).success();
+    assert!(!cache.exists());
+    fixture.echo(&["status"]).success();
+    let cached = std::fs::read_to_string(&cache).unwrap();
+    assert!(cached.contains("team/style.mdc"));
+    assert!(cached.contains(&fixture.flowmates_dir().display().to_string()));
+}
+
+#[cfg(unix)]
+#[test]
+fn init_links_rules_to_the_source_with_link() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture
+        .echo(&["init", "--project", "app", "--link"])
+        .success();
+    let installed = fixture.root().join(rule_path("team/style"));
+    assert!(installed.is_symlink());
+    assert!(Config::load(fixture.root()).unwrap().rules.link);
+
+    fixture.source_rule("team/style", "Use tabs.\n");
+    assert_eq!(fixture.read(rule_path("team/style")), "Use tabs.\n");
+    std::os::unix::fs::symlink(&installed, fixture.root().join(rule_path("mine"))).unwrap();
+    let status = fixture.echo(&["status"]).success();
+    assert!(status
+        .stderr()
+        .contains(".cursor/rules/mine.mdc is a link echo did not create"));
+
+    fixture.echo(&["uninstall"]).success();
+    assert!(!installed.exists());
+    assert!(fixture.root().join(rule_path("mine")).is_symlink());
+    assert!(fixture
+        .flowmates_dir()
+        .join("rules/team/style.mdc")
+        .is_file());
+}
+
+#[test]
+fn init_inside_the_flowmates_checkout_only_sets_up_issues() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.source_file("templates/bug_template.md", "# Bug\n");
+    let checkout = fixture.flowmates_dir().to_str().unwrap();
+
+    let init = fixture
+        .echo(&["--root", checkout, "init", "--project", "app"])
+        .success();
+    assert!(init.stdout().contains("is the flowmates checkout"));
+    let checkout = fixture.flowmates_dir();
+    assert!(checkout.join("issues/app/todo").is_dir());
+    assert!(!checkout.join(".cursor").exists());
+    assert!(!checkout.join("issues/shared/templates").exists());
+    assert_eq!(
+        std::fs::read_to_string(checkout.join("rules/team/style.mdc")).unwrap(),
+        "Use four spaces.\n"
+    );
+    fixture
+        .echo(&["--root", checkout.to_str().unwrap(), "update"])
+        .success();
+}
+
+#[test]
+fn init_source_repo_checks_and_registers_the_checkout() {
+    let fixture = Fixture::new();
+    let checkout = fixture.flowmates_dir().to_str().unwrap().to_string();
+    fixture.source_rule(
+        "team/style",
+        "---\ndescription: Team style\nglobs: *.rs\nalwaysApply: false\n---\n\nUse four spaces.\n",
+    );
+    let failed = fixture
+        .echo(&["--root", &checkout, "init", "--source-repo"])
+        .failure();
+    assert!(failed
+        .stderr()
+        .contains("templates: missing templates directory"));
+
+    fixture.source_file("templates/bug_template.md", "# Bug\n\n**Type:** bug\n");
+    let status = std::process::Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(&checkout)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let init = fixture
+        .echo(&[
+            "--root",
+            &checkout,
+            "init",
+            "--source-repo",
+            "--project",
+            "rules",
+        ])
+        .success();
+    assert!(init.stdout().contains("is a valid flowmates checkout"));
+    assert!(init.stdout().contains("installed hook pre-commit"));
+    assert!(fixture.flowmates_dir().join("issues/rules/todo").is_dir());
+    assert!(fixture
+        .flowmates_dir()
+        .join(".git/hooks/pre-commit")
+        .is_file());
+    assert!(!fixture.flowmates_dir().join(".cursor").exists());
+    let registry = std::fs::read_to_string(fixture.config_dir().join("projects.toml")).unwrap();
+    assert!(registry.contains("source = "));
+}
+
+#[test]
+fn source_validate_gates_a_checkout() {
+    let fixture = Fixture::new();
+    let checkout = fixture.flowmates_dir().to_str().unwrap().to_string();
+    fixture.source_rule("team/style", "---\nglob: *.rs\n---\n");
+    fixture.source_file("templates/bug_template.md", "# Bug\n\n**Type:** bug\n");
+
+    let failed = fixture
+        .echo(&["source", "validate", &checkout, "--ci"])
+        .failure();
+    assert!(failed
+        .stdout()
+        .contains("::error file=rules/team/style.mdc::missing description"));
+    assert!(failed.stderr().contains("problem(s) found in"));
+
+    fixture.source_rule(
+        "team/style",
+        "---\ndescription: Team style\nglobs: *.rs\nalwaysApply: false\n---\n\nUse four spaces.\n",
+    );
+    let valid = fixture.echo(&["source", "validate", &checkout]).success();
+    assert!(valid.stdout().contains("is a valid flowmates checkout"));
+    fixture
+        .echo(&["source", "validate", "missing-checkout"])
+        .failure();
+}
+
+#[test]
+fn init_clones_the_flowmates_repository_on_a_new_machine() {
+    let fixture = Fixture::new();
+    fixture.source_rule(
+        "team/style",
+        "---\ndescription: Team style\nglobs: *.rs\nalwaysApply: false\n---\n\nUse four spaces.\n",
+    );
+    let upstream = fixture.flowmates_dir();
+    for args in [
+        &["init", "-q"][..],
+        &["add", "-A"],
+        &[
+            "-c",
+            "user.name=t",
+            "-c",
+            "user.email=t@t",
+            "commit",
+            "-qm",
+            "rules",
+        ],
+    ] {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(upstream)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    let url = upstream.to_str().unwrap();
+
+    let run = fixture
+        .echo_without_checkout(&["init", "--project", "app", "--source-url", url])
+        .success();
+    let checkout = fixture.data_dir().join("flowmates");
+    assert!(run.stdout().contains("cloned"));
+    assert!(checkout.join("rules/team/style.mdc").is_file());
+    assert!(fixture.exists(".cursor/rules/team/style.mdc"));
+    let registry = std::fs::read_to_string(fixture.config_dir().join("projects.toml")).unwrap();
+    assert!(registry.contains("source ="));
+
+    // The registered clone serves later runs without the URL.
+    std::fs::remove_dir_all(fixture.root().join(".cursor")).unwrap();
+    fixture
+        .echo_without_checkout(&["init", "--project", "app"])
+        .success();
+    assert!(fixture.exists(".cursor/rules/team/style.mdc"));
+    fixture
+        .echo_without_checkout(&["init", "--project", "app", "--source-url", url])
+        .success();
+    fixture
+        .echo_without_checkout(&["init", "--project", "app", "--source-url", "/elsewhere"])
+        .failure();
+}
+
+#[test]
+fn non_interactive_runs_take_defaults_or_fail_clearly() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    fixture.source_rule("team/style", "Use tabs.\n");
+    fixture.write(rule_path("team/style"), "Use two spaces.\n");
+
+    let refused = fixture
+        .echo(&["--non-interactive", "update", "--strategy", "ask"])
+        .failure();
+    assert!(refused
+        .stderr()
+        .contains("non-interactively; pass --strategy theirs or ours"));
+    let kept = fixture.echo(&["update", "--non-interactive"]).success();
+    assert!(kept.stdout().contains("kept .cursor/rules/team/style.mdc"));
+    assert_eq!(fixture.read(rule_path("team/style")), "Use two spaces.\n");
+}
+
+#[test]
+fn stats_are_recorded_locally_once_enabled() {
+    let fixture = Fixture::new();
+    fixture.echo(&["init", "--project", "app"]).success();
+    let off = fixture.echo(&["stats"]).success();
+    assert!(off.stdout().contains("run `echo stats enable`"));
+    assert!(!fixture.cache_dir().join("stats.jsonl").exists());
+
+    fixture.echo(&["stats", "enable"]).success();
+    fixture.echo(&["status"]).success();
+    fixture.echo(&["status"]).success();
+    fixture.echo(&["rules", "disable", "missing"]).failure();
+    let summary = fixture.echo(&["stats"]).success().stdout();
+    assert!(summary.contains("3 run(s) since"), "{}", summary);
+    let lines: Vec<&str> = summary.lines().collect();
+    assert!(lines[2].starts_with("status "), "{}", summary);
+    assert!(lines[3].starts_with("rules disable "), "{}", summary);
+    assert!(lines[3].contains("      1 "), "{}", summary);
+
+    fixture.echo(&["stats", "disable"]).success();
+    assert!(!fixture.cache_dir().join("stats.jsonl").exists());
+}
+
+#[test]
+fn audit_log_records_what_mutating_commands_changed() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.write(".echo.toml", "audit-log = true\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    fixture.source_rule("team/style", "Use tabs.\n");
+    fixture.echo(&["update"]).success();
+    fixture.echo(&["status"]).success();
+
+    let show = fixture.echo(&["audit", "show"]).success().stdout();
+    let runs: Vec<&str> = show.lines().filter(|line| !line.starts_with(' ')).collect();
+    assert_eq!(runs.len(), 2, "{}", show);
+    assert!(runs[0].ends_with(" UTC  init"), "{}", show);
+    assert!(runs[1].contains("  update"), "{}", show);
+    assert!(show.contains("  A .cursor/rules/team/style.mdc  - -> "));
+    assert!(show.contains("  M .cursor/rules/team/style.mdc  "));
+    assert!(!show.contains("echo-audit.log"));
+
+    let rules = fixture
+        .echo(&["audit", "show", "--path", ".cursor/rules/team"])
+        .success()
+        .stdout();
+    assert!(!rules.contains("AGENT.md"));
+    let later = fixture
+        .echo(&["audit", "show", "--since", "2999-01-01"])
+        .success();
+    assert!(later.stdout().contains("no logged change"));
+    fixture
+        .echo(&["audit", "show", "--since", "yesterday"])
+        .failure();
+}
+
+#[test]
+fn undo_reverts_the_logged_commands_one_by_one() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "Use four spaces.\n");
+    fixture.write(".echo.toml", "audit-log = true\n");
+    fixture.echo(&["init", "--project", "app"]).success();
+    fixture.source_rule("team/style", "Use tabs.\n");
+    fixture.echo(&["update"]).success();
+    assert_eq!(fixture.read(rule_path("team/style")), "Use tabs.\n");
+
+    let list = fixture.echo(&["undo", "--list"]).success().stdout();
+    let listed: Vec<&str> = list.lines().collect();
+    assert_eq!(listed.len(), 2, "{}", list);
+    assert!(listed[0].contains("  update: "), "{}", list);
+
+    fixture.write(rule_path("team/style"), "Use two spaces.\n");
+    let refused = fixture.echo(&["undo"]).failure();
+    assert!(refused
+        .stderr()
+        .contains("changed since `update`: .cursor/rules/team/style.mdc"));
+    fixture.write(rule_path("team/style"), "Use tabs.\n");
+
+    let undo = fixture.echo(&["undo"]).success();
+    assert!(undo.stdout().contains("undid `update`"));
+    assert_eq!(fixture.read(rule_path("team/style")), "Use four spaces.\n");
+    // The lock is reverted too: the rule is behind its source, not edited.
+    let check = fixture.echo(&["check"]).failure();
+    assert!(check
+        .stderr()
+        .contains(".cursor/rules/team/style.mdc: outdated"));
+
+    fixture.echo(&["undo"]).success();
+    assert!(!fixture.exists(rule_path("team/style")));
+    assert!(!fixture.exists("AGENT.md"));
+    assert!(!fixture.exists("issues"));
+    assert!(!fixture.exists(".gitignore"));
+    assert_eq!(fixture.read(".echo.toml"), "audit-log = true\n");
+    let nothing = fixture.echo(&["undo"]).failure();
+    assert!(nothing.stderr().contains("nothing to undo"));
+}
+
+#[test]
+fn profiles_select_the_flowmates_checkout() {
+    let fixture = Fixture::new();
+    let work = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(work.path().join("rules")).unwrap();
+    std::fs::write(work.path().join("rules/work.mdc"), "Work rules.\n").unwrap();
+    std::fs::create_dir_all(fixture.config_dir()).unwrap();
+    std::fs::write(
+        fixture.config_dir().join("profiles.toml"),
+        format!(
+            "[work]\nsource = \"{}\"\nvariables = {{ TEAM = \"platform\" }}\n",
+            work.path().display()
+        ),
+    )
+    .unwrap();
+
+    fixture
+        .echo(&["init", "--project", "app", "--profile", "work"])
+        .success();
+    assert!(fixture.exists(rule_path("work")));
+    let status = fixture
+        .echo(&["status", "--profile", "work"])
+        .success()
+        .stdout();
+    assert!(status.contains("profile:  work"));
+    let unknown = fixture.echo(&["status", "--profile", "personal"]).failure();
+    assert!(unknown.stderr().contains("unknown profile 'personal'"));
+}
+
+#[test]
+fn a_source_pinned_in_echo_toml_overrides_the_users_checkout() {
+    let fixture = Fixture::new();
+    fixture.source_rule("team/style", "The user's checkout.\n");
+    let upstream = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+            .args(args)
+            .current_dir(upstream.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    let release = |content: &str, tag: &str| {
+        std::fs::create_dir_all(upstream.path().join("rules/team")).unwrap();
+        std::fs::write(upstream.path().join("rules/team/style.mdc"), content).unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-qm", tag]);
+        git(&["tag", tag]);
+    };
+    git(&["init", "-q"]);
+    release("Pinned v1.\n", "v1");
+    release("Pinned v2.\n", "v2");
+    let pin = |tag: &str| {
+        fixture.write(
+            ".echo.toml",
+            &format!(
+                "[source]\nurl = \"{}\"\nref = \"{}\"\n",
+                upstream.path().display(),
+                tag
+            ),
+        );
+    };
+
+    pin("v1");
+    let init = fixture.echo(&["init", "--project", "app"]).success();
+    assert!(init.stdout().contains("at v1 ("), "{}", init.stdout());
+    assert_eq!(fixture.read(rule_path("team/style")), "Pinned v1.\n");
+
+    pin("v2");
+    fixture.echo(&["update"]).success();
+    assert_eq!(fixture.read(rule_path("team/style")), "Pinned v2.\n");
+    pin("v3");
+    let missing = fixture.echo(&["update"]).failure();
+    assert!(missing.stderr().contains("has no ref 'v3'"));
+}
@@ -0,0 +1,173 @@
+//! Fixtures for the integration tests: a throwaway git repository, a fake
+//! flowmates checkout and a home directory, all in temporary directories,
+//! so nothing reads or writes the real `~/.config` or `$FLOWMATES_DIR`.
+
+#![allow(dead_code)]
+
+use echo::rules::{RulesConfig, FLOWMATES_RULES_DIR};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// A repository with its own flowmates checkout and home directory.
+pub struct Fixture {
+    repo: TempDir,
+    flowmates: TempDir,
+    home: TempDir,
+}
+
+impl Fixture {
+    /// An empty git repository and an empty flowmates checkout.
+    pub fn new() -> Fixture {
+        let fixture = Fixture {
+            repo: tempfile::tempdir().unwrap(),
+            flowmates: tempfile::tempdir().unwrap(),
+            home: tempfile::tempdir().unwrap(),
+        };
+        let status = Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(fixture.root())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git init failed");
+        fixture
+    }
+
+    /// Root of the repository.
+    pub fn root(&self) -> &Path {
+        self.repo.path()
+    }
+
+    pub fn flowmates_dir(&self) -> &Path {
+        self.flowmates.path()
+    }
+
+    /// `[rules]` pointing at the fake flowmates checkout.
+    pub fn rules_config(&self) -> RulesConfig {
+        RulesConfig {
+            flowmates_dir: Some(self.flowmates_dir().to_path_buf()),
+            ..RulesConfig::default()
+        }
+    }
+
+    /// Add or change the source rule `name`, e.g. `rust/style`.
+    pub fn source_rule(&self, name: &str, content: &str) {
+        let path = self
+            .flowmates_dir()
+            .join(FLOWMATES_RULES_DIR)
+            .join(format!("{}.mdc", name));
+        write(&path, content);
+    }
+
+    /// Add or change the file `rel_path` of the flowmates checkout.
+    pub fn source_file(&self, rel_path: &str, content: &str) {
+        write(&self.flowmates_dir().join(rel_path), content);
+    }
+
+    /// Contents of `rel_path` in the repository.
+    pub fn read(&self, rel_path: impl AsRef<Path>) -> String {
+        fs::read_to_string(self.root().join(rel_path)).unwrap()
+    }
+
+    /// Write `rel_path` in the repository, creating its directories.
+    pub fn write(&self, rel_path: impl AsRef<Path>, content: &str) {
+        write(&self.root().join(rel_path), content);
+    }
+
+    pub fn exists(&self, rel_path: impl AsRef<Path>) -> bool {
+        self.root().join(rel_path).exists()
+    }
+
+    /// Run the echo binary in the repository with `args`, isolated from the
+    /// environment of the user running the tests.
+    pub fn echo(&self, args: &[&str]) -> Run {
+        let mut command = self.command(args);
+        command.env("FLOWMATES_DIR", self.flowmates_dir());
+        Run {
+            output: command.output().unwrap(),
+        }
+    }
+
+    /// Like [`Fixture::echo`], on a machine where `$FLOWMATES_DIR` names no
+    /// checkout.
+    pub fn echo_without_checkout(&self, args: &[&str]) -> Run {
+        Run {
+            output: self.command(args).output().unwrap(),
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_echo"));
+        command
+            .args(args)
+            .current_dir(self.root())
+            .env("HOME", self.home.path())
+            .env("XDG_CONFIG_HOME", self.home.path().join(".config"))
+            .env("XDG_CACHE_HOME", self.home.path().join(".cache"))
+            .env("XDG_DATA_HOME", self.home.path().join(".local/share"))
+            .env("FLOWMATES_CONFIG_DIR", self.config_dir())
+            .env_remove("FLOWMATES_DIR")
+            .env_remove("GITHUB_ACTIONS")
+            .env_remove("GITHUB_STEP_SUMMARY")
+            .env_remove("RUST_BACKTRACE")
+            .stdin(std::process::Stdio::null());
+        command
+    }
+
+    /// echo's data directory in the fake home.
+    pub fn data_dir(&self) -> PathBuf {
+        self.home.path().join(".local/share/echo")
+    }
+
+    /// Flowmates configuration directory of the fake home.
+    pub fn config_dir(&self) -> PathBuf {
+        self.home.path().join(".config/flowmates")
+    }
+
+    /// echo's cache directory in the fake home.
+    pub fn cache_dir(&self) -> PathBuf {
+        self.home.path().join(".cache/echo")
+    }
+}
+
+/// Output of an echo run.
+pub struct Run {
+    pub output: Output,
+}
+
+impl Run {
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output.stdout).into_owned()
+    }
+
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.output.stderr).into_owned()
+    }
+
+    /// Panic with the output unless the run succeeded.
+    pub fn success(self) -> Run {
+        assert!(
+            self.output.status.success(),
+            "echo failed\nstdout:\n{}\nstderr:\n{}",
+            self.stdout(),
+            self.stderr()
+        );
+        self
+    }
+
+    /// Panic with the output unless the run failed.
+    pub fn failure(self) -> Run {
+        assert!(
+            !self.output.status.success(),
+            "echo succeeded\nstdout:\n{}",
+            self.stdout()
+        );
+        self
+    }
+}
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}